@@ -0,0 +1,445 @@
+#![warn(missing_docs)]
+
+//! DLNA casting. Renderers are found over SSDP, the current track is served from a small HTTP server,
+//! and the renderer's AVTransport service is driven over SOAP. Local playback stays paused while casting.
+//! Chromecast needs TLS and protobuf on top of this, so only DLNA/UPnP renderers are supported.
+
+use crate::library::{http, LibEvt, Library, Track};
+use crate::logging::*;
+
+use std::error::Error;
+use std::io::{Read, Write};
+use std::net::{IpAddr, TcpListener, TcpStream, ToSocketAddrs, UdpSocket};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, OnceLock, Weak};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use regex::Regex;
+
+const SSDP: &str = "239.255.255.250:1900";
+const AVTRANSPORT: &str = "urn:schemas-upnp-org:service:AVTransport:1";
+/// For discovery and every request to a renderer
+const TIMEOUT: Duration = Duration::from_secs(3);
+/// Longest a renderer may stop reading the file being cast, as paused ones do, before its connection is dropped
+const SERVE_TIMEOUT: Duration = Duration::from_secs(120);
+/// How often the renderer is asked whether it finished the track
+const POLL: Duration = Duration::from_secs(1);
+
+static SERVER: OnceLock<Server> = OnceLock::new();
+static SESSION: Mutex<Option<Session>> = Mutex::new(None);
+
+// ## HTTP ## {{{
+
+/// Split `http://host:port/path` into `host:port` and `/path`
+fn split_url(url: &str) -> Result<(String, String), Box<dyn Error>> {
+    let rest = url.strip_prefix("http://").ok_or(format!("Not an http:// url: {}", url))?;
+    Ok(match rest.split_once('/') {
+        Some((host, path)) => (host.to_string(), format!("/{}", path)),
+        None => (rest.to_string(), String::from("/")),
+    })
+}
+
+/// Undo `Transfer-Encoding: chunked`
+fn dechunk(mut body: &[u8]) -> Vec<u8> {
+    let mut result = Vec::new();
+    while let Some(line) = body.windows(2).position(|w| w == b"\r\n") {
+        let size = String::from_utf8_lossy(&body[..line]);
+        let Ok(size) = usize::from_str_radix(size.split(';').next().unwrap_or_default().trim(), 16) else {
+            break;
+        };
+        let data = &body[line + 2..];
+        if size == 0 || data.len() < size {
+            break;
+        }
+        result.extend_from_slice(&data[..size]);
+        body = data[size..].strip_prefix(b"\r\n").unwrap_or(&data[size..]);
+    }
+    result
+}
+
+/// Blocking HTTP/1.1 request returning the body of a 2xx response
+fn request(host: &str, method: &str, path: &str, headers: &[(&str, &str)], body: &str) -> Result<String, Box<dyn Error>> {
+    let address = host.to_socket_addrs()?.next().ok_or(format!("Could not resolve {}", host))?;
+    let mut stream = TcpStream::connect_timeout(&address, TIMEOUT)?;
+    stream.set_read_timeout(Some(TIMEOUT))?;
+
+    let mut message = format!(
+        "{} {} HTTP/1.1\r\nHost: {}\r\nConnection: close\r\nContent-Length: {}\r\n",
+        method,
+        path,
+        host,
+        body.len()
+    );
+    for (key, value) in headers {
+        message += &format!("{}: {}\r\n", key, value);
+    }
+    message += "\r\n";
+    message += body;
+    stream.write_all(message.as_bytes())?;
+
+    let mut response = Vec::new();
+    stream.read_to_end(&mut response)?;
+    let split = response.windows(4).position(|w| w == b"\r\n\r\n").ok_or("Malformed HTTP response")?;
+    let head = String::from_utf8_lossy(&response[..split]).to_ascii_lowercase();
+    let content = &response[split + 4..];
+    let content = match head.contains("transfer-encoding: chunked") {
+        true => dechunk(content),
+        false => content.to_vec(),
+    };
+
+    let status = head.split_whitespace().nth(1).unwrap_or_default();
+    if !status.starts_with('2') {
+        return Err(format!("{} {} returned {}", method, path, status).into());
+    }
+    Ok(String::from_utf8_lossy(&content).into_owned())
+}
+
+/// Text of the first element NAME, ignoring namespace prefixes
+fn tag(xml: &str, name: &str) -> Option<String> {
+    Regex::new(&format!(r"<(?:\w+:)?{0}(?:\s[^>]*)?>([^<]*)</(?:\w+:)?{0}>", name))
+        .ok()?
+        .captures(xml)
+        .map(|c| c[1].trim().to_string())
+}
+
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+// ## HTTP ## }}}
+
+// ## Renderer ## {{{
+
+/// A DLNA media renderer found on the LAN
+#[derive(Clone, Debug)]
+pub struct Renderer {
+    /// Friendly name from the device description
+    pub name: String,
+    /// `host:port` of the AVTransport control URL
+    host: String,
+    /// Path of the AVTransport control URL
+    control: String,
+}
+
+impl Renderer {
+    /// Read the device description at LOCATION
+    fn describe(location: &str) -> Result<Self, Box<dyn Error>> {
+        let (host, path) = split_url(location)?;
+        let xml = request(&host, "GET", &path, &[], "")?;
+        let service = xml
+            .split("<service>")
+            .find(|s| s.contains(AVTRANSPORT))
+            .ok_or("Device has no AVTransport service")?;
+        let control = tag(service, "controlURL").ok_or("AVTransport has no control URL")?;
+
+        let (host, control) = if control.starts_with("http://") {
+            split_url(&control)?
+        } else {
+            // relative to URLBase if given, else the description's host
+            let host = match tag(&xml, "URLBase") {
+                Some(base) => split_url(&base)?.0,
+                None => host,
+            };
+            let control = match control.starts_with('/') {
+                true => control,
+                false => format!("/{}", control),
+            };
+            (host, control)
+        };
+
+        Ok(Self {
+            name: tag(&xml, "friendlyName").unwrap_or(host.clone()),
+            host,
+            control,
+        })
+    }
+
+    /// Call ACTION on the AVTransport service with extra ARGS as XML
+    fn soap(&self, action: &str, args: &str) -> Result<String, Box<dyn Error>> {
+        let body = format!(
+            concat!(
+                r#"<?xml version="1.0" encoding="utf-8"?>"#,
+                r#"<s:Envelope xmlns:s="http://schemas.xmlsoap.org/soap/envelope/" s:encodingStyle="http://schemas.xmlsoap.org/soap/encoding/">"#,
+                r#"<s:Body><u:{0} xmlns:u="{1}"><InstanceID>0</InstanceID>{2}</u:{0}></s:Body></s:Envelope>"#
+            ),
+            action, AVTRANSPORT, args
+        );
+        let soap_action = format!("\"{}#{}\"", AVTRANSPORT, action);
+        request(
+            &self.host,
+            "POST",
+            &self.control,
+            &[("Content-Type", "text/xml; charset=\"utf-8\""), ("SOAPAction", &soap_action)],
+            &body,
+        )
+    }
+
+    /// Load URL with TRACK's metadata and start playing it
+    fn play_url(&self, url: &str, track: &Track) -> Result<(), Box<dyn Error>> {
+        let tag = |name: &str| escape(track.tags().get(name).map(|s| s.as_str()).unwrap_or_default());
+        let didl = format!(
+            concat!(
+                r#"<DIDL-Lite xmlns="urn:schemas-upnp-org:metadata-1-0/DIDL-Lite/" xmlns:dc="http://purl.org/dc/elements/1.1/" xmlns:upnp="urn:schemas-upnp-org:metadata-1-0/upnp/">"#,
+                r#"<item id="0" parentID="-1" restricted="1"><dc:title>{}</dc:title><upnp:artist>{}</upnp:artist><upnp:album>{}</upnp:album>"#,
+                r#"<upnp:class>object.item.audioItem.musicTrack</upnp:class><res protocolInfo="http-get:*:{}:*">{}</res></item></DIDL-Lite>"#
+            ),
+            tag("title"),
+            tag("artist"),
+            tag("album"),
+            http::mime(track.path()),
+            escape(url),
+        );
+        self.soap(
+            "SetAVTransportURI",
+            &format!(
+                "<CurrentURI>{}</CurrentURI><CurrentURIMetaData>{}</CurrentURIMetaData>",
+                escape(url),
+                escape(&didl)
+            ),
+        )?;
+        self.play()
+    }
+
+    fn play(&self) -> Result<(), Box<dyn Error>> {
+        self.soap("Play", "<Speed>1</Speed>").map(|_| ())
+    }
+
+    fn stop(&self) -> Result<(), Box<dyn Error>> {
+        self.soap("Stop", "").map(|_| ())
+    }
+
+    /// PLAYING, STOPPED, PAUSED_PLAYBACK, TRANSITIONING...
+    fn state(&self) -> Result<String, Box<dyn Error>> {
+        tag(&self.soap("GetTransportInfo", "")?, "CurrentTransportState").ok_or("Renderer gave no transport state".into())
+    }
+
+    /// This machine's address as seen from the renderer
+    fn local_ip(&self) -> Result<IpAddr, Box<dyn Error>> {
+        let socket = UdpSocket::bind("0.0.0.0:0")?;
+        socket.connect(&self.host)?;
+        Ok(socket.local_addr()?.ip())
+    }
+}
+
+/// Every renderer answering an SSDP search within a few seconds
+pub fn discover() -> Result<Vec<Renderer>, Box<dyn Error>> {
+    let socket = UdpSocket::bind("0.0.0.0:0")?;
+    socket.set_read_timeout(Some(Duration::from_millis(200)))?;
+    let search = format!(
+        "M-SEARCH * HTTP/1.1\r\nHOST: {}\r\nMAN: \"ssdp:discover\"\r\nMX: 2\r\nST: {}\r\n\r\n",
+        SSDP, AVTRANSPORT
+    );
+    socket.send_to(search.as_bytes(), SSDP)?;
+
+    let mut locations: Vec<String> = Vec::new();
+    let mut buf = [0u8; 2048];
+    let begin = Instant::now();
+    while begin.elapsed() < TIMEOUT {
+        let Ok((n, _)) = socket.recv_from(&mut buf) else { continue };
+        let reply = String::from_utf8_lossy(&buf[..n]);
+        let location = reply
+            .lines()
+            .filter_map(|l| l.split_once(':'))
+            .find(|(k, _)| k.trim().eq_ignore_ascii_case("location"))
+            .map(|(_, v)| v.trim().to_string());
+        if let Some(location) = location.filter(|l| !locations.contains(l)) {
+            locations.push(location)
+        }
+    }
+
+    Ok(locations
+        .iter()
+        .filter_map(|l| match Renderer::describe(l) {
+            Ok(renderer) => Some(renderer),
+            Err(e) => {
+                debug!("Skipping renderer at {}: {}", l, e);
+                None
+            }
+        })
+        .collect())
+}
+
+// ## Renderer ## }}}
+
+// ## Server ## {{{
+
+/// Serves the file being cast. Each track gets a new URL so renderers don't replay a cached one
+struct Server {
+    port: u16,
+    /// URL id and file currently served
+    current: Arc<Mutex<(u64, Option<PathBuf>)>>,
+}
+
+impl Server {
+    fn get() -> Result<&'static Self, Box<dyn Error>> {
+        if let Some(server) = SERVER.get() {
+            return Ok(server);
+        }
+        let listener = TcpListener::bind("0.0.0.0:0")?;
+        let server = Server {
+            port: listener.local_addr()?.port(),
+            current: Default::default(),
+        };
+        let current = server.current.clone();
+        thread::Builder::new().name(String::from("CAST Server")).spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let current = current.clone();
+                thread::spawn(move || {
+                    if let Err(e) = Self::respond(stream, &current) {
+                        debug!("Cast server: {}", e)
+                    }
+                });
+            }
+        })?;
+        Ok(SERVER.get_or_init(|| server))
+    }
+
+    /// Serve PATH under a new URL, returned relative to the server root
+    fn serve(&self, path: PathBuf) -> String {
+        let mut current = self.current.lock().unwrap_or_else(|e| e.into_inner());
+        current.0 += 1;
+        let url = format!("/{}.{}", current.0, path.extension().and_then(|e| e.to_str()).unwrap_or("audio"));
+        current.1 = Some(path);
+        url
+    }
+
+    fn respond(mut stream: TcpStream, current: &Mutex<(u64, Option<PathBuf>)>) -> Result<(), Box<dyn Error>> {
+        let request = http::Request::read(&stream, SERVE_TIMEOUT)?;
+        let path = {
+            let current = current.lock().unwrap_or_else(|e| e.into_inner());
+            let id = request
+                .target
+                .trim_start_matches('/')
+                .split('.')
+                .next()
+                .and_then(|i| i.parse::<u64>().ok());
+            current.1.clone().filter(|_| id == Some(current.0))
+        };
+        match path {
+            Some(path) => http::file(
+                &mut stream,
+                &request,
+                &path,
+                "transferMode.dlna.org: Streaming\r\ncontentFeatures.dlna.org: DLNA.ORG_OP=01\r\n",
+            )?,
+            None => http::empty(&mut stream, "404 Not Found")?,
+        }
+        Ok(())
+    }
+}
+
+// ## Server ## }}}
+
+// ## Session ## {{{
+
+struct Session {
+    renderer: Renderer,
+    stop: Arc<AtomicBool>,
+}
+
+/// Send the library's current track to RENDERER, pausing local playback
+fn cast_current(library: &Library, renderer: &Renderer, server: &Server, ip: IpAddr) -> Result<(), Box<dyn Error>> {
+    let Some(track) = library.track_get() else { return Ok(()) };
+    if library.playing() {
+        library.pause()
+    }
+    let url = format!("http://{}:{}{}", ip, server.port, server.serve(track.path().clone()));
+    debug!("Casting {} as {}", track.path().display(), url);
+    renderer.play_url(&url, &track)
+}
+
+/// Follow the library's track and play state on RENDERER until STOP
+fn run(library: Weak<Library>, renderer: Renderer, stop: Arc<AtomicBool>) -> Result<(), Box<dyn Error>> {
+    let server = Server::get()?;
+    let ip = renderer.local_ip()?;
+    let mut recv = library.upgrade().ok_or("Library is closed")?.get_receiver()?;
+    // the renderer has started the current track, so STOPPED afterwards means it finished
+    let mut started = false;
+
+    cast_current(&*library.upgrade().ok_or("Library is closed")?, &renderer, server, ip)?;
+
+    while !stop.load(Ordering::Relaxed) {
+        let event = recv.recv_timeout(POLL);
+        let Some(library) = library.upgrade() else { break };
+        match event {
+            Ok(LibEvt::Track) => {
+                started = false;
+                cast_current(&library, &renderer, server, ip)?
+            }
+            // local play while casting resumes the renderer instead
+            Ok(LibEvt::State) if library.playing() => {
+                library.pause();
+                renderer.play()?
+            }
+            Ok(_) => (),
+            Err(std::sync::mpsc::RecvTimeoutError::Timeout) => match renderer.state()?.as_str() {
+                "PLAYING" => started = true,
+                "STOPPED" if started => {
+                    started = false;
+                    library.next()
+                }
+                _ => (),
+            },
+            Err(std::sync::mpsc::RecvTimeoutError::Disconnected) => break,
+        }
+    }
+    renderer.stop()
+}
+
+/// Renderer names, one per line
+pub fn list() -> String {
+    match discover() {
+        Ok(renderers) if renderers.is_empty() => String::from("No DLNA renderers found"),
+        Ok(renderers) => renderers.iter().map(|r| r.name.clone()).collect::<Vec<String>>().join("\n"),
+        Err(e) => format!("Could not search for renderers: {}", e),
+    }
+}
+
+/// Start casting to the renderer named DEVICE, replacing any current session
+pub fn start(library: &Arc<Library>, device: &str) -> String {
+    let renderer = match discover() {
+        Ok(renderers) => match renderers.into_iter().find(|r| r.name.eq_ignore_ascii_case(device)) {
+            Some(renderer) => renderer,
+            None => return format!("No renderer named '{}'", device),
+        },
+        Err(e) => return format!("Could not search for renderers: {}", e),
+    };
+    stop();
+
+    let session = Session {
+        renderer: renderer.clone(),
+        stop: Arc::new(AtomicBool::new(false)),
+    };
+    let (weak, stop) = (Arc::downgrade(library), session.stop.clone());
+    let spawned = thread::Builder::new().name(String::from("CAST Session")).spawn(move || {
+        let name = renderer.name.clone();
+        if let Err(e) = run(weak, renderer, stop) {
+            error!("Casting to {} stopped: {}", name, e);
+        }
+    });
+    if let Err(e) = spawned {
+        return format!("Could not start casting: {}", e);
+    }
+    *SESSION.lock().unwrap_or_else(|e| e.into_inner()) = Some(session);
+    String::new()
+}
+
+/// Stop casting, leaving local playback paused
+pub fn stop() -> String {
+    match SESSION.lock().unwrap_or_else(|e| e.into_inner()).take() {
+        Some(session) => {
+            session.stop.store(true, Ordering::Relaxed);
+            debug!("Stopped casting to {}", session.renderer.name);
+            String::new()
+        }
+        None => String::from("Not casting"),
+    }
+}
+
+// ## Session ## }}}
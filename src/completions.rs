@@ -0,0 +1,115 @@
+use crate::{Args, CompletionShell};
+
+use std::io::Write;
+
+use clap::CommandFactory;
+use clap_complete::Shell;
+
+/// Wraps clap's _ompl to fill filter values, browse names, and view names from the running server.
+/// '=' is usually in COMP_WORDBREAKS, so the current word is rebuilt from COMP_LINE
+/// and replies only cover what readline will replace
+const BASH: &str = r#"
+_ompl_dynamic() {
+    local line="${COMP_LINE:0:COMP_POINT}" server=() subs=() word value i cur
+    local re='(\\.|[^[:space:]\\])*$'
+    [[ "$line" =~ $re ]] && cur="${BASH_REMATCH[0]}"
+    for ((i = 1; i < COMP_CWORD; i++)); do
+        word="${COMP_WORDS[i]}"
+        case "$word" in
+            --port | --host) server+=("$word" "${COMP_WORDS[i + 1]}"); ((i++)) ;;
+            :) subs=() ;;
+            -* | =) ;;
+            *) subs+=("$word") ;;
+        esac
+    done
+    local IFS=$'\n'
+    case "${subs[0]} ${subs[1]}" in
+        "filter set" | "filter insert" | "filter replace")
+            if [[ "$cur" == *=* ]]; then
+                local tag="${cur%%=*}" partial="${cur##*[=,]}"
+                local done="${cur%"$partial"}"
+                [[ "$COMP_WORDBREAKS" == *=* ]] && done="${done#*=}"
+                tag="${tag//\\/}"
+                for value in $(ompl "${server[@]}" print values "${tag%[<>]}" 2>/dev/null); do
+                    [[ "$value" == "${partial//\\/}"* ]] && COMPREPLY+=("$done$(printf '%q' "$value")")
+                done
+                return
+            fi
+            ;;
+        "browse "*)
+            local tag="${COMP_WORDS[COMP_CWORD - 1]/#track/title}"
+            if [[ "$tag" == artist || "$tag" == album || "$tag" == title ]]; then
+                for value in $(ompl "${server[@]}" print values "$tag" 2>/dev/null); do
+                    [[ "$value" == "${cur//\\/}"* ]] && COMPREPLY+=("$(printf '%q' "$value")")
+                done
+                return
+            fi
+            ;;
+        "view switch" | "view remove")
+            for value in $(ompl "${server[@]}" view get 2>/dev/null | sed 's/^[ *][0-9]*: //'); do
+                [[ "$value" == "${cur//\\/}"* ]] && COMPREPLY+=("$(printf '%q' "$value")")
+            done
+            return
+            ;;
+    esac
+    _ompl "$@"
+}
+
+complete -F _ompl_dynamic -o nosort -o bashdefault -o default ompl
+"#;
+
+const FISH: &str = r#"
+function __ompl_server
+    set -l words (commandline -opc)
+    for opt in --port --host
+        if set -l i (contains -i -- $opt $words)
+            echo $opt
+            echo $words[(math $i + 1)]
+        end
+    end
+end
+
+function __ompl_filter_values
+    set -l tok (commandline -ct)
+    string match -q -- '*=*' $tok; or return
+    set -l tag (string replace -r -- '[<>]?=.*' '' $tok)
+    set -l done (string replace -r -- '[^=,]*$' '' $tok)
+    for value in (ompl (__ompl_server) print values $tag 2>/dev/null)
+        echo $done$value
+    end
+end
+
+function __ompl_browse_values
+    set -l tag (commandline -opc)[-1]
+    contains -- $tag artist album track; or return
+    test $tag = track; and set tag title
+    ompl (__ompl_server) print values $tag 2>/dev/null
+end
+
+complete -c ompl -n '__fish_seen_subcommand_from filter; and __fish_seen_subcommand_from set insert replace' -f -a '(__ompl_filter_values)'
+complete -c ompl -n '__fish_seen_subcommand_from browse' -f -a '(__ompl_browse_values)'
+complete -c ompl -n '__fish_seen_subcommand_from view; and __fish_seen_subcommand_from switch remove' -f -a '(ompl (__ompl_server) view get 2>/dev/null | string replace -r "^[ *]\d+: " "")'
+"#;
+
+impl From<CompletionShell> for Shell {
+    fn from(shell: CompletionShell) -> Self {
+        match shell {
+            CompletionShell::Bash => Shell::Bash,
+            CompletionShell::Elvish => Shell::Elvish,
+            CompletionShell::Fish => Shell::Fish,
+            CompletionShell::PowerShell => Shell::PowerShell,
+            CompletionShell::Zsh => Shell::Zsh,
+        }
+    }
+}
+
+/// Write the completion script for SHELL to stdout
+pub fn generate(shell: CompletionShell) -> std::io::Result<()> {
+    let mut stdo = std::io::stdout().lock();
+    clap_complete::generate(Shell::from(shell), &mut Args::command(), "ompl", &mut stdo);
+    match shell {
+        CompletionShell::Bash => stdo.write_all(BASH.as_bytes()),
+        CompletionShell::Fish => stdo.write_all(FISH.as_bytes()),
+        _ => Ok(()),
+    }
+}
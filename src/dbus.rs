@@ -0,0 +1,380 @@
+#![warn(missing_docs)]
+
+use crate::library::{LibEvt, Library};
+use crate::logging::*;
+use crate::{parse_args, parse_filter, process, Action, FilterCmd, RepeatCmd, SeekCmd, ShuffleCmd, SorterCmd, StatuslineCmd, ViewCmd, VolumeCmd};
+
+use std::collections::HashMap;
+use std::error::Error;
+use std::path::PathBuf;
+use std::sync::{Arc, Weak};
+use std::thread;
+use std::time::Duration;
+
+use zbus::blocking::{Connection, ConnectionBuilder};
+use zbus::{dbus_interface, fdo, SignalContext};
+
+const PATH: &str = "/org/ompl";
+
+/// Native interface mirroring the CLI.
+/// Anything not covered by a dedicated method can go through Command
+struct Ompl {
+    library: Weak<Library>,
+}
+
+impl Ompl {
+    fn library(&self) -> fdo::Result<Arc<Library>> {
+        self.library.upgrade().ok_or(fdo::Error::Failed(String::from("Library is closed")))
+    }
+
+    /// The library if ACTION may run, held to the same kiosk limits as TCP clients without a token
+    fn allowed(&self, action: &Action) -> fdo::Result<Arc<Library>> {
+        let library = self.library()?;
+        match library.kiosk_get() && !action.is_control() {
+            true => Err(fdo::Error::AccessDenied(String::from("Not allowed in kiosk mode"))),
+            false => Ok(library),
+        }
+    }
+}
+
+#[dbus_interface(name = "org.ompl")]
+impl Ompl {
+    // ## Playback ## {{{
+
+    fn play(&self) -> fdo::Result<()> {
+        self.allowed(&Action::Play).map(|l| l.play())
+    }
+
+    fn pause(&self) -> fdo::Result<()> {
+        self.allowed(&Action::Pause).map(|l| l.pause())
+    }
+
+    fn stop(&self) -> fdo::Result<()> {
+        self.allowed(&Action::Stop).map(|l| l.stop())
+    }
+
+    fn play_pause(&self) -> fdo::Result<()> {
+        self.allowed(&Action::PlayPause).map(|l| l.play_pause())
+    }
+
+    fn next(&self) -> fdo::Result<()> {
+        self.allowed(&Action::Next).map(|l| l.next())
+    }
+
+    fn previous(&self) -> fdo::Result<()> {
+        self.allowed(&Action::Previous).map(|l| l.previous())
+    }
+
+    /// Seek to an absolute position in seconds
+    fn seek_to(&self, secs: f64) -> fdo::Result<()> {
+        let time = Duration::from_secs_f64(secs.max(0.0));
+        self.allowed(&Action::Seek(SeekCmd::To { time })).map(|l| l.seek(time))
+    }
+
+    /// Seek relative to the current position in seconds
+    fn seek_by(&self, secs: f64) -> fdo::Result<()> {
+        let secs = secs as f32;
+        self.allowed(&Action::Seek(SeekCmd::By { secs })).map(|l| l.seek_by(secs))
+    }
+
+    /// Current position and total length in seconds, or (-1, -1) if unknown
+    fn times(&self) -> fdo::Result<(f64, f64)> {
+        Ok(self
+            .library()?
+            .snapshot()
+            .times()
+            .map(|(cur, tot)| (cur.as_secs_f64(), tot.as_secs_f64()))
+            .unwrap_or((-1.0, -1.0)))
+    }
+
+    // ## Playback ## }}}
+
+    // ## Properties ## {{{
+
+    /// 'playing'/'paused'/'stopped'
+    #[dbus_interface(property)]
+    fn status(&self) -> String {
+        match self.library.upgrade().map(|l| l.snapshot()) {
+            Some(snapshot) if snapshot.playing => "playing",
+            Some(snapshot) if snapshot.paused => "paused",
+            _ => "stopped",
+        }
+        .to_string()
+    }
+
+    /// 0.0 -> 1.0
+    #[dbus_interface(property)]
+    fn volume(&self) -> f64 {
+        self.library.upgrade().map(|l| l.snapshot().volume as f64).unwrap_or_default()
+    }
+
+    #[dbus_interface(property)]
+    fn set_volume(&self, volume: f64) -> zbus::Result<()> {
+        let amount = volume as f32;
+        self.allowed(&Action::Volume(VolumeCmd::Set { amount }))?.volume_set(amount);
+        Ok(())
+    }
+
+    #[dbus_interface(property)]
+    fn shuffle(&self) -> bool {
+        self.library.upgrade().map(|l| l.shuffle_get()).unwrap_or_default()
+    }
+
+    #[dbus_interface(property)]
+    fn set_shuffle(&self, shuffle: bool) -> zbus::Result<()> {
+        let action = Action::Shuffle(if shuffle { ShuffleCmd::True } else { ShuffleCmd::False });
+        self.allowed(&action)?.shuffle_set(shuffle);
+        Ok(())
+    }
+
+    /// 'true'/'track'/'false', same as the CLI
+    #[dbus_interface(property)]
+    fn repeat(&self) -> String {
+        match self.library.upgrade().map(|l| l.repeat_get()) {
+            Some(Some(true)) => "true",
+            Some(Some(false)) => "track",
+            _ => "false",
+        }
+        .to_string()
+    }
+
+    #[dbus_interface(property)]
+    fn set_repeat(&self, repeat: String) -> zbus::Result<()> {
+        let (cmd, mode) = match repeat.to_ascii_lowercase().as_str() {
+            "true" => (RepeatCmd::True, Some(true)),
+            "track" => (RepeatCmd::Track, Some(false)),
+            "false" => (RepeatCmd::False, None),
+            _ => {
+                error!("Invalid repeat mode \"{}\"", repeat);
+                return Err(fdo::Error::InvalidArgs(format!("Invalid repeat mode \"{}\"", repeat)).into());
+            }
+        };
+        self.allowed(&Action::Repeat(cmd))?.repeat_set(mode);
+        Ok(())
+    }
+
+    /// Statusline tagstring
+    #[dbus_interface(property)]
+    fn statusline(&self) -> String {
+        self.library.upgrade().map(|l| l.statusline_get()).unwrap_or_default()
+    }
+
+    #[dbus_interface(property)]
+    fn set_statusline(&self, tagstring: String) -> zbus::Result<()> {
+        let library = self.allowed(&Action::Statusline(StatuslineCmd::Set {
+            tagstring: tagstring.clone(),
+        }))?;
+        library.statusline_set(tagstring);
+        Ok(())
+    }
+
+    // ## Properties ## }}}
+
+    // ## Track ## {{{
+
+    /// All tags of the current track
+    fn track(&self) -> fdo::Result<HashMap<String, String>> {
+        Ok(self.library()?.track_get().map(|t| t.tags().clone()).unwrap_or_default())
+    }
+
+    /// Path of the current track
+    fn file(&self) -> fdo::Result<String> {
+        Ok(self
+            .library()?
+            .track_get()
+            .map(|t| t.path().to_str().unwrap_or("???").to_string())
+            .unwrap_or_default())
+    }
+
+    /// Format the current track with a tagstring
+    fn tagstring(&self, tagstring: String) -> fdo::Result<String> {
+        Ok(self.library()?.track_get().map(|t| t.tagstring(tagstring)).unwrap_or_default())
+    }
+
+    // ## Track ## }}}
+
+    // ## Filters & Sorters ## {{{
+
+    /// Filters in CLI syntax, ex `artist=A,B`
+    fn filters(&self) -> fdo::Result<Vec<String>> {
+        Ok(self.library()?.get_filters().into_iter().map(|f| f.to_string()).collect())
+    }
+
+    fn set_filters(&self, filters: Vec<String>) -> fdo::Result<()> {
+        let filters = filters
+            .iter()
+            .map(|f| parse_filter(f))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(fdo::Error::InvalidArgs)?;
+        let library = self.allowed(&Action::Filter(FilterCmd::Set { filters: filters.clone() }))?;
+        library.set_filters(filters);
+        Ok(())
+    }
+
+    fn insert_filter(&self, index: u32, filter: String) -> fdo::Result<()> {
+        let filter = parse_filter(&filter).map_err(fdo::Error::InvalidArgs)?;
+        let index = index as usize;
+        let library = self.allowed(&Action::Filter(FilterCmd::Insert {
+            index,
+            filter: filter.clone(),
+        }))?;
+        library.insert_filter(filter, index);
+        Ok(())
+    }
+
+    fn remove_filter(&self, index: u32) -> fdo::Result<()> {
+        let index = index as usize;
+        self.allowed(&Action::Filter(FilterCmd::Remove { index })).map(|l| l.remove_filter(index))
+    }
+
+    fn sorters(&self) -> fdo::Result<Vec<String>> {
+        Ok(self.library()?.get_sorters())
+    }
+
+    fn set_sorters(&self, tagstrings: Vec<String>) -> fdo::Result<()> {
+        let library = self.allowed(&Action::Sorter(SorterCmd::Set {
+            tagstrings: tagstrings.clone(),
+        }))?;
+        library.set_sorters(tagstrings);
+        Ok(())
+    }
+
+    fn insert_sorter(&self, index: u32, tagstring: String) -> fdo::Result<()> {
+        let index = index as usize;
+        let library = self.allowed(&Action::Sorter(SorterCmd::Insert {
+            index,
+            tagstring: tagstring.clone(),
+        }))?;
+        library.insert_sorter(tagstring, index);
+        Ok(())
+    }
+
+    fn remove_sorter(&self, index: u32) -> fdo::Result<()> {
+        let index = index as usize;
+        self.allowed(&Action::Sorter(SorterCmd::Remove { index })).map(|l| l.remove_sorter(index))
+    }
+
+    /// View names in order
+    fn views(&self) -> fdo::Result<Vec<String>> {
+        Ok(self.library()?.get_views())
+    }
+
+    fn switch_view(&self, index: u32) -> fdo::Result<()> {
+        let view = index.to_string();
+        self.allowed(&Action::View(ViewCmd::Switch { view }))
+            .map(|l| l.switch_view(index as usize))
+    }
+
+    // ## Filters & Sorters ## }}}
+
+    // ## Library ## {{{
+
+    fn append(&self, path: String) -> fdo::Result<()> {
+        self.allowed(&Action::Append { path: PathBuf::from(&path) })?.append_library(path);
+        Ok(())
+    }
+
+    fn purge(&self) -> fdo::Result<()> {
+        self.allowed(&Action::Purge)?.purge();
+        Ok(())
+    }
+
+    /// Run any client command using CLI arguments, ex ["filter", "get"], with ":" separating batched actions.
+    /// Returns the same response the CLI would print
+    fn command(&self, args: Vec<String>) -> fdo::Result<String> {
+        let args = parse_args(args).map_err(|e| fdo::Error::InvalidArgs(e.to_string()))?;
+        match args.action {
+            Action::Main { .. } | Action::Exit | Action::Pipe | Action::Complete { .. } => {
+                Err(fdo::Error::NotSupported(String::from("Not available over D-Bus")))
+            }
+            action => Ok(process(&self.allowed(&action)?, action)),
+        }
+    }
+
+    // ## Library ## }}}
+
+    /// Library state changed.
+    /// KIND is one of 'state', 'track', 'volume', 'mode', 'filters', 'queue', 'tracks', 'views', 'theme', 'unreadable'
+    #[dbus_interface(signal)]
+    async fn changed(ctxt: &SignalContext<'_>, kind: &str) -> zbus::Result<()>;
+
+    /// Non-fatal library error
+    #[dbus_interface(signal)]
+    async fn error(ctxt: &SignalContext<'_>, message: &str) -> zbus::Result<()>;
+}
+
+/// Start serving org.ompl on the session bus.
+/// The interface lives as long as the returned Connection.
+pub fn serve(library: &Arc<Library>, port: u16) -> Result<Connection, Box<dyn Error>> {
+    let name = if port.to_string() == crate::PORT {
+        String::from("org.ompl")
+    } else {
+        format!("org.ompl.port{}", port)
+    };
+
+    let connection = ConnectionBuilder::session()?
+        .name(name.as_str())?
+        .serve_at(
+            PATH,
+            Ompl {
+                library: Arc::downgrade(library),
+            },
+        )?
+        .build()?;
+
+    let mut libevt_r = library.get_receiver()?;
+    let iface = connection.object_server().interface::<_, Ompl>(PATH)?;
+    thread::Builder::new().name(String::from("D-Bus Signals")).spawn(move || {
+        let result = zbus::block_on(async {
+            while let Ok(event) = libevt_r.recv() {
+                let ctxt = iface.signal_context();
+                let ompl = iface.get();
+                let kind = match event {
+                    LibEvt::Clock | LibEvt::Sorting(..) | LibEvt::ArtChips | LibEvt::Artist => continue,
+                    LibEvt::State => {
+                        ompl.status_changed(ctxt).await?;
+                        "state"
+                    }
+                    LibEvt::Track => "track",
+                    LibEvt::Volume => {
+                        ompl.volume_changed(ctxt).await?;
+                        "volume"
+                    }
+                    LibEvt::Mode => {
+                        ompl.shuffle_changed(ctxt).await?;
+                        ompl.repeat_changed(ctxt).await?;
+                        "mode"
+                    }
+                    LibEvt::Filters(_) => "filters",
+                    LibEvt::Queue => "queue",
+                    LibEvt::Tracks => "tracks",
+                    LibEvt::Views => "views",
+                    LibEvt::Unreadable => "unreadable",
+                    LibEvt::Theme => {
+                        ompl.statusline_changed(ctxt).await?;
+                        "theme"
+                    }
+                    LibEvt::Error(message) => {
+                        drop(ompl);
+                        Ompl::error(ctxt, &message).await?;
+                        continue;
+                    }
+                    LibEvt::Offline(root) => {
+                        drop(ompl);
+                        Ompl::error(ctxt, &format!("{} is offline", root.display())).await?;
+                        continue;
+                    }
+                };
+                drop(ompl);
+                Ompl::changed(ctxt, kind).await?
+            }
+            Ok::<(), zbus::Error>(())
+        });
+        if let Err(e) = result {
+            error!("D-Bus signals stopped: {}", e)
+        }
+    })?;
+
+    info!("D-Bus interface at {} {}", name, PATH);
+    Ok(connection)
+}
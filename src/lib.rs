@@ -0,0 +1,121 @@
+//! OMPL Opinionated Music Player/Library
+//! Everything licensed under GNU General Public License V3
+//! Alternatively, OMPL can be licensed under GOGAC which means if you can
+//! officially 1v1 me and win you get it licensed under what's effectively MIT
+//!
+//! The player engine without any frontend.
+//! [`library::Library`] is the whole command API: every CLI action is a method on it.
+//! Frontends subscribe with [`library::Library::get_receiver`] and redraw on [`library::LibEvt`]s.
+//!
+//! ```no_run
+//! use ompl_core::library::{Backend, LibEvt, Library};
+//!
+//! let library = Library::new(Backend::Default, None, None).unwrap();
+//! library.append_library("/path/to/music");
+//! library.play();
+//!
+//! let mut events = library.get_receiver().unwrap();
+//! while let Ok(event) = events.recv() {
+//!     if event == LibEvt::Track {
+//!         println!("{}", library.statusline_get_format());
+//!     }
+//! }
+//! ```
+
+#![warn(missing_docs)]
+
+/// Logging macros
+pub mod logging {
+    // {{{
+
+    use std::sync::{
+        atomic::{AtomicBool, AtomicU8},
+        Mutex,
+    };
+
+    /// Verbosity level of log to print/queue
+    pub static LOG_LEVEL: AtomicU8 = AtomicU8::new(0);
+    /// Print log if true else save for later
+    pub static PRINT_LOG: AtomicBool = AtomicBool::new(true);
+    /// The backlog of logs to print when resumed
+    pub static BACKLOG: Mutex<Vec<(u8, String)>> = Mutex::new(Vec::new());
+
+    /// If $v <= LOG_LEVEL print values
+    #[macro_export]
+    macro_rules! log {
+        ($v:expr, $($fmt_args:tt)*) => {
+            #[allow(unused_comparisons)]
+            if $crate::logging::LOG_LEVEL.load(std::sync::atomic::Ordering::Relaxed) >= $v {
+                if $crate::logging::PRINT_LOG.load(std::sync::atomic::Ordering::Relaxed) {
+                    println!($($fmt_args)*)
+                // Store if paused
+                } else if let Ok(mut backlog) = $crate::logging::BACKLOG.lock() {
+                    backlog.push(($v, format!($($fmt_args)*)))
+                }
+            }
+        };
+    }
+
+    /// Pause log and queue further entries
+    #[macro_export]
+    macro_rules! log_pause {
+        () => {
+            $crate::logging::PRINT_LOG.store(false, std::sync::atomic::Ordering::Relaxed)
+        };
+    }
+
+    /// Resume log and print queued entries
+    #[macro_export]
+    macro_rules! log_resume {
+        () => {
+            $crate::logging::PRINT_LOG.store(true, std::sync::atomic::Ordering::Relaxed);
+            if let Ok(mut backlog) = $crate::logging::BACKLOG.lock() {
+                for (n, s) in backlog.drain(..) {
+                    if n == 0 {
+                        eprintln!("{}", s);
+                    } else {
+                        println!("{}", s);
+                    }
+                }
+                backlog.shrink_to_fit();
+            }
+        };
+    }
+
+    /// Level 0
+    #[macro_export]
+    macro_rules! error {
+        ($($fmt_args:tt)*) => {$crate::log!(0, $($fmt_args)*)}
+    }
+    /// Level 1
+    #[macro_export]
+    macro_rules! info {
+        ($($fmt_args:tt)*) => {$crate::log!(1, $($fmt_args)*)}
+    }
+    /// Level 2
+    #[macro_export]
+    macro_rules! bench {
+        ($($fmt_args:tt)*) => {$crate::log!(2, $($fmt_args)*)}
+    }
+    /// Level 3
+    #[macro_export]
+    macro_rules! debug {
+        ($($fmt_args:tt)*) => {$crate::log!(3, $($fmt_args)*)}
+    }
+
+    pub use crate::{bench, debug, error, info, log, log_pause, log_resume};
+} // }}}
+
+/// petty 100
+#[macro_export]
+macro_rules! try_block {
+    ($enclosed:block) => {
+        || -> Result<(), Box<dyn std::error::Error>> { $enclosed }()
+    };
+}
+
+/// Player engine: tracks, filters, sorters, and audio backends
+pub mod library;
+
+/// Translations of UI strings
+pub mod locale;
@@ -0,0 +1,151 @@
+#![warn(missing_docs)]
+
+//! Tags from an outside program, for mood or energy models too big to ship with ompl.
+//! The command is run once per file and prints a JSON object, like `{"mood": "happy", "energy": 0.8}`,
+//! whose keys become synthetic tags. Results are kept in the stats file so each file is only analyzed once.
+
+use std::collections::BTreeMap;
+use std::ffi::OsString;
+use std::io::Read;
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::thread::sleep;
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// Longest one file may take before the command is killed. Models can be slow, so this is generous
+const TIMEOUT: Duration = Duration::from_secs(300);
+
+/// Command run on every file without stored results
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Analyzer {
+    /// Program and arguments. Any argument that's exactly `{}` is replaced by the file's path, else it goes last
+    pub command: Vec<String>,
+}
+
+impl Analyzer {
+    /// Arguments for the file at PATH, without the program
+    fn args(&self, path: &Path) -> Vec<OsString> {
+        let mut args: Vec<OsString> = self.command.iter().skip(1).map(OsString::from).collect();
+        let mut placed = false;
+        for arg in args.iter_mut().filter(|a| *a == "{}") {
+            *arg = path.as_os_str().to_owned();
+            placed = true;
+        }
+        if !placed {
+            args.push(path.as_os_str().to_owned())
+        }
+        args
+    }
+
+    /// Run the command on the file at PATH, returning the tags it printed
+    pub fn analyze(&self, path: &Path) -> Result<BTreeMap<String, String>, String> {
+        let program = self.command.first().ok_or("No analyzer command set")?;
+        let mut child = Command::new(program)
+            .args(self.args(path))
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .map_err(|e| format!("Could not run analyzer {}: {}", program, e))?;
+
+        // read alongside so a chatty command never blocks on a full pipe
+        let mut stdout = child.stdout.take().ok_or("Analyzer has no output")?;
+        let reader = std::thread::spawn(move || {
+            let mut output = String::new();
+            stdout.read_to_string(&mut output).map(|_| output)
+        });
+
+        let start = Instant::now();
+        loop {
+            match child.try_wait() {
+                Ok(Some(status)) if status.success() => break,
+                Ok(Some(status)) => return Err(format!("Analyzer {} failed with {}", program, status)),
+                Ok(None) if start.elapsed() > TIMEOUT => {
+                    let _ = child.kill();
+                    let _ = child.wait();
+                    return Err(format!("Analyzer {} took longer than {:?}", program, TIMEOUT));
+                }
+                Ok(None) => sleep(Duration::from_millis(20)),
+                Err(e) => return Err(e.to_string()),
+            }
+        }
+        let output = reader
+            .join()
+            .map_err(|_| String::from("Analyzer output reader panicked"))?
+            .map_err(|e| format!("Could not read analyzer output: {}", e))?;
+        parse(&output)
+    }
+}
+
+/// Tags in the JSON object OUTPUT. Keys are lowercased, numbers and booleans written out,
+/// lists joined by `; `, and nulls or nested objects left out
+fn parse(output: &str) -> Result<BTreeMap<String, String>, String> {
+    let value: Value = serde_json::from_str(output.trim()).map_err(|e| format!("Analyzer didn't print JSON: {}", e))?;
+    let Value::Object(object) = value else {
+        return Err(String::from("Analyzer didn't print a JSON object"));
+    };
+    let text = |value: &Value| match value {
+        Value::String(s) => Some(s.trim().to_string()),
+        Value::Number(n) => Some(n.to_string()),
+        Value::Bool(b) => Some(b.to_string()),
+        _ => None,
+    };
+    Ok(object
+        .iter()
+        .filter_map(|(key, value)| {
+            let value = match value {
+                Value::Array(items) => items.iter().filter_map(text).collect::<Vec<_>>().join("; "),
+                value => text(value)?,
+            };
+            let key = key.trim().to_lowercase();
+            (!key.is_empty() && !value.is_empty()).then_some((key, value))
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parsed() {
+        let tags =
+            parse(r#"{"Mood": "happy", "energy": 0.8, "danceable": true, "moods": ["happy", "party"], "model": {"v": 2}, "key": null}"#).unwrap();
+        let expected: BTreeMap<String, String> = [("mood", "happy"), ("energy", "0.8"), ("danceable", "true"), ("moods", "happy; party")]
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .into();
+        assert_eq!(tags, expected);
+        assert!(parse("[1, 2]").is_err());
+        assert!(parse("Segmentation fault").is_err());
+    }
+
+    #[test]
+    fn placeholder() {
+        let path = Path::new("/music/song.flac");
+        let analyzer = |command: &[&str]| Analyzer {
+            command: command.iter().map(|s| s.to_string()).collect(),
+        };
+        assert_eq!(
+            analyzer(&["essentia", "--in", "{}", "--json"]).args(path),
+            ["--in", "/music/song.flac", "--json"]
+        );
+        assert_eq!(analyzer(&["mood.py"]).args(path), ["/music/song.flac"]);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn ran() {
+        let analyzer = Analyzer {
+            command: ["sh", "-c", r#"echo "{\"path\": \"$0\"}""#, "{}"].map(String::from).to_vec(),
+        };
+        let tags = analyzer.analyze(Path::new("/music/song.flac")).unwrap();
+        assert_eq!(tags.get("path").map(|s| s.as_str()), Some("/music/song.flac"));
+        let failing = Analyzer {
+            command: vec![String::from("false")],
+        };
+        assert!(failing.analyze(Path::new("/music/song.flac")).is_err());
+    }
+}
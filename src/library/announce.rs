@@ -0,0 +1,89 @@
+#![warn(missing_docs)]
+
+use super::Track;
+
+use std::process::{Command, Stdio};
+use std::thread::sleep;
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+
+/// Longest an announcement may hold up the next track before it's killed
+const TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Command run before each track starts, like a text to speech program or an audio cue
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Announcer {
+    /// Program and arguments. Any argument that's exactly `{}` is replaced by the parsed tagstring
+    pub command: Vec<String>,
+    /// What's announced, ex `<title><artist| by <artist>>`
+    pub tagstring: String,
+}
+
+impl Announcer {
+    /// Arguments for TRACK, without the program
+    fn args(&self, track: &Track) -> Vec<String> {
+        let text = track.tagstring(&self.tagstring);
+        self.command
+            .iter()
+            .skip(1)
+            .map(|arg| if arg == "{}" { text.clone() } else { arg.clone() })
+            .collect()
+    }
+
+    /// Run the command for TRACK and wait for it to finish
+    pub fn announce(&self, track: &Track) -> Result<(), String> {
+        let program = self.command.first().ok_or("No announce command set")?;
+        let mut child = Command::new(program)
+            .args(self.args(track))
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .spawn()
+            .map_err(|e| format!("Could not run announce command {}: {}", program, e))?;
+
+        let start = Instant::now();
+        loop {
+            match child.try_wait() {
+                Ok(Some(status)) if status.success() => return Ok(()),
+                Ok(Some(status)) => return Err(format!("Announce command {} failed with {}", program, status)),
+                Ok(None) if start.elapsed() > TIMEOUT => {
+                    let _ = child.kill();
+                    let _ = child.wait();
+                    return Err(format!("Announce command {} took longer than {:?}", program, TIMEOUT));
+                }
+                Ok(None) => sleep(Duration::from_millis(20)),
+                Err(e) => return Err(e.to_string()),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn placeholder() {
+        let announcer = Announcer {
+            command: ["espeak", "-s", "160", "{}"].map(String::from).to_vec(),
+            tagstring: String::from("<!title|Nothing yet>"),
+        };
+        let track = Track::tagged("/music/song.flac", &[]);
+        assert_eq!(announcer.args(&track), ["-s", "160", "Nothing yet"]);
+    }
+
+    #[test]
+    fn failures() {
+        let track = Track::tagged("/music/song.flac", &[]);
+        let announcer = |command: &[&str]| Announcer {
+            command: command.iter().map(|s| s.to_string()).collect(),
+            tagstring: String::from("title"),
+        };
+        assert!(announcer(&[]).announce(&track).is_err());
+        assert!(announcer(&["ompl-no-such-program"]).announce(&track).is_err());
+        if cfg!(unix) {
+            assert!(announcer(&["true"]).announce(&track).is_ok());
+            assert!(announcer(&["false"]).announce(&track).is_err());
+        }
+    }
+}
@@ -0,0 +1,241 @@
+#![warn(missing_docs)]
+
+//! Artist biographies and pictures for the TUI's info panel.
+//! `artist.txt` and `artist.jpg` or `artist.png` are looked for next to the track and one folder up,
+//! where an Artist/Album layout keeps them. Artists without them can be asked of a provider command,
+//! whose answers are cached on disk so each artist is only looked up once.
+
+use super::track::read_image;
+use super::{RawImage, Track};
+
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::Arc;
+use std::thread::sleep;
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+
+/// Name of the biography and picture files
+const STEM: &str = "artist";
+/// Longest a lookup may take before the provider is killed
+const TIMEOUT: Duration = Duration::from_secs(30);
+/// Leads a provider's output line naming a picture
+const IMAGE_PREFIX: &str = "image:";
+
+/// What's shown about one artist
+#[derive(Debug, Default)]
+pub struct ArtistInfo {
+    /// As tagged on the track
+    pub name: String,
+    /// Biography text
+    pub bio: Option<String>,
+    /// Picture of the artist
+    pub image: Option<Arc<RawImage>>,
+}
+
+impl ArtistInfo {
+    /// Whether there's anything to show besides the name
+    pub fn is_empty(&self) -> bool {
+        self.bio.is_none() && self.image.is_none()
+    }
+}
+
+/// Command looking up artists without local files
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ArtistProvider {
+    /// Program and arguments. Any argument that's exactly `{}` is replaced by the artist's name, else it goes last
+    pub command: Vec<String>,
+}
+
+impl ArtistProvider {
+    /// Arguments for ARTIST, without the program
+    fn args(&self, artist: &str) -> Vec<String> {
+        let mut args: Vec<String> = self.command.iter().skip(1).cloned().collect();
+        let mut placed = false;
+        for arg in args.iter_mut().filter(|a| *a == "{}") {
+            *arg = artist.to_string();
+            placed = true;
+        }
+        if !placed {
+            args.push(artist.to_string())
+        }
+        args
+    }
+
+    /// Run the command for ARTIST and return what it printed
+    pub fn lookup(&self, artist: &str) -> Result<String, String> {
+        let program = self.command.first().ok_or("No artist provider set")?;
+        let mut child = Command::new(program)
+            .args(self.args(artist))
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .map_err(|e| format!("Could not run artist provider {}: {}", program, e))?;
+
+        // read alongside so a long biography never blocks on a full pipe
+        let mut stdout = child.stdout.take().ok_or("Artist provider has no output")?;
+        let reader = std::thread::spawn(move || {
+            let mut output = Vec::new();
+            stdout.read_to_end(&mut output).map(|_| output)
+        });
+
+        let start = Instant::now();
+        loop {
+            match child.try_wait() {
+                Ok(Some(status)) if status.success() => break,
+                Ok(Some(status)) => return Err(format!("Artist provider {} failed with {}", program, status)),
+                Ok(None) if start.elapsed() > TIMEOUT => {
+                    let _ = child.kill();
+                    let _ = child.wait();
+                    return Err(format!("Artist provider {} took longer than {:?}", program, TIMEOUT));
+                }
+                Ok(None) => sleep(Duration::from_millis(20)),
+                Err(e) => return Err(e.to_string()),
+            }
+        }
+        let output = reader
+            .join()
+            .map_err(|_| String::from("Artist provider output reader panicked"))?
+            .map_err(|e| format!("Could not read artist provider output: {}", e))?;
+        Ok(String::from_utf8_lossy(&output).to_string())
+    }
+}
+
+/// Split provider OUTPUT into the picture named by an `image: PATH` first line and the biography after it
+fn parse(output: &str) -> (Option<PathBuf>, Option<String>) {
+    let output = output.trim();
+    let (image, bio) = match output.lines().next().and_then(|l| l.strip_prefix(IMAGE_PREFIX)) {
+        Some(path) => (Some(PathBuf::from(path.trim())), output.split_once('\n').map(|s| s.1).unwrap_or_default()),
+        None => (None, output),
+    };
+    let bio = bio.trim();
+    (image.filter(|p| !p.as_os_str().is_empty()), (!bio.is_empty()).then(|| bio.to_string()))
+}
+
+/// The file in DIR holding a provider's answer for ARTIST
+fn cache_file(dir: &Path, artist: &str) -> PathBuf {
+    let mut hasher = DefaultHasher::new();
+    artist.hash(&mut hasher);
+    // readable enough to find by hand, the hash keeps "AC/DC" and "AC DC" apart
+    let name: String = artist.chars().map(|c| if c.is_alphanumeric() { c } else { '_' }).take(64).collect();
+    dir.join(format!("{}-{:016x}.txt", name, hasher.finish()))
+}
+
+/// artist.txt and an artist picture next to TRACK or one folder up, nearest first
+fn local(track: &Track) -> (Option<String>, Option<PathBuf>) {
+    let folders = track.path().ancestors().skip(1).take(2);
+    let (mut bio, mut image) = (None, None);
+    for folder in folders {
+        let Ok(entries) = folder.read_dir() else { continue };
+        for path in entries.filter_map(|e| e.ok()).map(|e| e.path()) {
+            if !path.file_stem().and_then(|s| s.to_str()).is_some_and(|s| s.eq_ignore_ascii_case(STEM)) {
+                continue;
+            }
+            let ext = path.extension().and_then(|e| e.to_str()).map(|e| e.to_lowercase()).unwrap_or_default();
+            if ext == "txt" && bio.is_none() {
+                bio = std::fs::read_to_string(&path)
+                    .ok()
+                    .map(|b| b.trim().to_string())
+                    .filter(|b| !b.is_empty());
+            } else if Track::ART_SEARCH_EXTS.contains(&ext.as_str()) && image.is_none() {
+                image = Some(path)
+            }
+        }
+    }
+    (bio, image)
+}
+
+/// Everything known about the artist of TRACK, from local files first, then CACHE, then PROVIDER.
+/// Provider answers are written to CACHE. None if the track has no artist tag
+pub fn find(track: &Track, provider: Option<&ArtistProvider>, cache: Option<&Path>) -> Option<Result<ArtistInfo, String>> {
+    let name = track.tags().get("artist")?.trim().to_string();
+    if name.is_empty() {
+        return None;
+    }
+    let (mut bio, mut image) = local(track);
+    let mut error = None;
+    if bio.is_none() || image.is_none() {
+        let cached = cache.and_then(|dir| std::fs::read_to_string(cache_file(dir, &name)).ok());
+        let answer = match (cached, provider) {
+            (Some(cached), _) => Some(cached),
+            (None, Some(provider)) => match provider.lookup(&name) {
+                Ok(answer) => {
+                    if let Some(dir) = cache {
+                        let _ = std::fs::create_dir_all(dir);
+                        let _ = std::fs::write(cache_file(dir, &name), &answer);
+                    }
+                    Some(answer)
+                }
+                Err(e) => {
+                    error = Some(e);
+                    None
+                }
+            },
+            (None, None) => None,
+        };
+        if let Some(answer) = answer {
+            let (provided_image, provided_bio) = parse(&answer);
+            bio = bio.or(provided_bio);
+            image = image.or(provided_image);
+        }
+    }
+    let info = ArtistInfo {
+        name,
+        bio,
+        image: image.and_then(|p| read_image(&p)).map(Arc::new),
+    };
+    Some(match (error, info.is_empty()) {
+        (Some(e), true) => Err(e),
+        _ => Ok(info),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parsed() {
+        assert_eq!(
+            parse("image: /tmp/a.jpg\nFormed in 1970.\n"),
+            (Some(PathBuf::from("/tmp/a.jpg")), Some(String::from("Formed in 1970.")))
+        );
+        assert_eq!(parse("Formed in 1970."), (None, Some(String::from("Formed in 1970."))));
+        assert_eq!(parse("image:\n\n"), (None, None));
+    }
+
+    #[test]
+    fn cache_names() {
+        let dir = Path::new("/cache");
+        assert_ne!(cache_file(dir, "AC/DC"), cache_file(dir, "AC DC"));
+        assert!(cache_file(dir, "AC/DC").starts_with(dir));
+    }
+
+    #[test]
+    fn local_files() {
+        let root = std::env::temp_dir().join(format!("ompl-artist-{}", std::process::id()));
+        let album = root.join("Album");
+        std::fs::create_dir_all(&album).unwrap();
+        std::fs::write(root.join("Artist.txt"), "One folder up\n").unwrap();
+        std::fs::write(album.join("song.flac"), "").unwrap();
+        let mut track = Track::new(album.join("song.flac")).unwrap();
+        track.tag_set("artist", String::from("Someone"));
+
+        let cache = root.join("cache");
+        std::fs::create_dir_all(&cache).unwrap();
+        std::fs::write(cache_file(&cache, "Someone"), "Cached").unwrap();
+        let info = find(&track, None, Some(&cache)).unwrap().unwrap();
+        // local files win over the cache
+        assert_eq!((info.name.as_str(), info.bio.as_deref()), ("Someone", Some("One folder up")));
+
+        std::fs::remove_file(root.join("Artist.txt")).unwrap();
+        let info = find(&track, None, Some(&cache)).unwrap().unwrap();
+        assert_eq!(info.bio.as_deref(), Some("Cached"));
+        let _ = std::fs::remove_dir_all(&root);
+    }
+}
@@ -0,0 +1,145 @@
+#![warn(missing_docs)]
+
+//! Tempo estimation for tracks without a bpm tag.
+//! Onsets are found as rises in short-term energy, and the tempo is the beat length those onsets repeat at most,
+//! leaning towards moderate tempos when half or double time fit about as well.
+
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+
+/// Tag tempos are stored under, as the bpm tag files have
+pub const TAG: &str = "bpm";
+
+/// What's done with a tempo worked out for a track missing the bpm tag
+#[derive(Debug, Default, PartialEq, Eq, Clone, Copy, Serialize, Deserialize, ValueEnum)]
+pub enum BpmAnalysis {
+    /// Tracks without a bpm tag are left alone
+    #[default]
+    Off,
+    /// Measure tracks once they're decoded and keep the tempo in the stats file
+    Store,
+    /// Also write the tempo into mp3 and flac files
+    Write,
+}
+
+/// Slowest tempo reported. Slower beats are read as double time
+#[cfg(any(test, feature = "backend-sympal"))]
+const MIN: f32 = 60.0;
+/// Fastest tempo reported. Faster beats are read as half time
+#[cfg(any(test, feature = "backend-sympal"))]
+const MAX: f32 = 200.0;
+/// Tempo the estimate leans towards when picking between half and double time
+#[cfg(any(test, feature = "backend-sympal"))]
+const CENTER: f32 = 120.0;
+/// How many envelope values are taken per second of audio
+#[cfg(any(test, feature = "backend-sympal"))]
+const ENVELOPE_RATE: u32 = 200;
+/// Longest stretch measured, from the middle of the track where intros and outros won't skew it
+#[cfg(any(test, feature = "backend-sympal"))]
+const WINDOW: f32 = 120.0;
+/// Shortest track worth measuring, in seconds
+#[cfg(any(test, feature = "backend-sympal"))]
+const SHORTEST: f32 = 8.0;
+
+/// How strongly onsets rise in each envelope step of interleaved SAMPLES, and the envelope's rate
+#[cfg(any(test, feature = "backend-sympal"))]
+fn onsets(samples: &[i16], channels: usize, rate: u32) -> (Vec<f32>, f32) {
+    let hop = (rate / ENVELOPE_RATE).max(1) as usize;
+    let energy: Vec<f32> = samples
+        .chunks(hop * channels)
+        .map(|chunk| {
+            let sum: f32 = chunk.iter().map(|s| (*s as f32 / -(i16::MIN as f32)).powi(2)).sum();
+            // log so quiet and loud passages count alike
+            (1.0 + 1000.0 * sum / chunk.len() as f32).ln()
+        })
+        .collect();
+    let flux = energy.windows(2).map(|w| (w[1] - w[0]).max(0.0)).collect();
+    (flux, rate as f32 / hop as f32)
+}
+
+/// Tempo of interleaved SAMPLES with CHANNELS at RATE, None for silence or audio too short to tell
+#[cfg(any(test, feature = "backend-sympal"))]
+pub fn detect(samples: &[i16], channels: usize, rate: u32) -> Option<f32> {
+    if channels == 0 || rate == 0 {
+        return None;
+    }
+    let frames = samples.len() / channels;
+    if (frames as f32) < SHORTEST * rate as f32 {
+        return None;
+    }
+    let window = frames.min((WINDOW * rate as f32) as usize);
+    let start = (frames - window) / 2 * channels;
+    let (mut flux, envelope_rate) = onsets(&samples[start..start + window * channels], channels, rate);
+
+    let mean = flux.iter().sum::<f32>() / flux.len() as f32;
+    flux.iter_mut().for_each(|f| *f -= mean);
+    let (shortest, longest) = (
+        (60.0 * envelope_rate / MAX).floor() as usize,
+        (60.0 * envelope_rate / MIN).ceil() as usize,
+    );
+    if flux.len() < longest * 4 || shortest < 2 {
+        return None;
+    }
+
+    // autocorrelation over every lag in range, plus one either side for refining the peak
+    let correlation: Vec<f32> = (shortest - 1..=longest + 1)
+        .map(|lag| flux.iter().zip(&flux[lag..]).map(|(a, b)| a * b).sum::<f32>() / (flux.len() - lag) as f32)
+        .collect();
+    let weight = |lag: f32| (-0.5 * (60.0 * envelope_rate / lag / CENTER).log2().powi(2)).exp();
+    let (best, peak) = (1..correlation.len() - 1)
+        .map(|i| (i, correlation[i] * weight((shortest - 1 + i) as f32)))
+        .max_by(|a, b| a.1.total_cmp(&b.1))?;
+    if peak <= 0.0 {
+        return None;
+    }
+
+    // parabola through the peak and its neighbours for a lag between envelope steps
+    let (a, b, c) = (correlation[best - 1], correlation[best], correlation[best + 1]);
+    let curve = a - 2.0 * b + c;
+    let offset = if curve < 0.0 { (0.5 * (a - c) / curve).clamp(-0.5, 0.5) } else { 0.0 };
+    let lag = (shortest - 1 + best) as f32 + offset;
+    Some((600.0 * envelope_rate / lag).round() / 10.0)
+}
+
+/// BPM as it's tagged, to the nearest beat
+pub fn format(bpm: f32) -> String {
+    format!("{:.0}", bpm)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Stereo clicks at BPM for SECONDS, with a quieter offbeat
+    fn clicks(bpm: f32, seconds: f32, rate: u32) -> Vec<i16> {
+        let beat = 60.0 / bpm;
+        (0..(seconds * rate as f32) as usize)
+            .flat_map(|n| {
+                let t = n as f32 / rate as f32;
+                let into = t % beat;
+                let level = match into < beat / 2.0 {
+                    true => 1.0,
+                    false => 0.3,
+                };
+                let since = into % (beat / 2.0);
+                let s = (level * 12000.0 * (-since * 80.0).exp() * (t * 2.0 * std::f32::consts::PI * 1000.0).sin()) as i16;
+                [s, s]
+            })
+            .collect()
+    }
+
+    #[test]
+    fn detected() {
+        for bpm in [90.0, 120.0, 128.0, 140.0] {
+            let found = detect(&clicks(bpm, 30.0, 44100), 2, 44100).unwrap();
+            assert!((found - bpm).abs() < 1.0, "{} detected as {}", bpm, found);
+        }
+    }
+
+    #[test]
+    fn nothing_to_tell() {
+        assert_eq!(detect(&vec![0; 44100 * 2 * 20], 2, 44100), None);
+        assert_eq!(detect(&clicks(120.0, 4.0, 44100), 2, 44100), None);
+        assert_eq!(detect(&[], 0, 44100), None);
+    }
+}
@@ -0,0 +1,44 @@
+#![warn(missing_docs)]
+
+//! Cooperative cancellation for long running work like scans and sorts.
+//! Loops check [`Cancel::cancelled`] between steps and stop where they are, leaving the library as it was.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+/// Shared flag handed to anything that can be stopped partway.
+/// Clones see the same flag, so one can be given to each worker thread
+#[derive(Clone, Debug, Default)]
+pub struct Cancel(Arc<AtomicBool>);
+
+impl Cancel {
+    /// Ask everything holding this to stop
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed)
+    }
+
+    /// Whether work should stop
+    pub fn cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+
+    /// The underlying flag, for things like signal handlers that set it directly
+    pub fn flag(&self) -> Arc<AtomicBool> {
+        self.0.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Cancel;
+
+    #[test]
+    fn shared() {
+        let cancel = Cancel::default();
+        let worker = cancel.clone();
+        assert!(!worker.cancelled());
+        cancel.cancel();
+        assert!(worker.cancelled());
+        assert!(!Cancel::default().cancelled());
+    }
+}
@@ -0,0 +1,265 @@
+#![warn(missing_docs)]
+
+//! How tagstrings compare when sorting the library and the filter panes.
+//! Defaults to the same natural, case and accent insensitive order ompl always used.
+
+use std::cmp::Ordering;
+use std::fmt::Display;
+
+use lexical_sort::iter::iterate_lexical_char;
+use lexical_sort::natural_lexical_cmp;
+use serde::{Deserialize, Serialize};
+
+/// Letters past 'z' in locales that alphabetize them last are mapped onto the private use area
+const TAIL: char = '\u{E000}';
+
+/// Articles dropped for every locale, since most tags are English anyway
+const ENGLISH: &[&str] = &["the ", "a ", "an "];
+
+/// What a locale changes about ordering
+#[derive(Default)]
+struct Rules {
+    /// Lowercase leading words, including the trailing space or apostrophe
+    articles: &'static [&'static str],
+    /// Letters spelled out before comparing
+    expand: &'static [(char, &'static str)],
+    /// Letters sorted after 'z', in order
+    tail: &'static [char],
+}
+
+impl Rules {
+    /// Rules for the language part of LOCALE, so "sv_SE.UTF-8" and "sv" are the same
+    fn new(locale: &str) -> Self {
+        let language = locale.split(['_', '-', '.']).next().unwrap_or_default().to_ascii_lowercase();
+        match language.as_str() {
+            "de" => Self {
+                articles: &["der ", "die ", "das ", "ein ", "eine "],
+                expand: &[('ä', "ae"), ('ö', "oe"), ('ü', "ue")],
+                ..Default::default()
+            },
+            "es" => Self {
+                articles: &["el ", "la ", "los ", "las ", "un ", "una "],
+                ..Default::default()
+            },
+            "fr" => Self {
+                articles: &["le ", "la ", "les ", "l'", "un ", "une "],
+                ..Default::default()
+            },
+            "it" => Self {
+                articles: &["il ", "lo ", "la ", "i ", "gli ", "le ", "l'", "un ", "una "],
+                ..Default::default()
+            },
+            "nl" => Self {
+                articles: &["de ", "het ", "een "],
+                ..Default::default()
+            },
+            "sv" | "fi" => Self {
+                tail: &['å', 'ä', 'ö'],
+                ..Default::default()
+            },
+            "da" | "nb" | "nn" | "no" => Self {
+                tail: &['æ', 'ø', 'å'],
+                ..Default::default()
+            },
+            _ => Self::default(),
+        }
+    }
+}
+
+/// Options for comparing tagstrings
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Collation {
+    /// Language rules like "sv" or "de_DE", None to sort the same for every language
+    pub locale: Option<String>,
+    /// Sort "The Beatles" under B. Drops English articles plus the locale's own
+    pub articles: bool,
+    /// Compare "Björk" as "Bjork" instead of putting accented letters after every plain one
+    pub transliterate: bool,
+    /// Sorters read tags like artistsort in place of artist when a track has them
+    pub sort_tags: bool,
+}
+
+impl Default for Collation {
+    fn default() -> Self {
+        Self {
+            locale: None,
+            articles: false,
+            transliterate: true,
+            sort_tags: true,
+        }
+    }
+}
+
+impl Display for Collation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "locale: {}\narticles: {}\ntransliterate: {}\nsort-tags: {}",
+            self.locale.as_deref().unwrap_or("none"),
+            self.articles,
+            self.transliterate,
+            self.sort_tags
+        )
+    }
+}
+
+impl Collation {
+    fn rules(&self) -> Rules {
+        self.locale.as_deref().map(Rules::new).unwrap_or_default()
+    }
+
+    /// S without a leading article, unless that's all there is
+    fn strip<'a>(&self, s: &'a str, rules: &Rules) -> &'a str {
+        if !self.articles {
+            return s;
+        }
+        for article in ENGLISH.iter().chain(rules.articles) {
+            if let Some(head) = s.get(..article.len()) {
+                let rest = s[article.len()..].trim_start();
+                if head.eq_ignore_ascii_case(article) && !rest.is_empty() {
+                    return rest;
+                }
+            }
+        }
+        s
+    }
+
+    /// Lowercase characters of S as they're compared
+    fn chars(&self, s: &str, rules: &Rules) -> Vec<char> {
+        let mut chars = Vec::with_capacity(s.len());
+        for c in s.chars().flat_map(char::to_lowercase) {
+            if let Some(n) = rules.tail.iter().position(|t| *t == c) {
+                chars.push(char::from_u32(TAIL as u32 + n as u32).unwrap_or(TAIL))
+            } else if let Some((_, expanded)) = rules.expand.iter().find(|(e, _)| *e == c) {
+                chars.extend(expanded.chars())
+            } else if self.transliterate {
+                chars.extend(iterate_lexical_char(c))
+            } else {
+                chars.push(c)
+            }
+        }
+        chars
+    }
+
+    /// Compare A and B naturally, so "2" comes before "10"
+    pub fn cmp(&self, a: &str, b: &str) -> Ordering {
+        // the default skips building char lists on every comparison
+        if self.locale.is_none() && !self.articles && self.transliterate {
+            return natural_lexical_cmp(a, b);
+        }
+        let rules = self.rules();
+        let (a, b) = (self.strip(a, &rules), self.strip(b, &rules));
+        let (ca, cb) = (self.chars(a, &rules), self.chars(b, &rules));
+        let alnum = |c: char| c.is_alphanumeric() || (TAIL..='\u{E0FF}').contains(&c);
+
+        let (mut i, mut j) = (0, 0);
+        while i < ca.len() && j < cb.len() {
+            let (x, y) = (ca[i], cb[j]);
+            if x.is_ascii_digit() && y.is_ascii_digit() {
+                let (si, sj) = (i, j);
+                while i < ca.len() && ca[i].is_ascii_digit() {
+                    i += 1
+                }
+                while j < cb.len() && cb[j].is_ascii_digit() {
+                    j += 1
+                }
+                // longer runs are bigger numbers, so "01" still comes after "1" like it always has
+                let (na, nb) = (&ca[si..i], &cb[sj..j]);
+                let order = na.len().cmp(&nb.len()).then_with(|| na.cmp(nb));
+                if order.is_ne() {
+                    return order;
+                }
+                continue;
+            }
+            if x != y {
+                return match (alnum(x), alnum(y)) {
+                    (true, false) => Ordering::Greater,
+                    (false, true) => Ordering::Less,
+                    _ => x.cmp(&y),
+                };
+            }
+            i += 1;
+            j += 1;
+        }
+        (ca.len() - i).cmp(&(cb.len() - j)).then_with(|| a.cmp(b))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sorted(collation: &Collation, items: &[&str]) -> Vec<String> {
+        let mut items: Vec<String> = items.iter().map(|s| s.to_string()).collect();
+        items.sort_by(|a, b| collation.cmp(a, b));
+        items
+    }
+
+    #[test]
+    fn matches_natural_lexical() {
+        // a locale without rules still goes through the char lists
+        let english = Collation {
+            locale: Some(String::from("en_US")),
+            ..Default::default()
+        };
+        let items = [
+            "b", "Björk", "Bjork", "a10", "a2", "A2", "_x", "x", "", "Ärger", "zed", "track 01", "track 1",
+        ];
+        let mut expected: Vec<String> = items.iter().map(|s| s.to_string()).collect();
+        expected.sort_by(|a, b| natural_lexical_cmp(a, b));
+        assert_eq!(sorted(&english, &items), expected);
+    }
+
+    #[test]
+    fn articles() {
+        let collation = Collation {
+            articles: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            sorted(&collation, &["The Beatles", "Blur", "A Tribe Called Quest", "The", "Thermal"]),
+            ["The Beatles", "Blur", "The", "Thermal", "A Tribe Called Quest"]
+                .map(String::from)
+                .to_vec()
+        );
+        let collation = Collation {
+            locale: Some(String::from("fr_FR.UTF-8")),
+            ..collation
+        };
+        assert_eq!(
+            sorted(&collation, &["L'Impératrice", "Justice"]),
+            ["L'Impératrice", "Justice"].map(String::from).to_vec()
+        );
+    }
+
+    #[test]
+    fn transliterate() {
+        let items = ["Öst", "Zed", "Ost"];
+        assert_eq!(sorted(&Collation::default(), &items), ["Ost", "Öst", "Zed"].map(String::from).to_vec());
+        let collation = Collation {
+            transliterate: false,
+            ..Default::default()
+        };
+        assert_eq!(sorted(&collation, &items), ["Ost", "Zed", "Öst"].map(String::from).to_vec());
+    }
+
+    #[test]
+    fn locales() {
+        let sv = Collation {
+            locale: Some(String::from("sv")),
+            ..Default::default()
+        };
+        assert_eq!(
+            sorted(&sv, &["Öst", "Zeta", "Åsa", "Ola"]),
+            ["Ola", "Zeta", "Åsa", "Öst"].map(String::from).to_vec()
+        );
+        let de = Collation {
+            locale: Some(String::from("de")),
+            ..Default::default()
+        };
+        assert_eq!(
+            sorted(&de, &["Adler", "Äpfel", "Af"]),
+            ["Adler", "Äpfel", "Af"].map(String::from).to_vec()
+        );
+    }
+}
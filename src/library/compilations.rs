@@ -0,0 +1,127 @@
+#![warn(missing_docs)]
+
+//! Working out which albums are compilations, so album-based filters keep them together
+//! instead of splitting them into one album per artist.
+
+use super::Track;
+
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+use std::sync::Arc;
+
+/// Synthetic tag holding the album artist, "Various Artists" for compilations
+pub const TAG: &str = "albumartist_eff";
+/// Album artist given to compilations without one
+pub const VARIOUS: &str = "Various Artists";
+/// Fewest distinct artists an album needs before it can count as a compilation
+const MIN_ARTISTS: usize = 3;
+
+/// Album and folder a track is grouped by. Same named albums in different folders are kept apart
+fn album_key(track: &Track) -> Option<(String, Option<PathBuf>)> {
+    let album = track.tags().get("album")?.trim().to_lowercase();
+    (!album.is_empty()).then(|| (album, track.path().parent().map(|p| p.to_path_buf())))
+}
+
+/// Whether the compilation tag is set, as 1, true, or yes
+fn flagged(track: &Track) -> bool {
+    track
+        .tags()
+        .get("compilation")
+        .is_some_and(|v| ["1", "true", "yes"].contains(&v.trim().to_ascii_lowercase().as_str()))
+}
+
+/// Whether ARTISTS, one per track, are spread out enough for a compilation:
+/// at least [`MIN_ARTISTS`] of them and none on half the tracks or more
+fn various(artists: &[String]) -> bool {
+    let mut counts: HashMap<String, usize> = HashMap::new();
+    for artist in artists {
+        *counts.entry(artist.trim().to_lowercase()).or_default() += 1
+    }
+    counts.len() >= MIN_ARTISTS && counts.values().all(|n| n * 2 < artists.len())
+}
+
+/// Set [`TAG`] on every track in NEW, counting album mates from EXISTING.
+/// An album artist tag always wins, then the compilation tag or enough different artists make it [`VARIOUS`],
+/// otherwise it's the track artist
+pub fn tag(existing: &[Arc<Track>], new: &mut [Track]) {
+    let mut albums: HashMap<(String, Option<PathBuf>), Vec<String>> = HashMap::new();
+    let replaced = new.iter().map(|t| t.path()).collect::<HashSet<_>>();
+    let tracks = existing
+        .iter()
+        .map(|t| t.as_ref())
+        .filter(|t| !replaced.contains(t.path()))
+        .chain(new.iter());
+    for track in tracks {
+        if let (Some(key), Some(artist)) = (album_key(track), track.tags().get("artist")) {
+            albums.entry(key).or_default().push(artist.clone())
+        }
+    }
+
+    for track in new.iter_mut() {
+        let tags = track.tags();
+        let value = match tags.get("albumartist").filter(|a| !a.trim().is_empty()) {
+            Some(albumartist) => albumartist.clone(),
+            None if flagged(track) => VARIOUS.to_string(),
+            None if album_key(track).and_then(|k| albums.get(&k)).is_some_and(|a| various(a)) => VARIOUS.to_string(),
+            None => match tags.get("artist") {
+                Some(artist) => artist.clone(),
+                None => continue,
+            },
+        };
+        track.tag_set(TAG, value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn eff(track: &Track) -> &str {
+        track.tags().get(TAG).map(|s| s.as_str()).unwrap_or_default()
+    }
+
+    #[test]
+    fn detected() {
+        let mut new: Vec<Track> = ["A", "B", "C", "D"]
+            .iter()
+            .enumerate()
+            .map(|(n, artist)| Track::tagged(&format!("/m/mix/{}.flac", n), &[("album", "Mix"), ("artist", artist)]))
+            .collect();
+        // same name, different folder
+        new.push(Track::tagged("/m/other/0.flac", &[("album", "Mix"), ("artist", "E")]));
+        tag(&[], &mut new);
+        assert!(new[..4].iter().all(|t| eff(t) == VARIOUS));
+        assert_eq!(eff(&new[4]), "E");
+    }
+
+    #[test]
+    fn single_artist() {
+        // a few features don't make a compilation
+        let mut new: Vec<Track> = ["A", "A", "A", "A feat. B", "A feat. C"]
+            .iter()
+            .enumerate()
+            .map(|(n, artist)| Track::tagged(&format!("/m/lp/{}.flac", n), &[("album", "LP"), ("artist", artist)]))
+            .collect();
+        tag(&[], &mut new);
+        assert_eq!(eff(&new[3]), "A feat. B");
+    }
+
+    #[test]
+    fn tagged() {
+        let mut new = vec![
+            Track::tagged("/m/x/0.flac", &[("album", "X"), ("artist", "A"), ("albumartist", "DJ")]),
+            Track::tagged("/m/x/1.flac", &[("album", "X"), ("artist", "A"), ("compilation", "1")]),
+            Track::tagged("/m/y/0.flac", &[("title", "No artist")]),
+        ];
+        // album mates already loaded count too
+        let existing: Vec<Arc<Track>> = ["B", "C", "D"]
+            .iter()
+            .enumerate()
+            .map(|(n, artist)| Arc::new(Track::tagged(&format!("/m/x/{}.flac", n + 2), &[("album", "X"), ("artist", artist)])))
+            .collect();
+        tag(&existing, &mut new);
+        assert_eq!(eff(&new[0]), "DJ");
+        assert_eq!(eff(&new[1]), VARIOUS);
+        assert_eq!(eff(&new[2]), "");
+    }
+}
@@ -0,0 +1,317 @@
+#![warn(missing_docs)]
+
+//! Theme, statusline, queue groups, seek step, and confirmation file, applied on startup and again whenever it's saved or the server gets SIGHUP.
+//! Written as `key: value` lines like `ompl print theme` and `ompl seek steps` give, so their output can be used as a starting point.
+//! Keys left out keep whatever is currently set, and `#` starts a comment line.
+//! Lines like `alias chill = "filter set genre=Ambient : play"` name commands for the client instead, see [`aliases`].
+//! `genre DnB = Drum and Bass` and `genre Breakcore > Electronic` lines group genres in filters, see [`Genres`].
+//! Unlike keys, leaving every genre line out clears them.
+
+use super::{ArtColors, ArtDither, Color, Genres, LibEvt, Library, SeekSteps, Theme};
+use crate::logging::*;
+
+use std::error::Error;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Weak};
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+use clap::ValueEnum;
+
+/// How often the file is checked for changes
+const POLL: Duration = Duration::from_secs(1);
+
+/// NAME and expansion if LINE defines an alias
+fn alias(line: &str) -> Option<Result<(String, String), String>> {
+    let rest = line.strip_prefix("alias ")?;
+    let Some((name, expansion)) = rest.split_once('=') else {
+        return Some(Err(String::from("expected alias name = command")));
+    };
+    let name = name.trim();
+    if name.is_empty() || name.starts_with('-') || name.contains(char::is_whitespace) {
+        return Some(Err(format!("invalid alias name \"{}\"", name)));
+    }
+    let expansion = expansion.trim();
+    let unquoted = ['"', '\'']
+        .iter()
+        .find_map(|q| expansion.strip_prefix(*q).and_then(|e| e.strip_suffix(*q)))
+        .unwrap_or(expansion);
+    Some(Ok((name.to_string(), unquoted.to_string())))
+}
+
+/// Every well formed alias in TEXT as (name, command), in order
+pub fn aliases(text: &str) -> Vec<(String, String)> {
+    text.lines().filter_map(|l| alias(l.trim())).flatten().collect()
+}
+
+/// Everything the file can set
+#[derive(Clone, Debug, PartialEq)]
+pub struct Config {
+    /// Colors and art
+    pub theme: Theme,
+    /// Tagstring for the statusline
+    pub statusline: String,
+    /// Tagstring the queue is grouped by
+    pub groupline: String,
+    /// Seconds seeked by each step
+    pub seek_steps: SeekSteps,
+    /// Ask before destructive actions in the TUI
+    pub confirm: bool,
+    /// Genre aliases and parents
+    pub genres: Genres,
+}
+
+impl Config {
+    /// What LIBRARY has set right now
+    pub fn current(library: &Library) -> Self {
+        Self {
+            theme: library.theme_get(),
+            statusline: library.statusline_get(),
+            groupline: library.groupline_get(),
+            seek_steps: library.seek_steps_get(),
+            confirm: library.confirm_get(),
+            genres: library.genres_get().as_ref().clone(),
+        }
+    }
+}
+
+/// Seconds for a seek step, see [`SeekSteps::valid`]
+fn parse_step(value: &str) -> Result<f32, Box<dyn Error>> {
+    let secs: f32 = value.parse()?;
+    if SeekSteps::valid(secs) {
+        Ok(secs)
+    } else {
+        Err("seek steps must be positive seconds".into())
+    }
+}
+
+/// Apply TEXT over CONFIG. Errors name the first bad line
+pub fn parse(text: &str, mut config: Config) -> Result<Config, String> {
+    let Config {
+        theme,
+        statusline,
+        groupline,
+        seek_steps,
+        confirm,
+        genres,
+    } = &mut config;
+    *genres = Genres::default();
+    for (n, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        // aliases are for the client, but still checked so mistakes show up somewhere
+        if let Some(alias) = alias(line) {
+            match alias {
+                Ok(_) => continue,
+                Err(e) => return Err(format!("Line {}: {}", n + 1, e)),
+            }
+        }
+        if let Some(result) = genres.line(line) {
+            match result {
+                Ok(()) => continue,
+                Err(e) => return Err(format!("Line {}: {}", n + 1, e)),
+            }
+        }
+        let result = try_block!({
+            let (key, value) = line.split_once(':').ok_or("expected key: value")?;
+            let value = value.trim();
+            match key.trim().to_ascii_lowercase().replace('-', "_").as_str() {
+                "fg" => theme.fg = Color::try_from(value)?,
+                "bg" => theme.bg = Color::try_from(value)?,
+                "acc" => theme.acc = Color::try_from(value)?,
+                "art_size" => {
+                    let size = value.parse()?;
+                    if !Theme::ART_SIZES.contains(&size) {
+                        return Err(format!("art size must be one of {:?}", Theme::ART_SIZES).into());
+                    }
+                    theme.art_size = size
+                }
+                "art_dither" => theme.art_dither = ArtDither::from_str(value, true)?,
+                "art_colors" => theme.art_colors = ArtColors::from_str(value, true)?,
+                "art_letterbox" => theme.art_letterbox = value.parse()?,
+                "art_chips" => theme.art_chips = value.parse()?,
+                "statusline" => *statusline = value.to_string(),
+                "groupline" => *groupline = value.to_string(),
+                "seek_small" => seek_steps.small = parse_step(value)?,
+                "seek_medium" => seek_steps.medium = parse_step(value)?,
+                "seek_large" => seek_steps.large = parse_step(value)?,
+                "confirm" => *confirm = value.parse()?,
+                key => return Err(format!("unknown key {}", key).into()),
+            }
+            Ok(())
+        });
+        if let Err(e) = result {
+            return Err(format!("Line {}: {}", n + 1, e));
+        }
+    }
+    Ok(config)
+}
+
+/// Read PATH and apply it to LIBRARY, only touching what changed
+pub fn apply(library: &Library, path: &Path) -> Result<(), String> {
+    let text = std::fs::read_to_string(path).map_err(|e| format!("Could not read config {}: {}", path.display(), e))?;
+    let current = Config::current(library);
+    let config = parse(&text, current.clone()).map_err(|e| format!("Could not apply config {}\n  {}", path.display(), e))?;
+    if config.theme != current.theme {
+        library.theme_set(config.theme)
+    }
+    if config.statusline != current.statusline {
+        library.statusline_set(config.statusline)
+    }
+    if config.groupline != current.groupline {
+        library.groupline_set(config.groupline)
+    }
+    if config.seek_steps != current.seek_steps {
+        library.seek_steps_set(config.seek_steps)
+    }
+    if config.confirm != current.confirm {
+        library.confirm_set(config.confirm)
+    }
+    if config.genres != current.genres {
+        library.genres_set(config.genres)
+    }
+    Ok(())
+}
+
+/// Apply PATH now if it exists, then again each time it's modified or the server gets SIGHUP.
+/// Errors while watching are broadcast so they show up in the UI
+pub fn watch(library: &Arc<Library>, path: PathBuf) -> Result<(), Box<dyn Error>> {
+    let modified = |path: &Path| std::fs::metadata(path).and_then(|m| m.modified()).ok();
+    let mut last: Option<SystemTime> = modified(&path);
+    // a broken file is still watched so saving a fix applies it
+    if last.is_some() {
+        if let Err(e) = apply(library, &path) {
+            error!("{}", e)
+        }
+    }
+
+    let hangup = Arc::new(AtomicBool::new(false));
+    #[cfg(unix)]
+    signal_hook::flag::register(signal_hook::consts::SIGHUP, hangup.clone())?;
+
+    let library: Weak<Library> = Arc::downgrade(library);
+    thread::Builder::new().name(String::from("CONFIG Watcher")).spawn(move || loop {
+        thread::sleep(POLL);
+        let Some(library) = library.upgrade() else { break };
+        let now = modified(&path);
+        if now != last || hangup.swap(false, Ordering::Relaxed) {
+            last = now;
+            if now.is_none() {
+                continue;
+            }
+            match apply(&library, &path) {
+                Ok(()) => {
+                    info!("Applied config {}", path.display())
+                }
+                Err(e) => library.broadcast(LibEvt::Error(e)),
+            }
+        }
+    })?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(theme: Theme, statusline: &str) -> Config {
+        Config {
+            theme,
+            statusline: statusline.to_string(),
+            groupline: String::new(),
+            seek_steps: SeekSteps::default(),
+            confirm: true,
+            genres: Genres::default(),
+        }
+    }
+
+    fn theme() -> Theme {
+        Theme {
+            fg: Color::None,
+            bg: Color::None,
+            acc: Color::Term(3),
+            art_size: 5,
+            art_dither: ArtDither::None,
+            art_colors: ArtColors::Auto,
+            art_letterbox: false,
+            art_chips: false,
+        }
+    }
+
+    #[test]
+    fn partial() {
+        let text = "# warmer\nacc: #ff8800\nart-size: 8\n\nstatusline: <title> - <artist>\n";
+        let new = parse(text, config(theme(), "title")).unwrap();
+        assert_eq!(new.theme.acc, Color::RGB([255, 136, 0]));
+        assert_eq!(new.theme.art_size, 8);
+        assert_eq!(new.theme.fg, theme().fg);
+        assert_eq!(new.statusline, "<title> - <artist>");
+        assert_eq!(new.groupline, "");
+        assert_eq!(parse("groupline: <album>", new.clone()).unwrap().groupline, "<album>");
+        assert!(new.confirm);
+        assert!(!parse("confirm: false", config(theme(), "")).unwrap().confirm);
+    }
+
+    #[test]
+    fn round_trip() {
+        let mut original = theme();
+        original.art_dither = ArtDither::FloydSteinberg;
+        original.art_colors = ArtColors::Ansi256;
+        original.art_letterbox = true;
+        original.art_chips = true;
+        original.bg = Color::RGB([1, 2, 3]);
+        assert_eq!(parse(&original.to_string(), config(theme(), "")).unwrap().theme, original);
+    }
+
+    #[test]
+    fn seek_steps() {
+        let steps = SeekSteps {
+            small: 2.5,
+            medium: 60.0,
+            large: 600.0,
+        };
+        assert_eq!(parse(&steps.to_string(), config(theme(), "")).unwrap().seek_steps, steps);
+        let new = parse("seek-large: 1800", config(theme(), "")).unwrap().seek_steps;
+        assert_eq!((new.small, new.large), (5.0, 1800.0));
+        assert!(parse("seek_small: 0", config(theme(), "")).is_err());
+        assert!(parse("seek_small: -5", config(theme(), "")).is_err());
+    }
+
+    #[test]
+    fn alias_lines() {
+        let text = "acc: red\nalias chill = \"filter set genre=Ambient,Downtempo : shuffle true : play\"\nalias v=volume set";
+        assert_eq!(
+            aliases(text),
+            [
+                (
+                    String::from("chill"),
+                    String::from("filter set genre=Ambient,Downtempo : shuffle true : play")
+                ),
+                (String::from("v"), String::from("volume set"))
+            ]
+        );
+        assert_eq!(parse(text, config(theme(), "")).unwrap().theme.acc, Color::Term(1));
+        assert!(parse("alias two words = play", config(theme(), "")).is_err());
+        assert!(parse("alias play", config(theme(), "")).is_err());
+    }
+
+    #[test]
+    fn genre_lines() {
+        let new = parse("genre DnB = Drum and Bass\ngenre Breakcore > Electronic", config(theme(), "")).unwrap();
+        assert_eq!(new.genres.expand("dnb"), ["Drum and Bass"]);
+        assert_eq!(new.genres.expand("Breakcore"), ["Breakcore", "Electronic"]);
+        // left out clears them
+        assert!(parse("acc: red", new).unwrap().genres.is_empty());
+        assert!(parse("genre Breakcore", config(theme(), "")).unwrap_err().starts_with("Line 1"));
+    }
+
+    #[test]
+    fn errors() {
+        assert!(parse("acc: #ff8800\nart_size: 3", config(theme(), "")).unwrap_err().starts_with("Line 2"));
+        assert!(parse("accent = red", config(theme(), "")).is_err());
+        assert!(parse("volume: 1", config(theme(), "")).is_err());
+    }
+}
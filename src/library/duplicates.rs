@@ -0,0 +1,119 @@
+#![warn(missing_docs)]
+
+//! Spotting the same recording loaded from more than one file, like a remux next to the original.
+
+use super::Track;
+
+use std::collections::{HashMap, HashSet};
+use std::fmt::Display;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+
+/// What's done with a newly found track that has the same tags as one already loaded
+#[derive(Debug, Default, PartialEq, Eq, Clone, Copy, Serialize, Deserialize, ValueEnum)]
+pub enum DuplicatePolicy {
+    /// Only a file at the same path counts as the same track. Tag matches are still reported
+    #[default]
+    Path,
+    /// Skip files whose artist, title, length, and album match a loaded track, or whose MusicBrainz IDs do
+    Tags,
+    /// Load both, adding the file type or folder to the newer one's title to tell them apart
+    Keep,
+}
+
+/// A track with the same tags as one loaded before it
+#[derive(Debug, Clone, PartialEq)]
+pub struct Duplicate {
+    /// The newer file
+    pub path: PathBuf,
+    /// The file it matched
+    pub original: PathBuf,
+    /// Policy in effect when it was found
+    pub policy: DuplicatePolicy,
+}
+
+impl Display for Duplicate {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let action = match self.policy {
+            DuplicatePolicy::Path => "loaded, same tags as",
+            DuplicatePolicy::Tags => "skipped, same tags as",
+            DuplicatePolicy::Keep => "renamed, same tags as",
+        };
+        write!(f, "{}: {} {}", self.path.display(), action, self.original.display())
+    }
+}
+
+/// What TRACK is matched on. None without enough tags to be sure, see [`Track::fingerprint`]
+fn key(track: &Track) -> Option<String> {
+    // the same recording on another release isn't a duplicate
+    let album = track.tags().get("album").map(|a| a.to_lowercase()).unwrap_or_default();
+    track.fingerprint().map(|f| format!("{}\0{}", f, album))
+}
+
+/// Each of NEW matching a track in EXISTING or earlier in NEW, as (index in NEW, path matched).
+/// EXISTING tracks at the same path as one in NEW are being replaced, so they're ignored
+pub(crate) fn find(existing: &[Arc<Track>], new: &[Track]) -> Vec<(usize, PathBuf)> {
+    let replaced: HashSet<&PathBuf> = new.iter().map(|t| t.path()).collect();
+    let mut seen: HashMap<String, PathBuf> = existing
+        .iter()
+        .filter(|t| !replaced.contains(t.path()))
+        .filter_map(|t| key(t).map(|k| (k, t.path().clone())))
+        .collect();
+    let mut found = Vec::new();
+    for (n, track) in new.iter().enumerate() {
+        let Some(key) = key(track) else { continue };
+        match seen.get(&key) {
+            Some(original) if original != track.path() => found.push((n, original.clone())),
+            Some(_) => (),
+            None => {
+                seen.insert(key, track.path().clone());
+            }
+        }
+    }
+    found
+}
+
+/// Note for TRACK's title telling it apart from ORIGINAL, the file type if they differ or else the folder
+pub(crate) fn disambiguation(track: &Track, original: &Path) -> String {
+    let extension = |p: &Path| p.extension().map(|e| e.to_string_lossy().to_lowercase());
+    match extension(track.path()) {
+        Some(ext) if Some(&ext) != extension(original).as_ref() => ext,
+        _ => track
+            .path()
+            .parent()
+            .and_then(|p| p.file_name())
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_default(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn track(path: &str, album: &str) -> Track {
+        Track::tagged(path, &[("artist", "Artist"), ("title", "Title"), ("album", album)])
+    }
+
+    #[test]
+    fn remux() {
+        let existing = vec![Arc::new(track("/m/a/t.flac", "X"))];
+        let new = vec![track("/m/a/t.opus", "X"), track("/m/b/t.opus", "X"), track("/m/c/t.flac", "Y")];
+        let found = find(&existing, &new);
+        assert_eq!(found, [(0, PathBuf::from("/m/a/t.flac")), (1, PathBuf::from("/m/a/t.flac"))]);
+        assert_eq!(disambiguation(&new[0], &found[0].1), "opus");
+        assert_eq!(disambiguation(&new[2], &found[0].1), "c");
+    }
+
+    #[test]
+    fn rescan() {
+        // the same files again replace themselves rather than matching
+        let existing = vec![Arc::new(track("/m/a/t.flac", "X"))];
+        let new = vec![track("/m/a/t.flac", "X"), track("/m/a/t.opus", "X")];
+        assert_eq!(find(&existing, &new), [(1, PathBuf::from("/m/a/t.flac"))]);
+        assert_eq!(find(&[], &new[..1]), []);
+    }
+}
@@ -0,0 +1,195 @@
+#![warn(missing_docs)]
+
+//! Delivery of [`LibEvt`]s to every frontend.
+//! Each receiver has its own bounded queue and a full queue drops its oldest event,
+//! so a stuck TUI or media controls thread only misses updates instead of holding up the library.
+
+use super::LibEvt;
+
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::mpsc::{RecvError, RecvTimeoutError, TryRecvError};
+use std::sync::{Arc, Weak};
+use std::time::{Duration, Instant};
+
+use parking_lot::{Condvar, Mutex};
+
+/// Events one receiver may fall behind by before the oldest are dropped
+pub const CAPACITY: usize = 99;
+
+#[derive(Default)]
+struct Queue {
+    events: Mutex<VecDeque<LibEvt>>,
+    ready: Condvar,
+    /// Set once the bus is gone so waiting receivers return
+    closed: AtomicBool,
+    /// Events dropped because this receiver fell behind
+    lagged: AtomicU64,
+}
+
+/// Sends every event to all live receivers without ever waiting on them
+#[derive(Default)]
+pub(crate) struct EventBus {
+    queues: Mutex<Vec<Weak<Queue>>>,
+    /// Events dropped across all receivers, including ones since disconnected
+    dropped: AtomicU64,
+}
+
+impl EventBus {
+    pub(crate) fn send(&self, event: LibEvt) {
+        let mut queues = self.queues.lock();
+        queues.retain(|q| q.strong_count() > 0);
+        for queue in queues.iter().filter_map(Weak::upgrade) {
+            let mut events = queue.events.lock();
+            if events.len() >= CAPACITY {
+                events.pop_front();
+                queue.lagged.fetch_add(1, Ordering::Relaxed);
+                self.dropped.fetch_add(1, Ordering::Relaxed);
+            }
+            events.push_back(event.clone());
+            drop(events);
+            queue.ready.notify_all();
+        }
+    }
+
+    pub(crate) fn subscribe(&self) -> EventReceiver {
+        let queue = Arc::new(Queue::default());
+        self.queues.lock().push(Arc::downgrade(&queue));
+        EventReceiver { queue }
+    }
+
+    /// Receivers still connected
+    pub(crate) fn receivers(&self) -> usize {
+        self.queues.lock().iter().filter(|q| q.strong_count() > 0).count()
+    }
+
+    /// Events dropped since startup
+    pub(crate) fn dropped(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+
+    /// Most events waiting in any one receiver's queue
+    pub(crate) fn backlog(&self) -> usize {
+        self.queues
+            .lock()
+            .iter()
+            .filter_map(Weak::upgrade)
+            .map(|q| q.events.lock().len())
+            .max()
+            .unwrap_or(0)
+    }
+}
+
+impl Drop for EventBus {
+    fn drop(&mut self) {
+        for queue in self.queues.get_mut().iter().filter_map(Weak::upgrade) {
+            // taken under the lock so a receiver can't check closed then miss the wakeup
+            let _events = queue.events.lock();
+            queue.closed.store(true, Ordering::Relaxed);
+            queue.ready.notify_all();
+        }
+    }
+}
+
+/// One subscriber's queue of library events from [`super::Library::get_receiver`].
+/// Errors mirror [`std::sync::mpsc::Receiver`], disconnecting once the library is dropped
+pub struct EventReceiver {
+    queue: Arc<Queue>,
+}
+
+impl EventReceiver {
+    /// Wait for the next event
+    pub fn recv(&mut self) -> Result<LibEvt, RecvError> {
+        let mut events = self.queue.events.lock();
+        loop {
+            if let Some(event) = events.pop_front() {
+                return Ok(event);
+            } else if self.queue.closed.load(Ordering::Relaxed) {
+                return Err(RecvError);
+            }
+            self.queue.ready.wait(&mut events);
+        }
+    }
+
+    /// Next event if one is waiting
+    pub fn try_recv(&mut self) -> Result<LibEvt, TryRecvError> {
+        let mut events = self.queue.events.lock();
+        match events.pop_front() {
+            Some(event) => Ok(event),
+            None if self.queue.closed.load(Ordering::Relaxed) => Err(TryRecvError::Disconnected),
+            None => Err(TryRecvError::Empty),
+        }
+    }
+
+    /// Wait up to TIMEOUT for the next event
+    pub fn recv_timeout(&mut self, timeout: Duration) -> Result<LibEvt, RecvTimeoutError> {
+        let deadline = Instant::now() + timeout;
+        let mut events = self.queue.events.lock();
+        loop {
+            if let Some(event) = events.pop_front() {
+                return Ok(event);
+            } else if self.queue.closed.load(Ordering::Relaxed) {
+                return Err(RecvTimeoutError::Disconnected);
+            } else if self.queue.ready.wait_until(&mut events, deadline).timed_out() {
+                return events.pop_front().ok_or(RecvTimeoutError::Timeout);
+            }
+        }
+    }
+
+    /// Events this receiver missed by falling more than [`CAPACITY`] behind
+    pub fn lagged(&self) -> u64 {
+        self.queue.lagged.load(Ordering::Relaxed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn drop_oldest() {
+        let bus = EventBus::default();
+        let mut rx = bus.subscribe();
+        for _ in 0..CAPACITY {
+            bus.send(LibEvt::Clock);
+        }
+        bus.send(LibEvt::Track);
+        bus.send(LibEvt::Queue);
+        assert_eq!(rx.lagged(), 2);
+        assert_eq!(bus.dropped(), 2);
+        assert_eq!(bus.backlog(), CAPACITY);
+        let events: Vec<LibEvt> = std::iter::from_fn(|| rx.try_recv().ok()).collect();
+        assert_eq!(events.len(), CAPACITY);
+        assert_eq!(events[CAPACITY - 2..], [LibEvt::Track, LibEvt::Queue]);
+    }
+
+    #[test]
+    fn independent() {
+        let bus = EventBus::default();
+        let mut slow = bus.subscribe();
+        let mut fast = bus.subscribe();
+        for _ in 0..CAPACITY * 2 {
+            bus.send(LibEvt::Clock);
+            assert_eq!(fast.try_recv(), Ok(LibEvt::Clock));
+        }
+        assert_eq!(fast.lagged(), 0);
+        assert_eq!(slow.lagged(), CAPACITY as u64);
+        assert_eq!(slow.try_recv(), Ok(LibEvt::Clock));
+    }
+
+    #[test]
+    fn disconnect() {
+        let bus = EventBus::default();
+        let mut rx = bus.subscribe();
+        let dropped = bus.subscribe();
+        drop(dropped);
+        assert_eq!(bus.receivers(), 1);
+        bus.send(LibEvt::Track);
+        assert_eq!(rx.recv_timeout(Duration::from_millis(1)), Ok(LibEvt::Track));
+        assert_eq!(rx.recv_timeout(Duration::from_millis(1)), Err(RecvTimeoutError::Timeout));
+        let waiter = std::thread::spawn(move || rx.recv());
+        std::thread::sleep(Duration::from_millis(50));
+        drop(bus);
+        assert_eq!(waiter.join().unwrap(), Err(RecvError));
+    }
+}
@@ -0,0 +1,254 @@
+#![warn(missing_docs)]
+
+//! Copying tracks out of the library for portable players.
+//! Each file is named by a tagstring with `/` making folders, and is either copied as is or decoded to WAV.
+//! There's no lossy encoder, so exports can't be shrunk for small players, only made readable by ones lacking a codec.
+
+use super::track::Track;
+use crate::logging::*;
+
+use std::collections::HashSet;
+use std::error::Error;
+use std::fs::{self, File};
+use std::io::{BufWriter, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use symphonia::core::audio::SampleBuffer;
+use symphonia::core::errors::Error as SymError;
+
+/// Naming used when none is given.
+/// Every tag is conditional so missing ones drop their folder instead of becoming "???"
+pub const DEFAULT_PATTERN: &str = "<albumartist|<albumartist>><!albumartist|<artist|<artist>>>/<album|<album>>/<tracknumber|<tracknumber> ><title>";
+
+/// What exported files are written as
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Serialize, Deserialize, ValueEnum)]
+pub enum Format {
+    /// The original file untouched
+    Copy,
+    /// Decoded to 16 bit PCM WAV, for players that can't read the source format.
+    /// Bigger than any compressed source, and only the basic tags carry over
+    Wav,
+}
+
+impl Format {
+    /// File extension of an export of SOURCE
+    pub fn extension(&self, source: &Path) -> String {
+        match self {
+            Self::Copy => source.extension().map(|e| e.to_string_lossy().to_string()).unwrap_or_default(),
+            Self::Wav => "wav".to_string(),
+        }
+    }
+}
+
+/// Make a tag value safe as a file name on FAT formatted players
+fn sanitize(name: &str) -> String {
+    let name: String = name
+        .chars()
+        .map(|c| match c {
+            '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '_',
+            c if c.is_control() => '_',
+            c => c,
+        })
+        .collect();
+    name.trim().trim_end_matches('.').to_string()
+}
+
+/// Path under the export folder for TRACK without an extension.
+/// Each `/` separated part of PATTERN is a tagstring, and parts that come out empty are left out
+pub fn name(track: &Track, pattern: &str) -> PathBuf {
+    let mut path: PathBuf = pattern
+        .split('/')
+        .map(|part| sanitize(&track.tagstring(part)))
+        .filter(|part| !part.is_empty())
+        .collect();
+    if path.as_os_str().is_empty() {
+        path = PathBuf::from(track.path().file_stem().map(|s| sanitize(&s.to_string_lossy())).unwrap_or_default());
+    }
+    path
+}
+
+/// Source paths of TRACKS paired with their names under PATTERN.
+/// Tracks from shared libraries are left out, and names that collide get a number appended
+pub fn plan(tracks: &[Arc<Track>], pattern: &str) -> Vec<(PathBuf, PathBuf)> {
+    let mut used = HashSet::new();
+    tracks
+        .iter()
+        .filter(|t| !t.is_remote())
+        .map(|track| {
+            let base = name(track, pattern);
+            let mut unique = base.clone();
+            let mut n = 1;
+            // case insensitive for FAT
+            while !used.insert(unique.to_string_lossy().to_lowercase()) {
+                n += 1;
+                unique = PathBuf::from(format!("{} ({})", base.to_string_lossy(), n));
+            }
+            (track.path().clone(), unique)
+        })
+        .collect()
+}
+
+/// Write SOURCE to TARGET in FORMAT, creating folders as needed
+pub fn export(source: &Path, target: &Path, format: Format) -> Result<(), Box<dyn Error>> {
+    if let Some(parent) = target.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    match format {
+        Format::Copy => {
+            fs::copy(source, target)?;
+            Ok(())
+        }
+        Format::Wav => {
+            let result = write_wav(source, target);
+            if result.is_err() {
+                let _ = fs::remove_file(target);
+            }
+            result
+        }
+    }
+}
+
+fn write_wav(source: &Path, target: &Path) -> Result<(), Box<dyn Error>> {
+    let mut track = Track::new(source).ok_or("File not found")?;
+    let mut format = track.probe().ok_or("Could not read the file's format")?.format;
+    let stream = format.default_track().ok_or("File has no audio")?;
+    let id = stream.id;
+    let mut decoder = symphonia::default::get_codecs().make(&stream.codec_params, &Default::default())?;
+
+    let mut file = BufWriter::new(File::create(target)?);
+    // sizes are filled in once the data length is known
+    let mut spec = None;
+    let mut bytes: u64 = 0;
+    let mut buffer: Option<SampleBuffer<i16>> = None;
+    loop {
+        let packet = match format.next_packet() {
+            Ok(packet) => packet,
+            Err(SymError::IoError(e)) if e.kind() == std::io::ErrorKind::UnexpectedEof => break,
+            Err(e) => return Err(e.into()),
+        };
+        if packet.track_id() != id {
+            continue;
+        }
+        let audio = match decoder.decode(&packet) {
+            Ok(audio) => audio,
+            Err(SymError::DecodeError(e)) => {
+                debug!("EXPORT skipping bad packet: {}", e);
+                continue;
+            }
+            Err(e) => return Err(e.into()),
+        };
+        if spec.is_none() {
+            let (channels, rate) = (audio.spec().channels.count() as u16, audio.spec().rate);
+            file.write_all(&wav_header(channels, rate, 0, 0))?;
+            spec = Some((channels, rate));
+        }
+        if buffer.as_ref().is_none_or(|b| b.capacity() < audio.capacity()) {
+            buffer = Some(SampleBuffer::new(audio.capacity() as u64, *audio.spec()));
+        }
+        let buffer = buffer.as_mut().unwrap();
+        buffer.copy_interleaved_ref(audio);
+        for sample in buffer.samples() {
+            file.write_all(&sample.to_le_bytes())?;
+        }
+        bytes += buffer.samples().len() as u64 * 2;
+    }
+
+    let (channels, rate) = spec.ok_or("File has no audio")?;
+    let bytes: u32 = bytes.try_into().map_err(|_| "Track too long for WAV")?;
+    track.load_meta();
+    let info = info_chunk(&track);
+    file.write_all(&info)?;
+    file.seek(SeekFrom::Start(0))?;
+    file.write_all(&wav_header(channels, rate, bytes, info.len() as u32))?;
+    file.flush()?;
+    Ok(())
+}
+
+/// WAV's `LIST` chunk equivalents of the tags portable players show
+const INFO_TAGS: &[(&[u8; 4], &str)] = &[
+    (b"INAM", "title"),
+    (b"IART", "artist"),
+    (b"IPRD", "album"),
+    (b"ITRK", "tracknumber"),
+    (b"IGNR", "genre"),
+    (b"ICRD", "date"),
+];
+
+/// `LIST` chunk holding TRACK's basic tags, or nothing if it has none of them
+fn info_chunk(track: &Track) -> Vec<u8> {
+    let mut info = b"INFO".to_vec();
+    for (id, tag) in INFO_TAGS {
+        let Some(value) = track.tags().get(*tag).filter(|v| !v.is_empty()) else {
+            continue;
+        };
+        // null terminated and padded to an even length
+        let mut text = value.as_bytes().to_vec();
+        text.push(0);
+        let size = text.len() as u32;
+        if text.len() % 2 == 1 {
+            text.push(0)
+        }
+        info.extend_from_slice(*id);
+        info.extend_from_slice(&size.to_le_bytes());
+        info.extend_from_slice(&text);
+    }
+    if info.len() == 4 {
+        return Vec::new();
+    }
+    let mut chunk = b"LIST".to_vec();
+    chunk.extend_from_slice(&(info.len() as u32).to_le_bytes());
+    chunk.extend_from_slice(&info);
+    chunk
+}
+
+/// RIFF header of 16 bit PCM with BYTES of samples, followed by TRAILING bytes of other chunks
+fn wav_header(channels: u16, rate: u32, bytes: u32, trailing: u32) -> Vec<u8> {
+    let mut header = Vec::with_capacity(44);
+    header.extend_from_slice(b"RIFF");
+    header.extend_from_slice(&(36 + bytes + trailing).to_le_bytes());
+    header.extend_from_slice(b"WAVEfmt ");
+    header.extend_from_slice(&16u32.to_le_bytes());
+    header.extend_from_slice(&1u16.to_le_bytes());
+    header.extend_from_slice(&channels.to_le_bytes());
+    header.extend_from_slice(&rate.to_le_bytes());
+    header.extend_from_slice(&(rate * channels as u32 * 2).to_le_bytes());
+    header.extend_from_slice(&(channels * 2).to_le_bytes());
+    header.extend_from_slice(&16u16.to_le_bytes());
+    header.extend_from_slice(b"data");
+    header.extend_from_slice(&bytes.to_le_bytes());
+    header
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sanitized() {
+        assert_eq!(sanitize("AC/DC: Live?"), "AC_DC_ Live_");
+        assert_eq!(sanitize(" Etc... "), "Etc");
+        assert_eq!(sanitize("3/12"), "3_12");
+    }
+
+    #[test]
+    fn header() {
+        let header = wav_header(2, 44100, 400, 20);
+        assert_eq!(header.len(), 44);
+        assert_eq!(&header[4..8], &456u32.to_le_bytes());
+        assert_eq!(&header[28..32], &176400u32.to_le_bytes());
+        assert_eq!(&header[40..44], &400u32.to_le_bytes());
+    }
+
+    #[test]
+    fn info() {
+        let track = Track::tagged("/m/song.flac", &[("title", "Song"), ("artist", "Band"), ("comment", "left out")]);
+        let chunk = info_chunk(&track);
+        assert_eq!(&chunk[..4], b"LIST");
+        assert_eq!(&chunk[4..8], &(chunk.len() as u32 - 8).to_le_bytes());
+        assert_eq!(&chunk[8..], b"INFOINAM\x05\0\0\0Song\0\0IART\x05\0\0\0Band\0\0");
+        assert!(info_chunk(&Track::tagged("/m/song.flac", &[])).is_empty());
+    }
+}
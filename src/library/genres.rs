@@ -0,0 +1,150 @@
+#![warn(missing_docs)]
+
+//! Genre aliases and parents from the config file, so filter panes can group genres without retagging.
+//! `genre DnB = Drum and Bass` shows DnB tracks under Drum and Bass,
+//! and `genre Breakcore > Electronic` has selecting Electronic take Breakcore along with it.
+
+use super::Filter;
+
+use std::collections::{HashMap, HashSet};
+
+/// Genre aliases and hierarchy. Names are matched ignoring case
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Genres {
+    /// Lowercase alias -> genre as written
+    aliases: HashMap<String, String>,
+    /// Lowercase genre -> its parents as written
+    parents: HashMap<String, Vec<String>>,
+}
+
+impl Genres {
+    /// Whether any aliases or parents are set
+    pub fn is_empty(&self) -> bool {
+        self.aliases.is_empty() && self.parents.is_empty()
+    }
+
+    /// Whether filters on TAGSTRING go through the mapping. Only plain genre filters do
+    pub fn applies(tagstring: &str) -> bool {
+        let tag = tagstring.trim();
+        let tag = tag.strip_prefix('<').and_then(|t| t.strip_suffix('>')).unwrap_or(tag);
+        tag.eq_ignore_ascii_case("genre")
+    }
+
+    /// Read the mapping from a config file LINE, None if it isn't a genre line
+    pub fn line(&mut self, line: &str) -> Option<Result<(), String>> {
+        let rest = line.strip_prefix("genre ")?;
+        let result = if let Some((alias, genre)) = rest.split_once('=') {
+            self.alias(alias, genre)
+        } else if let Some((child, parent)) = rest.split_once('>') {
+            self.parent(child, parent)
+        } else {
+            Err(String::from("expected genre alias = genre or genre child > parent"))
+        };
+        Some(result)
+    }
+
+    /// Show ALIAS as GENRE
+    pub fn alias(&mut self, alias: &str, genre: &str) -> Result<(), String> {
+        let (alias, genre) = (alias.trim(), genre.trim());
+        if alias.is_empty() || genre.is_empty() {
+            return Err(String::from("genre aliases need a name on both sides"));
+        }
+        self.aliases.insert(alias.to_lowercase(), genre.to_string());
+        Ok(())
+    }
+
+    /// Count CHILD as a kind of PARENT. A genre can have more than one parent
+    pub fn parent(&mut self, child: &str, parent: &str) -> Result<(), String> {
+        let (child, parent) = (child.trim(), parent.trim());
+        if child.is_empty() || parent.is_empty() {
+            return Err(String::from("genre parents need a name on both sides"));
+        }
+        let parents = self.parents.entry(child.to_lowercase()).or_default();
+        if !parents.iter().any(|p| p.eq_ignore_ascii_case(parent)) {
+            parents.push(parent.to_string())
+        }
+        Ok(())
+    }
+
+    /// VALUE under the name it's aliased to, else unchanged
+    pub fn canonical(&self, value: &str) -> String {
+        self.aliases.get(&value.to_lowercase()).cloned().unwrap_or_else(|| value.to_string())
+    }
+
+    /// VALUE's canonical name followed by every genre above it, nearest first.
+    /// Loops in the hierarchy are only followed once
+    pub fn expand(&self, value: &str) -> Vec<String> {
+        let mut result = vec![self.canonical(value)];
+        let mut seen: HashSet<String> = result.iter().map(|g| g.to_lowercase()).collect();
+        let mut i = 0;
+        while let Some(genre) = result.get(i) {
+            let parents = self.parents.get(&genre.to_lowercase()).into_iter().flatten();
+            let new: Vec<String> = parents.map(|p| self.canonical(p)).filter(|p| seen.insert(p.to_lowercase())).collect();
+            result.extend(new);
+            i += 1;
+        }
+        result
+    }
+
+    /// Whether a track with VALUE for FILTER's tagstring passes it, counting its aliases and parents for genre filters
+    pub fn matches(&self, filter: &Filter, value: &str) -> bool {
+        match !self.is_empty() && Self::applies(&filter.tag) {
+            true => self.expand(value).iter().any(|v| filter.matches(v)),
+            false => filter.matches(value),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::library::FilterOp;
+
+    fn genres() -> Genres {
+        let mut genres = Genres::default();
+        for line in [
+            "genre DnB = Drum and Bass",
+            "genre drum and bass > Electronic",
+            "genre Breakcore > Drum and Bass",
+            "genre Breakcore > Hardcore",
+            "genre Electronic > Breakcore",
+        ] {
+            genres.line(line).unwrap().unwrap();
+        }
+        genres
+    }
+
+    #[test]
+    fn expanded() {
+        let genres = genres();
+        assert_eq!(genres.expand("dnb"), ["Drum and Bass", "Electronic", "Breakcore", "Hardcore"]);
+        assert_eq!(genres.expand("Breakcore"), ["Breakcore", "Drum and Bass", "Hardcore", "Electronic"]);
+        assert_eq!(genres.expand("Jazz"), ["Jazz"]);
+    }
+
+    #[test]
+    fn filtered() {
+        let genres = genres();
+        let filter = Filter {
+            tag: String::from("<genre>"),
+            items: vec![String::from("Electronic")],
+            op: FilterOp::Eq,
+        };
+        assert!(genres.matches(&filter, "DnB"));
+        assert!(!genres.matches(&filter, "Jazz"));
+        // other tags are left alone
+        let filter = Filter {
+            tag: String::from("album"),
+            ..filter
+        };
+        assert!(!genres.matches(&filter, "DnB"));
+    }
+
+    #[test]
+    fn bad_lines() {
+        let mut genres = Genres::default();
+        assert!(genres.line("statusline: <title>").is_none());
+        assert!(genres.line("genre Breakcore").unwrap().is_err());
+        assert!(genres.line("genre = Electronic").unwrap().is_err());
+    }
+}
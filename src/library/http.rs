@@ -0,0 +1,231 @@
+#![warn(missing_docs)]
+
+//! The little HTTP/1.1 ompl's servers need: reading a request, and answering with a body or a range of a file.
+//! Every answer closes the connection.
+
+use std::ffi::OsStr;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Read, Seek, SeekFrom, Write};
+use std::net::TcpStream;
+use std::path::{Component, Path, Prefix};
+use std::time::Duration;
+
+/// A client's request, with only the headers ompl cares about
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Request {
+    /// GET, HEAD and so on
+    pub method: String,
+    /// Path asked for
+    pub target: String,
+    /// First and optionally last byte from a `Range: bytes=` header
+    pub range: Option<(u64, Option<u64>)>,
+}
+
+impl Request {
+    /// Read a request from STREAM, giving the client TIMEOUT for each read and write from here on
+    pub fn read(stream: &TcpStream, timeout: Duration) -> io::Result<Self> {
+        stream.set_read_timeout(Some(timeout))?;
+        stream.set_write_timeout(Some(timeout))?;
+        Self::parse(BufReader::new(stream))
+    }
+
+    fn parse(mut reader: impl BufRead) -> io::Result<Self> {
+        let mut line = String::new();
+        reader.read_line(&mut line)?;
+        let mut words = line.split_whitespace();
+        let mut request = Self {
+            method: words.next().unwrap_or_default().to_string(),
+            target: words.next().unwrap_or_default().to_string(),
+            range: None,
+        };
+        loop {
+            let mut header = String::new();
+            if reader.read_line(&mut header)? == 0 || header.trim().is_empty() {
+                break;
+            }
+            if let Some((key, value)) = header.split_once(':') {
+                if key.trim().eq_ignore_ascii_case("range") {
+                    request.range = value
+                        .trim()
+                        .strip_prefix("bytes=")
+                        .and_then(|r| r.split_once('-'))
+                        .and_then(|(a, b)| Some((a.parse().ok()?, b.parse().ok())));
+                }
+            }
+        }
+        Ok(request)
+    }
+
+    /// Whether only the headers are wanted
+    pub fn is_head(&self) -> bool {
+        self.method == "HEAD"
+    }
+}
+
+/// Answer with STATUS, like `404 Not Found`, and nothing else
+pub fn empty(stream: &mut TcpStream, status: &str) -> io::Result<()> {
+    stream.write_all(format!("HTTP/1.1 {}\r\nContent-Length: 0\r\nConnection: close\r\n\r\n", status).as_bytes())
+}
+
+/// Answer REQUEST with BODY of CONTENT_TYPE, leaving the body out for HEAD
+pub fn body(stream: &mut TcpStream, request: &Request, content_type: &str, body: &[u8]) -> io::Result<()> {
+    stream.write_all(
+        format!(
+            "HTTP/1.1 200 OK\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+            content_type,
+            body.len()
+        )
+        .as_bytes(),
+    )?;
+    if !request.is_head() {
+        stream.write_all(body)?;
+    }
+    Ok(())
+}
+
+/// Bytes from first to last of a file SIZE long for RANGE, or None if it starts past the end
+fn span(range: Option<(u64, Option<u64>)>, size: u64) -> Option<(u64, u64)> {
+    match range {
+        Some((start, end)) if start < size => Some((start, end.unwrap_or(size - 1).min(size - 1))),
+        Some(_) => None,
+        None => Some((0, size.saturating_sub(1))),
+    }
+}
+
+/// Answer REQUEST with the file at PATH, or the part of it a range asks for.
+/// EXTRA headers go after the standard ones, each ending in `\r\n`
+pub fn file(stream: &mut TcpStream, request: &Request, path: &Path, extra: &str) -> io::Result<()> {
+    let mut file = File::open(path)?;
+    let size = file.metadata()?.len();
+    let Some((start, end)) = span(request.range, size) else {
+        return empty(stream, &format!("416 Range Not Satisfiable\r\nContent-Range: bytes */{}", size));
+    };
+    let length = (end + 1).saturating_sub(start);
+
+    let mut head = match request.range.is_some() {
+        true => format!("HTTP/1.1 206 Partial Content\r\nContent-Range: bytes {}-{}/{}\r\n", start, end, size),
+        false => String::from("HTTP/1.1 200 OK\r\n"),
+    };
+    head += &format!(
+        "Content-Type: {}\r\nContent-Length: {}\r\nAccept-Ranges: bytes\r\n{}Connection: close\r\n\r\n",
+        mime(path),
+        length,
+        extra
+    );
+    stream.write_all(head.as_bytes())?;
+    if !request.is_head() {
+        file.seek(SeekFrom::Start(start))?;
+        io::copy(&mut file.take(length), stream)?;
+    }
+    Ok(())
+}
+
+/// Content type of an audio file by its extension
+pub fn mime(path: &Path) -> &'static str {
+    match path.extension().and_then(|e| e.to_str()).map(|e| e.to_ascii_lowercase()).as_deref() {
+        Some("mp3") => "audio/mpeg",
+        Some("flac") => "audio/flac",
+        Some("ogg" | "oga" | "opus") => "audio/ogg",
+        Some("wav") => "audio/wav",
+        Some("m4a" | "mp4" | "aac") => "audio/mp4",
+        _ => "application/octet-stream",
+    }
+}
+
+/// `file://` URL for the absolute PATH, or None if it's relative.
+/// Anything but letters, digits, and `-._~` is percent-encoded
+pub fn file_url(path: &Path) -> Option<String> {
+    if !path.is_absolute() {
+        return None;
+    }
+    let mut url = String::from("file://");
+    for component in path.components() {
+        match component {
+            Component::Prefix(prefix) => match prefix.kind() {
+                Prefix::Disk(letter) | Prefix::VerbatimDisk(letter) => url += &format!("/{}:", letter as char),
+                Prefix::UNC(server, share) | Prefix::VerbatimUNC(server, share) => {
+                    url += &encode(server)?;
+                    url += "/";
+                    url += &encode(share)?;
+                }
+                _ => return None,
+            },
+            Component::RootDir => (),
+            component => {
+                url += "/";
+                url += &encode(component.as_os_str())?;
+            }
+        }
+    }
+    if path.components().all(|c| matches!(c, Component::Prefix(_) | Component::RootDir)) {
+        url += "/"
+    }
+    Some(url)
+}
+
+/// Percent-encode one path component
+fn encode(part: &OsStr) -> Option<String> {
+    #[cfg(unix)]
+    let bytes = std::os::unix::ffi::OsStrExt::as_bytes(part);
+    #[cfg(not(unix))]
+    let bytes = part.to_str()?.as_bytes();
+    Some(
+        bytes
+            .iter()
+            .map(|b| match b {
+                b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => (*b as char).to_string(),
+                b => format!("%{:02X}", b),
+            })
+            .collect(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parsed() {
+        let request = Request::parse(&b"GET /track/1.flac HTTP/1.1\r\nHost: box\r\nrange: bytes=100-\r\n\r\n"[..]).unwrap();
+        assert_eq!(
+            request,
+            Request {
+                method: String::from("GET"),
+                target: String::from("/track/1.flac"),
+                range: Some((100, None)),
+            }
+        );
+        assert_eq!(Request::parse(&b""[..]).unwrap(), Request::default());
+    }
+
+    #[test]
+    fn spans() {
+        assert_eq!(span(None, 10), Some((0, 9)));
+        assert_eq!(span(Some((4, None)), 10), Some((4, 9)));
+        assert_eq!(span(Some((4, Some(50))), 10), Some((4, 9)));
+        assert_eq!(span(Some((10, None)), 10), None);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn file_urls() {
+        assert_eq!(
+            file_url(Path::new("/tmp/AC DC/#1 ü?.png")).as_deref(),
+            Some("file:///tmp/AC%20DC/%231%20%C3%BC%3F.png")
+        );
+        assert_eq!(file_url(Path::new("/")).as_deref(), Some("file:///"));
+        assert_eq!(file_url(Path::new("covers/a.png")), None);
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn file_urls() {
+        assert_eq!(
+            file_url(Path::new(r"C:\Users\Me\AC DC\#1.png")).as_deref(),
+            Some("file:///C:/Users/Me/AC%20DC/%231.png")
+        );
+        assert_eq!(file_url(Path::new(r"\\?\C:\cache\a.png")).as_deref(), Some("file:///C:/cache/a.png"));
+        assert_eq!(file_url(Path::new(r"\\box\music\a b.png")).as_deref(), Some("file://box/music/a%20b.png"));
+        assert_eq!(file_url(Path::new(r"covers\a.png")), None);
+    }
+}
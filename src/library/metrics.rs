@@ -0,0 +1,235 @@
+#![warn(missing_docs)]
+
+//! Counters for long running daemons, written in the Prometheus text format.
+//! Read with `ompl print metrics` or scraped from the `--metrics` HTTP listener.
+//! Library stages timed for `bench!` logging are also totalled here for `ompl bench`.
+
+use super::{http, Library};
+use crate::logging::*;
+
+use std::error::Error;
+use std::fmt::Write as _;
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, Weak};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Address listened on when none is given
+pub const DEFAULT_ADDRESS: &str = "127.0.0.1:18348";
+/// How long a scraper may take to send its request or read the answer, so a silent one can't hold up the rest
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Totalled runs of one stage of library work
+#[derive(Clone, Debug, PartialEq)]
+pub struct Timing {
+    /// Short name like "scan" or "sort"
+    pub stage: &'static str,
+    /// Times the stage ran
+    pub runs: u32,
+    /// Tracks handled across all runs
+    pub items: usize,
+    /// Time spent across all runs
+    pub total: Duration,
+}
+
+/// Running totals since startup
+#[derive(Debug)]
+pub(crate) struct Metrics {
+    started: Instant,
+    played: AtomicU64,
+    errors: AtomicU64,
+    requests: AtomicU64,
+    scans: AtomicU64,
+    /// Microseconds
+    scan_time: AtomicU64,
+    /// In the order stages first ran
+    timings: Mutex<Vec<Timing>>,
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Self {
+            started: Instant::now(),
+            played: AtomicU64::new(0),
+            errors: AtomicU64::new(0),
+            requests: AtomicU64::new(0),
+            scans: AtomicU64::new(0),
+            scan_time: AtomicU64::new(0),
+            timings: Mutex::new(Vec::new()),
+        }
+    }
+}
+
+impl Metrics {
+    pub(crate) fn played(&self) {
+        self.played.fetch_add(1, Ordering::Relaxed);
+    }
+    pub(crate) fn error(&self) {
+        self.errors.fetch_add(1, Ordering::Relaxed);
+    }
+    pub(crate) fn request(&self) {
+        self.requests.fetch_add(1, Ordering::Relaxed);
+    }
+    pub(crate) fn scanned(&self, time: Duration) {
+        self.scans.fetch_add(1, Ordering::Relaxed);
+        self.scan_time.fetch_add(time.as_micros() as u64, Ordering::Relaxed);
+    }
+
+    /// Add a run of STAGE over ITEMS tracks taking TIME.
+    /// Runs over nothing, like sorting before anything's loaded, would only drag the averages down
+    pub(crate) fn time(&self, stage: &'static str, items: usize, time: Duration) {
+        if items == 0 {
+            return;
+        }
+        let mut timings = self.timings.lock().unwrap_or_else(|e| e.into_inner());
+        match timings.iter_mut().find(|t| t.stage == stage) {
+            Some(timing) => {
+                timing.runs += 1;
+                timing.items += items;
+                timing.total += time;
+            }
+            None => timings.push(Timing {
+                stage,
+                runs: 1,
+                items,
+                total: time,
+            }),
+        }
+    }
+
+    pub(crate) fn timings(&self) -> Vec<Timing> {
+        self.timings.lock().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    /// Every metric with LIBRARY's current sizes
+    pub(crate) fn render(&self, library: &Library) -> String {
+        let load = |n: &AtomicU64| n.load(Ordering::Relaxed) as f64;
+        let mut metrics = vec![
+            (
+                "ompl_uptime_seconds",
+                "gauge",
+                "Seconds since the daemon started",
+                self.started.elapsed().as_secs_f64(),
+            ),
+            ("ompl_tracks_played_total", "counter", "Tracks started", load(&self.played)),
+            (
+                "ompl_decode_errors_total",
+                "counter",
+                "Errors while decoding or playing tracks",
+                load(&self.errors),
+            ),
+            ("ompl_requests_total", "counter", "Commands received from clients", load(&self.requests)),
+            ("ompl_scans_total", "counter", "Library folders scanned", load(&self.scans)),
+            (
+                "ompl_scan_seconds_total",
+                "counter",
+                "Time spent scanning library folders",
+                load(&self.scan_time) / 1e6,
+            ),
+            ("ompl_library_tracks", "gauge", "Tracks in the library", library.get_tracks().len() as f64),
+            ("ompl_queue_tracks", "gauge", "Tracks in the queue", library.get_queue().len() as f64),
+            (
+                "ompl_event_receivers",
+                "gauge",
+                "Frontends subscribed to library events",
+                library.events.receivers() as f64,
+            ),
+            (
+                "ompl_events_dropped_total",
+                "counter",
+                "Events dropped from receivers that fell behind",
+                library.events.dropped() as f64,
+            ),
+            (
+                "ompl_event_backlog",
+                "gauge",
+                "Most events waiting on any one receiver",
+                library.events.backlog() as f64,
+            ),
+        ];
+        if let Some(headroom) = library.headroom() {
+            metrics.push((
+                "ompl_limited_samples_total",
+                "counter",
+                "Samples the limiter kept from clipping",
+                headroom.limited_total as f64,
+            ));
+        }
+        if let Some(rss) = resident_bytes() {
+            metrics.push(("ompl_resident_memory_bytes", "gauge", "Resident memory", rss as f64));
+        }
+
+        let mut text = String::new();
+        for (name, kind, help, value) in metrics {
+            let _ = write!(text, "# HELP {name} {help}\n# TYPE {name} {kind}\n{name} {value}\n");
+        }
+        text
+    }
+}
+
+/// Resident set size. Only known on Linux
+fn resident_bytes() -> Option<u64> {
+    let status = std::fs::read_to_string("/proc/self/status").ok()?;
+    let line = status.lines().find(|l| l.starts_with("VmRSS:"))?;
+    let kb: u64 = line.trim_start_matches("VmRSS:").trim().trim_end_matches("kB").trim().parse().ok()?;
+    Some(kb * 1024)
+}
+
+fn respond(mut stream: TcpStream, library: &Weak<Library>) -> Result<(), Box<dyn Error>> {
+    let request = http::Request::read(&stream, TIMEOUT)?;
+    if request.method != "GET" && !request.is_head() {
+        return Ok(http::empty(&mut stream, "405 Method Not Allowed")?);
+    }
+    if request.target != "/" && request.target != "/metrics" {
+        return Ok(http::empty(&mut stream, "404 Not Found")?);
+    }
+    // only held while answering, so an idle connection doesn't keep the library alive
+    let body = library.upgrade().ok_or("Library is closed")?.metrics();
+    http::body(&mut stream, &request, "text/plain; version=0.0.4", body.as_bytes())?;
+    Ok(())
+}
+
+/// Serve LIBRARY's metrics at `http://ADDRESS/metrics` in a background thread until the library is dropped.
+/// Returns the address actually bound
+pub fn serve(library: &Arc<Library>, address: &str) -> Result<SocketAddr, Box<dyn Error>> {
+    let listener = TcpListener::bind(address)?;
+    let bound = listener.local_addr()?;
+    let library: Weak<Library> = Arc::downgrade(library);
+    thread::Builder::new().name(String::from("METRICS Server")).spawn(move || {
+        // scrapes are tiny, so one at a time is fine
+        for stream in listener.incoming().flatten() {
+            if library.strong_count() == 0 {
+                break;
+            }
+            if let Err(e) = respond(stream, &library) {
+                debug!("Metrics server: {}", e)
+            }
+        }
+    })?;
+    Ok(bound)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn totals() {
+        let metrics = Metrics::default();
+        metrics.time("sort", 10, Duration::from_millis(4));
+        metrics.time("scan", 10, Duration::from_millis(1));
+        metrics.time("sort", 10, Duration::from_millis(2));
+        metrics.time("filter", 0, Duration::from_millis(1));
+        let timings = metrics.timings();
+        assert_eq!(timings.iter().map(|t| t.stage).collect::<Vec<_>>(), ["sort", "scan"]);
+        assert_eq!((timings[0].runs, timings[0].items, timings[0].total), (2, 20, Duration::from_millis(6)));
+    }
+
+    #[test]
+    fn memory() {
+        if cfg!(target_os = "linux") {
+            assert!(resident_bytes().is_some_and(|b| b > 0));
+        }
+    }
+}
@@ -1,73 +1,152 @@
 #![warn(missing_docs)]
-use std::collections::HashMap;
+#[cfg(feature = "analyzer")]
+use std::collections::BTreeMap;
+use std::collections::{HashMap, HashSet};
 use std::error::Error;
 use std::fmt::Display;
+use std::ops::Deref;
+use std::panic::{catch_unwind, AssertUnwindSafe, Location};
 use std::path::{Path, PathBuf};
-use std::sync::atomic::{AtomicBool, Ordering};
-use std::sync::mpsc::{sync_channel, Receiver};
-use std::sync::{Arc, Mutex, MutexGuard, RwLock, RwLockReadGuard, RwLockWriteGuard};
-use std::thread::{self, sleep};
-use std::time::{Duration, Instant};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::mpsc::{channel, sync_channel, Receiver, RecvTimeoutError, Sender, SyncSender};
+use std::sync::{Arc, Weak};
+use std::thread::{self, sleep, JoinHandle};
+use std::time::{Duration, Instant, SystemTime};
 
-use bus::{Bus, BusReader};
 use lexical_sort::natural_lexical_cmp;
+use parking_lot::{Mutex, MutexGuard, RwLock, RwLockReadGuard, RwLockWriteGuard};
 use rand::random;
 
+#[cfg(feature = "analyzer")]
+mod analyzer;
+mod announce;
+mod artist;
+mod bpm;
+mod cancel;
+mod collate;
+/// Compilation albums and the `albumartist_eff` tag
+pub mod compilations;
+pub mod config;
+mod duplicates;
+mod events;
+/// Name files by tagstring, and copy them out to portable players
+pub mod export;
+mod genres;
+/// Just enough HTTP for the share, metrics, and cast servers, and file URLs for media controls
+pub mod http;
+pub mod metrics;
 mod player;
+mod queue_info;
+mod radio;
+/// Measure loudness and write ReplayGain tags
+#[cfg(feature = "replaygain")]
+pub mod replaygain;
+mod resume;
+/// Serve the library over HTTP for other instances
+#[cfg(feature = "share")]
+pub mod share;
+mod stats;
+pub mod supervisor;
+mod tag_snapshot;
 mod track;
 
+use crate::locale::{tr, trf};
 use crate::logging::*;
 
-pub use player::{Backend, Player};
-pub use track::{find_tracks, get_taglist, get_taglist_sort, tagstring, RawImage, Track};
+#[cfg(feature = "analyzer")]
+pub use analyzer::Analyzer;
+pub use announce::Announcer;
+pub use artist::{ArtistInfo, ArtistProvider};
+pub use bpm::BpmAnalysis;
+pub use cancel::Cancel;
+pub use collate::Collation;
+pub use duplicates::{Duplicate, DuplicatePolicy};
+pub use events::EventReceiver;
+pub use genres::Genres;
+pub use player::{Backend, Format, Headroom, Output, Player, Resampler, RouteChange, Silence};
+pub use queue_info::QueueInfo;
+pub use radio::Radio;
+pub use resume::Resume;
+pub use tag_snapshot::TagSnapshot;
+pub use track::{find_tracks, find_tracks_cancellable, get_taglist, get_taglist_sort, tagstring, ArtSource, RawImage, Track};
 
 use player::PlayerMessage;
+pub use stats::Bookmark;
+use stats::Stats;
 
 // ### Timed Lock Traits {{{
 
-macro_rules! timed_guard {
-    ($result:expr, $err:literal) => {
-        let mut n = 0;
-        loop {
-            let result = $result;
-            match result {
-                Err(std::sync::TryLockError::WouldBlock) => (),
-                _ => return Ok(result?),
-            }
-            // 2 seconds per lock should be 100x more than enough
-            if n > 2000 {
-                break;
-            }
-            sleep(Duration::from_millis(1));
-            n += 1
-        }
-        error!(concat!($err, " lock timed out."));
-        return Err(concat!($err, " lock timed out.").into());
-    };
+/// 2 seconds per lock should be 100x more than enough.
+/// Anything longer is a bug so it's reported instead of waited out
+const LOCK_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// A library lock wasn't acquired within LOCK_TIMEOUT
+#[derive(Debug)]
+pub struct LockTimeout {
+    kind: &'static str,
+    location: &'static Location<'static>,
+}
+
+impl LockTimeout {
+    fn new(kind: &'static str, location: &'static Location<'static>) -> Self {
+        let timeout = Self { kind, location };
+        error!("{}", timeout);
+        timeout
+    }
+}
+
+impl Display for LockTimeout {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} lock timed out at {}", self.kind, self.location)
+    }
 }
 
+impl Error for LockTimeout {}
+
+// Blocks properly instead of polling, and no poisoning to deal with.
 trait RwLockTimeout<T> {
-    fn timed_read<'a>(&'a self) -> Result<RwLockReadGuard<'a, T>, Box<dyn Error + 'a>>;
-    fn timed_write<'a>(&'a self) -> Result<RwLockWriteGuard<'a, T>, Box<dyn Error + 'a>>;
+    fn timed_read(&self) -> Result<RwLockReadGuard<'_, T>, LockTimeout>;
+    fn timed_write(&self) -> Result<RwLockWriteGuard<'_, T>, LockTimeout>;
 }
 
 impl<T> RwLockTimeout<T> for RwLock<T> {
-    fn timed_read<'a>(&'a self) -> Result<RwLockReadGuard<'a, T>, Box<dyn Error + 'a>> {
-        timed_guard!(self.try_read(), "Read");
+    #[track_caller]
+    fn timed_read(&self) -> Result<RwLockReadGuard<'_, T>, LockTimeout> {
+        let location = Location::caller();
+        self.try_read_for(LOCK_TIMEOUT).ok_or_else(|| LockTimeout::new("Read", location))
     }
 
-    fn timed_write<'a>(&'a self) -> Result<RwLockWriteGuard<'a, T>, Box<dyn Error + 'a>> {
-        timed_guard!(self.try_write(), "Write");
+    #[track_caller]
+    fn timed_write(&self) -> Result<RwLockWriteGuard<'_, T>, LockTimeout> {
+        let location = Location::caller();
+        self.try_write_for(LOCK_TIMEOUT).ok_or_else(|| LockTimeout::new("Write", location))
     }
 }
 
 trait MutexTimeout<T> {
-    fn timed_lock<'a>(&'a self) -> Result<MutexGuard<'a, T>, Box<dyn Error + 'a>>;
+    fn timed_lock(&self) -> Result<MutexGuard<'_, T>, LockTimeout>;
 }
 
 impl<T> MutexTimeout<T> for Mutex<T> {
-    fn timed_lock<'a>(&'a self) -> Result<MutexGuard<'a, T>, Box<dyn Error + 'a>> {
-        timed_guard!(self.try_lock(), "Mutex");
+    #[track_caller]
+    fn timed_lock(&self) -> Result<MutexGuard<'_, T>, LockTimeout> {
+        let location = Location::caller();
+        self.try_lock_for(LOCK_TIMEOUT).ok_or_else(|| LockTimeout::new("Mutex", location))
+    }
+}
+
+/// Logs every deadlocked thread's backtrace
+#[cfg(feature = "deadlock-detection")]
+fn deadlock_detector() {
+    debug!("Deadlock detector start");
+    loop {
+        sleep(Duration::from_secs(10));
+        for (n, threads) in parking_lot::deadlock::check_deadlock().iter().enumerate() {
+            error!("Deadlock #{}", n);
+            for thread in threads {
+                error!("Thread {:?}\n{:?}", thread.thread_id(), thread.backtrace());
+            }
+        }
     }
 }
 
@@ -75,28 +154,170 @@ impl<T> MutexTimeout<T> for Mutex<T> {
 
 // ## FILTER ## {{{
 
+/// How a Filter's items are compared against each track's tagstring
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+pub enum FilterOp {
+    /// Equal to any item
+    #[default]
+    Eq,
+    /// Naturally sorts after or equal to any item
+    Ge,
+    /// Naturally sorts before or equal to any item
+    Le,
+}
+
+impl Display for FilterOp {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            FilterOp::Eq => "=",
+            FilterOp::Ge => ">=",
+            FilterOp::Le => "<=",
+        })
+    }
+}
+
+/// Layer of the filter tree. Keeps tracks whose TAG tagstring matches ITEMS according to OP
 #[derive(Clone, Debug, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
 pub struct Filter {
+    /// Tagstring compared against
     pub tag: String,
+    /// Values to compare with. Empty means nothing is selected yet and every track passes
     pub items: Vec<String>,
+    /// Comparison against ITEMS
+    pub op: FilterOp,
+}
+
+impl Filter {
+    /// Whether a parsed tagstring passes the filter
+    pub fn matches(&self, value: &str) -> bool {
+        match self.op {
+            FilterOp::Eq => self.items.iter().any(|i| i == value),
+            FilterOp::Ge => self.items.iter().any(|i| natural_lexical_cmp(value, i).is_ge()),
+            FilterOp::Le => self.items.iter().any(|i| natural_lexical_cmp(value, i).is_le()),
+        }
+    }
+}
+
+impl Display for Filter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.items.is_empty() {
+            f.write_str(&self.tag)
+        } else {
+            f.write_fmt(format_args!("{}{}{}", self.tag, self.op, self.items.join(",")))
+        }
+    }
 }
 
 /// A Filter and its post-filtering tracks
 #[derive(Clone, Debug, PartialEq)]
 pub struct FilteredTracks {
+    /// Filter for this layer
     pub filter: Filter,
+    /// Tracks which passed this and all previous layers
     pub tracks: Vec<Arc<Track>>,
 }
 
+/// Whether both lists hold the exact same tracks in the same order
+fn same_tracks(a: &[Arc<Track>], b: &[Arc<Track>]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).all(|(a, b)| Arc::ptr_eq(a, b))
+}
+
+/// Whether both lists hold the exact same tracks in any order
+fn same_track_set(a: &[Arc<Track>], b: &[Arc<Track>]) -> bool {
+    a.len() == b.len() && a.iter().map(Arc::as_ptr).collect::<HashSet<_>>() == b.iter().map(Arc::as_ptr).collect::<HashSet<_>>()
+}
+
+/// Last non-empty layer, else every track
+fn queue_layer<'a>(tracks: &'a Vec<Arc<Track>>, tree: &'a [FilteredTracks]) -> &'a Vec<Arc<Track>> {
+    tree.iter().rev().map(|ft| &ft.tracks).find(|t| !t.is_empty()).unwrap_or(tracks)
+}
+
+/// Split a sorter into its tagstring and whether it sorts descending, marked by a leading '-'
+pub fn parse_sorter(sorter: &str) -> (&str, bool) {
+    match sorter.strip_prefix('-') {
+        Some(tagstring) => (tagstring, true),
+        None => (sorter, false),
+    }
+}
+
 // ## FILTER ## }}}
 
+// ## VIEW ## {{{
+
+/// A named, independent filter + sorter stack
+#[derive(Clone, Debug, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+pub struct View {
+    /// Display name
+    pub name: String,
+    /// Filter stack of the view
+    pub filters: Vec<Filter>,
+    /// Sorter stack of the view
+    pub sorters: Vec<String>,
+}
+
+// ## VIEW ## }}}
+
+// ## SNAPSHOT ## {{{
+
+/// Frequently polled playback state.
+/// Refreshed whenever it changes so readers never wait on the player or the library's locks
+#[derive(Clone, Debug)]
+pub struct Snapshot {
+    /// Currently loaded track
+    pub track: Option<Arc<Track>>,
+    /// Currently playing
+    pub playing: bool,
+    /// Currently paused
+    pub paused: bool,
+    /// Currently stopped
+    pub stopped: bool,
+    /// 0.0 -> 1.0
+    pub volume: f32,
+    /// Same as [`Library::seekable`]
+    pub seekable: Option<bool>,
+    /// Statusline formatted for the current track
+    pub statusline: String,
+    times: Option<(Duration, Duration)>,
+    taken: Instant,
+}
+
+impl Snapshot {
+    /// Track position, Track duration.
+    /// The position keeps counting from when the snapshot was taken while playing
+    pub fn times(&self) -> Option<(Duration, Duration)> {
+        self.times.map(|(current, total)| match self.playing {
+            true => ((current + self.taken.elapsed()).min(total), total),
+            false => (current, total),
+        })
+    }
+}
+
+// ## SNAPSHOT ## }}}
+
+// ## PREVIEW ## {{{
+
+/// A track played briefly with [`Library::preview`], and what to go back to after
+struct Preview {
+    /// Counts up so a replaced preview's timer doesn't end the one after it
+    id: usize,
+    previewing: Arc<Track>,
+    track: Option<Arc<Track>>,
+    position: Option<Duration>,
+    playing: bool,
+}
+
+// ## PREVIEW ## }}}
+
 // ## THEME ## {{{
 
 /// A theme color represented as 8bit sRGB or one of 16 terminal colors
 #[derive(Clone, Copy, Debug, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
 pub enum Color {
+    /// Plain sRGB
     RGB([u8; 3]),
+    /// Terminal palette index
     Term(u8),
+    /// Terminal default
     None,
 }
 
@@ -157,41 +378,377 @@ impl Display for Color {
     }
 }
 
+/// How album art colors are reduced to what the terminal can show
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, serde::Deserialize, serde::Serialize, clap::ValueEnum)]
+pub enum ArtDither {
+    /// Nearest color per cell
+    #[default]
+    None,
+    /// Bayer matrix, stable between frames
+    Ordered,
+    /// Error diffusion, best for detailed covers on 16 or 256 colors
+    FloydSteinberg,
+}
+
+/// Colors album art is drawn with
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, serde::Deserialize, serde::Serialize, clap::ValueEnum)]
+pub enum ArtColors {
+    /// 16 on terminals that only have those, otherwise truecolor
+    #[default]
+    Auto,
+    /// The terminal's own 16 colors
+    #[value(name = "16")]
+    Ansi16,
+    /// The xterm 256 color palette
+    #[value(name = "256")]
+    Ansi256,
+    /// 24 bit RGB
+    Truecolor,
+}
+
 /// 3-tone theme
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub struct Theme {
+    /// Foreground
     pub fg: Color,
+    /// Background
     pub bg: Color,
+    /// Accent
     pub acc: Color,
+    /// Art size in rows. 0 to hide
     pub art_size: u8,
+    /// Art dithering
+    pub art_dither: ArtDither,
+    /// Art color depth
+    pub art_colors: ArtColors,
+    /// Center art with bars on either side instead of holding it to the corner
+    pub art_letterbox: bool,
+    /// Color chip from each album's art beside the tracks in the queue
+    pub art_chips: bool,
+}
+
+impl Theme {
+    /// Valid art sizes. 32x32 is already insanely big so that's the hard limit to reduce complexity.
+    /// 4b * 32 * 32 * 10,000 == 41MB thumbnails worst case so the naive caching will do just fine
+    pub const ART_SIZES: &'static [u8] = &[0, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16];
 }
 
 impl Display for Theme {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.write_fmt(format_args!(
-            "fg: {}\nbg: {}\nacc: {}\nart_size: {}",
-            self.fg, self.bg, self.acc, self.art_size
+            "fg: {}\nbg: {}\nacc: {}\nart_size: {}\nart_dither: {}\nart_colors: {}\nart_letterbox: {}\nart_chips: {}",
+            self.fg,
+            self.bg,
+            self.acc,
+            self.art_size,
+            value_name(self.art_dither),
+            value_name(self.art_colors),
+            self.art_letterbox,
+            self.art_chips
+        ))
+    }
+}
+
+/// Which of the [`SeekSteps`] to seek by
+#[derive(Clone, Copy, Debug, PartialEq, Eq, serde::Deserialize, serde::Serialize, clap::ValueEnum)]
+pub enum SeekStep {
+    /// 5 seconds by default
+    Small,
+    /// 30 seconds by default
+    Medium,
+    /// 5 minutes by default
+    Large,
+}
+
+/// Seconds seeked by each [`SeekStep`], for getting around anything from a song to a 10 hour stream
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SeekSteps {
+    /// `.` and `,` in the TUI
+    pub small: f32,
+    /// `>` and `<`
+    pub medium: f32,
+    /// Alt+`.` and Alt+`,`
+    pub large: f32,
+}
+
+impl SeekSteps {
+    /// Whether SECS is usable as a step, which has to move somewhere
+    pub fn valid(secs: f32) -> bool {
+        secs.is_finite() && secs > 0.0
+    }
+
+    /// Seconds for STEP
+    pub fn get(&self, step: SeekStep) -> f32 {
+        match step {
+            SeekStep::Small => self.small,
+            SeekStep::Medium => self.medium,
+            SeekStep::Large => self.large,
+        }
+    }
+}
+
+impl Default for SeekSteps {
+    fn default() -> Self {
+        Self {
+            small: 5.0,
+            medium: 30.0,
+            large: 300.0,
+        }
+    }
+}
+
+impl Display for SeekSteps {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_fmt(format_args!(
+            "seek_small: {}\nseek_medium: {}\nseek_large: {}",
+            self.small, self.medium, self.large
         ))
     }
 }
 
+/// Name clap knows V by
+fn value_name(v: impl clap::ValueEnum) -> String {
+    v.to_possible_value().map(|p| p.get_name().to_string()).unwrap_or_default()
+}
+
 // ## THEME ## }}}
 
 // ### FNs ### {{{
 
-fn player_message_server(library: Arc<Library>, next_r: Receiver<PlayerMessage>) {
+/// How long a single filesystem call may take before its root is considered unreachable
+const IO_TIMEOUT: Duration = Duration::from_secs(2);
+/// How long a scan may go without any probe chunk finishing
+const PROBE_TIMEOUT: Duration = Duration::from_secs(15);
+/// How often offline roots are checked again
+const RETRY_INTERVAL: Duration = Duration::from_secs(30);
+/// How often every track is checked for a missing or unreadable file, on top of after each scan
+const VALIDATE_INTERVAL: Duration = Duration::from_secs(300);
+/// How often the clocks are compared to notice the system waking from suspend
+const WAKE_INTERVAL: Duration = Duration::from_secs(2);
+/// How far the wall clock can get ahead of the monotonic one before it counts as a suspend
+const WAKE_JUMP: Duration = Duration::from_secs(5);
+/// How long playback fades out for on exit
+const EXIT_FADE: Duration = Duration::from_millis(300);
+/// Volume steps in the exit fade
+const EXIT_FADE_STEPS: u32 = 10;
+/// How long a preview plays for
+const PREVIEW_LENGTH: Duration = Duration::from_secs(10);
+/// How far into a track previews start, as a fraction of its length
+const PREVIEW_START: f32 = 0.3;
+/// How long a track gets to become seekable before a position waiting on it is given up on
+const SEEK_TIMEOUT: Duration = Duration::from_secs(30);
+/// Failed decodes before a track is quarantined
+const QUARANTINE_AFTER: u32 = 3;
+/// Libraries at least this large report sorting progress
+const SORT_PROGRESS_MIN: usize = 10_000;
+/// Recently played tracks the radio passes over
+const RADIO_RECENT: usize = 50;
+/// Analyzed tracks gathered before filters are rebuilt with their tags
+#[cfg(feature = "analyzer")]
+const ANALYZED_BATCH: usize = 25;
+
+/// Blocking IO handed to a worker by [`timed_io`]
+type IoJob = Box<dyn FnOnce() + Send>;
+/// Idle IO workers waiting on their next job
+static IO_IDLE: Mutex<Vec<Sender<IoJob>>> = Mutex::new(Vec::new());
+/// Idle IO workers kept around, any more finish once their job is done
+const IO_IDLE_MAX: usize = 4;
+
+/// Runs blocking IO on an idle worker thread, starting one if none are free, and gives up after `timeout`.
+/// A hung network mount leaves its worker parked but no longer holds up the caller,
+/// and that worker never goes back to the idle list so later IO doesn't queue behind it.
+fn timed_io<T: Send + 'static>(timeout: Duration, f: impl FnOnce() -> T + Send + 'static) -> Option<T> {
+    let (sender, receiver) = sync_channel(1);
+    let mut job: IoJob = Box::new(move || {
+        let _ = sender.send(f());
+    });
+    let idle = IO_IDLE.lock().pop();
+    if let Some(worker) = idle {
+        match worker.send(job) {
+            Ok(()) => return receiver.recv_timeout(timeout).ok(),
+            Err(returned) => job = returned.0,
+        }
+    }
+    thread::Builder::new()
+        .name(String::from("LIBRARY IO"))
+        .spawn(move || io_worker(job))
+        .ok()?;
+    receiver.recv_timeout(timeout).ok()
+}
+
+fn io_worker(mut job: IoJob) {
+    let (sender, jobs) = channel();
+    loop {
+        job();
+        {
+            let mut idle = IO_IDLE.lock();
+            if idle.len() >= IO_IDLE_MAX {
+                break;
+            }
+            idle.push(sender.clone());
+        }
+        match jobs.recv() {
+            Ok(next) => job = next,
+            Err(_) => break,
+        }
+    }
+}
+
+/// Tracks of TYPES directly inside DIR, without walking any deeper
+fn tracks_in(dir: &Path, types: &[String], include_hidden: bool) -> Vec<Track> {
+    dir.read_dir()
+        .into_iter()
+        .flatten()
+        .filter_map(|e| e.ok())
+        .filter(|e| {
+            e.file_name()
+                .to_str()
+                .is_some_and(|s| (include_hidden || !s.starts_with('.')) && types.iter().any(|t| s.ends_with(t)))
+        })
+        .filter(|e| e.path().is_file())
+        .filter_map(|e| Track::new(e.path()))
+        .collect()
+}
+
+fn offline_retry_server(library_weak: Weak<Library>) {
+    debug!("Offline retry start");
+    loop {
+        sleep(RETRY_INTERVAL);
+        match library_weak.upgrade() {
+            Some(library) => library.retry_offline(),
+            None => break,
+        }
+    }
+    debug!("Offline retry end");
+}
+
+/// Restarts playback after the system wakes from suspend.
+/// The monotonic clock stops while suspended and the wall clock doesn't, so a gap between them means it slept
+fn wake_server(library_weak: Weak<Library>) {
+    debug!("Wake server start");
+    loop {
+        let Some(library) = library_weak.upgrade() else { break };
+        let position = library.playing().then(|| library.times().map(|t| t.0)).flatten();
+        drop(library);
+
+        let (mono, wall) = (Instant::now(), SystemTime::now());
+        sleep(WAKE_INTERVAL);
+        let slept = wall.elapsed().unwrap_or_default().saturating_sub(mono.elapsed());
+        if slept > WAKE_JUMP {
+            match library_weak.upgrade() {
+                Some(library) => library.wake(slept, position),
+                None => break,
+            }
+        }
+    }
+    debug!("Wake server end");
+}
+
+/// Runs the analyzer on each batch of paths queued by appends
+#[cfg(feature = "analyzer")]
+fn analyzer_server(library_weak: Weak<Library>, analyze_r: &Receiver<Vec<PathBuf>>) {
+    debug!("Analyzer server start");
+    while let Ok(paths) = analyze_r.recv() {
+        match library_weak.upgrade() {
+            Some(library) => library.analyze(paths),
+            None => break,
+        }
+    }
+    debug!("Analyzer server end");
+}
+
+/// Pauses while a call app has the audio and resumes after, as PulseAudio or PipeWire asks
+#[cfg(all(feature = "cork", target_os = "linux"))]
+fn cork_server(library_weak: Weak<Library>) {
+    debug!("Cork server start");
+    let result = player::cork::watch(|corked| match library_weak.upgrade() {
+        Some(library) => {
+            library.cork(corked);
+            true
+        }
+        None => false,
+    });
+    if let Err(e) = result {
+        error!("Not following corking, {}", e)
+    }
+    debug!("Cork server end");
+}
+
+/// Sorts whenever asked. Requests made during a sort are coalesced into one more pass
+fn sort_server(library_weak: Weak<Library>, sort_r: &Receiver<()>) {
+    debug!("Sort server start");
+    while sort_r.recv().is_ok() {
+        match library_weak.upgrade() {
+            Some(library) => library.sort_now(),
+            None => break,
+        }
+    }
+    debug!("Sort server end");
+}
+
+/// Sets the accent from each new track's art while auto-art is on
+fn accent_server(library_weak: Weak<Library>, accent_r: &Receiver<()>) {
+    debug!("Accent server start");
+    while accent_r.recv().is_ok() {
+        match library_weak.upgrade() {
+            Some(library) => library.accent_from_art(),
+            None => break,
+        }
+    }
+    debug!("Accent server end");
+}
+
+/// Looks up the artist of each track the info panel asks about
+fn artist_server(library_weak: Weak<Library>, artist_r: &Receiver<Arc<Track>>) {
+    debug!("Artist server start");
+    while let Ok(track) = artist_r.recv() {
+        match library_weak.upgrade() {
+            Some(library) => library.read_artist(track),
+            None => break,
+        }
+    }
+    debug!("Artist server end");
+}
+
+/// Checks every track can still be opened after each scan, and every so often for files removed since
+fn validate_server(library_weak: Weak<Library>, validate_r: &Receiver<()>) {
+    debug!("Validate server start");
+    while let Ok(()) | Err(RecvTimeoutError::Timeout) = validate_r.recv_timeout(VALIDATE_INTERVAL) {
+        match library_weak.upgrade() {
+            Some(library) => library.validate(),
+            None => break,
+        }
+    }
+    debug!("Validate server end");
+}
+
+/// Reads art for each batch of tracks the queue asks chips for, announcing once the batch is done
+fn chip_server(library_weak: Weak<Library>, chip_r: &Receiver<Vec<Arc<Track>>>) {
+    debug!("Chip server start");
+    while let Ok(tracks) = chip_r.recv() {
+        match library_weak.upgrade() {
+            Some(library) => library.read_chips(tracks),
+            None => break,
+        }
+    }
+    debug!("Chip server end");
+}
+
+fn player_message_server(library_weak: Weak<Library>, next_r: &Receiver<PlayerMessage>) {
     debug!("PMS Start");
-    let library_weak = Arc::downgrade(&library);
-    drop(library);
     loop {
         let msg = next_r.recv();
         if let Some(library) = library_weak.upgrade() {
             match msg {
                 Ok(msg) => match msg {
+                    // a preview running out goes back instead of on
+                    PlayerMessage::Request if library.previewing() => {
+                        library.preview_end(None);
+                    }
                     PlayerMessage::Request => match library.repeat_get() {
                         None => {
-                            if library.get_queue().last() == library.track_get().as_ref() && !library.shuffle_get() {
-                                library.broadcast(LibEvt::Playback)
+                            if library.get_queue().last() == library.track_get().as_ref() && !library.shuffle_get() && !library.radio_next() {
+                                library.broadcast(LibEvt::State)
                             } else {
                                 library.next()
                             }
@@ -200,8 +757,30 @@ fn player_message_server(library: Arc<Library>, next_r: Receiver<PlayerMessage>)
                         Some(true) => library.next(),
                     },
 
-                    PlayerMessage::Seekable | PlayerMessage::Clock => library.broadcast(LibEvt::Playback),
-                    PlayerMessage::Error(e) => library.broadcast(LibEvt::Error(e)),
+                    PlayerMessage::Seekable => {
+                        library.broadcast(LibEvt::Clock);
+                        library.bpm_measure()
+                    }
+                    PlayerMessage::Clock => library.broadcast(LibEvt::Clock),
+                    PlayerMessage::Error(e) => {
+                        library.metrics.error();
+                        library.broadcast(LibEvt::Error(e))
+                    }
+                    PlayerMessage::DecodeError(path, e) => {
+                        library.metrics.error();
+                        library.broadcast(LibEvt::Error(e));
+                        library.decode_failed(&path)
+                    }
+                    PlayerMessage::RouteChange(output) => match library.route_change_get() {
+                        RouteChange::Pause => {
+                            info!("Paused, {}", output);
+                            library.broadcast(LibEvt::State)
+                        }
+                        RouteChange::Follow => {
+                            info!("Resuming on the new output, {}", output);
+                            library.play()
+                        }
+                    },
                 },
                 Err(_) => break,
             }
@@ -224,6 +803,112 @@ fn integer_linspace(start: usize, end: usize, count: usize) -> Box<[usize]> {
     result.into()
 }
 
+/// Folder and album, which art chips are shared by
+type ChipKey = (PathBuf, String);
+
+/// Tracks in one folder with one album almost always have the same art
+fn chip_key(track: &Track) -> ChipKey {
+    (
+        track.path().parent().map(Path::to_path_buf).unwrap_or_default(),
+        track.tags().get("album").cloned().unwrap_or_default(),
+    )
+}
+
+/// ART shrunk to fit W by H, keeping its aspect ratio
+fn downscale(art: &RawImage, w: usize, h: usize) -> RawImage {
+    let aspect = art[0].len() as f64 / art.len() as f64;
+    let thumb_w = ((h as f64 * aspect).round() as usize).min(w);
+    let thumb_h = ((w as f64 / aspect).round() as usize).min(h);
+
+    let thumbnail: RawImage = split_count(art, thumb_h)
+        .into_iter()
+        .map(|column_chunk| {
+            column_chunk
+                .into_iter()
+                .map(|row| {
+                    split_count(row, thumb_w).into_iter().map(|row_chunk| {
+                        row_chunk
+                            .into_iter()
+                            .fold([0u64; 4], |mut acc, it| {
+                                acc.iter_mut().zip(it.into_iter()).for_each(|(a, b)| *a += *b as u64);
+                                acc
+                            })
+                            .map(|c| c / row_chunk.len() as u64)
+                    })
+                })
+                .fold(Vec::<[u64; 4]>::new(), |acc, it| {
+                    if acc.is_empty() {
+                        it.collect()
+                    } else {
+                        acc.into_iter()
+                            .zip(it.into_iter())
+                            .map(|(mut a, b)| {
+                                a.iter_mut().zip(b.into_iter()).for_each(|(c, d)| *c += d);
+                                a
+                            })
+                            .collect()
+                    }
+                })
+                .into_iter()
+                .map(|rgba| rgba.map(|c| (c / column_chunk.len() as u64) as u8))
+                .collect()
+        })
+        .collect();
+
+    debug!(
+        "Generated {}x{} thumbnail for requested {}x{} of image {}x{}",
+        thumbnail[0].len(),
+        thumbnail.len(),
+        w,
+        h,
+        art[0].len(),
+        art.len()
+    );
+    thumbnail
+}
+
+/// Average color of IMAGE's opaque pixels
+fn mean_color(image: &RawImage) -> Option<[u8; 3]> {
+    let (sum, count) = image
+        .iter()
+        .flat_map(|row| row.iter())
+        .filter(|p| p[3] > u8::MAX / 2)
+        .fold(([0u64; 3], 0u64), |(mut sum, count), p| {
+            sum.iter_mut().zip(p).for_each(|(s, c)| *s += *c as u64);
+            (sum, count + 1)
+        });
+    (count > 0).then(|| sum.map(|s| (s / count) as u8))
+}
+
+/// Most common hue in IMAGE, brightened enough to read as text. None for grayscale or empty images
+fn dominant_color(image: &RawImage) -> Option<[u8; 3]> {
+    // oklab sums and total chroma per 15 degrees of hue
+    let mut buckets = [([0f32; 3], 0f32); 24];
+    for pixel in image.iter().flat_map(|row| row.iter()).filter(|p| p[3] > u8::MAX / 2) {
+        let mut lab: [f32; 3] = colcon::irgb_to_srgb([pixel[0], pixel[1], pixel[2]]);
+        colcon::convert_space(colcon::Space::SRGB, colcon::Space::OKLAB, &mut lab);
+        let chroma = lab[1].hypot(lab[2]);
+        // near grays have no hue worth picking
+        if chroma < 0.03 {
+            continue;
+        }
+        let hue = lab[2].atan2(lab[1]).to_degrees().rem_euclid(360.0);
+        let bucket = &mut buckets[(hue / 15.0) as usize % 24];
+        bucket.0.iter_mut().zip(lab).for_each(|(sum, c)| *sum += c * chroma);
+        bucket.1 += chroma;
+    }
+    let (sum, weight) = buckets.into_iter().max_by(|a, b| a.1.total_cmp(&b.1))?;
+    if weight <= 0.0 {
+        return None;
+    }
+    let mut lch = sum.map(|c| c / weight);
+    colcon::convert_space(colcon::Space::OKLAB, colcon::Space::OKLCH, &mut lch);
+    lch[0] = lch[0].clamp(0.65, 0.85);
+    lch[1] = lch[1].max(0.08);
+    colcon::convert_space(colcon::Space::OKLCH, colcon::Space::SRGB, &mut lch);
+    Some(colcon::srgb_to_irgb(lch))
+}
+
 fn split_count<'a, T>(slice: &'a [T], n: usize) -> Vec<&'a [T]> {
     integer_linspace(0, slice.len(), (n + 1).min(slice.len()))
         .windows(2)
@@ -233,120 +918,512 @@ fn split_count<'a, T>(slice: &'a [T], n: usize) -> Vec<&'a [T]> {
 
 // ### FNs ### }}}
 
+/// Events broadcast to every receiver from [`Library::get_receiver`].
+/// Granular so frontends only refresh what actually changed
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum LibEvt {
-    /// Simple state change.
-    /// Pause/Play/Seek/etc.
-    Playback,
-    /// Complex state change.
-    /// Filters updated, etc.
-    Update,
-    /// Theme changed.
+    /// Play/pause/stop state changed
+    State,
+    /// A different track was loaded
+    Track,
+    /// Volume changed
+    Volume,
+    /// Playback position advanced, jumped from a seek, or became seekable.
+    /// Sent every second while playing
+    Clock,
+    /// Shuffle, repeat, consume, or radio changed
+    Mode,
+    /// Filters changed and the filter tree was rebuilt.
+    /// Holds the indices of layers whose filter or tracks changed.
+    /// Layers past the end of the new tree were removed
+    Filters(Vec<usize>),
+    /// The queue was reordered or refiltered
+    Queue,
+    /// Background sort is parsing keys, (done, total).
+    /// Only sent for large libraries, ends with Queue
+    Sorting(usize, usize),
+    /// Tracks were added to or removed from the library
+    Tracks,
+    /// Views were added, removed, renamed, or switched
+    Views,
+    /// Theme or statusline changed.
     Theme,
     /// Non-fatal error.
     Error(String),
+    /// Library root stopped responding and is skipped until it comes back.
+    Offline(PathBuf),
+    /// Tracks were found missing or unreadable, or readable again
+    Unreadable,
+    /// Art chips were read for more tracks
+    ArtChips,
+    /// The playing track's artist was looked up
+    Artist,
 }
 
+/// The player engine. Owns the tracks, the filter and sorter stacks, and the audio backend
 pub struct Library {
     tracks: RwLock<Vec<Arc<Track>>>,
     history: Mutex<Vec<Arc<Track>>>,
     player: Box<dyn Player>,
+    /// Volume from before muting. Any other volume change unmutes
+    muted: Mutex<Option<f32>>,
+    /// Highest volume anything can set
+    volume_max: RwLock<f32>,
+    /// Frontends and clients are limited to playback and volume
+    kiosk: AtomicBool,
+    /// Frontends ask before purging, dropping filters, or exiting
+    confirm: AtomicBool,
+    /// Per-track data kept across runs
+    stats: RwLock<Stats>,
     filtered_tree: RwLock<Vec<FilteredTracks>>,
     sorters: RwLock<Vec<String>>,
+    /// How sorters and filter panes compare tagstrings
+    collation: RwLock<Collation>,
+    /// Stored filter + sorter stacks. The active view's entry is only updated on switch
+    views: RwLock<Vec<View>>,
+    /// Name of the view holding loved tracks
+    favorites: RwLock<String>,
+    /// Run before each track starts
+    announcer: RwLock<Option<Announcer>>,
+    view: AtomicUsize,
     /// Broadcaster for all receivers of library events
-    bus: Mutex<Bus<LibEvt>>,
+    events: events::EventBus,
+    /// Events held back while a [`Library::batch`] runs
+    held: Mutex<Option<Vec<LibEvt>>>,
+    /// Swapped out whole, only ever locked long enough to clone the Arc
+    snapshot: RwLock<Arc<Snapshot>>,
+    /// Wakes the sort server
+    sort_s: SyncSender<()>,
+    /// The accent set by hand while it's taken from art, None when auto-art is off
+    accent: RwLock<Option<Color>>,
+    /// Wakes the accent server
+    accent_s: SyncSender<()>,
+    /// Wakes the validate server
+    validate_s: SyncSender<()>,
+    /// Hands the chip server tracks to read art for
+    chip_s: SyncSender<Vec<Arc<Track>>>,
+    /// Queue art chip per album, None for albums without art
+    chips: RwLock<HashMap<ChipKey, Option<[u8; 3]>>>,
+    /// Hands the artist server tracks whose artist should be looked up
+    artist_s: SyncSender<Arc<Track>>,
+    /// Looked up artists by name, None for ones with nothing to show
+    artists: RwLock<HashMap<String, Option<Arc<ArtistInfo>>>>,
+    /// Artist picture thumbnails by (width, height, artist)
+    artist_thumbnails: RwLock<HashMap<(usize, usize, String), Arc<RawImage>>>,
+    /// Command looking up artists without local files
+    artist_provider: RwLock<Option<ArtistProvider>>,
+    /// Where provider answers are kept. None doesn't keep them
+    artist_cache: RwLock<Option<PathBuf>>,
+    /// Tracks whose files couldn't be opened on the last check
+    unreadable: RwLock<HashSet<PathBuf>>,
+    /// Failed decodes per track this run, quarantined at [`QUARANTINE_AFTER`]
+    decode_failures: Mutex<HashMap<PathBuf, u32>>,
     shuffle: AtomicBool,
+    /// Tagstring grouping albums when shuffling whole albums, None to shuffle tracks
+    shuffle_album: RwLock<Option<String>>,
+    /// When the next track is picked by similarity to the current one
+    radio: RwLock<Radio>,
+    /// Drop tracks from the queue once played
+    consume: AtomicBool,
+    /// Tracks consumed so far, hidden from the queue on top of the filters
+    consumed: RwLock<HashSet<PathBuf>>,
     /// None - No loop
     /// Some(false) - track loop
     /// Some(true) - full loop
     repeat: RwLock<Option<bool>>,
     /// Scan hidden files during append
     hidden: AtomicBool,
+    /// Every path passed to append_library, searched when relocating moved tracks
+    roots: RwLock<Vec<PathBuf>>,
+    /// Handed to each scan and sort as it starts, swapped for a fresh one when cancelled
+    cancel: Mutex<Cancel>,
+    /// Set while a preview plays
+    preview: Mutex<Option<Preview>>,
+    previews: AtomicUsize,
+    /// Roots which timed out, retried in the background
+    offline: RwLock<Vec<PathBuf>>,
+    /// Files under offline roots which timed out while probing, by root
+    skipped: RwLock<HashMap<PathBuf, Vec<PathBuf>>>,
     /// Single line status for library
     statusline: RwLock<String>,
+    /// Tagstring the queue is split into groups by, empty for none
+    groupline: RwLock<String>,
     theme: RwLock<Theme>,
+    seek_steps: RwLock<SeekSteps>,
     art: RwLock<Option<Arc<RawImage>>>,
     thumbnails: RwLock<HashMap<(usize, usize, PathBuf), Option<Arc<RawImage>>>>,
+    /// Where art is looked for, in order
+    art_sources: RwLock<Vec<ArtSource>>,
+    /// What's done with tracks matching the tags of one already loaded
+    duplicate_policy: RwLock<DuplicatePolicy>,
+    /// Every such track found since the last purge
+    duplicates: RwLock<Vec<Duplicate>>,
+    /// Summed on first use, cleared whenever the queue may have changed
+    queue_info: RwLock<Option<QueueInfo>>,
+    /// What's done when the output device goes away mid-track
+    route_change: RwLock<RouteChange>,
+    /// Whether tracks missing a bpm tag are measured once decoded
+    bpm_analysis: RwLock<BpmAnalysis>,
+    /// Command whose JSON output tags each file
+    #[cfg(feature = "analyzer")]
+    analyzer: RwLock<Option<Analyzer>>,
+    /// Paths waiting on the analyzer
+    #[cfg(feature = "analyzer")]
+    analyze_s: Sender<Vec<PathBuf>>,
+    /// Genre aliases and parents applied to genre filters
+    genres: RwLock<Arc<Genres>>,
+    /// Paused for a call, to be picked back up once it ends
+    #[cfg(all(feature = "cork", target_os = "linux"))]
+    corked: AtomicBool,
+    metrics: metrics::Metrics,
+    supervisor: supervisor::Supervisor,
 }
 
 impl Library {
     // # new # {{{
-    pub fn new(backend: Backend, buffer: Option<u32>) -> Result<Arc<Self>, Box<dyn Error>> {
-        let bus = Mutex::new(Bus::<LibEvt>::new(99));
-
+    /// Create a library playing through BACKEND with an optional buffer size.
+    /// SINK is the `host:port` network backends stream to
+    pub fn new(backend: Backend, buffer: Option<u32>, sink: Option<String>) -> Result<Arc<Self>, Box<dyn Error>> {
         let (next_s, next_r) = sync_channel(1);
+        let (sort_s, sort_r) = sync_channel(1);
+        let (accent_s, accent_r) = sync_channel(1);
+        let (validate_s, validate_r) = sync_channel(1);
+        let (chip_s, chip_r) = sync_channel(1);
+        let (artist_s, artist_r) = sync_channel(1);
+        #[cfg(feature = "analyzer")]
+        let (analyze_s, analyze_r) = channel();
         let result = Arc::new(Self {
-            player: player::backend(backend, buffer, next_s),
+            player: player::backend(backend, buffer, sink, next_s),
+            muted: Mutex::new(None),
+            volume_max: RwLock::new(1.0),
+            kiosk: AtomicBool::new(false),
+            confirm: AtomicBool::new(true),
+            metrics: Default::default(),
+            supervisor: Default::default(),
+            stats: Default::default(),
             tracks: RwLock::new(Vec::new()),
             history: Mutex::new(Vec::new()),
             filtered_tree: RwLock::new(Vec::new()),
             sorters: RwLock::new(Vec::new()),
-            bus,
+            views: RwLock::new(vec![View {
+                name: String::from("main"),
+                filters: Vec::new(),
+                sorters: Vec::new(),
+            }]),
+            collation: RwLock::new(Collation::default()),
+            view: AtomicUsize::new(0),
+            favorites: RwLock::new(String::from("favorites")),
+            announcer: RwLock::new(None),
+            events: events::EventBus::default(),
+            held: Mutex::new(None),
+            snapshot: RwLock::new(Arc::new(Snapshot {
+                track: None,
+                playing: false,
+                paused: false,
+                stopped: true,
+                volume: 0.0,
+                seekable: None,
+                statusline: String::new(),
+                times: None,
+                taken: Instant::now(),
+            })),
+            sort_s,
+            accent: RwLock::new(None),
+            accent_s,
+            validate_s,
+            unreadable: Default::default(),
+            decode_failures: Default::default(),
+            chip_s,
+            chips: Default::default(),
+            artist_s,
+            artists: Default::default(),
+            artist_thumbnails: Default::default(),
+            artist_provider: RwLock::new(None),
+            artist_cache: RwLock::new(None),
             shuffle: AtomicBool::new(true),
+            shuffle_album: RwLock::new(None),
+            radio: Default::default(),
+            consume: AtomicBool::new(false),
+            consumed: RwLock::new(HashSet::new()),
             repeat: RwLock::new(Some(true)),
             hidden: AtomicBool::new(false),
+            roots: RwLock::new(Vec::new()),
+            cancel: Default::default(),
+            preview: Mutex::new(None),
+            previews: AtomicUsize::new(0),
+            offline: RwLock::new(Vec::new()),
+            skipped: RwLock::new(HashMap::new()),
             statusline: RwLock::new(String::from("title")),
+            groupline: RwLock::new(String::new()),
             theme: RwLock::new(Theme {
                 fg: Color::None,
                 bg: Color::None,
                 acc: Color::Term(3),
                 art_size: 0,
+                art_dither: ArtDither::None,
+                art_colors: ArtColors::Auto,
+                art_letterbox: false,
+                art_chips: false,
             }),
+            seek_steps: Default::default(),
             art: Default::default(),
             thumbnails: Default::default(),
+            art_sources: RwLock::new(ArtSource::DEFAULT.to_vec()),
+            duplicate_policy: Default::default(),
+            duplicates: Default::default(),
+            queue_info: Default::default(),
+            route_change: Default::default(),
+            bpm_analysis: Default::default(),
+            #[cfg(feature = "analyzer")]
+            analyzer: RwLock::new(None),
+            #[cfg(feature = "analyzer")]
+            analyze_s,
+            genres: Default::default(),
+            #[cfg(all(feature = "cork", target_os = "linux"))]
+            corked: AtomicBool::new(false),
         });
 
         result.volume_set(0.5);
 
-        let result_c = result.clone();
+        let result_w = Arc::downgrade(&result);
+        result.supervise("LIBRARY Player Message Server", true, move || {
+            player_message_server(result_w.clone(), &next_r)
+        })?;
+
+        let result_w = Arc::downgrade(&result);
+        result.supervise("LIBRARY Offline Retry", true, move || offline_retry_server(result_w.clone()))?;
+
+        let result_w = Arc::downgrade(&result);
+        result.supervise("LIBRARY Sort Server", true, move || sort_server(result_w.clone(), &sort_r))?;
+
+        let result_w = Arc::downgrade(&result);
+        result.supervise("LIBRARY Accent Server", true, move || accent_server(result_w.clone(), &accent_r))?;
+
+        let result_w = Arc::downgrade(&result);
+        result.supervise("LIBRARY Validate Server", true, move || validate_server(result_w.clone(), &validate_r))?;
+
+        let result_w = Arc::downgrade(&result);
+        result.supervise("LIBRARY Chip Server", true, move || chip_server(result_w.clone(), &chip_r))?;
+
+        let result_w = Arc::downgrade(&result);
+        result.supervise("LIBRARY Artist Server", true, move || artist_server(result_w.clone(), &artist_r))?;
+
+        let result_w = Arc::downgrade(&result);
+        result.supervise("LIBRARY Wake Server", true, move || wake_server(result_w.clone()))?;
+
+        #[cfg(feature = "analyzer")]
+        {
+            let result_w = Arc::downgrade(&result);
+            result.supervise("LIBRARY Analyzer Server", true, move || analyzer_server(result_w.clone(), &analyze_r))?;
+        }
+
+        #[cfg(all(feature = "cork", target_os = "linux"))]
+        {
+            let result_w = Arc::downgrade(&result);
+            result.supervise("LIBRARY Cork Server", false, move || cork_server(result_w.clone()))?;
+        }
 
+        #[cfg(feature = "deadlock-detection")]
         thread::Builder::new()
-            .name(String::from("LIBRARY Player Message Server"))
-            .spawn(move || player_message_server(result_c, next_r))?;
+            .name(String::from("LIBRARY Deadlock Detector"))
+            .spawn(deadlock_detector)?;
 
         Ok(result)
     }
     // # new # }}}
 
     fn broadcast(&self, message: LibEvt) {
-        if let Ok(mut bus) = self.bus.timed_lock() {
-            bus.broadcast(message)
+        // refreshed first so receivers reacting to the event see it
+        if matches!(message, LibEvt::State | LibEvt::Track | LibEvt::Volume | LibEvt::Clock | LibEvt::Theme) {
+            self.refresh_snapshot()
+        }
+        if matches!(message, LibEvt::Queue | LibEvt::Filters(_) | LibEvt::Tracks) {
+            let _ = self.queue_info.timed_write().map(|mut w| *w = None);
+        }
+        if let Ok(mut held) = self.held.timed_lock() {
+            if let Some(events) = held.as_mut() {
+                if !events.contains(&message) {
+                    events.push(message)
+                }
+                return;
+            }
+        }
+        self.events.send(message)
+    }
+
+    /// Run F with events held back, then send each distinct one once,
+    /// so receivers only ever see the finished result.
+    /// The snapshot still updates as F runs. Nested batches join the outer one
+    pub fn batch<R>(&self, f: impl FnOnce() -> R) -> R {
+        match self.held.timed_lock() {
+            Ok(mut held) if held.is_none() => *held = Some(Vec::new()),
+            _ => return f(),
+        }
+        let result = f();
+        let events = self.held.timed_lock().ok().and_then(|mut held| held.take()).unwrap_or_default();
+        for event in events {
+            self.broadcast(event)
+        }
+        result
+    }
+
+    fn take_snapshot(&self) -> Snapshot {
+        Snapshot {
+            track: self.track_get(),
+            playing: self.playing(),
+            paused: self.paused(),
+            stopped: self.stopped(),
+            volume: self.volume_get(),
+            seekable: self.seekable(),
+            statusline: self.statusline_get_format(),
+            times: self.times(),
+            taken: Instant::now(),
         }
     }
 
+    fn refresh_snapshot(&self) {
+        let snapshot = Arc::new(self.take_snapshot());
+        if let Ok(mut guard) = self.snapshot.timed_write() {
+            *guard = snapshot
+        }
+    }
+
+    /// Latest playback state without touching the player.
+    /// Prefer this for anything polled
+    pub fn snapshot(&self) -> Arc<Snapshot> {
+        self.snapshot
+            .timed_read()
+            .map(|s| s.clone())
+            .unwrap_or_else(|_| Arc::new(self.take_snapshot()))
+    }
+
     fn read_art(&self) {
         let now = Instant::now();
+        if !self.art.timed_read().map(|a| a.is_none()).unwrap_or(false) {
+            return;
+        }
+        // Read outside the lock so a slow disk doesn't time out everyone else waiting on art
+        let Some(track) = self.player.track_get() else { return };
+        if self.is_offline(track.path()) {
+            return;
+        }
+        let path = track.path().clone();
+        let sources = self.art_sources_get();
+        let Some(new_art) = timed_io(PROBE_TIMEOUT, move || track.read_art(&sources)) else {
+            self.set_offline(&path);
+            return;
+        };
         if let Ok(mut art) = self.art.timed_write() {
             if art.is_none() {
-                *art = self.player.track_get().map(|t| t.read_art()).flatten().map(|v| Arc::new(v));
+                *art = new_art.map(|v| Arc::new(v));
                 bench!("Loaded artwork in {:?}", now.elapsed());
             }
         }
     }
 
-    /// Receiver for all library events
-    pub fn get_receiver(&self) -> Result<BusReader<LibEvt>, Box<dyn Error>> {
-        Ok(self.bus.timed_lock().map_err(|e| e.to_string())?.add_rx())
+    /// Receiver for all library events.
+    /// A receiver more than [`events::CAPACITY`] events behind loses the oldest ones
+    pub fn get_receiver(&self) -> Result<EventReceiver, Box<dyn Error>> {
+        Ok(self.events.subscribe())
+    }
+
+    /// Run F on a thread named NAME, logging a panic and reporting it as a [`LibEvt::Error`].
+    /// With RESTART F is run again after a panic, up to [`supervisor::RESTART_LIMIT`] times,
+    /// so only use it where F picks up cleanly from wherever the last run stopped
+    pub fn supervise<F: FnMut() + Send + 'static>(self: &Arc<Self>, name: &str, restart: bool, mut f: F) -> std::io::Result<JoinHandle<()>> {
+        let id = self.supervisor.register(name);
+        let library_weak = Arc::downgrade(self);
+        let name = name.to_string();
+        thread::Builder::new().name(name.clone()).spawn(move || loop {
+            match catch_unwind(AssertUnwindSafe(&mut f)) {
+                Ok(()) => {
+                    if let Some(library) = library_weak.upgrade() {
+                        library.supervisor.finished(id)
+                    }
+                    break;
+                }
+                Err(payload) => {
+                    let message = supervisor::panic_message(payload.as_ref());
+                    error!("{} panicked: {}", name, message);
+                    let Some(library) = library_weak.upgrade() else { break };
+                    library.metrics.error();
+                    let again = library.supervisor.panicked(id, &message, restart);
+                    library.broadcast(LibEvt::Error(if again {
+                        trf("{} crashed and was restarted: {}", &[&name, &message])
+                    } else {
+                        trf("{} crashed: {}", &[&name, &message])
+                    }));
+                    if !again {
+                        break;
+                    }
+                }
+            }
+        })
+    }
+
+    /// Every thread started with [`Library::supervise`] and how it's doing
+    pub fn workers(&self) -> Vec<supervisor::Worker> {
+        self.supervisor.workers()
     }
 
     // ## Player forwards ## {{{
 
+    /// Resume or start playback
     pub fn play(&self) {
         self.player.play();
-        self.broadcast(LibEvt::Playback);
+        self.broadcast(LibEvt::State);
     }
+    /// Pause playback
     pub fn pause(&self) {
         self.player.pause();
-        self.broadcast(LibEvt::Playback);
+        self.broadcast(LibEvt::State);
     }
+    /// Stop playback
     pub fn stop(&self) {
         self.player.stop();
-        self.broadcast(LibEvt::Playback);
+        self.broadcast(LibEvt::State);
+    }
+    /// Fade out and stop for good, waiting on the backend's stream and decoder threads.
+    /// Nothing should be played after, as the process is expected to exit
+    pub fn shutdown(&self) -> Result<(), String> {
+        if self.player.playing() {
+            let volume = self.player.volume_get();
+            for step in (0..EXIT_FADE_STEPS).rev() {
+                self.player.volume_set(volume * step as f32 / EXIT_FADE_STEPS as f32);
+                sleep(EXIT_FADE / EXIT_FADE_STEPS);
+            }
+            self.player.pause();
+            self.player.volume_set(volume);
+        }
+        let result = self.player.shutdown();
+        self.broadcast(LibEvt::State);
+        result
+    }
+    /// Rebuild the audio stream after the system slept for SLEPT, as it can die without a word while suspended.
+    /// Picks back up at POSITION, where it was before the suspend. Does nothing if it wasn't playing then
+    fn wake(&self, slept: Duration, position: Option<Duration>) {
+        let Some(position) = position else { return };
+        info!("Woke from a {:?} suspend, restarting playback at {:?}", slept, position);
+        self.player.pause();
+        self.player.seek(position);
+        self.play();
+    }
+    /// Pause for a call when CORKED, and once it's over resume if it was the call that paused it
+    #[cfg(all(feature = "cork", target_os = "linux"))]
+    fn cork(&self, corked: bool) {
+        if corked && self.playing() {
+            info!("Corked, pausing");
+            self.pause();
+            self.corked.store(true, Ordering::Relaxed);
+        } else if !corked && self.corked.swap(false, Ordering::Relaxed) && !self.playing() {
+            info!("Uncorked, resuming");
+            self.play();
+        }
     }
     /// Toggle play/pause. Typical media key control
     pub fn play_pause(&self) {
         self.player.toggle();
-        self.broadcast(LibEvt::Playback);
+        self.broadcast(LibEvt::State);
     }
     /// 0.0 -> 1.0
     pub fn volume_get(&self) -> f32 {
@@ -354,13 +1431,104 @@ impl Library {
     }
     /// 0.0 -> 1.0
     pub fn volume_set(&self, volume: f32) {
-        self.player.volume_set(volume);
-        self.broadcast(LibEvt::Playback);
+        if let Ok(mut muted) = self.muted.timed_lock() {
+            *muted = None;
+            self.player.volume_set(volume.min(self.volume_max_get()));
+        }
+        self.broadcast(LibEvt::Volume);
     }
     /// -1.0 -> 1.0
     pub fn volume_add(&self, amount: f32) {
-        self.player.volume_add(amount);
-        self.broadcast(LibEvt::Playback);
+        if let Ok(mut muted) = self.muted.timed_lock() {
+            // relative to where it was before muting
+            if let Some(volume) = muted.take() {
+                self.player.volume_set(volume)
+            }
+            self.player.volume_add(amount);
+            let max = self.volume_max_get();
+            if self.player.volume_get() > max {
+                self.player.volume_set(max)
+            }
+        }
+        self.broadcast(LibEvt::Volume);
+    }
+    /// Highest volume [`Library::volume_set`] and [`Library::volume_add`] go to
+    pub fn volume_max_get(&self) -> f32 {
+        self.volume_max.timed_read().map(|m| *m).unwrap_or(1.0)
+    }
+    /// Lowers the volume right away if it's above MAX
+    pub fn volume_max_set(&self, max: f32) {
+        let max = max.clamp(0.0, 1.0);
+        if let Ok(mut guard) = self.volume_max.timed_write() {
+            *guard = max;
+        }
+        if self.volume_get() > max {
+            self.volume_set(max)
+        }
+    }
+    /// Whether the volume is held at 0 by [`Library::mute`]
+    pub fn muted(&self) -> bool {
+        self.muted.timed_lock().is_ok_and(|m| m.is_some())
+    }
+    /// Drop to 0, remembering the current volume for [`Library::unmute`]
+    pub fn mute(&self) {
+        if let Ok(mut muted) = self.muted.timed_lock() {
+            if muted.is_none() {
+                *muted = Some(self.player.volume_get());
+                self.player.volume_set(0.0);
+            }
+        }
+        self.broadcast(LibEvt::Volume);
+    }
+    /// Put back the volume from before muting
+    pub fn unmute(&self) {
+        if let Ok(mut muted) = self.muted.timed_lock() {
+            if let Some(volume) = muted.take() {
+                self.player.volume_set(volume.min(self.volume_max_get()))
+            }
+        }
+        self.broadcast(LibEvt::Volume);
+    }
+    /// Mute or unmute
+    pub fn mute_toggle(&self) {
+        match self.muted() {
+            true => self.unmute(),
+            false => self.mute(),
+        }
+    }
+
+    /// Pick how tracks are converted to the device's sample rate. Sympal only
+    pub fn resampler_set(&self, resampler: Resampler) -> Result<(), String> {
+        self.player.resampler_set(resampler)
+    }
+    /// Sample format of the playing track and device. Sympal only
+    pub fn format(&self) -> Option<Format> {
+        self.player.format()
+    }
+    /// What's done when the output device is unplugged or the system switches outputs mid-track
+    pub fn route_change_get(&self) -> RouteChange {
+        self.route_change.timed_read().as_deref().copied().unwrap_or_default()
+    }
+    /// Pause or carry on when the output changes, per ROUTE_CHANGE. Sympal only
+    pub fn route_change_set(&self, route_change: RouteChange) {
+        let _ = self.route_change.timed_write().map(|mut w| *w = route_change);
+    }
+    /// Peak and limiter counts for the playing track. None if the player doesn't limit. Sympal only
+    pub fn headroom(&self) -> Option<Headroom> {
+        self.player.headroom()
+    }
+    /// Extra devices that can play alongside the default one.
+    /// None if the backend only plays to the default device
+    pub fn outputs(&self) -> Option<Vec<Output>> {
+        self.player.outputs()
+    }
+    /// Start or stop also playing to output NAME
+    pub fn output_enable(&self, name: &str, enabled: bool) -> Result<(), String> {
+        self.player.output_enable(name, enabled)
+    }
+    /// 0.0 -> 1.0, on top of the main volume
+    pub fn output_volume_set(&self, name: &str, volume: f32) -> Result<(), String> {
+        self.player.output_volume_set(name, volume)
     }
 
     /// Currently playing/loaded track
@@ -369,46 +1537,108 @@ impl Library {
     }
 
     /// Set the currently loaded track and start playback
-    pub fn play_track(&self, track: Option<Arc<Track>>) {
+    pub fn play_track(&self, mut track: Option<Arc<Track>>) {
         // Check for moved tracks first.
         // Player could handle this but easier if library does
-        if let Some(track) = track.as_ref() {
-            if !track.path().exists() {
-                self.broadcast(LibEvt::Error(format!(
-                    "Track no longer found at {}\nRemoving from library",
-                    track.path().to_str().unwrap_or("???")
+        if let Some(requested) = track.clone() {
+            if self.is_offline(requested.path()) {
+                self.broadcast(LibEvt::Error(trf(
+                    "Track at {} is offline",
+                    &[&requested.path().to_str().unwrap_or("???")],
                 )));
-
-                if let Ok(mut tracks) = self.tracks.timed_write() {
-                    if let Some(id) = tracks.iter().position(|t| t == track) {
-                        tracks.remove(id);
-                        drop(tracks);
-                        self.force_build_filters();
-                        self.next();
+                return;
+            }
+            let path = requested.path().clone();
+            // remote tracks are checked by actually connecting once played
+            let remote = requested.is_remote();
+            let Some(exists) = timed_io(IO_TIMEOUT, move || remote || path.exists()) else {
+                self.set_offline(requested.path());
+                return;
+            };
+            if !exists {
+                match self.relocate(&requested) {
+                    Some(relocated) => track = Some(relocated),
+                    None => {
+                        self.broadcast(LibEvt::Error(trf(
+                            "Track no longer found at {}\nRemoving from library",
+                            &[&requested.path().to_str().unwrap_or("???")],
+                        )));
+
+                        if let Ok(mut tracks) = self.tracks.timed_write() {
+                            if let Some(id) = tracks.iter().position(|t| t == &requested) {
+                                tracks.remove(id);
+                                drop(tracks);
+                                self.force_build_filters();
+                                self.broadcast(LibEvt::Tracks);
+                                self.next();
+                            }
+                        }
+                        return;
                     }
                 }
-                return;
             }
         }
         if self.track_get() == track {
             self.play();
             return;
         }
-        if let Some(track) = self.player.play_track(track) {
-            if let Ok(mut history) = self.history.timed_lock() {
-                history.push(track)
+        let consumed = self.consume_get()
+            && self
+                .track_get()
+                .is_some_and(|old| self.consumed.timed_write().is_ok_and(|mut c| c.insert(old.path().clone())));
+        if let Some(next) = track.as_ref() {
+            self.metrics.played();
+            if let Some(announcer) = self.announcer_get() {
+                // don't talk over whatever's still playing
+                if self.playing() {
+                    self.player.pause()
+                }
+                if let Err(e) = announcer.announce(next) {
+                    self.broadcast(LibEvt::Error(e))
+                }
+            }
+        }
+        // playing something else mid preview leaves history as if it never happened
+        let preview = self.preview.timed_lock().ok().and_then(|mut p| p.take());
+        if let Some(old) = self.player.play_track(track) {
+            let old = match preview {
+                Some(preview) if preview.previewing == old => preview.track,
+                _ => Some(old),
+            };
+            if let (Some(old), Ok(mut history)) = (old, self.history.timed_lock()) {
+                history.push(old)
             }
         }
         let _ = self.art.timed_write().map(|mut a| *a = None);
-        self.broadcast(LibEvt::Playback);
+        self.broadcast(LibEvt::Track);
+        if self.accent_auto_get() {
+            let _ = self.accent_s.try_send(());
+        }
+        if consumed {
+            self.broadcast(LibEvt::Queue)
+        }
     }
 
-    pub fn playing(&self) -> bool {
+    /// Set the currently loaded track without starting playback, ex to restore a previous session.
+    /// Does nothing unless stopped
+    pub fn load_track(&self, track: Option<Arc<Track>>) {
+        if !self.stopped() || self.track_get() == track {
+            return;
+        }
+        self.player.track_set(track);
+        let _ = self.art.timed_write().map(|mut a| *a = None);
+        self.broadcast(LibEvt::Track);
+    }
+
+    /// Currently playing
+    pub fn playing(&self) -> bool {
         self.player.playing()
     }
+    /// Currently paused
     pub fn paused(&self) -> bool {
         self.player.paused()
     }
+    /// Currently stopped
     pub fn stopped(&self) -> bool {
         self.player.stopped()
     }
@@ -424,14 +1654,196 @@ impl Library {
         self.player.times()
     }
 
+    /// How far into the track can be seeked while it's still decoding.
+    /// `None` when the player doesn't decode ahead. Sympal only
+    pub fn decoded(&self) -> Option<Duration> {
+        self.player.decoded()
+    }
+
     /// Seek to this exact time
     pub fn seek(&self, time: Duration) {
-        self.player.seek(time)
+        self.player.seek(time);
+        self.broadcast(LibEvt::Clock);
+    }
+
+    /// Seek to POSITION once the player has decoded that far, from a background thread.
+    /// The player only decodes, and so can only seek, once it's been started
+    fn seek_when_seekable(self: &Arc<Self>, position: Duration) {
+        let library = Arc::downgrade(self);
+        let spawned = thread::Builder::new().name(String::from("LIBRARY Seek")).spawn(move || {
+            let start = Instant::now();
+            while start.elapsed() < SEEK_TIMEOUT {
+                let Some(library) = library.upgrade() else { return };
+                match library.seekable() {
+                    None => return,
+                    Some(ready) if ready || library.decoded().is_some_and(|d| d > position) => {
+                        library.seek(position);
+                        return;
+                    }
+                    Some(_) => (),
+                }
+                drop(library);
+                sleep(Duration::from_millis(50));
+            }
+            info!("Track wasn't seekable in time to seek to {:?}", position)
+        });
+        if let Err(e) = spawned {
+            error!("Could not spawn seek thread: {}", e)
+        }
+    }
+
+    /// Play TRACK for 10 seconds from 30% in, then go back to whatever was playing and where.
+    /// The queue position, history, and play counts are left alone
+    pub fn preview(self: &Arc<Self>, track: Arc<Track>) {
+        let id = self.previews.fetch_add(1, Ordering::Relaxed) + 1;
+        let Ok(mut preview) = self.preview.timed_lock() else { return };
+        *preview = Some(match preview.take() {
+            // previewing one after another goes back to what was playing before the first
+            Some(previous) if self.track_get().as_ref() == Some(&previous.previewing) => Preview {
+                id,
+                previewing: track.clone(),
+                ..previous
+            },
+            _ => Preview {
+                id,
+                previewing: track.clone(),
+                track: self.track_get(),
+                position: self.times().map(|t| t.0),
+                playing: self.playing(),
+            },
+        });
+        drop(preview);
+
+        self.player.play_track(Some(track.clone()));
+        let _ = self.art.timed_write().map(|mut a| *a = None);
+        self.broadcast(LibEvt::Track);
+        if let Some(length) = track.duration() {
+            self.seek_when_seekable(length.mul_f32(PREVIEW_START))
+        }
+
+        let library = Arc::downgrade(self);
+        let spawned = thread::Builder::new().name(String::from("LIBRARY Preview")).spawn(move || {
+            sleep(PREVIEW_LENGTH);
+            if let Some(library) = library.upgrade() {
+                library.preview_end(Some(id));
+            }
+        });
+        if let Err(e) = spawned {
+            error!("Could not spawn preview thread: {}", e)
+        }
+    }
+
+    /// Whether a preview is playing
+    pub fn previewing(&self) -> bool {
+        self.preview
+            .timed_lock()
+            .is_ok_and(|p| p.as_ref().is_some_and(|p| self.track_get().as_ref() == Some(&p.previewing)))
+    }
+
+    /// Go back to what was playing before the preview numbered ID, or any preview with None.
+    /// Does nothing if something else was played since. True if it went back
+    pub fn preview_end(self: &Arc<Self>, id: Option<usize>) -> bool {
+        let Ok(mut guard) = self.preview.timed_lock() else { return false };
+        let Some(preview) = guard.take_if(|p| id.is_none_or(|id| id == p.id)) else {
+            return false;
+        };
+        drop(guard);
+        if self.track_get().as_ref() != Some(&preview.previewing) {
+            return false;
+        }
+
+        match preview.track {
+            Some(track) if preview.playing || preview.position.is_some() => {
+                self.player.play_track(Some(track));
+                if !preview.playing {
+                    self.player.pause()
+                }
+                if let Some(position) = preview.position.filter(|p| !p.is_zero()) {
+                    self.seek_when_seekable(position)
+                }
+            }
+            track => {
+                self.player.track_set(track);
+            }
+        }
+        let _ = self.art.timed_write().map(|mut a| *a = None);
+        self.broadcast(LibEvt::Track);
+        self.broadcast(LibEvt::State);
+        true
+    }
+
+    /// Seek to FRACTION of the way through the track, 0.0 -> 1.0
+    pub fn seek_fraction(&self, fraction: f32) {
+        if let Some((_, total)) = self.times() {
+            self.seek(total.mul_f32(fraction.clamp(0.0, 1.0)))
+        }
     }
 
     /// Seek by +-n seconds
     pub fn seek_by(&self, secs: f32) {
-        self.player.seek_by(secs)
+        self.player.seek_by(secs);
+        self.broadcast(LibEvt::Clock);
+    }
+
+    /// Seek forward or back by STEP
+    pub fn seek_step(&self, step: SeekStep, forward: bool) {
+        let secs = self.seek_steps_get().get(step);
+        self.seek_by(if forward { secs } else { -secs })
+    }
+
+    /// Seconds each [`SeekStep`] seeks by
+    pub fn seek_steps_get(&self) -> SeekSteps {
+        self.seek_steps.timed_read().as_deref().copied().unwrap_or_default()
+    }
+
+    /// Change how far each [`SeekStep`] seeks
+    pub fn seek_steps_set(&self, steps: SeekSteps) {
+        let _ = self.seek_steps.timed_write().map(|mut w| *w = steps);
+    }
+
+    /// Skip silence from the next track on. Sympal only
+    pub fn silence_set(&self, silence: Silence) {
+        self.player.silence_set(silence)
+    }
+
+    /// What's skipped, or None if the player can't skip. Sympal only
+    pub fn silence_get(&self) -> Option<Silence> {
+        self.player.silence_get()
+    }
+
+    /// Start tracks INTRO in and end them OUTRO early, from the next track on.
+    /// None leaves that end as it is
+    pub fn skip_set(&self, intro: Option<Duration>, outro: Option<Duration>) -> Result<(), String> {
+        let mut silence = self.silence_get().ok_or("Skipping needs the Sympal backend")?;
+        silence.intro = intro.unwrap_or(silence.intro);
+        silence.outro = outro.unwrap_or(silence.outro);
+        self.silence_set(silence);
+        Ok(())
+    }
+
+    /// A-B loop points of the current track. A of None loops from the start
+    pub fn loop_get(&self) -> (Option<Duration>, Option<Duration>) {
+        self.player.ab_loop_get()
+    }
+
+    /// Jump back to A whenever playback reaches B. Both None clears the loop
+    pub fn loop_set(&self, a: Option<Duration>, b: Option<Duration>) -> Result<(), String> {
+        if let (Some(a), Some(b)) = (a, b) {
+            if b <= a {
+                return Err("Loop end B must come after start A".to_string());
+            }
+        }
+        self.player.ab_loop_set(a, b)
+    }
+
+    /// Step through setting A and B at the current time, then clearing the loop
+    pub fn loop_cycle(&self) -> Result<(), String> {
+        let now = self.times().map(|t| t.0).ok_or("No track is loaded")?;
+        match self.loop_get() {
+            (None, None) => self.loop_set(Some(now), None),
+            (a, None) => self.loop_set(a, Some(now)),
+            _ => self.loop_set(None, None),
+        }
     }
 
     /// Generate a waveform preview of the current track
@@ -443,19 +1855,71 @@ impl Library {
 
     // ## Other Settings ## {{{
 
+    /// Whether the next track is picked randomly
     pub fn shuffle_get(&self) -> bool {
         self.shuffle.load(Ordering::Relaxed)
     }
 
+    /// Set whether the next track is picked randomly
     pub fn shuffle_set(&self, shuffle: bool) {
         self.shuffle.store(shuffle, Ordering::Relaxed);
-        self.broadcast(LibEvt::Playback);
+        self.broadcast(LibEvt::Mode);
     }
 
+    /// Flip shuffle
     pub fn shuffle_toggle(&self) {
         self.shuffle_set(!self.shuffle_get())
     }
 
+    /// Tagstring grouping albums if shuffle picks whole albums rather than tracks
+    pub fn shuffle_album_get(&self) -> Option<String> {
+        self.shuffle_album.timed_read().as_deref().cloned().unwrap_or_default()
+    }
+
+    /// Shuffle albums grouped by TAGSTRING, playing each through in queue order. None goes back to shuffling tracks.
+    /// Only matters while shuffle is on
+    pub fn shuffle_album_set(&self, tagstring: Option<String>) {
+        if let Ok(mut guard) = self.shuffle_album.timed_write() {
+            *guard = tagstring;
+        } else {
+            return;
+        }
+        self.broadcast(LibEvt::Mode);
+    }
+
+    /// When the next track is picked by similarity
+    pub fn radio_get(&self) -> Radio {
+        self.radio.timed_read().as_deref().copied().unwrap_or_default()
+    }
+
+    /// Pick next tracks by similarity according to RADIO
+    pub fn radio_set(&self, radio: Radio) {
+        let _ = self.radio.timed_write().map(|mut w| *w = radio);
+        self.broadcast(LibEvt::Mode);
+    }
+
+    /// Whether played tracks leave the queue
+    pub fn consume_get(&self) -> bool {
+        self.consume.load(Ordering::Relaxed)
+    }
+
+    /// Set whether played tracks leave the queue. Turning it off brings every consumed track back
+    pub fn consume_set(&self, consume: bool) {
+        self.consume.store(consume, Ordering::Relaxed);
+        if !consume {
+            let cleared = self.consumed.timed_write().map(|mut c| std::mem::take(&mut *c)).unwrap_or_default();
+            if !cleared.is_empty() {
+                self.broadcast(LibEvt::Queue)
+            }
+        }
+        self.broadcast(LibEvt::Mode);
+    }
+
+    /// Flip consume
+    pub fn consume_toggle(&self) {
+        self.consume_set(!self.consume_get())
+    }
+
     /// None - No loop
     /// Some(false) - track loop
     /// Some(true) - full loop
@@ -469,8 +1933,10 @@ impl Library {
     pub fn repeat_set(&self, repeat: Option<bool>) {
         if let Ok(mut guard) = self.repeat.timed_write() {
             *guard = repeat;
-            self.broadcast(LibEvt::Playback);
+        } else {
+            return;
         }
+        self.broadcast(LibEvt::Mode);
     }
 
     /// Advances None -> Some(false) -> Some(true)
@@ -481,7 +1947,13 @@ impl Library {
             Some(false) => Some(true),
             Some(true) => None,
         };
-        self.broadcast(LibEvt::Playback);
+        drop(guard);
+        self.broadcast(LibEvt::Mode);
+    }
+
+    /// Every local folder appended since the last purge
+    pub fn roots_get(&self) -> Vec<PathBuf> {
+        self.roots.timed_read().as_deref().cloned().unwrap_or_default()
     }
 
     /// Whether append() scans hidden files
@@ -489,11 +1961,226 @@ impl Library {
         self.hidden.load(Ordering::Relaxed)
     }
 
+    /// Load per-track stats from FILE and keep it updated from now on
+    pub fn stats_open(&self, file: PathBuf) {
+        let stats = Stats::open(file);
+        if let Ok(tracks) = self.tracks.timed_read() {
+            tracks.iter().for_each(|t| stats.apply(t))
+        }
+        if let Ok(mut guard) = self.stats.timed_write() {
+            *guard = stats
+        }
+        self.favorites_sync();
+    }
+
+    /// Set TRACK's manual gain in dB, stored with its stats
+    pub fn gain_offset_set(&self, track: &Track, db: f32) {
+        track.gain_offset_set(db);
+        if let Ok(mut stats) = self.stats.timed_write() {
+            stats.update(track.path(), |s| s.gain_offset = db)
+        }
+        // backends holding onto a volume * gain multiplier pick it up on the next volume set
+        if self.track_get().is_some_and(|t| t.path() == track.path()) {
+            self.player.volume_set(self.player.volume_get())
+        }
+    }
+
+    /// What's done with tracks missing a bpm tag
+    pub fn bpm_analysis_get(&self) -> BpmAnalysis {
+        self.bpm_analysis.timed_read().as_deref().copied().unwrap_or_default()
+    }
+
+    /// Measure tracks missing a bpm tag once they're decoded, per ANALYSIS. Sympal only
+    pub fn bpm_analysis_set(&self, analysis: BpmAnalysis) -> Result<(), String> {
+        #[cfg(not(feature = "replaygain"))]
+        if analysis == BpmAnalysis::Write {
+            return Err(String::from("OMPL was built without the replaygain feature, so it can't write tags"));
+        }
+        let _ = self.bpm_analysis.timed_write().map(|mut w| *w = analysis);
+        Ok(())
+    }
+
+    /// Measure the playing track's tempo if it has no bpm tag, storing it and tagging the track for sorters and filters
+    fn bpm_measure(&self) {
+        let analysis = self.bpm_analysis_get();
+        if analysis == BpmAnalysis::Off {
+            return;
+        }
+        let Some(track) = self.track_get() else { return };
+        if track.is_remote() || track.tags().contains_key(bpm::TAG) {
+            return;
+        }
+        let now = Instant::now();
+        let Some(bpm) = self.player.bpm() else { return };
+        // moved on while measuring
+        if self.track_get().as_ref() != Some(&track) {
+            return;
+        }
+        bench!("Measured {} at {} BPM in {:?}", track.path().display(), bpm, now.elapsed());
+
+        if let Ok(mut stats) = self.stats.timed_write() {
+            stats.update(track.path(), |s| s.bpm = Some(bpm))
+        }
+        #[cfg(feature = "replaygain")]
+        if analysis == BpmAnalysis::Write {
+            if let Err(e) = replaygain::write_tags(track.path(), &[("BPM", bpm::format(bpm))]) {
+                error!("Could not tag {}: {}", track.path().display(), e)
+            }
+        }
+
+        let mut tagged = (*track).clone();
+        tagged.tag_set(bpm::TAG, bpm::format(bpm));
+        self.swap_tracks(&[Arc::new(tagged)]);
+        self.force_build_filters();
+        self.broadcast(LibEvt::Tracks);
+    }
+
+    /// TRACK's bookmarks in time order
+    pub fn bookmarks(&self, track: &Track) -> Vec<Bookmark> {
+        self.stats
+            .timed_read()
+            .ok()
+            .and_then(|stats| stats.get(track.path()).map(|s| s.bookmarks.clone()))
+            .unwrap_or_default()
+    }
+
+    /// Bookmark TIME in TRACK as NAME, replacing any bookmark already called that
+    pub fn bookmark_add(&self, track: &Track, name: String, time: Duration) {
+        if let Ok(mut stats) = self.stats.timed_write() {
+            stats.update(track.path(), |s| {
+                s.bookmarks.retain(|b| b.name != name);
+                s.bookmarks.push(Bookmark { name, time });
+                s.bookmarks.sort_by_key(|b| b.time);
+            })
+        }
+    }
+
+    /// Remove TRACK's bookmark NAME
+    pub fn bookmark_remove(&self, track: &Track, name: &str) -> Result<(), String> {
+        if !self.bookmarks(track).iter().any(|b| b.name == name) {
+            return Err(trf("No bookmark named '{}'", &[&name]));
+        }
+        if let Ok(mut stats) = self.stats.timed_write() {
+            stats.update(track.path(), |s| s.bookmarks.retain(|b| b.name != name))
+        }
+        Ok(())
+    }
+
+    /// Whether TRACK is in the favorites view
+    pub fn loved(&self, track: &Track) -> bool {
+        self.stats
+            .timed_read()
+            .ok()
+            .and_then(|stats| stats.get(track.path()).map(|s| s.loved))
+            .unwrap_or(false)
+    }
+
+    /// Add or remove TRACK from the favorites view, remembered with its stats
+    pub fn love(&self, track: &Track, loved: bool) -> Result<(), String> {
+        if track.is_remote() {
+            return Err(tr("Tracks from a shared library can't be loved").to_string());
+        }
+        if let Ok(mut stats) = self.stats.timed_write() {
+            stats.update(track.path(), |s| s.loved = loved)
+        }
+        self.favorites_sync();
+        Ok(())
+    }
+
+    /// Whether TRACK failed to decode often enough to be skipped
+    pub fn quarantined(&self, track: &Track) -> bool {
+        self.stats
+            .timed_read()
+            .ok()
+            .and_then(|stats| stats.get(track.path()).map(|s| s.quarantined))
+            .unwrap_or(false)
+    }
+
+    /// Paths of every quarantined track, sorted
+    pub fn get_quarantined(&self) -> Vec<PathBuf> {
+        self.stats.timed_read().map(|stats| stats.quarantined()).unwrap_or_default()
+    }
+
+    /// Let every quarantined track play again. Returns how many there were
+    pub fn quarantine_clear(&self) -> usize {
+        let quarantined = self.get_quarantined();
+        if let Ok(mut stats) = self.stats.timed_write() {
+            quarantined.iter().for_each(|path| stats.update(path, |s| s.quarantined = false))
+        }
+        let _ = self.decode_failures.timed_lock().map(|mut f| f.clear());
+        self.broadcast(LibEvt::Queue);
+        quarantined.len()
+    }
+
+    /// Count a failed decode of the track at PATH, quarantining it once it's failed [`QUARANTINE_AFTER`] times
+    fn decode_failed(&self, path: &Path) {
+        let Ok(mut failures) = self.decode_failures.timed_lock() else { return };
+        let count = failures.entry(path.to_path_buf()).or_default();
+        *count += 1;
+        if *count < QUARANTINE_AFTER {
+            return;
+        }
+        failures.remove(path);
+        drop(failures);
+        if let Ok(mut stats) = self.stats.timed_write() {
+            stats.update(path, |s| s.quarantined = true)
+        }
+        info!("Quarantined {} after {} failed decodes", path.display(), QUARANTINE_AFTER);
+        self.broadcast(LibEvt::Queue);
+    }
+
+    /// Seek the current track to its bookmark NAME
+    pub fn bookmark_jump(&self, name: &str) -> Result<(), String> {
+        let track = self.track_get().ok_or("No track is loaded")?;
+        let bookmark = self
+            .bookmarks(&track)
+            .into_iter()
+            .find(|b| b.name == name)
+            .ok_or(format!("No bookmark named '{}'", name))?;
+        self.seek(bookmark.time);
+        Ok(())
+    }
+
     /// Whether append() scans hidden files
     pub fn hidden_set(&self, include_hidden: bool) {
         self.hidden.store(include_hidden, Ordering::Relaxed)
     }
 
+    /// Whether frontends and clients should only offer playback and volume
+    pub fn kiosk_get(&self) -> bool {
+        self.kiosk.load(Ordering::Relaxed)
+    }
+
+    /// Limit frontends and clients to playback and volume, for leaving a player out in a shared space.
+    /// Enforced by the frontends themselves, the library only carries the setting
+    pub fn kiosk_set(&self, kiosk: bool) {
+        self.kiosk.store(kiosk, Ordering::Relaxed)
+    }
+
+    /// Whether frontends should ask before destructive actions
+    pub fn confirm_get(&self) -> bool {
+        self.confirm.load(Ordering::Relaxed)
+    }
+
+    /// Have frontends ask before purging, deleting filters with a selection, or exiting.
+    /// Enforced by the frontends themselves like kiosk mode
+    pub fn confirm_set(&self, confirm: bool) {
+        self.confirm.store(confirm, Ordering::Relaxed)
+    }
+
+    /// Count one command from a client toward the metrics
+    pub fn metrics_request(&self) {
+        self.metrics.request()
+    }
+    /// Running totals and sizes in the Prometheus text format
+    pub fn metrics(&self) -> String {
+        self.metrics.render(self)
+    }
+    /// Totals for each timed stage of library work, like scanning and sorting
+    pub fn timings(&self) -> Vec<metrics::Timing> {
+        self.metrics.timings()
+    }
+
     /// Tagstring for library status
     pub fn statusline_get(&self) -> String {
         self.statusline.timed_read().as_deref().unwrap_or(&String::from("???")).clone()
@@ -503,8 +2190,25 @@ impl Library {
     pub fn statusline_set<T: ToString>(&self, statusline: T) {
         if let Ok(mut guard) = self.statusline.timed_write() {
             *guard = statusline.to_string();
-            self.broadcast(LibEvt::Theme);
+        } else {
+            return;
+        }
+        self.broadcast(LibEvt::Theme);
+    }
+
+    /// Tagstring separating groups in the queue, empty for none
+    pub fn groupline_get(&self) -> String {
+        self.groupline.timed_read().as_deref().cloned().unwrap_or_default()
+    }
+
+    /// Tagstring separating groups in the queue, empty for none
+    pub fn groupline_set<T: ToString>(&self, groupline: T) {
+        if let Ok(mut guard) = self.groupline.timed_write() {
+            *guard = groupline.to_string();
+        } else {
+            return;
         }
+        self.broadcast(LibEvt::Theme);
     }
 
     /// Parses tagstring from playing track and statusline
@@ -512,21 +2216,224 @@ impl Library {
         self.track_get().map_or(String::from(""), |t| t.tagstring(self.statusline_get()))
     }
 
+    /// Current UI colors
     pub fn theme_get(&self) -> Theme {
         *self.theme.timed_read().expect("Library theme was not readable")
     }
 
+    /// Set UI colors and broadcast [`LibEvt::Theme`]
     pub fn theme_set(&self, theme: Theme) {
         if let Ok(mut guard) = self.theme.timed_write() {
             *guard = theme;
-            self.broadcast(LibEvt::Theme);
+        } else {
+            return;
+        }
+        self.broadcast(LibEvt::Theme);
+    }
+
+    /// Whether the accent follows the current track's art
+    pub fn accent_auto_get(&self) -> bool {
+        self.accent.timed_read().is_ok_and(|a| a.is_some())
+    }
+
+    /// Take the accent from each track's art, falling back to the current accent for tracks without any.
+    /// Turning it off puts that accent back
+    pub fn accent_auto_set(&self, auto: bool) {
+        let Ok(mut accent) = self.accent.timed_write() else { return };
+        match (auto, *accent) {
+            (true, None) => {
+                *accent = Some(self.theme_get().acc);
+                drop(accent);
+                let _ = self.accent_s.try_send(());
+            }
+            (false, Some(fallback)) => {
+                *accent = None;
+                drop(accent);
+                self.theme_set(Theme {
+                    acc: fallback,
+                    ..self.theme_get()
+                })
+            }
+            _ => (),
+        }
+    }
+
+    /// Set the accent to the dominant color of the current track's art
+    fn accent_from_art(&self) {
+        let Ok(Some(fallback)) = self.accent.timed_read().as_deref().copied() else {
+            return;
+        };
+        // a small thumbnail is plenty to find the dominant color, and is cached for the UI anyway
+        let acc = self
+            .thumbnail(32, 32)
+            .and_then(|thumbnail| dominant_color(&thumbnail))
+            .map(Color::RGB)
+            .unwrap_or(fallback);
+        let theme = self.theme_get();
+        // auto-art may have been turned off while reading
+        if theme.acc != acc && self.accent_auto_get() {
+            self.theme_set(Theme { acc, ..theme })
+        }
+    }
+
+    /// What's done with newly found tracks matching the tags of one already loaded
+    pub fn duplicate_policy_get(&self) -> DuplicatePolicy {
+        self.duplicate_policy.timed_read().as_deref().copied().unwrap_or_default()
+    }
+
+    /// Handle tracks matching the tags of one already loaded with POLICY from the next append on
+    pub fn duplicate_policy_set(&self, policy: DuplicatePolicy) {
+        let _ = self.duplicate_policy.timed_write().map(|mut w| *w = policy);
+    }
+
+    /// Tracks found matching the tags of one loaded before them since the last purge
+    pub fn duplicates(&self) -> Vec<Duplicate> {
+        self.duplicates.timed_read().as_deref().cloned().unwrap_or_default()
+    }
+
+    /// Where art is looked for, in order
+    pub fn art_sources_get(&self) -> Vec<ArtSource> {
+        self.art_sources.timed_read().as_deref().cloned().unwrap_or(ArtSource::DEFAULT.to_vec())
+    }
+
+    /// Look for art in SOURCES from now on, first to last. The current track's art is looked for again
+    pub fn art_sources_set(&self, sources: Vec<ArtSource>) {
+        if let Ok(mut guard) = self.art_sources.timed_write() {
+            *guard = sources
+        } else {
+            return;
+        }
+        let _ = self.thumbnails.timed_write().map(|mut w| *w = HashMap::new());
+        let _ = self.chips.timed_write().map(|mut w| *w = HashMap::new());
+        let _ = self.art.timed_write().map(|mut w| *w = None);
+        if self.accent_auto_get() {
+            let _ = self.accent_s.try_send(());
+        }
+        self.broadcast(LibEvt::Theme);
+    }
+
+    /// Full size artwork for current track
+    pub fn art(&self) -> Option<Arc<RawImage>> {
+        self.read_art();
+        self.art.timed_read().ok().and_then(|a| a.clone())
+    }
+
+    /// Color of TRACK's art for the queue, None if it has none or it hasn't been read yet.
+    /// See [`Self::art_chips_request`]
+    pub fn art_chip(&self, track: &Track) -> Option<[u8; 3]> {
+        self.chips.timed_read().ok()?.get(&chip_key(track)).copied().flatten()
+    }
+
+    /// Read art chips for any of TRACKS without one in the background, broadcasting [`LibEvt::ArtChips`] when done.
+    /// Dropped if the last batch is still being read, so ask again on the next draw
+    pub fn art_chips_request(&self, tracks: &[Arc<Track>]) {
+        let Ok(chips) = self.chips.timed_read() else { return };
+        let mut keys = HashSet::new();
+        let wanted: Vec<Arc<Track>> = tracks
+            .iter()
+            .filter(|t| {
+                let key = chip_key(t);
+                !chips.contains_key(&key) && keys.insert(key)
+            })
+            .cloned()
+            .collect();
+        drop(chips);
+        if !wanted.is_empty() {
+            let _ = self.chip_s.try_send(wanted);
+        }
+    }
+
+    /// Find the art chip for each of TRACKS
+    fn read_chips(&self, tracks: Vec<Arc<Track>>) {
+        let now = Instant::now();
+        let count = tracks.len();
+        let sources = self.art_sources_get();
+        for track in tracks {
+            if self.is_offline(track.path()) {
+                continue;
+            }
+            let key = chip_key(&track);
+            let path = track.path().clone();
+            let sources = sources.clone();
+            let Some(art) = timed_io(PROBE_TIMEOUT, move || track.read_art(&sources)) else {
+                self.set_offline(&path);
+                continue;
+            };
+            let chip = art.as_ref().and_then(|art| dominant_color(art).or_else(|| mean_color(art)));
+            let _ = self.chips.timed_write().map(|mut w| w.insert(key, chip));
+        }
+        bench!("Read {} art chips in {:?}", count, now.elapsed());
+        self.broadcast(LibEvt::ArtChips)
+    }
+
+    /// The playing track's artist for the info panel. None if there's nothing to show,
+    /// or while it's looked up in the background, which broadcasts [`LibEvt::Artist`] when done
+    pub fn artist_info(&self) -> Option<Arc<ArtistInfo>> {
+        let track = self.track_get()?;
+        let name = track.tags().get("artist")?.trim();
+        if let Some(found) = self.artists.timed_read().ok()?.get(name) {
+            return found.clone();
+        }
+        let _ = self.artist_s.try_send(track);
+        None
+    }
+
+    /// Look up the artist of TRACK unless it already was
+    fn read_artist(&self, track: Arc<Track>) {
+        let Some(name) = track.tags().get("artist").map(|a| a.trim().to_string()) else {
+            return;
+        };
+        if self.artists.timed_read().is_ok_and(|a| a.contains_key(&name)) || self.is_offline(track.path()) {
+            return;
+        }
+        let now = Instant::now();
+        let provider = self.artist_provider_get();
+        let cache = self.artist_cache.timed_read().ok().and_then(|c| c.clone());
+        let info = match artist::find(&track, provider.as_ref(), cache.as_deref()) {
+            Some(Ok(info)) => (!info.is_empty()).then(|| Arc::new(info)),
+            Some(Err(e)) => {
+                error!("Could not look up {}: {}", name, e);
+                None
+            }
+            None => return,
+        };
+        bench!("Looked up artist {} in {:?}", name, now.elapsed());
+        let _ = self.artists.timed_write().map(|mut w| w.insert(name, info));
+        self.broadcast(LibEvt::Artist)
+    }
+
+    /// Picture of the playing track's artist shrunk to fit W by H, None if there isn't one
+    pub fn artist_thumbnail(&self, w: usize, h: usize) -> Option<Arc<RawImage>> {
+        let info = self.artist_info()?;
+        let image = info.image.as_ref()?;
+        let key = (w, h, info.name.clone());
+        if let Some(thumbnail) = self.artist_thumbnails.timed_read().ok()?.get(&key) {
+            return Some(thumbnail.clone());
+        }
+        let thumbnail = Arc::new(downscale(image, w, h));
+        let _ = self.artist_thumbnails.timed_write().map(|mut w| w.insert(key, thumbnail.clone()));
+        Some(thumbnail)
+    }
+
+    /// Command looking up artists without an artist.txt or picture, if set
+    pub fn artist_provider_get(&self) -> Option<ArtistProvider> {
+        self.artist_provider.timed_read().ok().and_then(|p| p.clone())
+    }
+
+    /// Look up artists without local files with PROVIDER from now on, or only use local files and cached answers with None.
+    /// Artists nothing was found for are looked up again
+    pub fn artist_provider_set(&self, provider: Option<ArtistProvider>) {
+        if let Ok(mut guard) = self.artist_provider.timed_write() {
+            *guard = provider
         }
+        let _ = self.artists.timed_write().map(|mut w| w.retain(|_, info| info.is_some()));
+        self.broadcast(LibEvt::Artist)
     }
 
-    /// Get art for current track
-    //pub fn art(&self) -> Arc<Option<RawImage>> {
-    //    self.art.timed_read().unwrap().clone()
-    //}
+    /// Keep provider answers as files in DIR, so each artist is only looked up once
+    pub fn artist_cache_set(&self, dir: PathBuf) {
+        let _ = self.artist_cache.timed_write().map(|mut w| *w = Some(dir));
+    }
 
     /// Get thumbnail of N dimension for current track
     pub fn thumbnail(&self, w: usize, h: usize) -> Option<Arc<RawImage>> {
@@ -535,66 +2442,18 @@ impl Library {
             return thumbnail.clone();
         }
 
+        self.read_art();
+
         let Ok(mut thumbnail_writer) = self.thumbnails.timed_write() else {
             return None;
         };
-
-        self.read_art();
         let art_reader = &self.art.timed_read();
         let Ok(Some(art)) = art_reader.as_deref() else {
             thumbnail_writer.insert((w, h, track.path().to_owned()), None);
             return None;
         };
 
-        let aspect = art[0].len() as f64 / art.len() as f64;
-        let thumb_w = ((h as f64 * aspect).round() as usize).min(w);
-        let thumb_h = ((w as f64 / aspect).round() as usize).min(h);
-
-        let thumbnail: RawImage = split_count(art, thumb_h)
-            .into_iter()
-            .map(|column_chunk| {
-                column_chunk
-                    .into_iter()
-                    .map(|row| {
-                        split_count(row, thumb_w).into_iter().map(|row_chunk| {
-                            row_chunk
-                                .into_iter()
-                                .fold([0u64; 4], |mut acc, it| {
-                                    acc.iter_mut().zip(it.into_iter()).for_each(|(a, b)| *a += *b as u64);
-                                    acc
-                                })
-                                .map(|c| c / row_chunk.len() as u64)
-                        })
-                    })
-                    .fold(Vec::<[u64; 4]>::new(), |acc, it| {
-                        if acc.is_empty() {
-                            it.collect()
-                        } else {
-                            acc.into_iter()
-                                .zip(it.into_iter())
-                                .map(|(mut a, b)| {
-                                    a.iter_mut().zip(b.into_iter()).for_each(|(c, d)| *c += d);
-                                    a
-                                })
-                                .collect()
-                        }
-                    })
-                    .into_iter()
-                    .map(|rgba| rgba.map(|c| (c / column_chunk.len() as u64) as u8))
-                    .collect()
-            })
-            .collect();
-
-        debug!(
-            "Generated {}x{} thumbnail for requested {}x{} of image {}x{}",
-            thumbnail[0].len(),
-            thumbnail.len(),
-            w,
-            h,
-            art[0].len(),
-            art.len()
-        );
-
+        let thumbnail = downscale(art, w, h);
         let new_thumb = Some(Arc::new(thumbnail));
         thumbnail_writer.insert((w, h, track.path().to_owned()), new_thumb.clone());
         new_thumb
@@ -607,7 +2466,7 @@ impl Library {
     /// Get a random track from the filtered queue
     pub fn get_random(&self) -> Option<Arc<Track>> {
         debug!("Getting random track...");
-        let tracks = self.get_queue();
+        let tracks = self.get_playable();
         match tracks.len() {
             0 => None,
             1 => Some(tracks[0].clone()),
@@ -620,6 +2479,76 @@ impl Library {
         }
     }
 
+    /// Next track of the current album in queue order, or the first of a random other album once it's done.
+    /// Albums are grouped by TAGSTRING
+    pub fn get_random_album(&self, tagstring: &str) -> Option<Arc<Track>> {
+        debug!("Getting random album...");
+        let tracks = self.get_playable();
+        let current = self.track_get();
+        let album = current.as_ref().map(|t| t.tagstring(tagstring));
+
+        if let (Some(current), Some(album)) = (&current, &album) {
+            if let Some(n) = tracks.iter().position(|t| t == current) {
+                if let Some(track) = tracks[n + 1..].iter().find(|t| &t.tagstring(tagstring) == album) {
+                    return Some(track.clone());
+                }
+            }
+        }
+
+        let mut albums: Vec<String> = get_taglist(tagstring, &tracks);
+        albums.sort();
+        albums.dedup();
+        if albums.len() > 1 {
+            albums.retain(|a| Some(a) != album.as_ref());
+        }
+        let pick = albums.get(random::<usize>() % albums.len().max(1))?;
+        tracks.iter().find(|t| &t.tagstring(tagstring) == pick).cloned()
+    }
+
+    /// Next track if shuffling, by album or track
+    fn get_shuffled(&self) -> Option<Arc<Track>> {
+        match self.shuffle_album_get() {
+            Some(tagstring) => self.get_random_album(&tagstring),
+            None => self.get_random(),
+        }
+    }
+
+    /// Whether the radio picks the track after the current one: always when it's on,
+    /// or once the queue's last track is reached and for as long as what's playing is outside the queue
+    fn radio_next(&self) -> bool {
+        match self.radio_get() {
+            Radio::Off => false,
+            Radio::Always => true,
+            Radio::End => {
+                let queue = self.get_queue();
+                !self.shuffle_get() && self.track_get().is_some_and(|t| queue.last() == Some(&t) || !queue.contains(&t))
+            }
+        }
+    }
+
+    /// A track similar to the current one, from the queue with the radio always on, else from the whole library.
+    /// Random if nothing's playing
+    fn get_radio(&self) -> Option<Arc<Track>> {
+        debug!("Getting radio track...");
+        let Some(current) = self.track_get() else { return self.get_random() };
+        let candidates = match self.radio_get() {
+            Radio::Always => self.get_playable(),
+            _ => {
+                let quarantined = self.get_quarantined();
+                let mut tracks = self.get_tracks();
+                tracks.retain(|t| quarantined.binary_search(t.path()).is_err());
+                tracks
+            }
+        };
+        let recent = self
+            .history
+            .timed_lock()
+            .ok()
+            .map(|h| h[h.len().saturating_sub(RADIO_RECENT)..].to_vec())
+            .unwrap_or_default();
+        radio::pick(&current, &candidates, &recent, &self.genres_get())
+    }
+
     /// Get the next track from the filtered queue. Does not respect `repeat`
     pub fn get_sequential(&self, reverse: bool) -> Option<Arc<Track>> {
         let mut tracks = self.get_queue();
@@ -634,120 +2563,741 @@ impl Library {
                 }
             }
         }
-        if i >= tracks.len() {
-            i = 0
-        }
-
-        tracks.get(i).cloned()
-    }
-
-    /// Play the next track, either shuffled or sequential
-    pub fn next(&self) {
-        if self.shuffle_get() {
-            self.play_track(self.get_random())
-        } else {
-            self.play_track(self.get_sequential(false))
-        };
-    }
 
-    /// If shuffle, pop the previous track from history and play it
-    /// Else get the prior sequential track
-    pub fn previous(&self) {
-        if self.shuffle_get() {
-            let Ok(mut guard) = self.history.timed_lock() else { return };
-            let track = guard.pop();
-            drop(guard);
-            self.play_track(track);
-            // remove twice cause it gets re-added.
+        // wrapping around past quarantined tracks
+        (0..tracks.len())
+            .map(|n| &tracks[(i + n) % tracks.len()])
+            .find(|t| !self.quarantined(t))
+            .cloned()
+    }
+
+    /// The queue without quarantined tracks
+    fn get_playable(&self) -> Vec<Arc<Track>> {
+        let quarantined = self.get_quarantined();
+        let mut tracks = self.get_queue();
+        if !quarantined.is_empty() {
+            tracks.retain(|t| quarantined.binary_search(t.path()).is_err())
+        }
+        tracks
+    }
+
+    /// Play the next track, picked by the radio, shuffled, or sequential
+    pub fn next(&self) {
+        let track = if self.radio_next() {
+            self.get_radio()
+        } else if self.shuffle_get() {
+            self.get_shuffled()
+        } else {
+            self.get_sequential(false)
+        };
+        // the last track left is consumed rather than played again
+        let track = track.filter(|t| !self.consume_get() || Some(t) != self.track_get().as_ref());
+        self.play_track(track)
+    }
+
+    /// The track played before the current one
+    pub fn history_last(&self) -> Option<Arc<Track>> {
+        self.history.timed_lock().ok().and_then(|h| h.last().cloned())
+    }
+
+    /// If shuffle or the radio picked the current track, pop the previous track from history and play it
+    /// Else get the prior sequential track
+    pub fn previous(&self) {
+        let from_history = self.shuffle_get()
+            || match self.radio_get() {
+                Radio::Off => false,
+                Radio::Always => true,
+                Radio::End => self.track_get().is_some_and(|t| !self.get_queue().contains(&t)),
+            };
+        if from_history {
+            let Ok(mut guard) = self.history.timed_lock() else { return };
+            let track = guard.pop();
+            drop(guard);
+            self.play_track(track);
+            // remove twice cause it gets re-added.
+            if let Ok(mut history) = self.history.timed_lock() {
+                history.pop();
+            }
+        } else {
+            self.play_track(self.get_sequential(true))
+        }
+    }
+
+    // ## Track Controls ## }}}
+
+    // ## Library Paths Control ## {{{
+
+    /// Token for a scan or sort starting now
+    fn cancel_token(&self) -> Cancel {
+        self.cancel.timed_lock().map(|c| c.clone()).unwrap_or_default()
+    }
+
+    /// Stop every append and sort in progress where it is, leaving the library as it was.
+    /// Anything started afterwards runs as normal
+    pub fn cancel(&self) {
+        if let Ok(mut cancel) = self.cancel.timed_lock() {
+            cancel.cancel();
+            *cancel = Cancel::default();
+        }
+    }
+
+    /// Get compatible file extensions for the player
+    pub fn types(&self) -> Vec<String> {
+        self.player.types()
+    }
+
+    /// Scan path for compatible file extensions and load tracks into library.
+    /// `ompl://host:port/` appends the tracks shared by another instance instead.
+    /// Stopped by [`Library::cancel`] without loading anything or remembering PATH
+    pub fn append_library<T: AsRef<Path>>(&self, path: T) {
+        let begin = Instant::now();
+        let path = path.as_ref().to_path_buf();
+        let cancel = self.cancel_token();
+
+        if let Some(url) = path.to_str().filter(|p| p.starts_with("ompl://")) {
+            return self.append_remote(url);
+        }
+
+        let path_c = path.clone();
+        let Some(canonical) = timed_io(IO_TIMEOUT, move || path_c.canonicalize()) else {
+            self.set_offline(&path);
+            return;
+        };
+
+        let now = Instant::now();
+        let found_tracks: Vec<Track> = find_tracks_cancellable(&path, &self.player.types(), self.hidden_get(), &cancel);
+        self.metrics.time("scan", found_tracks.len(), now.elapsed());
+        let now = Instant::now();
+        let (new_tracks, skipped) = self.probe(found_tracks, &cancel);
+        if !skipped.is_empty() {
+            self.skip(&path, skipped)
+        }
+        if cancel.cancelled() {
+            info!("Cancelled appending {}", path.display());
+            return;
+        }
+        bench!("Probed meta for {} tracks in {:?}", new_tracks.len(), now.elapsed());
+        self.metrics.time("probe", new_tracks.len(), now.elapsed());
+
+        // only remembered once it's loaded, so a cancelled scan isn't repeated next start
+        if let (Ok(root), Ok(mut roots)) = (canonical, self.roots.timed_write()) {
+            if !roots.contains(&root) {
+                roots.push(root)
+            }
+        }
+
+        let count = self.add_tracks(new_tracks);
+        self.metrics.scanned(begin.elapsed());
+        bench!("Finished appending {} tracks in total {:?}", count, begin.elapsed())
+    }
+
+    /// Load metadata for FOUND_TRACKS across probe threads.
+    /// Should a chunk take longer than [`PROBE_TIMEOUT`] the rest are given up on,
+    /// returning the tracks loaded and the paths of those that never answered
+    fn probe(&self, mut found_tracks: Vec<Track>, cancel: &Cancel) -> (Vec<Track>, Vec<PathBuf>) {
+        let mut pending: HashSet<PathBuf> = found_tracks.iter().map(|t| t.path().clone()).collect();
+        let mut new_tracks = Vec::with_capacity(found_tracks.len());
+        // 50 is a completely arbitrary value that seems to perform well enough
+        // Basically tradeoff between thread spawn overhead and IO calls.
+        // I dont want an entire async runtime for loading metadata so here it is.
+        // Chunks are owned by their threads so a hung mount can be abandoned instead of joined.
+        let (sender, receiver) = channel();
+        while !found_tracks.is_empty() {
+            let mut chunk = found_tracks.split_off(found_tracks.len().saturating_sub(50));
+            let (sender, cancel) = (sender.clone(), cancel.clone());
+            let spawned = thread::Builder::new().name(String::from("LIBRARY Probe")).spawn(move || {
+                chunk.iter_mut().take_while(|_| !cancel.cancelled()).for_each(|track| track.load_meta());
+                let _ = sender.send(chunk);
+            });
+            if let Err(e) = spawned {
+                error!("Could not spawn probe thread: {}", e);
+            }
+        }
+        drop(sender);
+
+        loop {
+            match receiver.recv_timeout(PROBE_TIMEOUT) {
+                Ok(chunk) => {
+                    chunk.iter().for_each(|t| {
+                        pending.remove(t.path());
+                    });
+                    new_tracks.extend(chunk)
+                }
+                Err(RecvTimeoutError::Disconnected) => break,
+                Err(RecvTimeoutError::Timeout) => return (new_tracks, pending.into_iter().collect()),
+            }
+        }
+        (new_tracks, Vec::new())
+    }
+
+    /// Mark the root holding PATH offline and report SKIPPED,
+    /// keeping them so only they are probed once it's back
+    fn skip(&self, path: &Path, skipped: Vec<PathBuf>) {
+        let root = self.set_offline(path);
+        error!("{} files under {} timed out and were skipped", skipped.len(), root.display());
+        for skipped in skipped.iter() {
+            debug!("Skipped {}", skipped.display())
+        }
+        self.broadcast(LibEvt::Error(trf(
+            "{} files under {} timed out and were skipped until it's back",
+            &[&skipped.len(), &root.display()],
+        )));
+        if let Ok(mut all) = self.skipped.timed_write() {
+            all.entry(root).or_default().extend(skipped)
+        }
+    }
+
+    /// Probe and load FILES skipped while their root was offline
+    fn append_skipped(&self, root: &Path, files: Vec<PathBuf>) {
+        let now = Instant::now();
+        let found_tracks = files.into_iter().filter_map(Track::new).collect();
+        let (new_tracks, skipped) = self.probe(found_tracks, &self.cancel_token());
+        if !skipped.is_empty() {
+            self.skip(root, skipped)
+        }
+        let count = self.add_tracks(new_tracks);
+        bench!("Appended {} skipped tracks under {} in {:?}", count, root.display(), now.elapsed())
+    }
+
+    /// Append the tracks shared by the instance at URL
+    fn append_remote(&self, url: &str) {
+        #[cfg(not(feature = "share"))]
+        self.broadcast(LibEvt::Error(trf("Appending {} needs ompl built with the `share` feature", &[&url])));
+        #[cfg(feature = "share")]
+        {
+            let now = Instant::now();
+            let types = self.player.types();
+            match share::fetch(url) {
+                Ok(mut tracks) => {
+                    // the server's player might decode more than ours
+                    tracks.retain(|t| t.path().to_str().is_some_and(|p| types.iter().any(|ty| p.ends_with(ty))));
+                    let count = self.add_tracks(tracks);
+                    bench!("Appended {} remote tracks from {} in {:?}", count, url, now.elapsed())
+                }
+                Err(e) => self.broadcast(LibEvt::Error(trf("Could not append {}: {}", &[&url, &e]))),
+            }
+        }
+    }
+
+    /// Merge NEW_TRACKS into the library, replacing any already loaded from the same path.
+    /// Returns how many tracks were new
+    fn add_tracks(&self, mut new_tracks: Vec<Track>) -> usize {
+        let now = Instant::now();
+        self.handle_duplicates(&mut new_tracks);
+        compilations::tag(&self.get_tracks(), &mut new_tracks);
+        let mut count = new_tracks.len();
+
+        if let Ok(stats) = self.stats.timed_read() {
+            new_tracks.iter_mut().for_each(|t| {
+                stats.apply(t);
+                stats.tag(t)
+            })
+        }
+        #[cfg(feature = "analyzer")]
+        self.analyze_queue(new_tracks.iter());
+
+        if let Ok(mut tracks) = self.tracks.timed_write() {
+            new_tracks.into_iter().map(|t| Arc::new(t)).for_each(|t| tracks.push(t));
+            let len = tracks.len();
+
+            // pushed vals in front
+            tracks.reverse();
+            // Stable sort should prioritize new tracks due to reverse()
+            tracks.sort_by(|a, b| a.path().cmp(b.path()));
+            // Therefore the dedupe will use the newly probed tracks
+            tracks.dedup_by(|a, b| a.path() == b.path());
+
+            if len > tracks.len() {
+                info!("Removed {} duplicate tracks during append", len - tracks.len());
+                count -= len - tracks.len()
+            }
+        }
+
+        bench!("Loaded {} tracks into library in {:?}", count, now.elapsed());
+        self.metrics.time("load", count, now.elapsed());
+
+        // usable right away, sorted order follows shortly
+        self.force_build_filters();
+        self.sort();
+
+        if self.player.track_get().is_none() {
+            self.player.track_set(if self.shuffle_get() {
+                self.get_shuffled()
+            } else {
+                self.get_sequential(false)
+            });
+            let _ = self.art.timed_write().map(|mut a| *a = None);
+        }
+        self.broadcast(LibEvt::Tracks);
+        let _ = self.validate_s.try_send(());
+        count
+    }
+
+    /// Record NEW_TRACKS matching the tags of a loaded track or an earlier new one,
+    /// then skip or rename them according to the duplicate policy
+    fn handle_duplicates(&self, new_tracks: &mut Vec<Track>) {
+        // sorted so which of a pair counts as the original doesn't depend on probe thread timing
+        new_tracks.sort_by(|a, b| a.path().cmp(b.path()));
+        let found = duplicates::find(&self.get_tracks(), new_tracks);
+        if found.is_empty() {
+            return;
+        }
+        let policy = self.duplicate_policy_get();
+        info!("Found {} tracks with the same tags as another", found.len());
+        if let Ok(mut duplicates) = self.duplicates.timed_write() {
+            for (n, original) in found.iter() {
+                let path = new_tracks[*n].path().clone();
+                duplicates.retain(|d| d.path != path);
+                duplicates.push(Duplicate {
+                    path,
+                    original: original.clone(),
+                    policy,
+                })
+            }
+        }
+        match policy {
+            DuplicatePolicy::Path => (),
+            DuplicatePolicy::Tags => {
+                *new_tracks = std::mem::take(new_tracks)
+                    .into_iter()
+                    .enumerate()
+                    .filter(|(n, _)| !found.iter().any(|f| f.0 == *n))
+                    .map(|(_, t)| t)
+                    .collect()
+            }
+            DuplicatePolicy::Keep => {
+                for (n, original) in found {
+                    let track = &mut new_tracks[n];
+                    let title = format!("{} ({})", track.tagstring("title"), duplicates::disambiguation(track, &original));
+                    track.tag_set("title", title)
+                }
+            }
+        }
+    }
+
+    /// Find a missing track's new location by its fingerprint and swap it in everywhere it's referenced.
+    /// Checks tracks already in the library before rescanning the roots.
+    fn relocate(&self, track: &Arc<Track>) -> Option<Arc<Track>> {
+        let now = Instant::now();
+        let fingerprint = track.fingerprint()?;
+
+        let known = self
+            .tracks
+            .timed_read()
+            .ok()?
+            .iter()
+            .find(|t| t.fingerprint().as_ref() == Some(&fingerprint) && t != &track && !self.is_offline(t.path()) && t.path().exists())
+            .cloned();
+
+        // only where a rename or retag would likely leave it, so a play doesn't wait on walking whole roots
+        let found = known.clone().or_else(|| {
+            let root = self
+                .roots
+                .timed_read()
+                .ok()?
+                .iter()
+                .filter(|r| track.path().starts_with(r) && !self.is_offline(r))
+                .max_by_key(|r| r.components().count())?
+                .clone();
+            let parent = track.path().parent()?.to_path_buf();
+            let (types, hidden) = (self.player.types(), self.hidden_get());
+            timed_io(PROBE_TIMEOUT, move || {
+                // the old directory and its siblings, or what's under it when it's the root
+                let above = parent.parent().filter(|p| p.starts_with(&root)).unwrap_or(&parent).to_path_buf();
+                let mut dirs = vec![parent.clone()];
+                dirs.extend(
+                    above
+                        .read_dir()
+                        .into_iter()
+                        .flatten()
+                        .filter_map(|e| e.ok())
+                        .map(|e| e.path())
+                        .filter(|p| p != &parent && p.is_dir()),
+                );
+                dirs.iter().find_map(|dir| {
+                    tracks_in(dir, &types, hidden).into_iter().find_map(|mut candidate| {
+                        candidate.load_meta();
+                        (candidate.fingerprint().as_ref() == Some(&fingerprint)).then(|| Arc::new(candidate))
+                    })
+                })
+            })
+            .flatten()
+        })?;
+
+        if let Ok(mut stats) = self.stats.timed_write() {
+            stats.rename(track.path(), found.path());
+            stats.apply(&found);
+        }
+
+        info!(
+            "Relocated {} to {} in {:?}",
+            track.path().to_str().unwrap_or("???"),
+            found.path().to_str().unwrap_or("???"),
+            now.elapsed()
+        );
+
+        if let Ok(mut tracks) = self.tracks.timed_write() {
+            // takes the old one's place, sorting puts it where the sorters want
+            match tracks.iter().position(|t| t == track) {
+                Some(n) if known.is_none() => tracks[n] = found.clone(),
+                Some(n) => {
+                    tracks.remove(n);
+                }
+                None if known.is_none() => tracks.push(found.clone()),
+                None => (),
+            }
+        }
+        if let Ok(mut history) = self.history.timed_lock() {
+            history.iter_mut().filter(|t| *t == track).for_each(|t| *t = found.clone())
+        }
+        self.favorites_sync();
+        self.force_build_filters();
+        self.sort();
+        self.broadcast(LibEvt::Tracks);
+
+        Some(found)
+    }
+
+    /// Check every local track's file can be opened, skipping offline roots,
+    /// and broadcast if the set of unreadable tracks changed
+    fn validate(&self) {
+        let now = Instant::now();
+        let paths: Vec<PathBuf> = self
+            .get_tracks()
+            .iter()
+            .filter(|t| !t.is_remote() && !self.is_offline(t.path()))
+            .map(|t| t.path().clone())
+            .collect();
+        let count = paths.len();
+        let Some(unreadable) = timed_io(PROBE_TIMEOUT, move || {
+            paths
+                .into_iter()
+                .filter(|p| std::fs::File::open(p).is_err())
+                .collect::<HashSet<PathBuf>>()
+        }) else {
+            error!("Gave up checking tracks for missing files after {:?}", PROBE_TIMEOUT);
+            return;
+        };
+        bench!("Validated {} tracks in {:?}", count, now.elapsed());
+        let Ok(mut current) = self.unreadable.timed_write() else { return };
+        if *current != unreadable {
+            info!("{} tracks are missing or unreadable", unreadable.len());
+            *current = unreadable;
+            drop(current);
+            self.broadcast(LibEvt::Unreadable)
+        }
+    }
+
+    /// Whether PATH couldn't be opened on the last check
+    pub fn is_unreadable<T: AsRef<Path>>(&self, path: T) -> bool {
+        self.unreadable.timed_read().is_ok_and(|u| u.contains(path.as_ref()))
+    }
+
+    /// Tracks whose files couldn't be opened on the last check, sorted
+    pub fn get_unreadable(&self) -> Vec<PathBuf> {
+        let mut unreadable: Vec<PathBuf> = self.unreadable.timed_read().map(|u| u.iter().cloned().collect()).unwrap_or_default();
+        unreadable.sort();
+        unreadable
+    }
+
+    /// Check every track again and drop those with missing or unreadable files from the library.
+    /// Stats are kept in case the files come back. Returns the number of tracks dropped
+    pub fn clean(&self) -> usize {
+        self.validate();
+        let unreadable = self.unreadable.timed_write().map(|mut u| std::mem::take(&mut *u)).unwrap_or_default();
+        if unreadable.is_empty() {
+            return 0;
+        }
+        let mut count = 0;
+        if let Ok(mut tracks) = self.tracks.timed_write() {
+            let len = tracks.len();
+            tracks.retain(|t| !unreadable.contains(t.path()));
+            count = len - tracks.len();
+        }
+        if let Ok(mut history) = self.history.timed_lock() {
+            history.retain(|t| !unreadable.contains(t.path()))
+        }
+        info!("Cleaned {} missing or unreadable tracks", count);
+        self.favorites_sync();
+        self.force_build_filters();
+        self.broadcast(LibEvt::Unreadable);
+        self.broadcast(LibEvt::Tracks);
+        count
+    }
+
+    /// Whether the path lives under a root that's currently unreachable
+    pub fn is_offline<T: AsRef<Path>>(&self, path: T) -> bool {
+        self.offline
+            .timed_read()
+            .map(|o| o.iter().any(|root| path.as_ref().starts_with(root)))
+            .unwrap_or(false)
+    }
+
+    /// Offline roots
+    pub fn get_offline(&self) -> Vec<PathBuf> {
+        self.offline.timed_read().as_deref().cloned().unwrap_or_default()
+    }
+
+    /// Mark the root containing path as offline until the retry thread can reach it again.
+    /// Returns that root
+    fn set_offline(&self, path: &Path) -> PathBuf {
+        let root = self
+            .roots
+            .timed_read()
+            .ok()
+            .and_then(|roots| roots.iter().find(|r| path.starts_with(r)).cloned())
+            .unwrap_or(path.to_path_buf());
+        let Ok(mut offline) = self.offline.timed_write() else { return root };
+        if !offline.contains(&root) {
+            error!("{} timed out, marking offline", root.to_str().unwrap_or("???"));
+            offline.push(root.clone());
+            drop(offline);
+            self.broadcast(LibEvt::Offline(root.clone()));
+        }
+        root
+    }
+
+    /// Reload any offline roots which respond again.
+    /// Roots cut off partway through probing only have the files skipped then probed again
+    fn retry_offline(&self) {
+        for root in self.get_offline() {
+            let root_c = root.clone();
+            if timed_io(IO_TIMEOUT, move || root_c.read_dir().is_ok()) == Some(true) {
+                info!("{} reachable again", root.to_str().unwrap_or("???"));
+                let _ = self.offline.timed_write().map(|mut o| o.retain(|r| r != &root));
+                match self.skipped.timed_write().ok().and_then(|mut s| s.remove(&root)) {
+                    Some(files) => self.append_skipped(&root, files),
+                    None => self.append_library(&root),
+                }
+            }
+        }
+    }
+
+    /// Move files to where PATTERN names them under their library root, see [`export::name`].
+    /// ALL takes every track rather than the queue. Without APPLY nothing is touched.
+    /// Returns each planned move relative to its root, then anything skipped or failed
+    pub fn organize(&self, pattern: &str, all: bool, apply: bool) -> Vec<String> {
+        let tracks = if all { self.get_tracks() } else { self.get_queue() };
+        let roots = self.roots.timed_read().as_deref().cloned().unwrap_or_default();
+        let current = self.track_get();
+        let mut report = Vec::new();
+        let mut moved = Vec::new();
+
+        for (source, name) in export::plan(&tracks, pattern) {
+            let Some(root) = roots.iter().filter(|r| source.starts_with(r)).max_by_key(|r| r.components().count()) else {
+                continue;
+            };
+            // pushed rather than set so dots in titles aren't taken for an extension
+            let mut target = root.join(name).into_os_string();
+            if let Some(ext) = source.extension() {
+                target.push(".");
+                target.push(ext);
+            }
+            let target = PathBuf::from(target);
+            if target == source {
+                continue;
+            }
+            let relative = |p: &Path| p.strip_prefix(root).unwrap_or(p).to_string_lossy().to_string();
+            let line = format!("{} -> {}", relative(&source), relative(&target));
+            if current.as_ref().is_some_and(|t| t.path() == &source) {
+                report.push(format!("{}: skipped, currently loaded", line));
+                continue;
+            } else if self.is_offline(&source) {
+                report.push(format!("{}: skipped, root offline", line));
+                continue;
+            } else if target.exists() {
+                report.push(format!("{}: skipped, target exists", line));
+                continue;
+            } else if !apply {
+                report.push(line);
+                continue;
+            }
+
+            let result = target
+                .parent()
+                .map(std::fs::create_dir_all)
+                .unwrap_or(Ok(()))
+                .and_then(|_| std::fs::rename(&source, &target));
+            match result {
+                Ok(()) => {
+                    // clear out folders the move emptied, stopping at the first with anything left
+                    for dir in source.ancestors().skip(1).take_while(|d| d.starts_with(root) && *d != root) {
+                        if std::fs::remove_dir(dir).is_err() {
+                            break;
+                        }
+                    }
+                    report.push(line);
+                    moved.push((source, target));
+                }
+                Err(e) => report.push(format!("{}: {}", line, e)),
+            }
+        }
+
+        if !moved.is_empty() {
+            info!("Organized {} files", moved.len());
+            if let Ok(mut stats) = self.stats.timed_write() {
+                moved.iter().for_each(|(from, to)| stats.rename(from, to));
+            }
+            let moved: HashMap<&PathBuf, &PathBuf> = moved.iter().map(|(from, to)| (from, to)).collect();
+            if let Ok(mut tracks) = self.tracks.timed_write() {
+                for track in tracks.iter_mut() {
+                    if let Some(to) = moved.get(track.path()) {
+                        *track = Arc::new(track.moved(to.to_path_buf()))
+                    }
+                }
+            }
             if let Ok(mut history) = self.history.timed_lock() {
-                history.pop();
+                for track in history.iter_mut() {
+                    if let Some(to) = moved.get(track.path()) {
+                        *track = Arc::new(track.moved(to.to_path_buf()))
+                    }
+                }
             }
-        } else {
-            self.play_track(self.get_sequential(true))
+            self.favorites_sync();
+            self.force_build_filters();
+            // sorters may use the path and filename tags
+            self.sort();
+            self.broadcast(LibEvt::Tracks);
         }
-    }
-
-    // ## Track Controls ## }}}
 
-    // ## Library Paths Control ## {{{
-
-    /// Get compatible file extensions for the player
-    pub fn types(&self) -> Vec<String> {
-        self.player.types()
+        report
     }
 
-    /// Scan path for compatible file extensions and load tracks into library
-    pub fn append_library<T: AsRef<Path>>(&self, path: T) {
-        let begin = Instant::now();
-
-        let mut new_tracks: Vec<Track> = find_tracks(path, &self.player.types(), self.hidden_get());
-        let mut count = new_tracks.len();
-
-        let now = Instant::now();
-        thread::scope(|scope| {
-            // 50 is a completely arbitrary value that seems to perform well enough
-            // Basically tradeoff between thread spawn overhead and IO calls.
-            // I dont want an entire async runtime for loading metadata so here it is
-            for chunk in new_tracks.chunks_mut(50) {
-                scope.spawn(|| chunk.iter_mut().for_each(|track| track.load_meta()));
+    /// Delete TRACK's file and drop it from the library, moving on first if it's playing.
+    /// TRASH sends the file to the system trash instead, needing the `trash` feature
+    pub fn delete_file(&self, track: &Arc<Track>, trash: bool) -> Result<(), String> {
+        if track.is_remote() {
+            return Err(tr("Tracks from a shared library can't be deleted").to_string());
+        }
+        if self.track_get().as_ref() == Some(track) {
+            self.next();
+            // nothing else to play
+            if self.track_get().as_ref() == Some(track) {
+                self.play_track(None);
             }
-        });
+        }
 
-        bench!("Probed meta for {} tracks in {:?}", count, now.elapsed());
-        let now = Instant::now();
+        let path = track.path().clone();
+        let result = if trash {
+            #[cfg(feature = "trash")]
+            {
+                trash::delete(&path).map_err(|e| e.to_string())
+            }
+            #[cfg(not(feature = "trash"))]
+            Err(tr("OMPL was built without the trash feature").to_string())
+        } else {
+            std::fs::remove_file(&path).map_err(|e| e.to_string())
+        };
+        result.map_err(|e| trf("Could not delete {}: {}", &[&path.to_str().unwrap_or("???"), &e]))?;
+        info!("Deleted {}", path.to_str().unwrap_or("???"));
 
+        if let Ok(mut stats) = self.stats.timed_write() {
+            stats.remove(&path)
+        }
         if let Ok(mut tracks) = self.tracks.timed_write() {
-            new_tracks.into_iter().map(|t| Arc::new(t)).for_each(|t| tracks.push(t));
-            let len = tracks.len();
-
-            // pushed vals in front
-            tracks.reverse();
-            // Stable sort should prioritize new tracks due to reverse()
-            tracks.sort_by(|a, b| a.path().cmp(b.path()));
-            // Therefore the dedupe will use the newly probed tracks
-            tracks.dedup_by(|a, b| a.path() == b.path());
+            tracks.retain(|t| t != track)
+        }
+        if let Ok(mut history) = self.history.timed_lock() {
+            history.retain(|t| t != track)
+        }
+        self.favorites_sync();
+        self.force_build_filters();
+        self.broadcast(LibEvt::Tracks);
+        Ok(())
+    }
 
-            if len > tracks.len() {
-                info!("Removed {} duplicate tracks during append", len - tracks.len());
-                count -= len - tracks.len()
+    /// Read tags again for the loaded track and every file modified since it was scanned,
+    /// so edits from outside taggers show up without a rescan. Playback continues uninterrupted.
+    /// Returns the number of tracks reloaded
+    pub fn reload(&self) -> usize {
+        let current = self.track_get();
+        let stale: Vec<Arc<Track>> = self
+            .get_tracks()
+            .into_iter()
+            .chain(current.clone())
+            .filter(|t| !t.is_remote() && !self.is_offline(t.path()))
+            .filter(|t| current.as_ref() == Some(t) || t.changed())
+            .collect();
+        let mut reloaded: Vec<Track> = Vec::with_capacity(stale.len());
+        for track in stale {
+            if !reloaded.iter().any(|t| t.path() == track.path()) {
+                reloaded.push(track.reloaded())
+            }
+        }
+        compilations::tag(&self.get_tracks(), &mut reloaded);
+        if let Ok(stats) = self.stats.timed_read() {
+            reloaded.iter_mut().for_each(|t| stats.tag(t))
+        }
+        let reloaded: Vec<Arc<Track>> = reloaded.into_iter().map(Arc::new).collect();
+
+        if let Some(new) = self.swap_tracks(&reloaded) {
+            // the art may have been swapped too
+            let _ = self.thumbnails.timed_write().map(|mut w| w.retain(|(_, _, p), _| p != new.path()));
+            let _ = self.art.timed_write().map(|mut w| *w = None);
+            if self.accent_auto_get() {
+                let _ = self.accent_s.try_send(());
             }
         }
 
-        bench!("Loaded {} tracks into library in {:?}", count, now.elapsed());
+        let keys: HashSet<ChipKey> = reloaded.iter().map(|t| chip_key(t)).collect();
+        let _ = self.chips.timed_write().map(|mut w| w.retain(|k, _| !keys.contains(k)));
 
-        self.sort();
+        debug!("Reloaded {} tracks", reloaded.len());
+        self.force_build_filters();
+        self.broadcast(LibEvt::Tracks);
+        // not Track, which would restart anything following playback like casts
+        self.broadcast(LibEvt::Theme);
+        reloaded.len()
+    }
 
-        if self.player.track_get().is_none() {
-            self.player.track_set(if self.shuffle_get() {
-                self.get_random()
-            } else {
-                self.get_sequential(false)
-            });
-            let _ = self.art.timed_write().map(|mut a| *a = None);
+    /// Put each of NEW in place of the loaded track at its path, in the library, history, and player.
+    /// Returns the new playing track if it was one of them
+    fn swap_tracks(&self, new: &[Arc<Track>]) -> Option<Arc<Track>> {
+        let current = self.track_get();
+        if let Ok(mut tracks) = self.tracks.timed_write() {
+            for track in tracks.iter_mut() {
+                if let Some(new) = new.iter().find(|t| t.path() == track.path()) {
+                    *track = new.clone()
+                }
+            }
         }
-        bench!("Finished appending {} tracks in total {:?}", count, begin.elapsed())
+        if let Ok(mut history) = self.history.timed_lock() {
+            for track in history.iter_mut() {
+                if let Some(new) = new.iter().find(|t| t.path() == track.path()) {
+                    *track = new.clone()
+                }
+            }
+        }
+        let new = current.and_then(|c| new.iter().find(|t| t.path() == c.path()))?;
+        self.player.track_retag(new.clone());
+        Some(new.clone())
     }
 
     /// Drop all tracks from the library
     pub fn purge(&self) {
+        let _ = self.offline.timed_write().map(|mut w| *w = Vec::new());
         let _ = self.tracks.timed_write().map(|mut w| *w = Vec::new());
+        let _ = self.roots.timed_write().map(|mut w| *w = Vec::new());
         let _ = self.thumbnails.timed_write().map(|mut w| *w = HashMap::new());
+        let _ = self.chips.timed_write().map(|mut w| *w = HashMap::new());
         let _ = self.art.timed_write().map(|mut w| *w = None);
+        let _ = self.duplicates.timed_write().map(|mut w| *w = Vec::new());
         self.force_build_filters();
-        self.broadcast(LibEvt::Update);
+        self.broadcast(LibEvt::Tracks);
     }
 
     // ## Library Paths Control ## }}}
 
     // ## Filters Control ## {{{
 
-    /// rebuilds whole filter tree without caching
+    /// rebuilds whole filter tree without caching.
+    /// Doesn't broadcast, callers know better what changed
     fn force_build_filters(&self) {
         let filters = self.get_filters();
         if let Ok(mut ft) = self.filtered_tree.timed_write() {
             *ft = Vec::new();
         }
-        self.set_filters(filters);
+        self.build_filters(filters);
     }
 
     /// Amount of filters
@@ -773,44 +3323,103 @@ impl Library {
 
     /// Replace all Filters, rebuilding the FilteredTracks
     pub fn set_filters(&self, filters: Vec<Filter>) {
+        let (changed, queue) = self.build_filters(filters);
+        self.broadcast(LibEvt::Filters(changed));
+        if queue {
+            self.broadcast(LibEvt::Queue)
+        }
+    }
+
+    /// Rebuild FilteredTracks incrementally.
+    /// Layers whose filter and input are unchanged are reused outright.
+    /// Layers with an unchanged filter but new input only match tracks they haven't seen before.
+    /// Returns the indices of layers whose filter or tracks changed, and whether the queue changed
+    fn build_filters(&self, filters: Vec<Filter>) -> (Vec<usize>, bool) {
         debug!("Updating filters...");
         let now = Instant::now();
-        let mut cache = true;
+        let old_tree = self.get_filter_tree();
+        let genres = self.genres_get();
+        let Ok(itracks) = self.tracks.timed_read() else {
+            return (Vec::new(), false);
+        };
         let mut filtered_tree = Vec::<FilteredTracks>::new();
+        let mut changed = Vec::new();
+        let mut input_same = true;
 
         for (i, f) in filters.into_iter().enumerate() {
-            // if filters continuously match existing, don't rebuild.
-            if let Some(ft) = self.filtered_tree.timed_read().as_ref().ok().map(|v| v.get(i)).flatten() {
-                if ft.filter == f && cache {
-                    filtered_tree.push(ft.clone());
-                    continue;
-                } else {
-                    cache = false
-                }
-            }
+            let old = old_tree.get(i).filter(|old| old.filter == f);
+            let input = if i == 0 { &*itracks } else { &filtered_tree[i - 1].tracks };
 
-            let Ok(itracks) = self.tracks.timed_read() else { break };
-            let iter = if i == 0 { itracks.iter() } else { filtered_tree[i - 1].tracks.iter() };
+            if let Some(old) = old.filter(|_| input_same) {
+                filtered_tree.push(old.clone());
+                continue;
+            }
 
-            let tracks = if !f.items.is_empty() {
-                let mut tracks_f = Vec::new();
-                for t in iter {
-                    if f.items.contains(&tagstring::parse(&f.tag, &t.tags())) {
-                        tracks_f.push(t.clone())
-                    }
-                }
-                tracks_f
+            let tracks: Vec<Arc<Track>> = if f.items.is_empty() {
+                input.clone()
+            } else if let Some(old) = old.filter(|_| i > 0) {
+                // Same filter, different input. Anything in both inputs already has its answer
+                let old_input = old_tree[i - 1].tracks.iter().map(Arc::as_ptr).collect::<HashSet<_>>();
+                let old_passed = old.tracks.iter().map(Arc::as_ptr).collect::<HashSet<_>>();
+                input
+                    .iter()
+                    .filter(|t| match old_input.contains(&Arc::as_ptr(t)) {
+                        true => old_passed.contains(&Arc::as_ptr(t)),
+                        false => genres.matches(&f, &t.tagstring(&f.tag)),
+                    })
+                    .cloned()
+                    .collect()
             } else {
-                iter.map(|t| t.clone()).collect()
+                input.iter().filter(|t| genres.matches(&f, &t.tagstring(&f.tag))).cloned().collect()
             };
+
+            let old = old_tree.get(i);
+            input_same = old.is_some_and(|old| same_tracks(&old.tracks, &tracks));
+            if !input_same || old.is_some_and(|old| old.filter != f) {
+                changed.push(i)
+            }
             filtered_tree.push(FilteredTracks { filter: f, tracks })
         }
 
+        let queue = !same_tracks(queue_layer(&itracks, &old_tree), queue_layer(&itracks, &filtered_tree));
+        let total = itracks.len();
+        drop(itracks);
+
         if let Ok(mut ft) = self.filtered_tree.timed_write() {
             *ft = filtered_tree;
-            self.broadcast(LibEvt::Update);
         };
-        bench!("Filters updated in {:?}", now.elapsed())
+        bench!("Filters updated in {:?}", now.elapsed());
+        self.metrics.time("filter", total, now.elapsed());
+        (changed, queue)
+    }
+
+    /// Genre aliases and parents applied to genre filters
+    pub fn genres_get(&self) -> Arc<Genres> {
+        self.genres.timed_read().as_deref().cloned().unwrap_or_default()
+    }
+
+    /// Apply GENRES to genre filters, rebuilding them all
+    pub fn genres_set(&self, genres: Genres) {
+        let filters = self.get_filters();
+        if let Ok(mut w) = self.genres.timed_write() {
+            *w = Arc::new(genres)
+        }
+        // unchanged filters would otherwise keep what they matched before
+        let _ = self.filtered_tree.timed_write().map(|mut ft| ft.clear());
+        self.set_filters(filters)
+    }
+
+    /// Values of TAGSTRING among TRACKS as a filter pane lists them, ordered by the collation.
+    /// Genre panes also list every genre the values are aliased to or fall under
+    pub fn filter_values<T: Deref<Target = Track>>(&self, tagstring: &str, tracks: &Vec<T>) -> Vec<String> {
+        let (genres, collation) = (self.genres_get(), self.collation_get());
+        if genres.is_empty() || !Genres::applies(tagstring) {
+            return get_taglist_sort(tagstring, tracks, &collation);
+        }
+        let mut values: Vec<String> = get_taglist(tagstring, tracks).iter().flat_map(|v| genres.expand(v)).collect();
+        values.sort_by(|a, b| collation.cmp(a, b));
+        values.dedup();
+        values
     }
 
     /// Get clone of Nth Filter
@@ -850,6 +3459,30 @@ impl Library {
         self.set_filters(filters);
     }
 
+    /// Add a filter layer on TAGSTRING holding every value in the queue containing QUERY, ignoring case.
+    /// Returns how many values matched, adding nothing if none did
+    pub fn filter_search(&self, tagstring: String, query: &str) -> usize {
+        let query = query.to_lowercase();
+        let mut items: Vec<String> = Vec::new();
+        for value in self.get_taglist(&tagstring) {
+            if value.to_lowercase().contains(&query) && !items.contains(&value) {
+                items.push(value)
+            }
+        }
+        let count = items.len();
+        if count > 0 {
+            self.insert_filter(
+                Filter {
+                    tag: tagstring,
+                    items,
+                    op: Default::default(),
+                },
+                usize::MAX,
+            )
+        }
+        count
+    }
+
     /// Get clone of Nth FilteredTracks items
     pub fn get_filter_items(&self, pos: usize) -> Option<Vec<String>> {
         self.filtered_tree
@@ -890,20 +3523,77 @@ impl Library {
 
     // ## Sorters Control ## {{{
 
-    /// Sort unfiltered tracks based on sorter tagstrings
+    /// Sort unfiltered tracks based on sorter tagstrings in the background.
+    /// Broadcasts Queue once the sorted tracks are swapped in
     fn sort(&self) {
+        // a full channel means a sort is already pending
+        let _ = self.sort_s.try_send(());
+    }
+
+    /// Sorts a snapshot of the tracks without holding any locks,
+    /// then swaps it in if nothing changed underneath
+    fn sort_now(&self) {
         let now = Instant::now();
-        if let Ok(mut tracks) = self.tracks.timed_write() {
-            tracks.sort_by(|a, b| {
-                let mut result = std::cmp::Ordering::Equal;
-                for ts in self.sorters.timed_read().as_deref().unwrap_or(&Vec::new()).iter() {
-                    result = result.then(natural_lexical_cmp(&a.tagstring(ts), &b.tagstring(ts)))
+        let cancel = self.cancel_token();
+        let sorters = self.get_sorters();
+        let collation = self.collation_get();
+        let snapshot = self.get_tracks();
+        let total = snapshot.len();
+        let progress = total >= SORT_PROGRESS_MIN;
+
+        // Parse every key once up front instead of twice per comparison
+        let mut keyed = Vec::with_capacity(total);
+        for (n, track) in snapshot.into_iter().enumerate() {
+            if n % (SORT_PROGRESS_MIN / 2) == 0 {
+                if cancel.cancelled() {
+                    break;
                 }
-                result
-            });
-            bench!("Sorted {} tracks in {:?}", tracks.len(), now.elapsed());
+                if progress {
+                    self.broadcast(LibEvt::Sorting(n, total))
+                }
+            }
+            let key = |ts: &str| match collation.sort_tags {
+                true => track.sort_tagstring(ts),
+                false => track.tagstring(ts),
+            };
+            keyed.push((sorters.iter().map(|s| key(parse_sorter(s).0)).collect::<Vec<String>>(), track))
+        }
+        if cancel.cancelled() {
+            info!("Cancelled sorting");
+            // clears any sorting progress shown
+            self.broadcast(LibEvt::Queue);
+            return;
+        }
+        let descending = sorters.iter().map(|s| parse_sorter(s).1).collect::<Vec<bool>>();
+        keyed.sort_by(|(a, _), (b, _)| {
+            a.iter()
+                .zip(b.iter())
+                .zip(descending.iter())
+                .fold(std::cmp::Ordering::Equal, |result, ((a, b), descending)| {
+                    result.then_with(|| match descending {
+                        true => collation.cmp(b, a),
+                        false => collation.cmp(a, b),
+                    })
+                })
+        });
+        let sorted = keyed.into_iter().map(|(_, track)| track).collect::<Vec<Arc<Track>>>();
+
+        if let Ok(mut tracks) = self.tracks.timed_write() {
+            // tracks were added/removed or sorters changed mid-sort
+            if self.get_sorters() != sorters || self.collation_get() != collation || !same_track_set(&tracks, &sorted) {
+                debug!("Library changed while sorting, trying again");
+                drop(tracks);
+                self.sort();
+                return;
+            }
+            *tracks = sorted;
+        } else {
+            return;
         }
-        self.force_build_filters()
+        bench!("Sorted {} tracks in {:?}", total, now.elapsed());
+        self.metrics.time("sort", total, now.elapsed());
+        self.force_build_filters();
+        self.broadcast(LibEvt::Queue);
     }
 
     /// Amount of sorter tagstrings
@@ -911,6 +3601,23 @@ impl Library {
         self.sorters.timed_read().map(|v| v.len()).unwrap_or(0)
     }
 
+    /// How sorters and filter panes compare tagstrings
+    pub fn collation_get(&self) -> Collation {
+        self.collation.timed_read().as_deref().cloned().unwrap_or_default()
+    }
+
+    /// Change how tagstrings compare and re-sort library
+    pub fn collation_set(&self, collation: Collation) {
+        if let Ok(mut guard) = self.collation.timed_write() {
+            *guard = collation
+        } else {
+            return;
+        }
+        self.sort();
+        // pane items are sorted on their own
+        self.broadcast(LibEvt::Filters((0..self.filter_count()).collect()));
+    }
+
     /// Get clone of sorter tagstrings
     pub fn get_sorters(&self) -> Vec<String> {
         self.sorters.timed_read().as_deref().cloned().unwrap_or(Vec::new())
@@ -941,6 +3648,21 @@ impl Library {
         self.sort();
     }
 
+    /// Flip Nth sorter between ascending and descending and re-sort library
+    pub fn sorter_toggle_direction(&self, index: usize) {
+        if let Ok(mut tagstrings) = self.sorters.timed_write() {
+            if let Some(ts) = tagstrings.get_mut(index) {
+                *ts = match parse_sorter(ts) {
+                    (tagstring, true) => tagstring.to_string(),
+                    (tagstring, false) => format!("-{}", tagstring),
+                }
+            } else {
+                return;
+            }
+        }
+        self.sort();
+    }
+
     /// Remove Nth sorter tagstring and re-sort library
     pub fn remove_sorter(&self, index: usize) {
         let mut sorters = self.get_sorters();
@@ -961,6 +3683,255 @@ impl Library {
 
     // ## Sorters Control ## }}}
 
+    // ## Views Control ## {{{
+
+    /// Names of all views
+    pub fn get_views(&self) -> Vec<String> {
+        self.views
+            .timed_read()
+            .as_deref()
+            .map(|v| v.iter().map(|view| view.name.clone()).collect())
+            .unwrap_or(Vec::new())
+    }
+
+    /// Index of the active view
+    pub fn view_index(&self) -> usize {
+        self.view.load(Ordering::Relaxed)
+    }
+
+    /// Find index of view by name
+    pub fn find_view(&self, name: &str) -> Option<usize> {
+        self.get_views().iter().position(|v| v == name)
+    }
+
+    /// Store the active filters + sorters then load the view at `index`
+    pub fn switch_view(&self, index: usize) {
+        let Ok(mut views) = self.views.timed_write() else { return };
+        let current = self.view_index();
+        if index >= views.len() || index == current {
+            return;
+        }
+        if let Some(view) = views.get_mut(current) {
+            view.filters = self.get_filters();
+            view.sorters = self.get_sorters();
+        }
+        self.view.store(index, Ordering::Relaxed);
+        let View { filters, sorters, .. } = views[index].clone();
+        drop(views);
+
+        self.set_sorters(sorters);
+        self.set_filters(filters);
+        self.broadcast(LibEvt::Views);
+    }
+
+    /// Create a new view as a copy of the active stacks and switch to it
+    pub fn new_view(&self, name: String) {
+        let Ok(mut views) = self.views.timed_write() else { return };
+        views.push(View {
+            name,
+            filters: self.get_filters(),
+            sorters: self.get_sorters(),
+        });
+        let index = views.len() - 1;
+        drop(views);
+        self.switch_view(index);
+    }
+
+    /// Remove the view at `index`. The last view cannot be removed
+    pub fn remove_view(&self, index: usize) {
+        let Ok(mut views) = self.views.timed_write() else { return };
+        if views.len() < 2 || index >= views.len() {
+            return;
+        }
+        let current = self.view_index();
+        if index == current {
+            // load a neighbor first so the removed stacks don't linger
+            let next = if index == 0 { 1 } else { index - 1 };
+            drop(views);
+            self.switch_view(next);
+            let Ok(mut views) = self.views.timed_write() else { return };
+            views.remove(index);
+            if next > index {
+                self.view.store(next - 1, Ordering::Relaxed)
+            }
+        } else {
+            views.remove(index);
+            if current > index {
+                self.view.store(current - 1, Ordering::Relaxed)
+            }
+        }
+        self.broadcast(LibEvt::Views);
+    }
+
+    /// Rename the view at `index`
+    pub fn rename_view(&self, index: usize, name: String) {
+        if let Ok(mut views) = self.views.timed_write() {
+            if let Some(view) = views.get_mut(index) {
+                view.name = name;
+                self.broadcast(LibEvt::Views);
+            }
+        }
+    }
+
+    /// Name of the view holding loved tracks
+    pub fn favorites_get(&self) -> String {
+        self.favorites.timed_read().as_deref().cloned().unwrap_or_default()
+    }
+
+    /// Keep loved tracks in the view called NAME from now on
+    pub fn favorites_set(&self, name: String) {
+        if let Ok(mut favorites) = self.favorites.timed_write() {
+            *favorites = name
+        }
+        self.favorites_sync();
+    }
+
+    /// Command run before each track starts
+    pub fn announcer_get(&self) -> Option<Announcer> {
+        self.announcer.timed_read().ok().and_then(|a| a.clone())
+    }
+
+    /// Run ANNOUNCER before each track from now on, or stop announcing with None
+    pub fn announcer_set(&self, announcer: Option<Announcer>) {
+        if let Ok(mut guard) = self.announcer.timed_write() {
+            *guard = announcer
+        }
+    }
+
+    /// Command tagging files from its JSON output, if set
+    #[cfg(feature = "analyzer")]
+    pub fn analyzer_get(&self) -> Option<Analyzer> {
+        self.analyzer.timed_read().ok().and_then(|a| a.clone())
+    }
+
+    /// Run ANALYZER on every loaded and appended file it hasn't tagged yet, or stop analyzing with None
+    #[cfg(feature = "analyzer")]
+    pub fn analyzer_set(&self, analyzer: Option<Analyzer>) {
+        let enabled = analyzer.is_some();
+        if let Ok(mut guard) = self.analyzer.timed_write() {
+            *guard = analyzer
+        }
+        if enabled {
+            self.analyze_queue(self.get_tracks().iter().map(|t| t.as_ref()))
+        }
+    }
+
+    /// Queue the local files among TRACKS without analyzer results, if there's an analyzer
+    #[cfg(feature = "analyzer")]
+    fn analyze_queue<'a>(&self, tracks: impl Iterator<Item = &'a Track>) {
+        if self.analyzer_get().is_none() {
+            return;
+        }
+        let Ok(stats) = self.stats.timed_read() else { return };
+        let paths: Vec<PathBuf> = tracks
+            .filter(|t| !t.is_remote() && stats.get(t.path()).is_none_or(|s| s.analyzed.is_none()))
+            .map(|t| t.path().to_path_buf())
+            .collect();
+        if !paths.is_empty() {
+            let _ = self.analyze_s.send(paths);
+        }
+    }
+
+    /// Run the analyzer on each of PATHS still without results, storing them and tagging the loaded tracks in batches
+    #[cfg(feature = "analyzer")]
+    fn analyze(&self, paths: Vec<PathBuf>) {
+        let now = Instant::now();
+        let mut results = Vec::new();
+        let mut count = 0;
+        for path in paths {
+            // turned off partway
+            let Some(analyzer) = self.analyzer_get() else { break };
+            if self.stats.timed_read().is_ok_and(|s| s.get(&path).is_some_and(|s| s.analyzed.is_some())) {
+                continue;
+            }
+            match analyzer.analyze(&path) {
+                Ok(tags) => results.push((path, tags)),
+                Err(e) => {
+                    error!("Could not analyze {}: {}", path.display(), e);
+                    continue;
+                }
+            };
+            count += 1;
+            if results.len() >= ANALYZED_BATCH {
+                self.analyzed(std::mem::take(&mut results))
+            }
+        }
+        if !results.is_empty() {
+            self.analyzed(results)
+        }
+        bench!("Analyzed {} tracks in {:?}", count, now.elapsed());
+    }
+
+    /// Store analyzer RESULTS in one write and swap in their loaded tracks with the new tags
+    #[cfg(feature = "analyzer")]
+    fn analyzed(&self, results: Vec<(PathBuf, BTreeMap<String, String>)>) {
+        let Ok(mut stats) = self.stats.timed_write() else { return };
+        let paths: HashSet<PathBuf> = results.iter().map(|(path, _)| path.clone()).collect();
+        stats.update_all(results, |s, tags| s.analyzed = Some(tags));
+        let tagged: Vec<Arc<Track>> = self
+            .tracks
+            .timed_read()
+            .map(|tracks| {
+                tracks
+                    .iter()
+                    .filter(|t| paths.contains(t.path()))
+                    .map(|t| {
+                        let mut track = (**t).clone();
+                        stats.tag(&mut track);
+                        Arc::new(track)
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+        drop(stats);
+        if !tagged.is_empty() {
+            self.swap_tracks(&tagged);
+            self.force_build_filters();
+            self.broadcast(LibEvt::Tracks);
+        }
+    }
+
+    /// Point the favorites view's leading `path` filter at the loved tracks,
+    /// creating the view behind the others if there's anything loved and it's missing
+    fn favorites_sync(&self) {
+        let loved = self.stats.timed_read().map(|stats| stats.loved()).unwrap_or_default();
+        let filter = Filter {
+            tag: String::from("path"),
+            items: loved.iter().filter_map(|p| p.to_str()).map(String::from).collect(),
+            op: FilterOp::Eq,
+        };
+        let with_loved = |mut filters: Vec<Filter>| {
+            match filters.first_mut() {
+                Some(first) if first.tag == filter.tag => *first = filter.clone(),
+                _ => filters.insert(0, filter.clone()),
+            }
+            filters
+        };
+
+        let name = self.favorites_get();
+        let Ok(mut views) = self.views.timed_write() else { return };
+        match views.iter().position(|v| v.name == name) {
+            // the active view's entry is stale until switched away from
+            Some(index) if index == self.view_index() => {
+                drop(views);
+                self.set_filters(with_loved(self.get_filters()));
+            }
+            Some(index) => views[index].filters = with_loved(views[index].filters.clone()),
+            None if !loved.is_empty() => {
+                views.push(View {
+                    name,
+                    filters: vec![filter.clone()],
+                    sorters: self.get_sorters(),
+                });
+                drop(views);
+                self.broadcast(LibEvt::Views);
+            }
+            None => (),
+        }
+    }
+
+    // ## Views Control ## }}}
+
     // ## Tracklist Control ## {{{
 
     /// Get cloned references to all tracks
@@ -968,23 +3939,33 @@ impl Library {
         self.tracks.timed_read().as_deref().cloned().unwrap_or(Vec::new())
     }
 
-    /// Get cloned references to last non-empty FilteredTracks
+    /// Get cloned references to last non-empty FilteredTracks, less any consumed tracks
     pub fn get_queue(&self) -> Vec<Arc<Track>> {
         let Ok(tguard) = self.tracks.timed_read() else {
             return Vec::new();
         };
-        let mut ptr: &Vec<Arc<Track>> = &tguard;
         let Ok(fguard) = self.filtered_tree.timed_read() else {
             return Vec::new();
         };
-        let tree = &fguard;
-        for ft in tree.iter().rev() {
-            if !ft.tracks.is_empty() {
-                ptr = &ft.tracks;
-                break;
-            }
+        let queue = queue_layer(&tguard, &fguard);
+        match self.consumed.timed_read() {
+            Ok(consumed) if !consumed.is_empty() => queue.iter().filter(|t| !consumed.contains(t.path())).cloned().collect(),
+            _ => queue.clone(),
+        }
+    }
+
+    /// Track count and total duration of [`Self::get_queue`]
+    pub fn queue_info(&self) -> QueueInfo {
+        if let Ok(Some(info)) = self.queue_info.timed_read().as_deref() {
+            return *info;
         }
-        ptr.clone()
+        let mut info = QueueInfo::new(&self.get_queue());
+        let quarantined = self.get_quarantined();
+        if !quarantined.is_empty() {
+            info.quarantined = self.get_queue().iter().filter(|t| quarantined.binary_search(t.path()).is_ok()).count();
+        }
+        let _ = self.queue_info.timed_write().map(|mut w| *w = Some(info));
+        info
     }
 
     /// Fetch all tags from filtered queue. Will map 1:1 with get_queue()
@@ -994,3 +3975,97 @@ impl Library {
 
     // ## Tracklist Control ## }}}
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Silent WAV at PATH with an ID3 tag in front, which is read like any other file's
+    fn tagged_wav(path: &Path, artist: &str, title: &str) {
+        let frame = |id: &[u8], text: &str| [id, &(text.len() as u32 + 1).to_be_bytes(), &[0, 0, 3], text.as_bytes()].concat();
+        let tag = [frame(b"TPE1", artist), frame(b"TIT2", title)].concat();
+        let size = tag.len() as u32;
+        let samples = [0u8; 1600];
+        let file = [
+            b"ID3\x03\x00\x00".as_slice(),
+            &[21, 14, 7, 0].map(|shift| (size >> shift) as u8 & 0x7f),
+            &tag,
+            b"RIFF",
+            &(36 + samples.len() as u32).to_le_bytes(),
+            b"WAVEfmt ",
+            &16u32.to_le_bytes(),
+            // PCM, mono, 8kHz, 2 bytes a frame, 16 bit
+            &[1, 0, 1, 0],
+            &8000u32.to_le_bytes(),
+            &16000u32.to_le_bytes(),
+            &[2, 0, 16, 0],
+            b"data",
+            &(samples.len() as u32).to_le_bytes(),
+            &samples,
+        ]
+        .concat();
+        std::fs::write(path, file).unwrap()
+    }
+
+    /// Poll F until it holds or a few seconds pass, for the background sort
+    fn eventually(f: impl Fn() -> bool) -> bool {
+        let start = Instant::now();
+        while start.elapsed() < Duration::from_secs(5) {
+            if f() {
+                return true;
+            }
+            sleep(Duration::from_millis(20))
+        }
+        false
+    }
+
+    #[test]
+    fn relocate_keeps_sort() {
+        let dir = std::env::temp_dir().join(format!("ompl-relocate-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(dir.join("album")).unwrap();
+        std::fs::create_dir_all(dir.join("renamed")).unwrap();
+        // path order isn't artist order
+        for (file, artist) in [("a", "Max"), ("b", "Zed"), ("c", "Amy")] {
+            tagged_wav(&dir.join("album").join(format!("{}.wav", file)), artist, "Song")
+        }
+
+        let library = Library::new(Backend::Default, None, None).unwrap();
+        library.set_sorters(vec![String::from("artist")]);
+        library.append_library(&dir);
+        let artists = || library.get_tracks().iter().map(|t| t.tagstring("artist")).collect::<Vec<String>>();
+        assert!(eventually(|| artists() == ["Amy", "Max", "Zed"]), "{:?}", artists());
+
+        std::fs::rename(dir.join("album/a.wav"), dir.join("renamed/a.wav")).unwrap();
+        let moved = library.get_tracks().into_iter().find(|t| t.tagstring("artist") == "Max").unwrap();
+        library.play_track(Some(moved));
+        let relocated = || library.get_tracks().iter().any(|t| t.path().ends_with("renamed/a.wav"));
+        assert!(eventually(|| relocated() && artists() == ["Amy", "Max", "Zed"]), "{:?}", artists());
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn organize_keeps_sort() {
+        let dir = std::env::temp_dir().join(format!("ompl-organize-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        for (file, artist, title) in [("a", "Max", "Zero"), ("b", "Zed", "One"), ("c", "Amy", "Two")] {
+            tagged_wav(&dir.join(format!("{}.wav", file)), artist, title)
+        }
+
+        let library = Library::new(Backend::Default, None, None).unwrap();
+        library.set_sorters(vec![String::from("artist")]);
+        library.append_library(&dir);
+        let artists = || library.get_tracks().iter().map(|t| t.tagstring("artist")).collect::<Vec<String>>();
+        assert!(eventually(|| artists() == ["Amy", "Max", "Zed"]), "{:?}", artists());
+
+        // renamed by title, so path order changes again
+        let report = library.organize("<title>", true, true);
+        assert_eq!(report.len(), 3, "{:?}", report);
+        assert!(dir.join("Zero.wav").exists());
+        assert!(eventually(|| artists() == ["Amy", "Max", "Zed"]), "{:?}", artists());
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -1,5 +1,4 @@
 #![warn(missing_docs)]
-use std::fs::File;
 use std::io::BufReader;
 use std::sync::mpsc::sync_channel;
 use std::sync::mpsc::{Receiver, SyncSender};
@@ -98,6 +97,13 @@ impl Player for Backend {
         self.track.read().unwrap().as_ref().cloned()
     }
 
+    fn track_retag(&self, track: Arc<Track>) {
+        let mut guard = self.track.write().unwrap();
+        if guard.as_ref().is_some_and(|t| t.path() == track.path()) {
+            *guard = Some(track)
+        }
+    }
+
     fn track_set(&self, mut track: Option<Arc<Track>>) -> Option<Arc<Track>> {
         let guard: &mut Option<Arc<Track>> = &mut self.track.write().unwrap();
         std::mem::swap(guard, &mut track);
@@ -154,7 +160,7 @@ impl Player for Backend {
                 .unwrap()
                 .as_ref()
                 .unwrap()
-                .play_once(BufReader::new(File::open(track.path()).unwrap()))
+                .play_once(BufReader::new(track.open().unwrap()))
             {
                 Ok(sink) => {
                     sink.set_volume(*self.volume_retained.read().unwrap() * track.gain());
@@ -0,0 +1,108 @@
+#![warn(missing_docs)]
+
+//! Following PulseAudio and PipeWire's lead when a call takes the audio.
+//! Talks to the server through `pactl`, which pipewire-pulse provides too,
+//! and treats any phone or communication stream as a request to cork, like module-role-cork does.
+
+use std::io::{BufRead, BufReader};
+use std::process::{Command, Stdio};
+
+/// Stream roles a call app plays under
+const ROLES: [&str; 2] = ["phone", "communication"];
+
+/// Whether any stream in `pactl list sink-inputs` output LIST is a call, leaving out process PID's own
+fn calling(list: &str, pid: u32) -> bool {
+    list.split("Sink Input #").skip(1).any(|input| {
+        let property = |key: &str| {
+            input.lines().find_map(|line| {
+                let (k, v) = line.trim().split_once(" = ")?;
+                (k == key).then(|| v.trim_matches('"').to_string())
+            })
+        };
+        property("application.process.id").is_none_or(|p| p != pid.to_string())
+            && property("media.role").is_some_and(|role| ROLES.contains(&role.to_ascii_lowercase().as_str()))
+    })
+}
+
+fn pactl() -> Command {
+    let mut command = Command::new("pactl");
+    // the list is parsed, so no translated labels
+    command.env("LC_ALL", "C").stdin(Stdio::null()).stderr(Stdio::null());
+    command
+}
+
+/// Call F with whether ompl should be corked each time that changes, starting with the current state.
+/// Runs until F returns false or the audio server goes away
+pub fn watch(mut f: impl FnMut(bool) -> bool) -> Result<(), String> {
+    let pid = std::process::id();
+    let check = || -> Result<bool, String> {
+        let output = pactl()
+            .args(["list", "sink-inputs"])
+            .output()
+            .map_err(|e| format!("Could not run pactl: {}", e))?;
+        Ok(calling(&String::from_utf8_lossy(&output.stdout), pid))
+    };
+
+    let mut corked = check()?;
+    let mut child = pactl()
+        .arg("subscribe")
+        .stdout(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("Could not run pactl: {}", e))?;
+    let stdout = child.stdout.take().ok_or("pactl has no output")?;
+
+    let mut result = Ok(());
+    if f(corked) {
+        for line in BufReader::new(stdout).lines() {
+            let Ok(line) = line else { break };
+            // only streams coming and going can change it
+            if !line.contains("sink-input") {
+                continue;
+            }
+            match check() {
+                Ok(now) if now == corked => continue,
+                Ok(now) => corked = now,
+                Err(e) => {
+                    result = Err(e);
+                    break;
+                }
+            }
+            if !f(corked) {
+                break;
+            }
+        }
+    }
+    let _ = child.kill();
+    let _ = child.wait();
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::calling;
+
+    const LIST: &str = "Sink Input #41
+\tDriver: protocol-native.c
+\tCorked: no
+\tProperties:
+\t\tapplication.name = \"ompl\"
+\t\tapplication.process.id = \"100\"
+
+Sink Input #42
+\tDriver: protocol-native.c
+\tCorked: no
+\tProperties:
+\t\tmedia.role = \"phone\"
+\t\tapplication.process.id = \"200\"
+";
+
+    #[test]
+    fn calls() {
+        assert!(calling(LIST, 100));
+        // our own stream never corks us
+        assert!(!calling(LIST, 200));
+        assert!(!calling(&LIST.replace("phone", "music"), 100));
+        assert!(calling(&LIST.replace("phone", "Communication"), 100));
+        assert!(!calling("", 100));
+    }
+}
@@ -11,6 +11,13 @@ mod brodio;
 #[cfg(feature = "backend-sympal")]
 mod sympal;
 
+#[cfg(feature = "backend-sympal")]
+mod sink;
+
+#[cfg(all(feature = "cork", target_os = "linux"))]
+pub mod cork;
+
+use std::path::PathBuf;
 use std::sync::mpsc::SyncSender;
 use std::sync::Arc;
 use std::time::Duration;
@@ -20,6 +27,7 @@ use serde::{Deserialize, Serialize};
 
 use crate::library::Track;
 
+/// Audio output implementation
 #[derive(Debug, PartialEq, Eq, Clone, Copy, Serialize, Deserialize, ValueEnum)]
 pub enum Backend {
     /// Pick from enabled backends, prioritizing Rodio on Windows, otherwise Sympal
@@ -30,11 +38,19 @@ pub enum Backend {
     /// Safe backend with maximum compatibility and lower memory usage
     #[cfg(feature = "backend-rodio")]
     Rodio,
+    /// Sympal sending PCM to a Snapcast server or other network sink set by --sink
+    #[cfg(feature = "backend-sympal")]
+    Snapcast,
 }
 
 /// Panics if no backends are enabled at compile time.
-pub fn backend(backend: Backend, buffer: Option<u32>, signal: SyncSender<PlayerMessage>) -> Box<dyn Player> {
+/// SINK is only used by network backends, falling back to a local Snapcast server
+pub fn backend(backend: Backend, buffer: Option<u32>, sink: Option<String>, signal: SyncSender<PlayerMessage>) -> Box<dyn Player> {
     // {{{
+    #[cfg(not(feature = "backend-sympal"))]
+    let _ = sink;
+    #[cfg(not(any(feature = "backend-sympal", feature = "backend-rodio")))]
+    let _ = (buffer, signal);
     #[allow(unreachable_code)]
     match backend {
         Backend::Default => {
@@ -48,6 +64,8 @@ pub fn backend(backend: Backend, buffer: Option<u32>, signal: SyncSender<PlayerM
         Backend::Sympal => Box::new(sympal::Backend::new(buffer, signal)),
         #[cfg(feature = "backend-rodio")]
         Backend::Rodio => Box::new(brodio::Backend::new(buffer, signal)),
+        #[cfg(feature = "backend-sympal")]
+        Backend::Snapcast => Box::new(sympal::Backend::new(buffer, signal).with_sink(sink.unwrap_or(sink::DEFAULT_SINK.to_string()))),
     }
     // }}}
 }
@@ -63,8 +81,161 @@ pub enum PlayerMessage {
     Clock,
     /// Other non-fatal error
     Error(String),
+    /// The track at the path couldn't be decoded, with why
+    DecodeError(PathBuf, String),
+    /// The device being played to went away or the system switched outputs, with which.
+    /// Playback has been paused where it was
+    RouteChange(String),
+}
+
+/// What's done when the output device is unplugged or the system switches outputs mid-track
+#[derive(Debug, Default, PartialEq, Eq, Clone, Copy, Serialize, Deserialize, ValueEnum)]
+pub enum RouteChange {
+    /// Stay paused, so unplugging headphones doesn't carry on out of the speakers
+    #[default]
+    Pause,
+    /// Carry on from the same spot on whatever the new default output is
+    Follow,
+}
+
+/// An extra device that can play alongside the default output
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Output {
+    /// Device name as reported by the audio host
+    pub name: String,
+    /// Whether it's currently receiving audio
+    pub enabled: bool,
+    /// Multiplier on top of the player volume, 1.0 == unchanged
+    pub volume: f32,
+}
+
+/// How sympal converts tracks to the device's sample rate
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Resampler {
+    /// Straight line between neighboring samples. Cheap, but dulls the highs and lets aliasing through
+    #[default]
+    Linear,
+    /// Hann windowed sinc reaching this many samples to either side. Sharper the more taps, and slower
+    Sinc(usize),
+}
+
+impl Resampler {
+    /// Taps used by plain `sinc`
+    pub const SINC_TAPS: usize = 16;
+    /// Most taps allowed, past which a slow machine can't keep up
+    pub const SINC_TAPS_MAX: usize = 64;
+}
+
+impl std::str::FromStr for Resampler {
+    type Err = String;
+    /// `linear`, `sinc`, or `sinc:TAPS`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let lower = s.trim().to_ascii_lowercase();
+        match lower.split_once(':') {
+            None if lower == "linear" => Ok(Self::Linear),
+            None if lower == "sinc" => Ok(Self::Sinc(Self::SINC_TAPS)),
+            Some(("sinc", taps)) => match taps.parse() {
+                Ok(taps) if (1..=Self::SINC_TAPS_MAX).contains(&taps) => Ok(Self::Sinc(taps)),
+                _ => Err(format!("sinc taps must be 1 to {}", Self::SINC_TAPS_MAX)),
+            },
+            _ => Err(format!("unknown resampler \"{}\", expected linear, sinc, or sinc:TAPS", s)),
+        }
+    }
 }
 
+impl std::fmt::Display for Resampler {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Linear => write!(f, "linear"),
+            Self::Sinc(taps) => write!(f, "sinc:{}", taps),
+        }
+    }
+}
+
+/// What's decoded and what the device plays it as, from [`Player::format`]
+#[derive(Clone, Debug, PartialEq)]
+pub struct Format {
+    /// Sample rate and channels of the track
+    pub track: (u32, usize),
+    /// Sample rate, channels, and sample format of the output device, once one's been opened
+    pub device: Option<(u32, u32, String)>,
+    /// Used when the rates differ
+    pub resampler: Resampler,
+}
+
+impl std::fmt::Display for Format {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "track: {}hz {}ch", self.track.0, self.track.1)?;
+        match &self.device {
+            Some((rate, channels, format)) => {
+                writeln!(f, "device: {}hz {}ch {}", rate, channels, format)?;
+                match *rate == self.track.0 {
+                    true => write!(f, "resampler: none ({})", self.resampler),
+                    false => write!(f, "resampler: {}", self.resampler),
+                }
+            }
+            None => write!(f, "device: none\nresampler: {}", self.resampler),
+        }
+    }
+}
+
+/// How close the current track comes to clipping, from [`Player::headroom`]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Headroom {
+    /// Loudest sample decoded so far, 1.0 == full scale
+    pub peak: f32,
+    /// Volume and replaygain multiplier it's played at
+    pub amplitude: f32,
+    /// Samples the limiter kept from clipping this track
+    pub limited: u64,
+    /// Samples the limiter kept from clipping since startup
+    pub limited_total: u64,
+}
+
+impl Headroom {
+    /// dB left before the peak clips, negative when the limiter has to step in
+    pub fn db(&self) -> f32 {
+        -20.0 * (self.peak * self.amplitude).log10()
+    }
+}
+
+impl std::fmt::Display for Headroom {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "peak: {:.1} dBFS", 20.0 * self.peak.log10())?;
+        writeln!(f, "gain: {:+.1} dB", 20.0 * self.amplitude.log10())?;
+        writeln!(f, "headroom: {:.1} dB", self.db())?;
+        write!(f, "limited: {} samples this track, {} total", self.limited, self.limited_total)
+    }
+}
+
+/// What a player skips, as silence or as fixed lengths at either end
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Silence {
+    /// Skip silence at the start and end of tracks
+    pub trim: bool,
+    /// Move on to the next track at the first silence at least this long, for hidden tracks
+    pub split: Option<Duration>,
+    /// Level in dBFS at or below which audio counts as silent
+    pub threshold: f32,
+    /// Start every track this far in, for podcast ads and long intros
+    pub intro: Duration,
+    /// Move on this long before every track ends, for long fade outs
+    pub outro: Duration,
+}
+
+impl Default for Silence {
+    fn default() -> Self {
+        Self {
+            trim: false,
+            split: None,
+            threshold: -60.0,
+            intro: Duration::ZERO,
+            outro: Duration::ZERO,
+        }
+    }
+}
+
+/// Common interface for audio backends
 pub trait Player: Send + Sync {
     // ### REQUIRED FNS {{{
 
@@ -87,6 +258,12 @@ pub trait Player: Send + Sync {
     /// Returns current and total time
     fn times(&self) -> Option<(Duration, Duration)>;
 
+    /// How far into the track is decoded and can be seeked within before seekable() turns true.
+    /// None for backends that don't decode ahead
+    fn decoded(&self) -> Option<Duration> {
+        None
+    }
+
     /// Seek to specified time
     fn seek(&self, time: Duration);
 
@@ -103,6 +280,9 @@ pub trait Player: Send + Sync {
     fn track_set(&self, track: Option<Arc<Track>>) -> Option<Arc<Track>>;
     /// Get currently set track.
     fn track_get(&self) -> Option<Arc<Track>>;
+    /// Swap in TRACK if it's the same file as the set track, as after its tags were reloaded.
+    /// Should not touch playback.
+    fn track_retag(&self, track: Arc<Track>);
 
     /// Play currently set track.
     fn play(&self);
@@ -120,11 +300,19 @@ pub trait Player: Send + Sync {
 
     // ### PROVIDED FNS ### {{{
 
+    /// Add onto the current volume, clamped to 0.0 -> 1.0
     fn volume_add(&self, amount: f32) {
         let current = self.volume_get();
         self.volume_set(current + amount)
     }
 
+    /// Stop playback for good, waiting on anything still streaming or decoding
+    /// so nothing's left running as the process exits
+    fn shutdown(&self) -> Result<(), String> {
+        self.stop();
+        Ok(())
+    }
+
     /// Set new track && play immediately.
     /// Returns old track.
     fn play_track(&self, track: Option<Arc<Track>>) -> Option<Arc<Track>> {
@@ -149,12 +337,68 @@ pub trait Player: Send + Sync {
 
     /// Advance seek by seconds, positive or negative
     fn seek_by(&self, secs: f32) {
-        if let Some(true) = self.seekable() {
+        if self.seekable() == Some(true) || self.decoded().is_some() {
             if let Some((current, total)) = self.times() {
                 self.seek(Duration::from_secs_f32((current.as_secs_f32() + secs).max(0.0)).min(total))
             }
         }
     }
 
+    /// A-B loop points of the current track. A of None loops from the start
+    fn ab_loop_get(&self) -> (Option<Duration>, Option<Duration>) {
+        (None, None)
+    }
+
+    /// Jump back to A whenever playback reaches B. Clearing B stops looping.
+    /// Points are dropped when the track changes
+    fn ab_loop_set(&self, _a: Option<Duration>, _b: Option<Duration>) -> Result<(), String> {
+        Err("This backend can't loop".to_string())
+    }
+
+    /// Set what counts as silence and how it's skipped, starting with the next track
+    fn silence_set(&self, _silence: Silence) {}
+
+    /// What's currently skipped. None if the backend can't skip anything
+    fn silence_get(&self) -> Option<Silence> {
+        None
+    }
+
+    /// Pick how tracks are converted to the device rate, from the next time playback starts
+    fn resampler_set(&self, _resampler: Resampler) -> Result<(), String> {
+        Err("This backend picks its own resampler".to_string())
+    }
+
+    /// Sample format of the current track and the device, None if nothing's decoded or the backend doesn't say
+    fn format(&self) -> Option<Format> {
+        None
+    }
+
+    /// Tempo of the current track measured from its decoded audio.
+    /// None until it's fully decoded, or if the backend doesn't decode it itself
+    fn bpm(&self) -> Option<f32> {
+        None
+    }
+
+    /// Peak and limiter counts for the current track. None if the backend doesn't limit
+    fn headroom(&self) -> Option<Headroom> {
+        None
+    }
+
+    /// Devices other than the default that can be played to.
+    /// None means player as a whole only plays to the default device.
+    fn outputs(&self) -> Option<Vec<Output>> {
+        None
+    }
+
+    /// Start or stop also playing to output NAME
+    fn output_enable(&self, _name: &str, _enabled: bool) -> Result<(), String> {
+        Err("This backend only supports the default output".to_string())
+    }
+
+    /// Set the volume of enabled output NAME. Multiplier, 1.0 == unchanged
+    fn output_volume_set(&self, _name: &str, _volume: f32) -> Result<(), String> {
+        Err("This backend only supports the default output".to_string())
+    }
+
     // ### PROVIDED FNS ### }}}
 }
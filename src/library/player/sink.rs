@@ -0,0 +1,78 @@
+#![warn(missing_docs)]
+
+use std::error::Error;
+use std::io::Write;
+use std::net::{TcpStream, ToSocketAddrs, UdpSocket};
+use std::time::{Duration, Instant};
+
+/// Where the Snapcast backend sends its PCM.
+/// Snapcast's TCP source listens on 4953 by default
+pub const DEFAULT_SINK: &str = "localhost:4953";
+
+/// Sample rate of everything sent to a sink. Matches Snapcast's default `sampleformat` of 48000:16:2
+pub const SINK_RATE: u32 = 48000;
+/// Channels of everything sent to a sink
+pub const SINK_CHANNELS: usize = 2;
+/// How far ahead of real time audio is sent, so pause and seek stay responsive
+const LEAD: Duration = Duration::from_millis(200);
+
+enum Socket {
+    /// Default, for Snapcast's `tcp://` source
+    Tcp(TcpStream),
+    /// Best effort, for sinks that would rather drop packets than fall behind
+    Udp(UdpSocket),
+}
+
+/// Raw little-endian 16 bit PCM over the network, paced to real time.
+/// The pace carries across tracks so short ones don't get sent in a burst
+pub struct Connection {
+    socket: Socket,
+    started: Instant,
+    /// Audio sent since `started`
+    sent: Duration,
+}
+
+impl Connection {
+    /// Connect to SINK as `host:port`, optionally prefixed with `tcp://` or `udp://`
+    pub fn open(sink: &str) -> Result<Self, Box<dyn Error>> {
+        let (protocol, address) = sink.split_once("://").unwrap_or(("tcp", sink));
+        let address = address.to_socket_addrs()?.next().ok_or(format!("Could not resolve sink '{}'", address))?;
+        let socket = match protocol {
+            "tcp" => {
+                let stream = TcpStream::connect(address)?;
+                stream.set_nodelay(true)?;
+                Socket::Tcp(stream)
+            }
+            "udp" => {
+                let socket = UdpSocket::bind(if address.is_ipv4() { "0.0.0.0:0" } else { "[::]:0" })?;
+                socket.connect(address)?;
+                Socket::Udp(socket)
+            }
+            _ => return Err(format!("Unknown sink protocol '{}'. Use tcp:// or udp://", protocol).into()),
+        };
+        Ok(Self {
+            socket,
+            started: Instant::now(),
+            sent: Duration::ZERO,
+        })
+    }
+
+    /// Whether another chunk can go out without getting too far ahead
+    pub fn ready(&mut self) -> bool {
+        let elapsed = self.started.elapsed();
+        // fell behind from pausing or a slow sink, so pick up from now instead of catching up
+        self.sent = self.sent.max(elapsed);
+        self.sent - elapsed <= LEAD
+    }
+
+    /// Send interleaved stereo SAMPLES as one chunk
+    pub fn send(&mut self, samples: &[i16]) -> std::io::Result<()> {
+        let bytes: Vec<u8> = samples.iter().flat_map(|s| s.to_le_bytes()).collect();
+        match &mut self.socket {
+            Socket::Tcp(stream) => stream.write_all(&bytes)?,
+            Socket::Udp(socket) => socket.send(&bytes).map(|_| ())?,
+        }
+        self.sent += Duration::from_secs_f64(samples.len() as f64 / (SINK_RATE as usize * SINK_CHANNELS) as f64);
+        Ok(())
+    }
+}
@@ -1,15 +1,19 @@
 #![warn(missing_docs)]
 
-use super::{Player, PlayerMessage};
+use super::sink::{Connection, SINK_CHANNELS, SINK_RATE};
+use super::{Format, Headroom, Output, Player, PlayerMessage, Resampler, Silence};
+use crate::library::bpm;
+use crate::library::supervisor::panic_message;
 use crate::library::Track;
 use crate::logging::*;
-use crate::try_block;
 
+use std::collections::VecDeque;
 use std::error::Error;
-use std::fs::File;
+use std::f64::consts::PI;
 use std::mem::{swap, transmute};
 use std::ops::Deref;
-use std::sync::atomic::{AtomicBool, AtomicU32, AtomicU8, AtomicUsize, Ordering};
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::sync::atomic::{AtomicBool, AtomicU16, AtomicU32, AtomicU64, AtomicU8, AtomicUsize, Ordering};
 use std::sync::mpsc::SyncSender;
 use std::sync::{Arc, Mutex, RwLock};
 use std::thread;
@@ -19,7 +23,8 @@ use cpal::{
     traits::{DeviceTrait, HostTrait, StreamTrait},
     SampleFormat, SampleRate,
 };
-use dasp::{Sample, Signal};
+use dasp::interpolate::{linear::Linear, Interpolator};
+use dasp::Sample;
 
 use symphonia::core::{
     audio::SampleBuffer,
@@ -79,32 +84,348 @@ pub struct Backend {
     // TODO: dynamic typing
     samples: Arc<RwLock<Vec<i16>>>,
     pos: Arc<AtomicUsize>,
+    /// Length of the track in frames as reported by the container, 0 if unknown
+    frames: Arc<AtomicUsize>,
     rate: Arc<AtomicU32>,
     channels: Arc<AtomicUsize>,
     device_rate: Arc<AtomicU32>,
     device_channels: Arc<AtomicU32>,
     device_format: Arc<AtomicU8>,
     buffer: Option<u32>,
+    outputs: Mutex<Vec<Secondary>>,
+    /// Network address to stream to instead of the default device
+    sink: Option<String>,
+    /// Kept between tracks so the sink doesn't see a reconnect each time
+    connection: Arc<Mutex<Option<Connection>>>,
+    /// A-B loop points as sample positions, usize::MAX when unset
+    ab_loop: Arc<[AtomicUsize; 2]>,
+    silence: Mutex<Silence>,
+    /// Sample positions playback starts and ends at once silence is found. [0, usize::MAX] plays everything
+    audible: Arc<[AtomicUsize; 2]>,
+    /// Loudest decoded sample of the track, as its distance from 0
+    peak: Arc<AtomicU16>,
+    /// Samples kept from clipping by [`limit`] this track and since startup
+    limited: Arc<[AtomicU64; 2]>,
+    /// Gain of the track before this one, eased away from by [`ramp`]. 0.0 if there wasn't one
+    ramp_from: Arc<AtomicU32>,
+    /// Read each time playback starts
+    resampler: Mutex<Resampler>,
 }
 
-impl Backend {
-    fn get_device(&self) -> Result<(cpal::Device, cpal::SupportedStreamConfig), Box<dyn Error>> {
-        // {{{
-        #[cfg(feature = "jack")]
-        let requested_id = Some(cpal::HostId::Jack);
+fn host() -> Result<cpal::Host, Box<dyn Error>> {
+    #[cfg(feature = "jack")]
+    return Ok(cpal::host_from_id(cpal::HostId::Jack)?);
+
+    #[cfg(not(feature = "jack"))]
+    return Ok(cpal::default_host());
+}
+
+fn default_device_name() -> Option<String> {
+    host().ok()?.default_output_device()?.name().ok()
+}
+
+/// How often a playing stream checks whether the system's default output moved
+const ROUTE_POLL: Duration = Duration::from_secs(1);
+
+/// Where to continue from if playing START -> CUR crossed the loop's B point.
+/// Moving past B by seeking doesn't count, so the loop can be escaped
+fn loop_back(ab_loop: &[AtomicUsize; 2], start: usize, cur: usize) -> Option<usize> {
+    let b = ab_loop[1].load(Ordering::Relaxed);
+    (b != usize::MAX && start < b && cur >= b).then(|| match ab_loop[0].load(Ordering::Relaxed) {
+        usize::MAX => 0,
+        a => a,
+    })
+}
+
+/// Index of the first sample louder than THRESHOLD
+fn first_loud(samples: &[i16], threshold: i16) -> Option<usize> {
+    samples.iter().position(|s| s.unsigned_abs() > threshold.unsigned_abs())
+}
+
+/// Where audible audio ends in SAMPLES, searching from sample START.
+/// With SPLIT, ends at the first run of at least SPLIT silent frames, else with TRIM at the last loud frame
+fn audible_end(samples: &[i16], channels: usize, threshold: i16, start: usize, trim: bool, split: Option<usize>) -> usize {
+    let mut silent_from = None;
+    for (n, frame) in samples.chunks_exact(channels.max(1)).enumerate().skip(start / channels.max(1)) {
+        if frame.iter().any(|s| s.unsigned_abs() > threshold.unsigned_abs()) {
+            silent_from = None
+        } else {
+            let from = *silent_from.get_or_insert(n);
+            if split.is_some_and(|split| n + 1 - from >= split) {
+                return from * channels;
+            }
+        }
+    }
+    match (trim, silent_from) {
+        (true, Some(from)) => from * channels,
+        _ => samples.len(),
+    }
+}
+
+/// Ceiling the limiter keeps samples under, about -0.2 dBFS
+const LIMIT: f32 = 0.98;
+/// Share of the way back to full gain the limiter goes each buffer once peaks drop
+const RELEASE: f32 = 0.05;
+/// Gain changes between tracks at least this big are eased in rather than jumped to, about 3dB
+const RAMP_MIN: f32 = 1.41;
+/// Seconds a big gain change between tracks takes to ease in
+const RAMP_LENGTH: f32 = 1.5;
+
+/// GAIN at SECONDS into a track, eased in from the last track's FROM over [`RAMP_LENGTH`] if they're far apart.
+/// Eased in dB so the change sounds even the whole way
+fn ramp(from: f32, gain: f32, seconds: f32) -> f32 {
+    if from <= 0.0 || gain <= 0.0 || seconds >= RAMP_LENGTH || (gain / from).max(from / gain) < RAMP_MIN {
+        return gain;
+    }
+    from * (gain / from).powf(seconds / RAMP_LENGTH)
+}
+
+/// The part of SAMPLES from START that fills LEN device samples, for the limiter to look at before it's played
+fn window(samples: &[i16], start: usize, len: usize, rate: u32, device_rate: u32) -> &[i16] {
+    let needed = (len as u64 * rate as u64).div_ceil(device_rate.max(1) as u64) as usize + 2;
+    samples.get(start..).map(|s| &s[..needed.min(s.len())]).unwrap_or_default()
+}
+
+/// AMPLITUDE for the next buffer of WINDOW, pulled back under [`LIMIT`] if its peak would clip.
+/// REDUCTION carries between buffers, dropping at once for a peak and easing back by [`RELEASE`].
+/// Also returns how many samples would have clipped without it
+fn limit(window: &[i16], amplitude: f32, reduction: &mut f32) -> (f32, u64) {
+    let full = -(i16::MIN as f32);
+    let peak = window.iter().map(|s| s.unsigned_abs()).max().unwrap_or(0) as f32 / full * amplitude;
+    let target = if peak > 1.0 { LIMIT / peak } else { 1.0 };
+    *reduction = target.min(*reduction + (1.0 - *reduction) * RELEASE);
+    let clipped = match amplitude > 1.0 {
+        true => window.iter().filter(|s| s.unsigned_abs() as f32 * amplitude > full).count() as u64,
+        false => 0,
+    };
+    (amplitude * *reduction, clipped)
+}
+
+/// Where resampling left off, so each buffer carries on from the last instead of restarting on a whole frame
+struct Resampling {
+    resampler: Resampler,
+    /// Sample position the last buffer ended at and how far it was into the frame after
+    end: (usize, f64),
+}
+
+impl Resampling {
+    fn new(resampler: Resampler) -> Self {
+        Self { resampler, end: (0, 0.0) }
+    }
+}
+
+/// Hann windowed sinc over TAPS frames either side of the point being interpolated.
+/// Written here since dasp's own weighs its frames back to front
+struct WindowedSinc {
+    /// Oldest first, with the frame left of the point at `taps - 1`
+    frames: VecDeque<[f32; 2]>,
+    taps: usize,
+}
+
+impl WindowedSinc {
+    fn new(frames: impl IntoIterator<Item = [f32; 2]>, taps: usize) -> Self {
+        let frames: VecDeque<[f32; 2]> = frames.into_iter().collect();
+        debug_assert_eq!(frames.len(), taps * 2);
+        Self { frames, taps }
+    }
+}
+
+impl Interpolator for WindowedSinc {
+    type Frame = [f32; 2];
+
+    fn interpolate(&self, x: f64) -> [f32; 2] {
+        let width = self.taps as f64;
+        self.frames.iter().enumerate().fold([0.0; 2], |[l, r], (n, frame)| {
+            let d = n as f64 - (self.taps - 1) as f64 - x;
+            let sinc = match d == 0.0 {
+                true => 1.0,
+                false => (PI * d).sin() / (PI * d),
+            };
+            let weight = (sinc * (0.5 + 0.5 * (PI * d / width).cos())) as f32;
+            [l + frame[0] * weight, r + frame[1] * weight]
+        })
+    }
+
+    fn next_source_frame(&mut self, frame: [f32; 2]) {
+        self.frames.pop_front();
+        self.frames.push_back(frame);
+    }
+}
+
+/// Fill SINK with frames from INTERP at AMPLITUDE, stepping RATIO source frames per frame starting PHASE into one.
+/// NEXT gives each source frame as INTERP moves past it.
+/// Returns how many source frames were moved past and how far into the next the last step landed
+fn resample<T, I>(sink: &mut [T], mut interp: I, mut next: impl FnMut() -> [f32; 2], mut phase: f64, ratio: f64, amplitude: f32) -> (usize, f64)
+where
+    T: Sample + dasp::sample::FromSample<f32>,
+    I: Interpolator<Frame = [f32; 2]>,
+{
+    let mut advanced = 0;
+    for frame in sink.chunks_exact_mut(2) {
+        while phase >= 1.0 {
+            interp.next_source_frame(next());
+            phase -= 1.0;
+            advanced += 1;
+        }
+        let [l, r] = interp.interpolate(phase);
+        frame[0] = (l * amplitude).to_sample();
+        frame[1] = (r * amplitude).to_sample();
+        phase += ratio;
+    }
+    (advanced + phase.floor() as usize, phase.fract())
+}
+
+/// Write SAMPLES from START_POS into SINK at AMPLITUDE, resampling if the device rate differs.
+/// Returns the position after the last sample used
+fn fill_slice<T>(
+    sink: &mut [T],
+    samples: &[i16],
+    start_pos: usize,
+    rate: u32,
+    device_rate: u32,
+    amplitude: f32,
+    resampling: &mut Resampling,
+) -> Result<usize, Box<dyn Error>>
+where
+    T: Sample + dasp::sample::FromSample<i16> + dasp::sample::FromSample<f32>,
+    T::Float: From<f32>,
+{
+    let source = samples.get(start_pos..).ok_or("Sample pos out of bounds")?;
+    if rate == device_rate {
+        sink.iter_mut()
+            .zip(source.iter())
+            .for_each(|(sink_sample, signal_sample)| *sink_sample = signal_sample.to_sample::<T>().mul_amp(amplitude.into()));
+        return Ok(start_pos + sink.len().min(source.len()));
+    }
+
+    // stereo frames, silent before the start and past the end
+    let frame = |n: isize| -> [f32; 2] {
+        match usize::try_from(n).ok().and_then(|n| samples.get(n * 2..n * 2 + 2)) {
+            Some(&[l, r]) => [l.to_sample(), r.to_sample()],
+            _ => [0.0; 2],
+        }
+    };
+    let start = (start_pos / 2) as isize;
+    let phase = match resampling.end.0 == start_pos {
+        true => resampling.end.1,
+        false => 0.0,
+    };
+    let ratio = rate as f64 / device_rate as f64;
+    let (advanced, phase) = match resampling.resampler {
+        Resampler::Linear => {
+            let mut n = start + 2;
+            let interp = Linear::new(frame(start), frame(start + 1));
+            resample(
+                sink,
+                interp,
+                || {
+                    n += 1;
+                    frame(n - 1)
+                },
+                phase,
+                ratio,
+                amplitude,
+            )
+        }
+        Resampler::Sinc(taps) => {
+            let mut n = start + taps as isize + 1;
+            let interp = WindowedSinc::new((n - taps as isize * 2..n).map(frame), taps);
+            resample(
+                sink,
+                interp,
+                || {
+                    n += 1;
+                    frame(n - 1)
+                },
+                phase,
+                ratio,
+                amplitude,
+            )
+        }
+    };
+    let end = (start_pos + advanced * 2).min(samples.len());
+    resampling.end = (end, phase);
+    Ok(end)
+}
+
+/// [`fill_slice`] for whatever format the device asked for.
+/// None if the device's sample format isn't supported
+fn fill_buffer(
+    ring_buffer: &mut cpal::Data,
+    samples: &[i16],
+    start_pos: usize,
+    rate: u32,
+    device_rate: u32,
+    amplitude: f32,
+    resampling: &mut Resampling,
+) -> Result<Option<usize>, Box<dyn Error>> {
+    macro_rules! typed_stream {
+        ($format:ty) => {
+            fill_slice(
+                ring_buffer.as_slice_mut::<$format>().ok_or("Ring buffer has no slice")?,
+                samples,
+                start_pos,
+                rate,
+                device_rate,
+                amplitude,
+                resampling,
+            )?
+        };
+    }
+
+    Ok(Some(match ring_buffer.sample_format() {
+        SampleFormat::I8 => typed_stream!(i8),
+        SampleFormat::I16 => typed_stream!(i16),
+        SampleFormat::I32 => typed_stream!(i32),
+        SampleFormat::I64 => typed_stream!(i64),
+        SampleFormat::U8 => typed_stream!(u8),
+        SampleFormat::U16 => typed_stream!(u16),
+        SampleFormat::U32 => typed_stream!(u32),
+        SampleFormat::U64 => typed_stream!(u64),
+        SampleFormat::F32 => typed_stream!(f32),
+        SampleFormat::F64 => typed_stream!(f64),
+        // SampleFormat is non-exhaustive
+        _ => return Ok(None),
+    }))
+}
+
+/// A device enabled alongside the default output
+struct Secondary {
+    name: String,
+    volume: Arc<AtomicU32>,
+    /// Ends the current stream to this device. Replaced each time playback starts
+    stop: Arc<AtomicBool>,
+}
+
+impl Secondary {
+    fn new(name: String) -> Self {
+        Self {
+            name,
+            volume: Arc::new(AtomicU32::from(1.0f32.to_bits())),
+            stop: Arc::new(AtomicBool::new(true)),
+        }
+    }
+}
 
-        #[cfg(not(feature = "jack"))]
-        let requested_id = None;
+impl Backend {
+    /// Stream to SINK over the network instead of the default device
+    pub fn with_sink(mut self, sink: String) -> Self {
+        self.sink = Some(sink);
+        self
+    }
 
+    /// Device NAME or the default device, with a config matching the decoded track
+    fn get_device(&self, name: Option<&str>) -> Result<(cpal::Device, cpal::SupportedStreamConfig), Box<dyn Error>> {
+        // {{{
         let format = SampleFormat::I16;
         let rate = self.rate.load(Ordering::Relaxed);
         let channels = self.channels.load(Ordering::Relaxed) as u16;
 
         debug!("Sympal play acquire device");
 
-        let device = match requested_id {
-            Some(id) => cpal::host_from_id(id)?.default_output_device(),
-            None => cpal::default_host().default_output_device(),
+        let device = match name {
+            Some(name) => host()?.output_devices()?.find(|d| d.name().is_ok_and(|n| n == name)),
+            None => host()?.default_output_device(),
         }
         .ok_or("Could not find a output device.")?;
 
@@ -164,21 +485,21 @@ impl Backend {
             config.sample_rate().0,
             device.name().unwrap_or(String::from("NONE"))
         );
-        // self.device_format.store(config.sample_format(), Ordering::SeqCst);
-        self.device_rate.store(config.sample_rate().0, Ordering::SeqCst);
-        self.device_channels.store(config.channels().into(), Ordering::SeqCst);
-        self.device_format.store(config.sample_format() as u8, Ordering::SeqCst);
+        if name.is_none() {
+            // self.device_format.store(config.sample_format(), Ordering::SeqCst);
+            self.device_rate.store(config.sample_rate().0, Ordering::SeqCst);
+            self.device_channels.store(config.channels().into(), Ordering::SeqCst);
+            self.device_format.store(config.sample_format() as u8, Ordering::SeqCst);
+        }
         Ok((device, config))
     } // }}}
 
     fn play_internal<'a>(&'a self) -> Result<(), Box<dyn Error + 'a>> {
         // {{{
 
-        let gain = if let Some(track) = self.track_get() {
-            track.gain()
-        } else {
-            return Ok(());
-        };
+        // gain is read every buffer so manual offsets apply mid-track
+        let Some(track) = self.track_get() else { return Ok(()) };
+        let secondary_track = track.clone();
 
         // if already playing then just set pos to 0
         // so far no negative side-effects
@@ -203,13 +524,31 @@ impl Backend {
             "play decoder guard"
         )?;
 
+        // if play requested on last pos, reset.
+        // basically if you manage to pause it after samples[] ends,
+        // this restarts playback instead of playing nothing
+        if self.decoder_state.load(Ordering::Relaxed) == *DecoderState::Complete
+            && self.pos.load(Ordering::Relaxed) >= self.samples.read()?.len().min(self.audible[1].load(Ordering::Relaxed))
+        {
+            self.pos.store(0, Ordering::Relaxed)
+        }
+
+        if let Some(sink) = self.sink.as_ref() {
+            return self.play_sink(sink, track);
+        }
+
         let vol = self.volume.clone();
-        let (device, config) = self.get_device()?;
+        let limited = self.limited.clone();
+        let ramp_from = self.ramp_from.clone();
+        let resampler = *self.resampler.lock()?;
+        let (device, config) = self.get_device(None)?;
+        let device_name = device.name().ok();
         let join_thread = self.join_stream.clone();
         let join_data = self.join_stream.clone();
         let streaming = self.streaming.clone();
         let pos = self.pos.clone();
-        let decoder_state = self.decoder_state.clone();
+        let ab_loop = self.ab_loop.clone();
+        let audible = self.audible.clone();
         let samples = self.samples.clone();
         let channels = self.channels.load(Ordering::Relaxed) as u32;
         let rate = self.rate.load(Ordering::Relaxed);
@@ -232,116 +571,344 @@ impl Backend {
             }
         }
 
-        // if play requested on last pos, reset.
-        // basically if you manage to pause it after samples[] ends,
-        // this restarts playback instead of playing nothing
-        if self.decoder_state.load(Ordering::Relaxed) == *DecoderState::Complete && self.samples.read()?.len() == self.pos.load(Ordering::Relaxed) {
-            self.pos.store(0, Ordering::Relaxed)
-        }
-
         debug!("Sympal play spawn stream");
+        let streaming_er = streaming.clone();
+        let join_thread_er = join_thread.clone();
+        let channel_panic = self.channel.clone();
         thread::Builder::new().name(String::from("SYMPAL Audio Stream")).spawn(move || {
-            streaming.store(true, Ordering::Relaxed);
-            let stream = device.build_output_stream_raw(
-                &stream_config,
-                device_format,
-                move |ring_buffer: &mut cpal::Data, _: &cpal::OutputCallbackInfo| {
-                    let result = try_block!({
-                        let amplitude = gain * f32::from_bits(vol.load(Ordering::Relaxed)).powi(3);
-                        let mut start_pos = pos.load(Ordering::Relaxed);
-                        start_pos -= start_pos % channels as usize;
-                        let mut cur_pos = start_pos;
-                        let samples = samples.read()?;
+            // marked stopped on a panic so the next play builds a fresh stream
+            let result = catch_unwind(AssertUnwindSafe(move || {
+                streaming.store(true, Ordering::Relaxed);
+                let mut reduction = 1.0;
+                let mut resampling = Resampling::new(resampler);
+                let stream = device.build_output_stream_raw(
+                    &stream_config,
+                    device_format,
+                    move |ring_buffer: &mut cpal::Data, _: &cpal::OutputCallbackInfo| {
+                        let result = try_block!({
+                            // past leading silence
+                            let skipped = pos.load(Ordering::Relaxed) < audible[0].load(Ordering::Relaxed);
+                            let mut start_pos = pos.load(Ordering::Relaxed).max(audible[0].load(Ordering::Relaxed));
+                            start_pos -= start_pos % channels as usize;
+                            let seconds = start_pos as f32 / (channels * rate) as f32;
+                            let amplitude = ramp(f32::from_bits(ramp_from.load(Ordering::Relaxed)), track.gain(), seconds)
+                                * f32::from_bits(vol.load(Ordering::Relaxed)).powi(3);
+                            let samples = samples.read()?;
+                            let end = samples.len().min(audible[1].load(Ordering::Relaxed));
 
-                        macro_rules! typed_stream {
-                            ($format:ty) => {{
-                                let samples_iter = samples.get(start_pos..).ok_or("Sample pos out of bounds")?.iter().map(|s| {
-                                    cur_pos += 1;
-                                    s.to_sample::<$format>()
-                                });
-                                let ring_slice = ring_buffer.as_slice_mut::<$format>().ok_or("Ring buffer has no slice")?;
-                                if rate == device_rate {
-                                    ring_slice
-                                        .iter_mut()
-                                        .zip(samples_iter)
-                                        .for_each(|(sink_sample, signal_sample)| *sink_sample = signal_sample.mul_amp(amplitude.into()));
-                                } else {
-                                    let mut signal = dasp::signal::from_interleaved_samples_iter::<_, [_; 2]>(samples_iter);
-                                    let a = signal.next();
-                                    let b = signal.next();
-                                    let interp = dasp::interpolate::linear::Linear::new(a, b);
-                                    let frames = signal.from_hz_to_hz(interp, rate as f64, device_rate as f64);
-                                    for (sink_sample, signal_sample) in ring_slice.iter_mut().zip(frames.into_interleaved_samples().into_iter()) {
-                                        *sink_sample = signal_sample.mul_amp(amplitude.into());
-                                    }
-                                }
-                            }};
-                        }
+                            let (amplitude, clipped) = limit(
+                                window(&samples, start_pos, ring_buffer.len(), rate, device_rate),
+                                amplitude,
+                                &mut reduction,
+                            );
+                            for count in limited.iter() {
+                                count.fetch_add(clipped, Ordering::Relaxed);
+                            }
 
-                        match ring_buffer.sample_format() {
-                            SampleFormat::I8 => typed_stream!(i8),
-                            SampleFormat::I16 => typed_stream!(i16),
-                            SampleFormat::I32 => typed_stream!(i32),
-                            SampleFormat::I64 => typed_stream!(i64),
-                            SampleFormat::U8 => typed_stream!(u8),
-                            SampleFormat::U16 => typed_stream!(u16),
-                            SampleFormat::U32 => typed_stream!(u32),
-                            SampleFormat::U64 => typed_stream!(u64),
-                            SampleFormat::F32 => typed_stream!(f32),
-                            SampleFormat::F64 => typed_stream!(f64),
-                            // SampleFormat is non-exhaustive
-                            _ => {
+                            let Some(cur_pos) = fill_buffer(ring_buffer, &samples, start_pos, rate, device_rate, amplitude, &mut resampling)? else {
                                 join_data.store(true, Ordering::Relaxed);
                                 pos.store(0, Ordering::Relaxed);
                                 return Err(format!("Sympal unsupported stream format '{}'", ring_buffer.sample_format()).into());
+                            };
+
+                            let cur_pos = match loop_back(&ab_loop, start_pos, cur_pos) {
+                                Some(a) => {
+                                    channel_str.send(PlayerMessage::Clock)?;
+                                    a
+                                }
+                                None => cur_pos,
+                            };
+                            pos.store(cur_pos, Ordering::Relaxed);
+                            if skipped {
+                                channel_str.send(PlayerMessage::Clock)?;
+                            }
+                            if cur_pos >= end && !join_data.load(Ordering::Relaxed) {
+                                join_data.store(true, Ordering::Relaxed);
+                                channel_str.send(PlayerMessage::Request)?;
+                            }
+                            // every second, decoded or not, so elapsed time stays live for media controls
+                            if (start_pos as f32 / (rate * channels) as f32).floor() < (cur_pos as f32 / (rate * channels) as f32).floor() {
+                                channel_str.send(PlayerMessage::Clock)?;
+                            }
+                            Ok(())
+                        });
+                        match result {
+                            Ok(()) => (),
+                            Err(e) => {
+                                let _ = channel_str.send(PlayerMessage::Error(format!("Error occured while decoding the track:\n  {}", e)));
                             }
                         };
-
-                        pos.store(cur_pos, Ordering::Relaxed);
-                        if cur_pos >= samples.len() && !join_data.load(Ordering::Relaxed) {
-                            join_data.store(true, Ordering::Relaxed);
-                            channel_str.send(PlayerMessage::Request)?;
+                    },
+                    move |err| {
+                        join_err.store(true, Ordering::Relaxed);
+                        match err {
+                            // unplugged, so hold the spot for when it's back or playback moves elsewhere
+                            cpal::StreamError::DeviceNotAvailable => {
+                                let _ = channel_err.send(PlayerMessage::RouteChange("the output device went away".to_string()));
+                            }
+                            // TODO can some of these actually be handled?
+                            err => {
+                                let _ = channel_err.send(PlayerMessage::Error(format!("SYMPAL Audio Stream Error:\n{}", err)));
+                                pos_err.store(0, Ordering::Relaxed);
+                            }
+                        }
+                    },
+                    None, // None=blocking, Some(Duration)=timeout
+                );
+                if let Ok(stream) = stream {
+                    let Ok(()) = stream.play() else {
+                        let _ = channel_thread.send(PlayerMessage::Error("CPAL Audio stream could not play".into()));
+                        streaming.store(false, Ordering::Relaxed);
+                        join_thread.store(true, Ordering::Relaxed);
+                        return;
+                    };
+                    // not using wait_on! because its only purpose is
+                    // to keep the stream object in scope until its done
+                    let mut checked = Instant::now();
+                    while !join_thread.load(Ordering::Relaxed) {
+                        // Millis instead of nanos because Windows will cook on the loop somehow
+                        thread::sleep(Duration::from_millis(1));
+                        // hosts that move the default output, like headphones on a laptop, leave the old stream playing
+                        if checked.elapsed() >= ROUTE_POLL {
+                            checked = Instant::now();
+                            if let Some(default) = default_device_name().filter(|d| device_name.as_ref().is_some_and(|n| n != d)) {
+                                join_thread.store(true, Ordering::Relaxed);
+                                let _ = channel_thread.send(PlayerMessage::RouteChange(format!("output switched to '{}'", default)));
+                            }
                         }
-                        // only clock if seekable
-                        if (start_pos as f32 / (rate * channels) as f32).floor() < (cur_pos as f32 / (rate * channels) as f32).floor()
-                            && decoder_state.load(Ordering::Relaxed) == *DecoderState::Complete
-                        {
+                    }
+                    streaming.store(false, Ordering::Relaxed);
+                }
+            }));
+            if let Err(payload) = result {
+                streaming_er.store(false, Ordering::Relaxed);
+                join_thread_er.store(true, Ordering::Relaxed);
+                let _ = channel_panic.send(PlayerMessage::Error(format!(
+                    "SYMPAL Audio Stream panicked: {}",
+                    panic_message(payload.as_ref())
+                )));
+            }
+        })?;
+
+        for output in self.outputs.lock()?.iter_mut() {
+            if let Err(e) = self.play_secondary(output, secondary_track.clone()) {
+                let _ = self
+                    .channel
+                    .send(PlayerMessage::Error(format!("Could not play to output '{}':\n  {}", output.name, e)));
+            }
+        }
+
+        debug!("Sympal play end");
+        Ok(())
+    }
+    // }}}
+
+    /// Stream to the network SINK in place of a device, paced to real time.
+    /// Always sent as [`SINK_RATE`] stereo so the sink never has to reconfigure, upmixing mono tracks
+    fn play_sink<'a>(&'a self, sink: &str, track: Arc<Track>) -> Result<(), Box<dyn Error + 'a>> {
+        // {{{
+        // 20ms
+        const CHUNK: usize = SINK_RATE as usize * SINK_CHANNELS / 50;
+
+        let channels = self.channels.load(Ordering::Relaxed);
+        if channels != 1 && channels != SINK_CHANNELS {
+            self.join_stream.store(true, Ordering::Relaxed);
+            return Err("Network sinks only play mono or stereo tracks".into());
+        }
+
+        let mut guard = self.connection.lock()?;
+        if guard.is_none() {
+            debug!("Sympal connect sink {}", sink);
+            *guard = Some(Connection::open(sink).map_err(|e| format!("Could not connect to sink '{}': {}", sink, e))?);
+        }
+        drop(guard);
+
+        let vol = self.volume.clone();
+        let limited = self.limited.clone();
+        let ramp_from = self.ramp_from.clone();
+        let resampler = *self.resampler.lock()?;
+        let join = self.join_stream.clone();
+        let streaming = self.streaming.clone();
+        let connection = self.connection.clone();
+        let pos = self.pos.clone();
+        let ab_loop = self.ab_loop.clone();
+        let audible = self.audible.clone();
+        let samples = self.samples.clone();
+        let rate = self.rate.load(Ordering::Relaxed);
+        let channel_str = self.channel.clone();
+
+        debug!("Sympal play spawn sink stream");
+        thread::Builder::new().name(String::from("SYMPAL Network Sink")).spawn(move || {
+            streaming.store(true, Ordering::Relaxed);
+            let mut buffer = vec![0i16; CHUNK];
+            let mut reduction = 1.0;
+            let mut resampling = Resampling::new(resampler);
+            while !join.load(Ordering::Relaxed) {
+                if !connection.lock().is_ok_and(|mut c| c.as_mut().is_none_or(|c| c.ready())) {
+                    thread::sleep(Duration::from_millis(1));
+                    continue;
+                }
+                let result = try_block!({
+                    // past leading silence
+                    let skipped = pos.load(Ordering::Relaxed) < audible[0].load(Ordering::Relaxed);
+                    let mut start_pos = pos.load(Ordering::Relaxed).max(audible[0].load(Ordering::Relaxed));
+                    start_pos -= start_pos % channels;
+                    let seconds = start_pos as f32 / (channels as u32 * rate) as f32;
+                    let amplitude = ramp(f32::from_bits(ramp_from.load(Ordering::Relaxed)), track.gain(), seconds)
+                        * f32::from_bits(vol.load(Ordering::Relaxed)).powi(3);
+                    let samples = samples.read()?;
+                    let end = samples.len().min(audible[1].load(Ordering::Relaxed));
+
+                    let (amplitude, clipped) = limit(
+                        window(&samples, start_pos, CHUNK / SINK_CHANNELS * channels, rate, SINK_RATE),
+                        amplitude,
+                        &mut reduction,
+                    );
+                    for count in limited.iter() {
+                        count.fetch_add(clipped, Ordering::Relaxed);
+                    }
+
+                    // silence past the end of the track
+                    buffer.fill(0);
+                    let cur_pos = if channels == 1 {
+                        // upmix only as much as the chunk can use
+                        let wanted = (CHUNK / SINK_CHANNELS) * rate as usize / SINK_RATE as usize + 2;
+                        let mono = samples.get(start_pos..).ok_or("Sample pos out of bounds")?;
+                        let stereo: Vec<i16> = mono.iter().take(wanted).flat_map(|s| [*s, *s]).collect();
+                        start_pos + fill_slice(&mut buffer, &stereo, 0, rate, SINK_RATE, amplitude, &mut resampling)? / SINK_CHANNELS
+                    } else {
+                        fill_slice(&mut buffer, &samples, start_pos, rate, SINK_RATE, amplitude, &mut resampling)?
+                    };
+
+                    let cur_pos = match loop_back(&ab_loop, start_pos, cur_pos) {
+                        Some(a) => {
                             channel_str.send(PlayerMessage::Clock)?;
+                            a
                         }
+                        None => cur_pos,
+                    };
+                    pos.store(cur_pos, Ordering::Relaxed);
+                    if skipped {
+                        channel_str.send(PlayerMessage::Clock)?;
+                    }
+                    if cur_pos >= end && !join.load(Ordering::Relaxed) {
+                        join.store(true, Ordering::Relaxed);
+                        channel_str.send(PlayerMessage::Request)?;
+                    }
+                    if (start_pos / (rate as usize * channels)) < (cur_pos / (rate as usize * channels)) {
+                        channel_str.send(PlayerMessage::Clock)?;
+                    }
+                    drop(samples);
+
+                    let mut guard = connection.lock()?;
+                    let written = guard.as_mut().ok_or("Sink disconnected")?.send(&buffer);
+                    if written.is_err() {
+                        // reconnect on the next play
+                        *guard = None
+                    }
+                    written?;
+                    Ok(())
+                });
+                if let Err(e) = result {
+                    join.store(true, Ordering::Relaxed);
+                    let _ = channel_str.send(PlayerMessage::Error(format!("SYMPAL Network Sink Error:\n  {}", e)));
+                }
+            }
+            streaming.store(false, Ordering::Relaxed);
+        })?;
+        Ok(())
+    }
+    // }}}
+
+    /// Stream to OUTPUT alongside the default device.
+    /// Keeps its own cursor so the buffer sizes don't have to match,
+    /// snapping back to the main position if the two drift apart
+    fn play_secondary(&self, output: &mut Secondary, track: Arc<Track>) -> Result<(), Box<dyn Error>> {
+        // {{{
+        output.stop.store(true, Ordering::Relaxed);
+        output.stop = Arc::new(AtomicBool::new(false));
+
+        let (device, config) = self.get_device(Some(&output.name))?;
+        let vol = self.volume.clone();
+        let output_vol = output.volume.clone();
+        let ramp_from = self.ramp_from.clone();
+        let resampler = *self.resampler.lock().map_err(|e| e.to_string())?;
+        let stop_thread = output.stop.clone();
+        let stop_err = output.stop.clone();
+        let join_thread = self.join_stream.clone();
+        let pos = self.pos.clone();
+        let samples = self.samples.clone();
+        let channels = self.channels.load(Ordering::Relaxed);
+        let rate = self.rate.load(Ordering::Relaxed);
+        let device_rate = config.sample_rate().0;
+        let device_format = config.sample_format();
+        let channel_str = self.channel.clone();
+        let channel_err = self.channel.clone();
+        let channel_thread = self.channel.clone();
+        let name = output.name.clone();
+
+        // quarter second
+        let max_drift = (rate as usize * channels) / 4;
+        let mut cursor = pos.load(Ordering::Relaxed);
+
+        let mut stream_config = config.config();
+        if let Some(b) = self.buffer {
+            if let cpal::SupportedBufferSize::Range { min, max } = config.buffer_size() {
+                stream_config.buffer_size = cpal::BufferSize::Fixed(b.clamp(*min, *max));
+            }
+        }
+
+        debug!("Sympal spawn output stream {}", name);
+        thread::Builder::new().name(format!("SYMPAL Output {}", name)).spawn(move || {
+            // its own, as the output volume can differ. Clipping is only counted on the main stream
+            let mut reduction = 1.0;
+            let mut resampling = Resampling::new(resampler);
+            let stream = device.build_output_stream_raw(
+                &stream_config,
+                device_format,
+                move |ring_buffer: &mut cpal::Data, _: &cpal::OutputCallbackInfo| {
+                    let result = try_block!({
+                        let main = pos.load(Ordering::Relaxed);
+                        if cursor.abs_diff(main) > max_drift {
+                            cursor = main
+                        }
+                        let samples = samples.read()?;
+                        cursor = cursor.min(samples.len());
+                        cursor -= cursor % channels;
+                        let seconds = cursor as f32 / (channels as u32 * rate) as f32;
+                        let amplitude = ramp(f32::from_bits(ramp_from.load(Ordering::Relaxed)), track.gain(), seconds)
+                            * f32::from_bits(vol.load(Ordering::Relaxed)).powi(3)
+                            * f32::from_bits(output_vol.load(Ordering::Relaxed)).powi(3);
+                        let (amplitude, _) = limit(window(&samples, cursor, ring_buffer.len(), rate, device_rate), amplitude, &mut reduction);
+
+                        cursor = fill_buffer(ring_buffer, &samples, cursor, rate, device_rate, amplitude, &mut resampling)?
+                            .ok_or_else(|| format!("Sympal unsupported stream format '{}'", ring_buffer.sample_format()))?;
                         Ok(())
                     });
-                    match result {
-                        Ok(()) => (),
-                        Err(e) => {
-                            let _ = channel_str.send(PlayerMessage::Error(format!("Error occured while decoding the track:\n  {}", e)));
-                        }
-                    };
+                    if let Err(e) = result {
+                        stop_err.store(true, Ordering::Relaxed);
+                        let _ = channel_str.send(PlayerMessage::Error(format!("Error occured while playing to output:\n  {}", e)));
+                    }
                 },
                 move |err| {
-                    // TODO can some of these actually be handled?
-                    join_err.store(true, Ordering::Relaxed);
-                    let _ = channel_err.send(PlayerMessage::Error(format!("SYMPAL Audio Stream Error:\n{}", err)));
-                    pos_err.store(0, Ordering::Relaxed);
+                    let _ = channel_err.send(PlayerMessage::Error(format!("SYMPAL Output Stream Error:\n{}", err)));
                 },
-                None, // None=blocking, Some(Duration)=timeout
+                None,
             );
-            if let Ok(stream) = stream {
-                let Ok(()) = stream.play() else {
-                    let _ = channel_thread.send(PlayerMessage::Error("CPAL Audio stream could not play".into()));
-                    streaming.store(false, Ordering::Relaxed);
-                    join_thread.store(true, Ordering::Relaxed);
-                    return;
-                };
-                // not using wait_on! because its only purpose is
-                // to keep the stream object in scope until its done
-                while !join_thread.load(Ordering::Relaxed) {
-                    // Millis instead of nanos because Windows will cook on the loop somehow
-                    thread::sleep(Duration::from_millis(1))
+            match stream
+                .map_err(|e| e.to_string())
+                .and_then(|s| s.play().map(|_| s).map_err(|e| e.to_string()))
+            {
+                Ok(_stream) => {
+                    while !join_thread.load(Ordering::Relaxed) && !stop_thread.load(Ordering::Relaxed) {
+                        thread::sleep(Duration::from_millis(1))
+                    }
+                }
+                Err(e) => {
+                    let _ = channel_thread.send(PlayerMessage::Error(format!("Could not play to output '{}':\n  {}", name, e)));
                 }
-                streaming.store(false, Ordering::Relaxed);
             }
         })?;
-        debug!("Sympal play end");
         Ok(())
     }
     // }}}
@@ -355,10 +922,18 @@ impl Backend {
             self.stop();
             return Ok(track);
         }
+        self.ab_loop.iter().for_each(|p| p.store(usize::MAX, Ordering::Relaxed));
+        self.audible[0].store(0, Ordering::Relaxed);
+        self.audible[1].store(usize::MAX, Ordering::Relaxed);
+        self.peak.store(0, Ordering::Relaxed);
+        self.limited[0].store(0, Ordering::Relaxed);
         swap(guard, &mut track);
+        // track now holds the last one
+        self.ramp_from
+            .store(track.as_ref().map_or(0.0, |t| t.gain()).to_bits(), Ordering::Relaxed);
 
         if let Some(track) = guard.as_ref() {
-            let mss = MediaSourceStream::new(Box::new(File::open(track.path().clone())?), Default::default());
+            let mss = MediaSourceStream::new(track.open()?, Default::default());
 
             let mut fr = symphonia::default::get_probe()
                 .format(
@@ -371,7 +946,7 @@ impl Backend {
                             .ok_or("Extension characters are invalid")?,
                     ),
                     mss,
-                    &Default::default(),
+                    &Track::format_options(),
                     &Default::default(),
                 )?
                 .format;
@@ -422,6 +997,11 @@ impl Backend {
             self.decoder_state.store(*DecoderState::Empty, Ordering::Relaxed);
             self.join_decode.store(false, Ordering::Relaxed);
             self.pos.store(0, Ordering::Relaxed);
+            if let (Some(delay), Some(padding)) = (decoder.codec_params().delay, decoder.codec_params().padding) {
+                debug!("Sympal trimming {} frames of encoder delay and {} of padding", delay, padding);
+            }
+            self.frames
+                .store(decoder.codec_params().n_frames.unwrap_or(0) as usize, Ordering::Relaxed);
 
             let channel = self.channel.clone();
             let channel_er = self.channel.clone();
@@ -431,59 +1011,109 @@ impl Backend {
             let join_decode = self.join_decode.clone();
             let rate = self.rate.clone();
             let samples = self.samples.clone();
+            let audible = self.audible.clone();
+            let peak = self.peak.clone();
+            let silence = *self.silence.lock()?;
+            let path = track.path().clone();
+            let threshold = (10f32.powf(silence.threshold / 20.0) * i16::MAX as f32) as i16;
 
             thread::Builder::new().name(String::from("SYMPAL Decoder")).spawn(move || {
-                let result = try_block!({
-                    let mut decoder = decoder; // assign in closure for FnOnce()
-                    let begin = Instant::now();
-                    decoder_state.store(*DecoderState::Init, Ordering::Relaxed);
-                    while let Ok(packet) = fr.next_packet() {
-                        // A new decoder is waiting to start
-                        if join_decode.load(Ordering::Relaxed) {
-                            decoder_state.store(*DecoderState::Empty, Ordering::Relaxed);
-                            break;
-                        }
-                        // 0 length packets are possible I guess
-                        if packet.dur() < 1 {
-                            continue;
-                        }
-                        let ab = match decoder.decode(&packet) {
-                            Err(symphonia::core::errors::Error::IoError(_)) => continue,
-                            Err(symphonia::core::errors::Error::DecodeError(_)) => continue,
-                            r => r?,
-                        };
+                let samples_er = samples.clone();
+                // a panic is reported like any other decode error so the track can be skipped or quarantined
+                let result = catch_unwind(AssertUnwindSafe(|| {
+                    try_block!({
+                        let mut decoder = decoder; // assign in closure for FnOnce()
+                        let begin = Instant::now();
+                        decoder_state.store(*DecoderState::Init, Ordering::Relaxed);
+                        while let Ok(packet) = fr.next_packet() {
+                            // A new decoder is waiting to start
+                            if join_decode.load(Ordering::Relaxed) {
+                                decoder_state.store(*DecoderState::Empty, Ordering::Relaxed);
+                                break;
+                            }
+                            // 0 length packets are possible I guess
+                            if packet.dur() < 1 {
+                                continue;
+                            }
+                            let ab = match decoder.decode(&packet) {
+                                Err(symphonia::core::errors::Error::IoError(_)) => continue,
+                                Err(symphonia::core::errors::Error::DecodeError(_)) => continue,
+                                r => r?,
+                            };
 
-                        // Set channels and rate on first packet
-                        // Also allocate 2 minutes of sample storage
-                        if decoder_state.load(Ordering::Relaxed) == *DecoderState::Init {
-                            let new_rate = ab.spec().rate;
-                            let new_channels = ab.spec().channels.count();
-                            rate.store(new_rate, Ordering::Relaxed);
-                            channels.store(new_channels, Ordering::Relaxed);
+                            // Set channels and rate on first packet
+                            // Also allocate 2 minutes of sample storage
+                            if decoder_state.load(Ordering::Relaxed) == *DecoderState::Init {
+                                let new_rate = ab.spec().rate;
+                                let new_channels = ab.spec().channels.count();
+                                rate.store(new_rate, Ordering::Relaxed);
+                                channels.store(new_channels, Ordering::Relaxed);
 
-                            *samples.write()? = Vec::with_capacity(new_rate as usize * 120 * new_channels);
-                            decoder_state.store(*DecoderState::Decoding, Ordering::Relaxed)
-                        }
+                                *samples.write()? = Vec::with_capacity(new_rate as usize * 120 * new_channels);
+                                decoder_state.store(*DecoderState::Decoding, Ordering::Relaxed)
+                            }
 
-                        let mut sb = SampleBuffer::<i16>::new(packet.dur, *ab.spec());
-                        // avoid panic
-                        if sb.capacity() < ab.frames() {
-                            return Err("SampleBuffer capacity was lower than AudioBuffer frame count!".into());
-                        };
-                        sb.copy_interleaved_ref(ab);
-                        samples.write()?.append(&mut sb.samples_mut().to_vec());
-                    }
-                    bench!("Track fully decoded in {:?}", begin.elapsed());
-                    decoder_state.store(*DecoderState::Complete, Ordering::Relaxed);
-                    samples.write()?.shrink_to_fit();
-                    channel.send(PlayerMessage::Seekable)?;
-                    Ok(())
+                            let mut sb = SampleBuffer::<i16>::new(packet.dur, *ab.spec());
+                            // avoid panic
+                            if sb.capacity() < ab.frames() {
+                                return Err("SampleBuffer capacity was lower than AudioBuffer frame count!".into());
+                            };
+                            sb.copy_interleaved_ref(ab);
+                            let mut samples = samples.write()?;
+                            let decoded = samples.len();
+                            samples.append(&mut sb.samples_mut().to_vec());
+                            if let Some(loudest) = samples[decoded..].iter().map(|s| s.unsigned_abs()).max() {
+                                peak.fetch_max(loudest, Ordering::Relaxed);
+                            }
+
+                            // playback starts during decoding, so leading silence is skipped as soon as it's over
+                            if silence.trim && audible[0].load(Ordering::Relaxed) == 0 {
+                                if let Some(loud) = first_loud(&samples[decoded..], threshold) {
+                                    let loud = decoded + loud;
+                                    audible[0].store(loud - loud % channels.load(Ordering::Relaxed).max(1), Ordering::Relaxed);
+                                }
+                            }
+                            // likewise the intro, once there's enough decoded to start after it
+                            let intro =
+                                (silence.intro.as_secs_f64() * rate.load(Ordering::Relaxed) as f64) as usize * channels.load(Ordering::Relaxed);
+                            if intro > audible[0].load(Ordering::Relaxed) && samples.len() > intro {
+                                audible[0].store(intro, Ordering::Relaxed);
+                            }
+                        }
+                        // everything's buffered, so let go of the file for anything wanting to write to it
+                        drop(fr);
+                        bench!("Track fully decoded in {:?}", begin.elapsed());
+                        if silence.trim || silence.split.is_some() {
+                            let samples = samples.read()?;
+                            let channels = channels.load(Ordering::Relaxed);
+                            let split = silence.split.map(|d| (d.as_secs_f64() * rate.load(Ordering::Relaxed) as f64) as usize);
+                            let start = first_loud(&samples, threshold).unwrap_or(0);
+                            audible[1].store(audible_end(&samples, channels, threshold, start, silence.trim, split), Ordering::Relaxed);
+                        }
+                        if !silence.outro.is_zero() {
+                            let outro =
+                                (silence.outro.as_secs_f64() * rate.load(Ordering::Relaxed) as f64) as usize * channels.load(Ordering::Relaxed);
+                            let end = samples.read()?.len().saturating_sub(outro).max(audible[0].load(Ordering::Relaxed));
+                            audible[1].fetch_min(end, Ordering::Relaxed);
+                        }
+                        decoder_state.store(*DecoderState::Complete, Ordering::Relaxed);
+                        samples.write()?.shrink_to_fit();
+                        channel.send(PlayerMessage::Seekable)?;
+                        Ok(())
+                    })
+                }))
+                .unwrap_or_else(|payload| {
+                    samples_er.clear_poison();
+                    Err(format!("Decoder panicked: {}", panic_message(payload.as_ref())).into())
                 });
                 match result {
                     Ok(()) => (),
                     Err(e) => {
                         decoder_state_er.store(*DecoderState::Error, Ordering::Relaxed);
-                        let _ = channel_er.send(PlayerMessage::Error(format!("Error occured while decoding the track:\n  {}", e)));
+                        let _ = channel_er.send(PlayerMessage::DecodeError(
+                            path,
+                            format!("Error occured while decoding the track:\n  {}", e),
+                        ));
                     }
                 };
             })?;
@@ -511,12 +1141,23 @@ impl Player for Backend {
             decoder_state: Arc::new(AtomicU8::new(*DecoderState::Empty)),
             samples: Default::default(),
             pos: Arc::new(AtomicUsize::new(0)),
+            frames: Arc::new(AtomicUsize::new(0)),
             rate: Arc::new(AtomicU32::new(0)),
             channels: Arc::new(AtomicUsize::new(0)),
             device_rate: Arc::new(AtomicU32::new(0)),
             device_channels: Arc::new(AtomicU32::new(0)),
             device_format: Arc::new(AtomicU8::new(0)),
             buffer,
+            outputs: Mutex::new(Vec::new()),
+            sink: None,
+            connection: Default::default(),
+            ab_loop: Arc::new([AtomicUsize::new(usize::MAX), AtomicUsize::new(usize::MAX)]),
+            silence: Mutex::new(Silence::default()),
+            audible: Arc::new([AtomicUsize::new(0), AtomicUsize::new(usize::MAX)]),
+            peak: Arc::new(AtomicU16::new(0)),
+            limited: Default::default(),
+            ramp_from: Default::default(),
+            resampler: Mutex::new(Resampler::default()),
         }
     }
     fn types(&self) -> Vec<String> {
@@ -546,7 +1187,9 @@ impl Player for Backend {
     fn play(&self) {
         if let Err(e) = self.play_internal() {
             self.stop();
-            let _ = self.channel.send(PlayerMessage::Error(
+            // play() is also called from the thread reading this channel when a track ends,
+            // so a full channel must not block
+            let _ = self.channel.try_send(PlayerMessage::Error(
                 (format!("Error occured when attempting to play the stream:\n  {}", e)).to_string(),
             ));
         }
@@ -560,6 +1203,19 @@ impl Player for Backend {
     fn pause(&self) {
         self.join_stream.store(true, Ordering::Relaxed);
     }
+    fn shutdown(&self) -> Result<(), String> {
+        self.stop();
+        self.join_decode.store(true, Ordering::Relaxed);
+        wait_on!(!self.streaming.load(Ordering::Relaxed), "shutdown streaming guard")?;
+        wait_on!(
+            !matches!(
+                self.decoder_state.load(Ordering::Relaxed).into(),
+                DecoderState::Init | DecoderState::Decoding
+            ),
+            "shutdown decoder guard"
+        )?;
+        Ok(())
+    }
     fn playing(&self) -> bool {
         !self.join_stream.load(Ordering::Relaxed)
     }
@@ -571,32 +1227,113 @@ impl Player for Backend {
     }
     fn times(&self) -> Option<(Duration, Duration)> {
         if let Ok(samples) = self.samples.read() {
-            // unwrap as it's hardcoded to Some
-            match self.seekable().unwrap() {
-                true => Some((
+            match self.decoder_state.load(Ordering::Relaxed).into() {
+                // total is the container's frame count until decoding catches up, if it has one
+                DecoderState::Decoding | DecoderState::Complete => Some((
                     Duration::from_secs_f64(
                         (self.pos.load(Ordering::Relaxed) as f64)
                             / (self.rate.load(Ordering::Relaxed) as f64)
                             / (self.channels.load(Ordering::Relaxed) as f64),
                     ),
                     Duration::from_secs_f64(
-                        (samples.len() as f64) / (self.rate.load(Ordering::Relaxed) as f64) / (self.channels.load(Ordering::Relaxed) as f64),
+                        (samples.len() as f64 / self.channels.load(Ordering::Relaxed) as f64).max(self.frames.load(Ordering::Relaxed) as f64)
+                            / (self.rate.load(Ordering::Relaxed) as f64),
                     ),
                 )),
-                false => None,
+                _ => None,
             }
         } else {
             None
         }
     }
+    fn decoded(&self) -> Option<Duration> {
+        let (rate, channels) = (self.rate.load(Ordering::Relaxed), self.channels.load(Ordering::Relaxed));
+        match self.decoder_state.load(Ordering::Relaxed).into() {
+            DecoderState::Decoding | DecoderState::Complete if rate > 0 && channels > 0 => Some(Duration::from_secs_f64(
+                self.samples.read().ok()?.len() as f64 / channels as f64 / rate as f64,
+            )),
+            _ => None,
+        }
+    }
     fn seek(&self, time: Duration) {
-        if self.seekable() == Some(true) {
-            self.pos.store(
-                (time.as_secs_f32() * self.rate.load(Ordering::Relaxed) as f32) as usize * self.channels.load(Ordering::Relaxed),
-                Ordering::Release,
-            );
-            let _ = self.channel.send(PlayerMessage::Clock);
+        let (rate, channels) = (self.rate.load(Ordering::Relaxed), self.channels.load(Ordering::Relaxed));
+        let mut pos = (time.as_secs_f32() * rate as f32) as usize * channels;
+        match self.decoder_state.load(Ordering::Relaxed).into() {
+            DecoderState::Complete => (),
+            // a second short of the decoded edge, since running into it ends the track
+            DecoderState::Decoding => {
+                let Ok(samples) = self.samples.read() else { return };
+                pos = pos.min(samples.len().saturating_sub(rate as usize * channels));
+            }
+            _ => return,
+        }
+        self.pos.store(pos, Ordering::Release);
+        let _ = self.channel.send(PlayerMessage::Clock);
+    }
+    fn ab_loop_get(&self) -> (Option<Duration>, Option<Duration>) {
+        let (rate, channels) = (self.rate.load(Ordering::Relaxed) as f64, self.channels.load(Ordering::Relaxed) as f64);
+        if rate == 0.0 || channels == 0.0 {
+            return (None, None);
         }
+        let [a, b] = [0, 1].map(|n| match self.ab_loop[n].load(Ordering::Relaxed) {
+            usize::MAX => None,
+            p => Some(Duration::from_secs_f64(p as f64 / rate / channels)),
+        });
+        (a, b)
+    }
+    fn ab_loop_set(&self, a: Option<Duration>, b: Option<Duration>) -> Result<(), String> {
+        if self.times().is_none() {
+            return Err("No track is loaded".to_string());
+        }
+        let (rate, channels) = (self.rate.load(Ordering::Relaxed) as f64, self.channels.load(Ordering::Relaxed));
+        for (point, time) in self.ab_loop.iter().zip([a, b]) {
+            point.store(
+                time.map_or(usize::MAX, |t| (t.as_secs_f64() * rate) as usize * channels),
+                Ordering::Relaxed,
+            )
+        }
+        Ok(())
+    }
+    fn silence_set(&self, silence: Silence) {
+        if let Ok(mut guard) = self.silence.lock() {
+            *guard = silence
+        }
+    }
+    fn silence_get(&self) -> Option<Silence> {
+        self.silence.lock().ok().map(|s| *s)
+    }
+    fn resampler_set(&self, resampler: Resampler) -> Result<(), String> {
+        *self.resampler.lock().map_err(|e| e.to_string())? = resampler;
+        Ok(())
+    }
+    fn format(&self) -> Option<Format> {
+        let rate = self.rate.load(Ordering::Relaxed);
+        if self.track_get().is_none() || rate == 0 {
+            return None;
+        }
+        let device_rate = self.device_rate.load(Ordering::Relaxed);
+        let device_format: SampleFormat = unsafe { transmute(self.device_format.load(Ordering::Relaxed)) };
+        Some(Format {
+            track: (rate, self.channels.load(Ordering::Relaxed)),
+            device: (device_rate != 0).then(|| (device_rate, self.device_channels.load(Ordering::Relaxed), device_format.to_string())),
+            resampler: *self.resampler.lock().ok()?,
+        })
+    }
+    fn bpm(&self) -> Option<f32> {
+        if self.decoder_state.load(Ordering::Relaxed) != *DecoderState::Complete {
+            return None;
+        }
+        let samples = self.samples.read().ok()?;
+        bpm::detect(&samples, self.channels.load(Ordering::Relaxed), self.rate.load(Ordering::Relaxed))
+    }
+    fn headroom(&self) -> Option<Headroom> {
+        let track = self.track_get()?;
+        Some(Headroom {
+            peak: self.peak.load(Ordering::Relaxed) as f32 / -(i16::MIN as f32),
+            amplitude: track.gain() * f32::from_bits(self.volume.load(Ordering::Relaxed)).powi(3),
+            limited: self.limited[0].load(Ordering::Relaxed),
+            limited_total: self.limited[1].load(Ordering::Relaxed),
+        })
     }
     fn waveform(&self, count: usize) -> Option<Vec<f32>> {
         if self.seekable() == Some(true) {
@@ -635,15 +1372,196 @@ impl Player for Backend {
     fn track_get(&self) -> Option<Arc<Track>> {
         self.track.lock().ok()?.clone()
     }
+    fn track_retag(&self, track: Arc<Track>) {
+        if let Ok(mut guard) = self.track.lock() {
+            if guard.as_ref().is_some_and(|t| t.path() == track.path()) {
+                *guard = Some(track)
+            }
+        }
+    }
+    fn outputs(&self) -> Option<Vec<Output>> {
+        if self.sink.is_some() {
+            return None;
+        }
+        let default = default_device_name();
+        let enabled = self.outputs.lock().ok()?;
+        let mut names: Vec<String> = host()
+            .ok()
+            .and_then(|h| h.output_devices().ok())
+            .map(|devices| devices.filter_map(|d| d.name().ok()).collect())
+            .unwrap_or_default();
+        names.retain(|n| Some(n) != default.as_ref());
+        // enabled but unplugged devices still show
+        for output in enabled.iter() {
+            if !names.contains(&output.name) {
+                names.push(output.name.clone())
+            }
+        }
+        names.dedup();
+        Some(
+            names
+                .into_iter()
+                .map(|name| match enabled.iter().find(|o| o.name == name) {
+                    Some(o) => Output {
+                        name,
+                        enabled: true,
+                        volume: f32::from_bits(o.volume.load(Ordering::Relaxed)),
+                    },
+                    None => Output {
+                        name,
+                        enabled: false,
+                        volume: 1.0,
+                    },
+                })
+                .collect(),
+        )
+    }
+    fn output_enable(&self, name: &str, enabled: bool) -> Result<(), String> {
+        if self.sink.is_some() {
+            return Err("Extra outputs can't be used with a network sink".to_string());
+        }
+        if enabled && default_device_name().as_deref() == Some(name) {
+            return Err(format!("'{}' is the default output", name));
+        }
+        if enabled && !self.outputs().is_some_and(|o| o.iter().any(|o| o.name == name)) {
+            return Err(format!("No output named '{}'", name));
+        }
+        let mut outputs = self.outputs.lock().map_err(|e| e.to_string())?;
+        let index = outputs.iter().position(|o| o.name == name);
+        match (index, enabled) {
+            (Some(_), true) | (None, false) => Ok(()),
+            (Some(index), false) => {
+                outputs.remove(index).stop.store(true, Ordering::Relaxed);
+                Ok(())
+            }
+            (None, true) => {
+                let mut output = Secondary::new(name.to_string());
+                if let (true, Some(track)) = (self.playing(), self.track_get()) {
+                    self.play_secondary(&mut output, track).map_err(|e| e.to_string())?;
+                }
+                outputs.push(output);
+                Ok(())
+            }
+        }
+    }
+    fn output_volume_set(&self, name: &str, volume: f32) -> Result<(), String> {
+        let outputs = self.outputs.lock().map_err(|e| e.to_string())?;
+        let output = outputs
+            .iter()
+            .find(|o| o.name == name)
+            .ok_or(format!("Output '{}' is not enabled", name))?;
+        output.volume.store(volume.clamp(0.0, 1.0).to_bits(), Ordering::Relaxed);
+        Ok(())
+    }
     fn track_set(&self, track: Option<Arc<Track>>) -> Option<Arc<Track>> {
+        let path = track.as_ref().map(|t| t.path().clone());
         self.track_set_internal(track).map_or_else(
             |e| {
-                let _ = self.channel.send(PlayerMessage::Error(
-                    (format!("Error occured when attempting to set the track:\n  {}", e)).to_string(),
-                ));
+                let message = format!("Error occured when attempting to set the track:\n  {}", e);
+                let _ = self.channel.send(match path {
+                    Some(path) => PlayerMessage::DecodeError(path, message),
+                    None => PlayerMessage::Error(message),
+                });
                 None
             },
             |b| b,
         )
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{audible_end, fill_slice, first_loud, limit, loop_back, ramp, Resampler, Resampling, LIMIT, RAMP_LENGTH};
+    use std::sync::atomic::AtomicUsize;
+
+    fn points(a: usize, b: usize) -> [AtomicUsize; 2] {
+        [AtomicUsize::new(a), AtomicUsize::new(b)]
+    }
+
+    #[test]
+    fn crossing() {
+        assert_eq!(loop_back(&points(100, 200), 150, 250), Some(100));
+        assert_eq!(loop_back(&points(100, 200), 150, 200), Some(100));
+        assert_eq!(loop_back(&points(100, 200), 150, 199), None);
+    }
+
+    #[test]
+    fn escape() {
+        // seeked past B
+        assert_eq!(loop_back(&points(100, 200), 300, 350), None);
+    }
+
+    #[test]
+    fn unset() {
+        assert_eq!(loop_back(&points(usize::MAX, 200), 150, 250), Some(0));
+        assert_eq!(loop_back(&points(100, usize::MAX), 150, 250), None);
+    }
+
+    #[test]
+    fn silence_trimmed() {
+        // stereo, 2 silent frames, 2 loud, 2 silent
+        let samples = [0, 0, 1, -1, 500, 0, 0, -500, 2, 0, 0, 0];
+        assert_eq!(first_loud(&samples, 10), Some(4));
+        assert_eq!(audible_end(&samples, 2, 10, 4, true, None), 8);
+        assert_eq!(audible_end(&samples, 2, 10, 4, false, None), samples.len());
+    }
+
+    #[test]
+    fn silence_split() {
+        // mono, loud, 3 silent, loud, 2 silent
+        let samples = [100, 0, 0, 0, 100, 0, 0];
+        assert_eq!(audible_end(&samples, 1, 10, 0, false, Some(3)), 1);
+        assert_eq!(audible_end(&samples, 1, 10, 0, false, Some(4)), samples.len());
+        assert_eq!(audible_end(&samples, 1, 10, 0, true, Some(4)), 5);
+    }
+
+    #[test]
+    fn limiter() {
+        let quiet = [8000i16, -8000];
+        let loud = [i16::MAX, i16::MIN, 16384];
+        let mut reduction = 1.0;
+        // room to spare, nothing changes
+        assert_eq!(limit(&quiet, 2.0, &mut reduction), (2.0, 0));
+        // a 2x boost clips both full scale samples but not the half scale one
+        let (amplitude, clipped) = limit(&loud, 2.0, &mut reduction);
+        assert_eq!(clipped, 2);
+        assert!((amplitude - LIMIT).abs() < 0.001);
+        // eases back rather than jumping
+        let (amplitude, _) = limit(&quiet, 2.0, &mut reduction);
+        assert!(amplitude > LIMIT && amplitude < 2.0);
+        // attenuation never counts as clipping
+        assert_eq!(limit(&loud, 0.5, &mut 1.0).1, 0);
+    }
+
+    #[test]
+    fn resampled() {
+        let samples = [1000i16; 200];
+        for resampler in [Resampler::Linear, Resampler::Sinc(8)] {
+            // double the rate, so a buffer moves half as far through the track
+            let mut whole = [0i16; 80];
+            let end = fill_slice(&mut whole, &samples, 40, 22050, 44100, 1.0, &mut Resampling::new(resampler)).unwrap();
+            assert_eq!(end, 80);
+            // away from the edges a flat signal stays flat
+            assert!(whole[20..60].iter().all(|s| (s - 1000).abs() < 20), "{resampler} {whole:?}");
+
+            // split buffers carry on where the last stopped
+            let mut resampling = Resampling::new(resampler);
+            let mut split = [0i16; 80];
+            let mid = fill_slice(&mut split[..30], &samples, 40, 22050, 44100, 1.0, &mut resampling).unwrap();
+            fill_slice(&mut split[30..], &samples, mid, 22050, 44100, 1.0, &mut resampling).unwrap();
+            assert_eq!(whole, split, "{resampler}");
+        }
+    }
+
+    #[test]
+    fn ramped() {
+        // close enough to jump straight to
+        assert_eq!(ramp(1.0, 1.2, 0.0), 1.2);
+        // no track before
+        assert_eq!(ramp(0.0, 0.25, 0.0), 0.25);
+        // eased in dB, so halfway between 1.0 and 0.25 is 0.5
+        assert_eq!(ramp(1.0, 0.25, 0.0), 1.0);
+        assert!((ramp(1.0, 0.25, RAMP_LENGTH / 2.0) - 0.5).abs() < 0.001);
+        assert_eq!(ramp(1.0, 0.25, RAMP_LENGTH), 0.25);
+    }
+}
@@ -0,0 +1,87 @@
+#![warn(missing_docs)]
+
+//! How much is queued up, for the status bar and `ompl print queue-info`.
+
+use super::Track;
+
+use std::fmt::Display;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Length of the queue, summed from each track's probed duration
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct QueueInfo {
+    /// Tracks in the queue
+    pub tracks: usize,
+    /// Total of every known duration
+    pub duration: Duration,
+    /// Tracks whose duration couldn't be probed, like some streams, so the total is a lower bound
+    pub unknown: usize,
+    /// Tracks skipped for failing to decode, filled in by the library
+    pub quarantined: usize,
+}
+
+impl QueueInfo {
+    /// Sum up TRACKS
+    pub fn new(tracks: &[Arc<Track>]) -> Self {
+        tracks.iter().fold(
+            Self {
+                tracks: tracks.len(),
+                ..Default::default()
+            },
+            |mut info, track| {
+                match track.duration() {
+                    Some(duration) => info.duration += duration,
+                    None => info.unknown += 1,
+                }
+                info
+            },
+        )
+    }
+}
+
+impl Display for QueueInfo {
+    /// "N tracks · 3h 42m", with a + when some durations are unknown and the quarantined count when there are any
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let minutes = (self.duration.as_secs_f64() / 60.0).round() as u64;
+        write!(
+            f,
+            "{} track{} · {}{}",
+            self.tracks,
+            if self.tracks == 1 { "" } else { "s" },
+            match minutes {
+                0..=59 => format!("{}m", minutes),
+                _ => format!("{}h {}m", minutes / 60, minutes % 60),
+            },
+            if self.unknown > 0 { "+" } else { "" }
+        )?;
+        if self.quarantined > 0 {
+            write!(f, " · {} quarantined", self.quarantined)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn display() {
+        let info = |tracks, secs, unknown| QueueInfo {
+            tracks,
+            duration: Duration::from_secs(secs),
+            unknown,
+            quarantined: 0,
+        };
+        assert_eq!(info(0, 0, 0).to_string(), "0 tracks · 0m");
+        assert_eq!(info(1, 200, 0).to_string(), "1 track · 3m");
+        assert_eq!(info(52, 3 * 3600 + 42 * 60 + 10, 0).to_string(), "52 tracks · 3h 42m");
+        assert_eq!(info(3, 3590, 1).to_string(), "3 tracks · 1h 0m+");
+        let quarantined = QueueInfo {
+            quarantined: 2,
+            ..info(10, 600, 0)
+        };
+        assert_eq!(quarantined.to_string(), "10 tracks · 10m · 2 quarantined");
+    }
+}
@@ -0,0 +1,193 @@
+#![warn(missing_docs)]
+
+//! Picking the next track by how close it is to the one playing, like a radio DJ would,
+//! instead of at random. Tracks are compared by genre, counting aliases and parents from the config,
+//! artist, tempo, and year, so the tags from [`super::bpm`] and the analyzer feed straight into it.
+
+use super::{bpm, compilations, Genres, Track};
+
+use std::collections::HashSet;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use clap::ValueEnum;
+use rand::random;
+use serde::{Deserialize, Serialize};
+
+/// When the next track is picked by similarity
+#[derive(Debug, Default, PartialEq, Eq, Clone, Copy, Serialize, Deserialize, ValueEnum)]
+pub enum Radio {
+    /// Tracks are picked by shuffle or queue order alone
+    #[default]
+    Off,
+    /// Once the queue's last track is done, keep going with similar tracks from the whole library
+    End,
+    /// Always play a track similar to the current one from the queue, in place of shuffle
+    Always,
+}
+
+/// Score for sharing a genre, half as much for only sharing a parent genre
+const GENRE: f32 = 3.0;
+/// Score for the same artist, half as much for only the same album artist
+const ARTIST: f32 = 2.0;
+/// Score for the same tempo, falling to nothing at [`BPM_SPREAD`] apart
+const BPM: f32 = 2.0;
+/// Tempo ratio where tracks stop counting as alike
+const BPM_SPREAD: f32 = 1.2;
+/// Score for the same year, falling to nothing at [`YEAR_SPREAD`] apart
+const YEAR: f32 = 1.0;
+/// Years apart where tracks stop counting as alike
+const YEAR_SPREAD: f32 = 6.0;
+/// How many of the closest tracks the pick is made from, so the same few don't come round every time
+const CHOICES: usize = 5;
+
+/// What a track is compared by
+#[derive(Debug, Default)]
+struct Traits {
+    /// Lowercase genres as tagged, under the names they're aliased to
+    genres: HashSet<String>,
+    /// Lowercase genres and every parent above them
+    families: HashSet<String>,
+    artist: Option<String>,
+    albumartist: Option<String>,
+    bpm: Option<f32>,
+    year: Option<i32>,
+}
+
+impl Traits {
+    fn of(track: &Track, mapping: &Genres) -> Self {
+        let tags = track.tags();
+        let lower = |tag: &str| tags.get(tag).map(|v| v.trim().to_lowercase()).filter(|v| !v.is_empty());
+        let mut traits = Self {
+            artist: lower("artist"),
+            // a compilation's album artist says nothing about its tracks
+            albumartist: lower(compilations::TAG).filter(|a| !a.eq_ignore_ascii_case(compilations::VARIOUS)),
+            bpm: tags.get(bpm::TAG).and_then(|b| b.trim().parse::<f32>().ok()).filter(|b| *b > 0.0),
+            year: ["date", "year"]
+                .iter()
+                .find_map(|tag| tags.get(*tag))
+                .and_then(|d| d.trim().get(..4)?.parse().ok()),
+            ..Default::default()
+        };
+        let genres = tags.get("genre").map(|g| g.as_str()).unwrap_or_default();
+        for genre in genres.split([';', '/', ',']).map(|g| g.trim()).filter(|g| !g.is_empty()) {
+            let expanded = mapping.expand(genre);
+            traits.genres.insert(expanded[0].to_lowercase());
+            traits.families.extend(expanded.iter().map(|g| g.to_lowercase()));
+        }
+        traits
+    }
+
+    /// How alike self and OTHER are, higher is closer
+    fn similarity(&self, other: &Self) -> f32 {
+        let mut score = 0.0;
+        if !self.genres.is_disjoint(&other.genres) {
+            score += GENRE
+        } else if !self.families.is_disjoint(&other.families) {
+            score += GENRE / 2.0
+        }
+        if self.artist.is_some() && self.artist == other.artist {
+            score += ARTIST
+        } else if self.albumartist.is_some() && self.albumartist == other.albumartist {
+            score += ARTIST / 2.0
+        }
+        if let (Some(a), Some(b)) = (self.bpm, other.bpm) {
+            score += BPM * (1.0 - (a / b).ln().abs() / BPM_SPREAD.ln()).max(0.0)
+        }
+        if let (Some(a), Some(b)) = (self.year, other.year) {
+            score += YEAR * (1.0 - (a - b).abs() as f32 / YEAR_SPREAD).max(0.0)
+        }
+        score
+    }
+}
+
+/// CANDIDATES other than CURRENT with how close they are to it, closest first.
+/// Tracks in RECENT are left out while there's anything else
+fn ranked(current: &Track, candidates: &[Arc<Track>], recent: &[Arc<Track>], genres: &Genres) -> Vec<(f32, Arc<Track>)> {
+    let recent: HashSet<&PathBuf> = recent.iter().map(|t| t.path()).collect();
+    let mut others: Vec<&Arc<Track>> = candidates.iter().filter(|t| t.path() != current.path()).collect();
+    if others.iter().any(|t| !recent.contains(t.path())) {
+        others.retain(|t| !recent.contains(t.path()))
+    }
+    let traits = Traits::of(current, genres);
+    let mut scored: Vec<(f32, Arc<Track>)> = others
+        .into_iter()
+        .map(|t| (traits.similarity(&Traits::of(t, genres)), t.clone()))
+        .collect();
+    scored.sort_by(|a, b| b.0.total_cmp(&a.0));
+    scored
+}
+
+/// A track from CANDIDATES close to CURRENT, picked at random from the closest few.
+/// Tracks in RECENT are passed over while there's anything else
+pub fn pick(current: &Track, candidates: &[Arc<Track>], recent: &[Arc<Track>], genres: &Genres) -> Option<Arc<Track>> {
+    let mut ranked = ranked(current, candidates, recent, genres);
+    ranked.truncate(CHOICES);
+    if ranked.is_empty() {
+        return None;
+    }
+    let n = random::<usize>() % ranked.len();
+    Some(ranked.swap_remove(n).1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn track(path: &str, tags: &[(&str, &str)]) -> Arc<Track> {
+        Arc::new(Track::tagged(path, tags))
+    }
+
+    fn paths(ranked: &[(f32, Arc<Track>)]) -> Vec<&str> {
+        ranked.iter().map(|(_, t)| t.path().to_str().unwrap()).collect()
+    }
+
+    #[test]
+    fn closest_first() {
+        let current = track("/m/now", &[("genre", "Jazz"), ("artist", "A"), ("bpm", "120"), ("date", "1965-03-01")]);
+        let candidates = [
+            track("/m/unrelated", &[("genre", "Metal"), ("artist", "Z"), ("bpm", "180"), ("date", "2010")]),
+            track("/m/same-artist", &[("genre", "Funk"), ("artist", "A"), ("bpm", "90")]),
+            track(
+                "/m/same-genre",
+                &[("genre", "Rock; Jazz"), ("artist", "B"), ("bpm", "122"), ("date", "1967")],
+            ),
+            current.clone(),
+        ];
+        assert_eq!(
+            paths(&ranked(&current, &candidates, &[], &Genres::default())),
+            ["/m/same-genre", "/m/same-artist", "/m/unrelated"]
+        );
+    }
+
+    #[test]
+    fn genre_families() {
+        let mut genres = Genres::default();
+        genres.line("genre Bebop > Jazz").unwrap().unwrap();
+        genres.line("genre Hard Bop > Jazz").unwrap().unwrap();
+        genres.line("genre DnB = Drum and Bass").unwrap().unwrap();
+        let current = track("/m/now", &[("genre", "Bebop")]);
+        let candidates = [
+            track("/m/dnb", &[("genre", "Drum and Bass")]),
+            track("/m/cousin", &[("genre", "Hard Bop")]),
+            track("/m/parent", &[("genre", "jazz")]),
+        ];
+        let order = ranked(&current, &candidates, &[], &genres);
+        assert_eq!(paths(&order[..2]), ["/m/cousin", "/m/parent"]);
+        assert_eq!(order[2].0, 0.0);
+        // aliases count as the genre they stand for
+        let current = track("/m/now", &[("genre", "DnB")]);
+        assert_eq!(paths(&ranked(&current, &candidates, &[], &genres))[0], "/m/dnb");
+    }
+
+    #[test]
+    fn recent_passed_over() {
+        let current = track("/m/now", &[("artist", "A")]);
+        let candidates = [track("/m/played", &[("artist", "A")]), track("/m/fresh", &[("artist", "B")])];
+        let recent = [candidates[0].clone()];
+        assert_eq!(paths(&ranked(&current, &candidates, &recent, &Genres::default())), ["/m/fresh"]);
+        // nothing else left, so it comes back round
+        assert_eq!(paths(&ranked(&current, &candidates[..1], &recent, &Genres::default())), ["/m/played"]);
+        assert!(pick(&current, std::slice::from_ref(&current), &[], &Genres::default()).is_none());
+    }
+}
@@ -0,0 +1,592 @@
+#![warn(missing_docs)]
+
+//! ReplayGain 2.0 analysis and tag writing.
+//! Loudness is the gated integrated loudness of ITU BS.1770 / EBU R128 measured on the decoded audio,
+//! and gains are relative to the -18 LUFS reference. Album gain treats every track of an album as one long recording.
+//!
+//! Tags can be written to MP3 as ID3v2 TXXX frames and to FLAC as Vorbis comments.
+//! The same writer stores measured tempos as BPM.
+
+use super::track::Track;
+use super::Cancel;
+use crate::logging::*;
+
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::f64::consts::PI;
+use std::fs::{self, File};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+use symphonia::core::audio::SampleBuffer;
+use symphonia::core::errors::Error as SymError;
+
+/// Extensions of files tags can be written to
+pub const TYPES: &[&str] = &["mp3", "flac"];
+/// ReplayGain 2.0 reference loudness in LUFS
+const REFERENCE: f64 = -18.0;
+/// Tag keys written, in the case every known reader matches
+const KEYS: [&str; 4] = [
+    "REPLAYGAIN_TRACK_GAIN",
+    "REPLAYGAIN_TRACK_PEAK",
+    "REPLAYGAIN_ALBUM_GAIN",
+    "REPLAYGAIN_ALBUM_PEAK",
+];
+
+// ## Loudness ## {{{
+
+/// Direct form I biquad
+#[derive(Clone, Copy, Debug, Default)]
+struct Biquad {
+    b: [f64; 3],
+    a: [f64; 2],
+    x: [f64; 2],
+    y: [f64; 2],
+}
+
+impl Biquad {
+    fn process(&mut self, x: f64) -> f64 {
+        let y = self.b[0] * x + self.b[1] * self.x[0] + self.b[2] * self.x[1] - self.a[0] * self.y[0] - self.a[1] * self.y[1];
+        self.x = [x, self.x[0]];
+        self.y = [y, self.y[0]];
+        y
+    }
+}
+
+/// BS.1770 K-weighting as a high shelf then a high pass, with coefficients solved for any sample rate
+fn k_weighting(rate: u32) -> [Biquad; 2] {
+    let rate = rate as f64;
+
+    let (f0, g, q) = (1681.974450955533, 3.999843853973347, 0.7071752369554196);
+    let k = (PI * f0 / rate).tan();
+    let vh = 10f64.powf(g / 20.0);
+    let vb = vh.powf(0.4996667741545416);
+    let a0 = 1.0 + k / q + k * k;
+    let shelf = Biquad {
+        b: [(vh + vb * k / q + k * k) / a0, 2.0 * (k * k - vh) / a0, (vh - vb * k / q + k * k) / a0],
+        a: [2.0 * (k * k - 1.0) / a0, (1.0 - k / q + k * k) / a0],
+        ..Default::default()
+    };
+
+    let (f0, q) = (38.13547087602444, 0.5003270373238773);
+    let k = (PI * f0 / rate).tan();
+    let a0 = 1.0 + k / q + k * k;
+    let pass = Biquad {
+        b: [1.0, -2.0, 1.0],
+        a: [2.0 * (k * k - 1.0) / a0, (1.0 - k / q + k * k) / a0],
+        ..Default::default()
+    };
+
+    [shelf, pass]
+}
+
+/// Loudness measurement of one or more recordings
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Loudness {
+    /// Weighted mean square of every 100ms, overlapped 4 at a time into the 400ms gating blocks
+    steps: Vec<f64>,
+    /// Highest absolute sample
+    pub peak: f32,
+}
+
+impl Loudness {
+    /// Measure interleaved SAMPLES
+    pub fn measure(samples: &[f32], channels: usize, rate: u32) -> Self {
+        let mut meter = Meter::new(channels, rate);
+        meter.push(samples);
+        meter.finish()
+    }
+
+    /// Gated integrated loudness in LUFS. None if everything is below the -70 LUFS absolute gate
+    pub fn integrated(&self) -> Option<f64> {
+        let lufs = |power: f64| -0.691 + 10.0 * power.log10();
+        let blocks: Vec<f64> = self.steps.windows(4).map(|w| w.iter().sum::<f64>() / 4.0).collect();
+
+        let gated = |gate: f64| {
+            let passed: Vec<f64> = blocks.iter().copied().filter(|b| lufs(*b) > gate).collect();
+            (!passed.is_empty()).then(|| passed.iter().sum::<f64>() / passed.len() as f64)
+        };
+
+        let relative = lufs(gated(-70.0)?) - 10.0;
+        gated(relative.max(-70.0)).map(lufs)
+    }
+
+    /// ReplayGain 2.0 gain in dB
+    pub fn gain(&self) -> Option<f64> {
+        self.integrated().map(|l| REFERENCE - l)
+    }
+
+    /// Combine with the measurement of the following recording
+    pub fn extend(&mut self, other: &Self) {
+        self.steps.extend_from_slice(&other.steps);
+        self.peak = self.peak.max(other.peak);
+    }
+}
+
+/// Running loudness measurement
+struct Meter {
+    filters: Vec<[Biquad; 2]>,
+    weights: Vec<f64>,
+    step: usize,
+    frames: usize,
+    sum: f64,
+    result: Loudness,
+}
+
+impl Meter {
+    fn new(channels: usize, rate: u32) -> Self {
+        Self {
+            filters: vec![k_weighting(rate); channels],
+            // 5.1 order; LFE is ignored and surrounds are boosted
+            weights: (0..channels)
+                .map(|n| match (channels, n) {
+                    (6.., 3) => 0.0,
+                    (5.., 4 | 5) => 1.41,
+                    _ => 1.0,
+                })
+                .collect(),
+            step: (rate as usize / 10).max(1),
+            frames: 0,
+            sum: 0.0,
+            result: Loudness::default(),
+        }
+    }
+
+    fn push(&mut self, samples: &[f32]) {
+        let channels = self.filters.len();
+        if channels == 0 {
+            return;
+        }
+        for frame in samples.chunks_exact(channels) {
+            for (n, sample) in frame.iter().enumerate() {
+                self.result.peak = self.result.peak.max(sample.abs());
+                let [shelf, pass] = &mut self.filters[n];
+                let y = pass.process(shelf.process(*sample as f64));
+                self.sum += self.weights[n] * y * y;
+            }
+            self.frames += 1;
+            if self.frames == self.step {
+                self.result.steps.push(self.sum / self.step as f64);
+                self.frames = 0;
+                self.sum = 0.0;
+            }
+        }
+    }
+
+    /// Drops the trailing partial step like any other incomplete gating block
+    fn finish(self) -> Loudness {
+        self.result
+    }
+}
+
+/// Decode a whole track and measure it
+pub fn analyze(track: &Track) -> Result<Loudness, Box<dyn Error>> {
+    let mut format = track.probe().ok_or("Could not read the file's format")?.format;
+    let source = format.default_track().ok_or("File has no audio")?;
+    let id = source.id;
+    let mut decoder = symphonia::default::get_codecs().make(&source.codec_params, &Default::default())?;
+
+    let mut meter: Option<Meter> = None;
+    let mut buffer: Option<SampleBuffer<f32>> = None;
+    loop {
+        let packet = match format.next_packet() {
+            Ok(packet) => packet,
+            Err(SymError::IoError(e)) if e.kind() == std::io::ErrorKind::UnexpectedEof => break,
+            Err(e) => return Err(e.into()),
+        };
+        if packet.track_id() != id {
+            continue;
+        }
+        let audio = match decoder.decode(&packet) {
+            Ok(audio) => audio,
+            // a corrupt frame or two shouldn't spoil the whole track
+            Err(SymError::DecodeError(e)) => {
+                debug!("REPLAYGAIN skipping bad packet: {}", e);
+                continue;
+            }
+            Err(e) => return Err(e.into()),
+        };
+        let spec = *audio.spec();
+        let meter = meter.get_or_insert_with(|| Meter::new(spec.channels.count(), spec.rate));
+        if buffer.as_ref().is_none_or(|b| b.capacity() < audio.capacity()) {
+            buffer = Some(SampleBuffer::new(audio.capacity() as u64, spec));
+        }
+        let buffer = buffer.as_mut().unwrap();
+        buffer.copy_interleaved_ref(audio);
+        meter.push(buffer.samples());
+    }
+
+    Ok(meter.ok_or("File has no audio")?.finish())
+}
+
+// ## Loudness ## }}}
+
+// ## Tags ## {{{
+
+/// Track and album ReplayGain tag values
+#[derive(Clone, Debug, PartialEq)]
+pub struct Gains {
+    /// Track gain in dB
+    pub track_gain: f64,
+    /// Track peak as a linear amplitude
+    pub track_peak: f32,
+    /// Album gain in dB
+    pub album_gain: f64,
+    /// Album peak as a linear amplitude
+    pub album_peak: f32,
+}
+
+impl Gains {
+    /// Tag keys and values
+    fn tags(&self) -> [(&'static str, String); 4] {
+        [
+            (KEYS[0], format!("{:+.2} dB", self.track_gain)),
+            (KEYS[1], format!("{:.6}", self.track_peak)),
+            (KEYS[2], format!("{:+.2} dB", self.album_gain)),
+            (KEYS[3], format!("{:.6}", self.album_peak)),
+        ]
+    }
+}
+
+/// Write GAINS into the file at PATH, replacing any ReplayGain tags already there
+pub fn write(path: &Path, gains: &Gains) -> Result<(), Box<dyn Error>> {
+    write_tags(path, &gains.tags())
+}
+
+/// Write TAGS, as Vorbis comment keys and values, into the file at PATH, replacing any of the same keys
+pub fn write_tags(path: &Path, tags: &[(&str, String)]) -> Result<(), Box<dyn Error>> {
+    match path.extension().and_then(|e| e.to_str()).map(|e| e.to_lowercase()).as_deref() {
+        Some("mp3") => write_id3(path, tags),
+        Some("flac") => write_flac(path, tags),
+        _ => Err("Can only write tags to mp3 and flac files".into()),
+    }
+}
+
+/// ID3v2 frames for keys that have one. Anything else goes in a TXXX frame
+const ID3_FRAMES: &[(&str, &str)] = &[("BPM", "TBPM")];
+
+fn write_id3(path: &Path, tags: &[(&str, String)]) -> Result<(), Box<dyn Error>> {
+    use id3::{frame::ExtendedText, Tag, TagLike, Version};
+    let mut tag = match Tag::read_from_path(path) {
+        Ok(tag) => tag,
+        Err(id3::Error {
+            kind: id3::ErrorKind::NoTag, ..
+        }) => Tag::new(),
+        Err(e) => return Err(e.into()),
+    };
+    let version = match tag.version() {
+        Version::Id3v22 => Version::Id3v23,
+        v => v,
+    };
+    for (key, value) in tags {
+        match ID3_FRAMES.iter().find(|(k, _)| k.eq_ignore_ascii_case(key)) {
+            Some((_, id)) => tag.set_text(*id, value.clone()),
+            None => {
+                tag.remove_extended_text(Some(key), None);
+                tag.add_frame(ExtendedText {
+                    description: key.to_string(),
+                    value: value.clone(),
+                });
+            }
+        }
+    }
+    tag.write_to_path(path, version)?;
+    Ok(())
+}
+
+/// FLAC metadata block types
+const STREAMINFO: u8 = 0;
+const PADDING: u8 = 1;
+const VORBIS_COMMENT: u8 = 4;
+/// Padding added when the comments outgrow the file's existing padding
+const NEW_PADDING: usize = 4096;
+
+/// Vorbis comment block body with COMMENTS replacing any of the same keys in BODY
+fn vorbis_comment(body: Option<&[u8]>, comments: &[(&str, String)]) -> Result<Vec<u8>, Box<dyn Error>> {
+    let malformed = "Malformed FLAC Vorbis comment";
+    let mut vendor: &[u8] = b"ompl";
+    let mut kept: Vec<&[u8]> = Vec::new();
+
+    if let Some(body) = body {
+        // strings are a little endian length then that many bytes
+        let mut pos = 0;
+        let mut take = |len: Option<usize>| -> Result<&[u8], &str> {
+            let len = match len {
+                Some(len) => len,
+                None => {
+                    let bytes = body.get(pos..pos + 4).ok_or(malformed)?;
+                    pos += 4;
+                    u32::from_le_bytes(bytes.try_into().unwrap()) as usize
+                }
+            };
+            let bytes = body.get(pos..pos + len).ok_or(malformed)?;
+            pos += len;
+            Ok(bytes)
+        };
+        vendor = take(None)?;
+        let count = u32::from_le_bytes(take(Some(4))?.try_into().unwrap());
+        for _ in 0..count {
+            let comment = take(None)?;
+            let key = comment.split(|b| *b == b'=').next().unwrap_or_default();
+            if !comments.iter().any(|(k, _)| k.as_bytes().eq_ignore_ascii_case(key)) {
+                kept.push(comment);
+            }
+        }
+    }
+
+    let added: Vec<Vec<u8>> = comments.iter().map(|(k, v)| format!("{}={}", k, v).into_bytes()).collect();
+    let mut result = Vec::new();
+    result.extend_from_slice(&(vendor.len() as u32).to_le_bytes());
+    result.extend_from_slice(vendor);
+    result.extend_from_slice(&((kept.len() + added.len()) as u32).to_le_bytes());
+    for comment in kept.into_iter().chain(added.iter().map(|c| c.as_slice())) {
+        result.extend_from_slice(&(comment.len() as u32).to_le_bytes());
+        result.extend_from_slice(comment);
+    }
+    Ok(result)
+}
+
+fn write_flac(path: &Path, tags: &[(&str, String)]) -> Result<(), Box<dyn Error>> {
+    let mut file = File::options().read(true).write(true).open(path)?;
+    let mut magic = [0u8; 4];
+    file.read_exact(&mut magic)?;
+    if &magic != b"fLaC" {
+        return Err("Not a FLAC stream".into());
+    }
+
+    // (type, body) of every block but padding, which gets rebuilt
+    let mut blocks: Vec<(u8, Vec<u8>)> = Vec::new();
+    loop {
+        let mut header = [0u8; 4];
+        file.read_exact(&mut header)?;
+        let kind = header[0] & 0x7F;
+        let mut body = vec![0u8; u32::from_be_bytes([0, header[1], header[2], header[3]]) as usize];
+        file.read_exact(&mut body)?;
+        if kind != PADDING {
+            blocks.push((kind, body));
+        }
+        if header[0] & 0x80 != 0 {
+            break;
+        }
+    }
+    if blocks.first().map(|b| b.0) != Some(STREAMINFO) {
+        return Err("FLAC stream doesn't start with STREAMINFO".into());
+    }
+    let audio_start = file.stream_position()? as usize;
+
+    let comment = vorbis_comment(blocks.iter().find(|b| b.0 == VORBIS_COMMENT).map(|b| b.1.as_slice()), tags)?;
+    match blocks.iter_mut().find(|b| b.0 == VORBIS_COMMENT) {
+        Some(block) => block.1 = comment,
+        None => blocks.insert(1, (VORBIS_COMMENT, comment)),
+    }
+
+    let used: usize = 4 + blocks.iter().map(|b| 4 + b.1.len()).sum::<usize>();
+    // reuse the old padding to rewrite only the header when possible
+    let (padding, in_place) = match audio_start.checked_sub(used + 4) {
+        Some(padding) => (padding, true),
+        None => (NEW_PADDING, false),
+    };
+    if padding >= 1 << 24 {
+        return Err("FLAC padding too large".into());
+    }
+    blocks.push((PADDING, vec![0; padding]));
+
+    let mut header = b"fLaC".to_vec();
+    let last = blocks.len() - 1;
+    for (n, (kind, body)) in blocks.iter().enumerate() {
+        if body.len() >= 1 << 24 {
+            return Err("FLAC metadata block too large".into());
+        }
+        header.push(kind | if n == last { 0x80 } else { 0 });
+        header.extend_from_slice(&(body.len() as u32).to_be_bytes()[1..]);
+        header.extend_from_slice(body);
+    }
+
+    if in_place {
+        file.seek(SeekFrom::Start(0))?;
+        file.write_all(&header)?;
+    } else {
+        // write beside the original and swap so a failure never leaves half a file
+        let mut audio = Vec::new();
+        file.read_to_end(&mut audio)?;
+        let mut temp = path.as_os_str().to_owned();
+        temp.push(".ompl-tmp");
+        let temp = PathBuf::from(temp);
+        let result = File::create(&temp)
+            .and_then(|mut f| f.write_all(&header).and_then(|_| f.write_all(&audio)).and_then(|_| f.sync_all()))
+            .and_then(|_| fs::rename(&temp, path));
+        if result.is_err() {
+            let _ = fs::remove_file(&temp);
+        }
+        result?;
+    }
+    Ok(())
+}
+
+// ## Tags ## }}}
+
+// ## Scan ## {{{
+
+/// Progress of [`scan`]
+#[derive(Debug)]
+pub enum Progress<'a> {
+    /// N of TOTAL files that need tags was measured, or failed to be
+    Analyzed {
+        /// 1-based count of files finished
+        n: usize,
+        /// Files that will be measured
+        total: usize,
+        /// The file
+        track: &'a Track,
+        /// Its measurement
+        result: &'a Result<Loudness, Box<dyn Error>>,
+    },
+    /// An album was measured and is about to be tagged
+    Album {
+        /// Album tag, or the directory name if untagged
+        name: &'a str,
+        /// Album gain in dB
+        gain: Option<f64>,
+    },
+    /// Tags were written to a file, or failed to be
+    Written {
+        /// The file
+        track: &'a Track,
+        /// The write
+        result: &'a Result<(), Box<dyn Error>>,
+    },
+}
+
+/// Measure and tag every mp3 and flac file under PATH missing ReplayGain tags.
+/// Files are grouped into albums by directory and album tag, and an album with any untagged file is measured in full.
+/// FORCE rescans everything. Stops before the next album once CANCEL is set. Returns the number of files tagged
+pub fn scan<F: FnMut(Progress)>(path: &Path, force: bool, cancel: &Cancel, mut report: F) -> usize {
+    let types: Vec<String> = TYPES.iter().map(|t| t.to_string()).collect();
+    let mut albums: BTreeMap<(PathBuf, String), Vec<Track>> = BTreeMap::new();
+    for mut track in super::track::find_tracks_cancellable(path, &types, false, cancel) {
+        if cancel.cancelled() {
+            return 0;
+        }
+        track.load_meta();
+        let directory = track.path().parent().map(Path::to_path_buf).unwrap_or_default();
+        let album = track.tags().get("album").cloned().unwrap_or_default();
+        albums.entry((directory, album)).or_default().push(track);
+    }
+    albums.retain(|_, tracks| {
+        force
+            || tracks.iter().any(|t| {
+                !["replaygain_track_gain", "replaygain_album_gain"]
+                    .iter()
+                    .all(|k| t.tags().contains_key(*k))
+            })
+    });
+
+    let total = albums.values().map(Vec::len).sum();
+    let mut n = 0;
+    let mut tagged = 0;
+    for ((directory, album), tracks) in albums {
+        let mut whole = Loudness::default();
+        let mut measured = Vec::new();
+        for track in tracks.iter() {
+            // stopped between albums' worth of tags, as album gain needs every track measured
+            if cancel.cancelled() {
+                return tagged;
+            }
+            let result = analyze(track);
+            n += 1;
+            report(Progress::Analyzed {
+                n,
+                total,
+                track,
+                result: &result,
+            });
+            if let Ok(loudness) = result {
+                whole.extend(&loudness);
+                measured.push((track, loudness));
+            }
+        }
+
+        let name = match album.is_empty() {
+            true => directory.file_name().map(|s| s.to_string_lossy().to_string()).unwrap_or_default(),
+            false => album,
+        };
+        let album_gain = whole.gain();
+        report(Progress::Album {
+            name: &name,
+            gain: album_gain,
+        });
+
+        for (track, loudness) in measured {
+            // silent files are left at unity
+            let gains = Gains {
+                track_gain: loudness.gain().unwrap_or(0.0),
+                track_peak: loudness.peak,
+                album_gain: album_gain.unwrap_or(0.0),
+                album_peak: whole.peak,
+            };
+            let result = write(track.path(), &gains);
+            if result.is_ok() {
+                tagged += 1;
+            }
+            report(Progress::Written { track, result: &result });
+        }
+    }
+    tagged
+}
+
+// ## Scan ## }}}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sine(amplitude: f32, secs: f32, channels: usize, rate: u32) -> Vec<f32> {
+        (0..(secs * rate as f32) as usize)
+            .flat_map(|n| {
+                let s = amplitude * (2.0 * std::f32::consts::PI * 1000.0 * n as f32 / rate as f32).sin();
+                std::iter::repeat_n(s, channels)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn reference_tone() {
+        // BS.1770: a full scale 1kHz sine in both stereo channels reads about 0 LUFS
+        for rate in [44100, 48000] {
+            let loudness = Loudness::measure(&sine(1.0, 5.0, 2, rate), 2, rate);
+            let lufs = loudness.integrated().unwrap();
+            assert!(lufs.abs() < 0.1, "{} at {}", lufs, rate);
+            assert!((loudness.peak - 1.0).abs() < 1e-3);
+        }
+        let lufs = Loudness::measure(&sine(0.1, 5.0, 2, 48000), 2, 48000).integrated().unwrap();
+        assert!((lufs + 20.0).abs() < 0.1, "{}", lufs);
+    }
+
+    #[test]
+    fn gated() {
+        let tone = Loudness::measure(&sine(0.5, 10.0, 1, 48000), 1, 48000);
+        let mut gapped = Loudness::measure(&vec![0.0; 48000 * 5], 1, 48000);
+        // only the few blocks straddling the edge count partially
+        gapped.extend(&tone);
+        assert!((tone.integrated().unwrap() - gapped.integrated().unwrap()).abs() < 0.1);
+        assert_eq!(Loudness::measure(&vec![0.0; 48000], 1, 48000).integrated(), None);
+        assert!((tone.gain().unwrap() - (REFERENCE - tone.integrated().unwrap())).abs() < 1e-9);
+    }
+
+    #[test]
+    fn comments() {
+        let gains = Gains {
+            track_gain: -6.5,
+            track_peak: 0.9,
+            album_gain: -7.0,
+            album_peak: 1.0,
+        };
+        let first = vorbis_comment(None, &[("TITLE", "a".to_string()), ("replaygain_track_gain", "+1.00 dB".to_string())]).unwrap();
+        let second = vorbis_comment(Some(&first), &gains.tags()).unwrap();
+        let text = String::from_utf8_lossy(&second);
+        assert!(text.contains("TITLE=a"));
+        assert!(text.contains("REPLAYGAIN_TRACK_GAIN=-6.50 dB"));
+        assert!(!text.contains("+1.00 dB"));
+        assert_eq!(u32::from_le_bytes(second[8..12].try_into().unwrap()), 5);
+    }
+}
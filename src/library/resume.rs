@@ -0,0 +1,85 @@
+#![warn(missing_docs)]
+
+use super::{Filter, Library};
+use crate::logging::*;
+
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+/// What the daemon was doing when it exited, put back by `ompl main --resume`
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Resume {
+    track: Option<PathBuf>,
+    position: Option<Duration>,
+    playing: bool,
+    filters: Vec<Filter>,
+    sorters: Vec<String>,
+}
+
+impl Resume {
+    /// Snapshot LIBRARY's track, position, play state, and queue
+    pub fn from_library(library: &Library) -> Self {
+        Self {
+            track: library.track_get().map(|t| t.path().clone()),
+            position: library.times().map(|t| t.0),
+            playing: library.playing(),
+            filters: library.get_filters(),
+            sorters: library.get_sorters(),
+        }
+    }
+
+    /// None if nothing was saved or it can't be read
+    pub fn load(path: &Path) -> Option<Self> {
+        let bytes = std::fs::read(path).ok()?;
+        match bincode::deserialize(&bytes) {
+            Ok(resume) => Some(resume),
+            Err(e) => {
+                info!("Discarding unreadable resume file {}: {}", path.display(), e);
+                None
+            }
+        }
+    }
+
+    /// Write to PATH
+    pub fn save(&self, path: &Path) {
+        let result = try_block!({
+            std::fs::write(path, bincode::serialize(self)?)?;
+            Ok(())
+        });
+        if let Err(e) = result {
+            error!("Could not save resume file to {}: {}", path.display(), e)
+        }
+    }
+
+    /// Put the queue and track back into LIBRARY, seeking to the old position once the player's ready.
+    /// Filters and sorters given on launch win. Starts paused unless PLAY and it was playing before
+    pub fn restore(&self, library: &Arc<Library>, play: bool) {
+        if library.get_filters().is_empty() && !self.filters.is_empty() {
+            library.set_filters(self.filters.clone())
+        }
+        if library.get_sorters().is_empty() && !self.sorters.is_empty() {
+            library.set_sorters(self.sorters.clone())
+        }
+
+        let Some(track) = self
+            .track
+            .as_ref()
+            .and_then(|path| library.get_tracks().into_iter().find(|t| t.path() == path))
+        else {
+            return;
+        };
+        // the player only decodes, and so can only seek, once it's been started
+        library.play_track(Some(track));
+        if !(play && self.playing) {
+            library.pause()
+        }
+
+        let Some(position) = self.position.filter(|p| !p.is_zero()) else {
+            return;
+        };
+        library.seek_when_seekable(position);
+    }
+}
@@ -0,0 +1,348 @@
+#![warn(missing_docs)]
+
+//! Library sharing over plain HTTP.
+//! `GET /` returns a JSON [`Index`] of every track, and each track's file is served at its entry's url with range support
+//! so a remote player can seek. Only files currently in the library are reachable.
+//!
+//! Another instance appends `ompl://host:port/` to get [`Track`]s that stream their files from here.
+
+use super::track::Tags;
+use super::{http, Library, Track};
+use crate::logging::*;
+
+use std::collections::hash_map::DefaultHasher;
+use std::error::Error;
+use std::hash::{Hash, Hasher};
+use std::io::{self, BufRead, BufReader, ErrorKind, Read, Seek, SeekFrom, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Weak};
+use std::thread;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use symphonia::core::io::MediaSource;
+
+/// Port shared on when none is given
+pub const DEFAULT_PORT: u16 = 18347;
+/// For connecting to and reading from a remote library, and for clients of this one
+const TIMEOUT: Duration = Duration::from_secs(10);
+/// Most requests answered at once. Any more are turned away until one finishes
+const MAX_REQUESTS: usize = 16;
+/// Forward seeks shorter than this read through the open response instead of starting a new one
+const SKIP: u64 = 64 * 1024;
+/// Tags filled in from the file on this machine, which would only leak its layout to clients
+const LOCAL_TAGS: &[&str] = &["path", "filename", "mtime", "filesize"];
+
+/// One shared track
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Entry {
+    /// File location relative to the server root
+    pub url: String,
+    /// All tags, lowercased keys
+    pub tags: Tags,
+    /// Length in seconds if known
+    pub duration: Option<f64>,
+}
+
+/// Everything a server shares
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Index {
+    /// Tracks in library order
+    pub tracks: Vec<Entry>,
+}
+
+// ## Server ## {{{
+
+/// Id of a track's file, stable for as long as the server binary and path stay the same
+fn track_id(track: &Track) -> String {
+    let mut hasher = DefaultHasher::new();
+    track.path().hash(&mut hasher);
+    format!("{:016x}", hasher.finish())
+}
+
+fn track_url(track: &Track) -> String {
+    format!(
+        "/track/{}.{}",
+        track_id(track),
+        track.path().extension().and_then(|e| e.to_str()).unwrap_or("audio")
+    )
+}
+
+fn entry(track: &Track) -> Entry {
+    Entry {
+        url: track_url(track),
+        tags: track
+            .tags()
+            .iter()
+            .filter(|(tag, _)| !LOCAL_TAGS.contains(&tag.as_str()))
+            .map(|(tag, value)| (tag.clone(), value.clone()))
+            .collect(),
+        duration: track.duration().map(|d| d.as_secs_f64()),
+    }
+}
+
+fn index(library: &Library) -> Index {
+    Index {
+        tracks: library
+            .get_tracks()
+            .iter()
+            // their files aren't here to serve
+            .filter(|track| !track.is_remote())
+            .map(|track| entry(track))
+            .collect(),
+    }
+}
+
+fn respond(mut stream: TcpStream, library: &Weak<Library>) -> Result<(), Box<dyn Error>> {
+    let request = http::Request::read(&stream, TIMEOUT)?;
+    if request.method != "GET" && !request.is_head() {
+        return Ok(http::empty(&mut stream, "405 Method Not Allowed")?);
+    }
+    // only held once there's a request, so an idle connection doesn't keep the library alive
+    let library = library.upgrade().ok_or("Library is closed")?;
+
+    if request.target == "/" || request.target == "/index.json" {
+        return Ok(http::body(
+            &mut stream,
+            &request,
+            "application/json",
+            &serde_json::to_vec(&index(&library))?,
+        )?);
+    }
+    match library.get_tracks().into_iter().find(|t| track_url(t) == request.target) {
+        Some(track) => http::file(&mut stream, &request, track.path(), "")?,
+        None => http::empty(&mut stream, "404 Not Found")?,
+    }
+    Ok(())
+}
+
+/// Share LIBRARY at ADDRESS until the library is dropped. Returns the address actually bound
+pub fn serve(library: &Arc<Library>, address: &str) -> Result<SocketAddr, Box<dyn Error>> {
+    let listener = TcpListener::bind(address)?;
+    let bound = listener.local_addr()?;
+    let library: Weak<Library> = Arc::downgrade(library);
+    let active = Arc::new(AtomicUsize::new(0));
+    thread::Builder::new().name(String::from("SHARE Server")).spawn(move || {
+        for mut stream in listener.incoming().flatten() {
+            if library.strong_count() == 0 {
+                break;
+            }
+            if active.fetch_add(1, Ordering::SeqCst) >= MAX_REQUESTS {
+                active.fetch_sub(1, Ordering::SeqCst);
+                let _ = stream.write_all(b"HTTP/1.1 503 Service Unavailable\r\nContent-Length: 0\r\nConnection: close\r\n\r\n");
+                continue;
+            }
+            let (library, active_thread) = (library.clone(), active.clone());
+            let spawned = thread::Builder::new().name(String::from("SHARE Request")).spawn(move || {
+                if let Err(e) = respond(stream, &library) {
+                    debug!("Share server: {}", e)
+                }
+                active_thread.fetch_sub(1, Ordering::SeqCst);
+            });
+            if let Err(e) = spawned {
+                active.fetch_sub(1, Ordering::SeqCst);
+                error!("Could not spawn share request thread: {}", e);
+            }
+        }
+    })?;
+    Ok(bound)
+}
+
+// ## Server ## }}}
+
+// ## Client ## {{{
+
+/// Split `ompl://host[:port]/path` into `host:port` and `/path`
+fn split_url(url: &str) -> Option<(String, String)> {
+    let rest = url.strip_prefix("ompl://")?;
+    let (host, path) = match rest.split_once('/') {
+        Some((host, path)) => (host, format!("/{}", path)),
+        None => (rest, String::from("/")),
+    };
+    if host.is_empty() {
+        return None;
+    }
+    let host = match host.rsplit_once(':').is_some_and(|(_, port)| port.parse::<u16>().is_ok()) {
+        true => host.to_string(),
+        false => format!("{}:{}", host, DEFAULT_PORT),
+    };
+    Some((host, path))
+}
+
+/// GET PATH from HOST starting at byte START.
+/// Returns the reader positioned at START and the file's full length if the server gave it
+fn get(host: &str, path: &str, start: u64) -> io::Result<(BufReader<TcpStream>, Option<u64>)> {
+    let address = host
+        .to_socket_addrs()?
+        .next()
+        .ok_or_else(|| io::Error::new(ErrorKind::NotFound, format!("Could not resolve {}", host)))?;
+    let mut stream = TcpStream::connect_timeout(&address, TIMEOUT)?;
+    stream.set_read_timeout(Some(TIMEOUT))?;
+
+    let mut request = format!("GET {} HTTP/1.1\r\nHost: {}\r\nConnection: close\r\n", path, host);
+    if start > 0 {
+        request += &format!("Range: bytes={}-\r\n", start);
+    }
+    request += "\r\n";
+    stream.write_all(request.as_bytes())?;
+
+    let mut reader = BufReader::new(stream);
+    let mut status = String::new();
+    reader.read_line(&mut status)?;
+    let status = status.split_whitespace().nth(1).unwrap_or_default().to_string();
+
+    let (mut length, mut total) = (None, None);
+    loop {
+        let mut header = String::new();
+        if reader.read_line(&mut header)? == 0 || header.trim().is_empty() {
+            break;
+        }
+        if let Some((key, value)) = header.split_once(':') {
+            match key.trim().to_ascii_lowercase().as_str() {
+                "content-length" => length = value.trim().parse::<u64>().ok(),
+                "content-range" => total = value.rsplit_once('/').and_then(|(_, t)| t.trim().parse::<u64>().ok()),
+                _ => (),
+            }
+        }
+    }
+
+    match status.as_str() {
+        "206" => Ok((reader, total)),
+        "200" => {
+            // range ignored, so skip up to it
+            io::copy(&mut (&mut reader).take(start), &mut io::sink())?;
+            Ok((reader, length))
+        }
+        _ => Err(io::Error::other(format!("GET {}{} returned {}", host, path, status))),
+    }
+}
+
+/// Tracks shared by the instance at URL, `ompl://host[:port]/`
+pub fn fetch(url: &str) -> Result<Vec<Track>, Box<dyn Error>> {
+    let (host, _) = split_url(url).ok_or(format!("Not an ompl://host:port/ url: {}", url))?;
+    let (body, _) = get(&host, "/", 0)?;
+    let index: Index = serde_json::from_reader(body)?;
+    Ok(index
+        .tracks
+        .into_iter()
+        .map(|entry| {
+            Track::remote(
+                format!("ompl://{}{}", host, entry.url),
+                entry.tags,
+                entry.duration.map(Duration::from_secs_f64),
+            )
+        })
+        .collect())
+}
+
+/// A shared track's file read over HTTP.
+/// Seeking reconnects at the new offset, so decoders can jump around like in a local file
+pub struct Stream {
+    host: String,
+    path: String,
+    position: u64,
+    length: Option<u64>,
+    /// Response currently being read at `position`
+    body: Option<BufReader<TcpStream>>,
+}
+
+impl Stream {
+    /// Start reading the `ompl://` URL
+    pub fn open(url: &str) -> io::Result<Self> {
+        let (host, path) = split_url(url).ok_or_else(|| io::Error::new(ErrorKind::InvalidInput, format!("Not an ompl:// url: {}", url)))?;
+        let (body, length) = get(&host, &path, 0)?;
+        Ok(Self {
+            host,
+            path,
+            position: 0,
+            length,
+            body: Some(body),
+        })
+    }
+}
+
+impl Read for Stream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() || self.length.is_some_and(|length| self.position >= length) {
+            return Ok(0);
+        }
+        let body = match self.body.as_mut() {
+            Some(body) => body,
+            None => self.body.insert(get(&self.host, &self.path, self.position)?.0),
+        };
+        let mut read = body.read(buf)?;
+        // the server gave up on this response early, so pick up where it left off
+        if read == 0 && self.length.is_some_and(|length| self.position < length) {
+            read = self.body.insert(get(&self.host, &self.path, self.position)?.0).read(buf)?;
+        }
+        self.position += read as u64;
+        Ok(read)
+    }
+}
+
+impl Seek for Stream {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let position = match pos {
+            SeekFrom::Start(position) => Some(position),
+            SeekFrom::Current(offset) => self.position.checked_add_signed(offset),
+            SeekFrom::End(offset) => self.length.and_then(|length| length.checked_add_signed(offset)),
+        }
+        .ok_or_else(|| io::Error::new(ErrorKind::InvalidInput, "Seek out of range"))?;
+
+        match (self.body.as_mut(), position.checked_sub(self.position)) {
+            (_, Some(0)) => (),
+            (Some(body), Some(skip)) if skip < SKIP => {
+                io::copy(&mut body.take(skip), &mut io::sink())?;
+            }
+            _ => self.body = None,
+        }
+        self.position = position;
+        Ok(position)
+    }
+}
+
+impl MediaSource for Stream {
+    fn is_seekable(&self) -> bool {
+        true
+    }
+
+    fn byte_len(&self) -> Option<u64> {
+        self.length
+    }
+}
+
+// ## Client ## }}}
+
+#[cfg(test)]
+mod tests {
+    use super::{entry, split_url, Track};
+
+    #[test]
+    fn urls() {
+        assert_eq!(split_url("ompl://box:1234/"), Some((String::from("box:1234"), String::from("/"))));
+        assert_eq!(split_url("ompl://box"), Some((String::from("box:18347"), String::from("/"))));
+        assert_eq!(
+            split_url("ompl://[::1]/track/0a.flac"),
+            Some((String::from("[::1]:18347"), String::from("/track/0a.flac")))
+        );
+        assert_eq!(split_url("ompl:///"), None);
+        assert_eq!(split_url("http://box/"), None);
+    }
+
+    #[test]
+    fn local_tags() {
+        let track = Track::tagged(
+            "/home/me/music/song.flac",
+            &[
+                ("title", "Song"),
+                ("path", "/home/me/music/song.flac"),
+                ("filesize", "100"),
+                ("ext", "flac"),
+            ],
+        );
+        let mut tags: Vec<String> = entry(&track).tags.into_keys().collect();
+        tags.sort();
+        assert_eq!(tags, ["ext", "title"]);
+    }
+}
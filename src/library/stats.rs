@@ -0,0 +1,575 @@
+#![warn(missing_docs)]
+
+use super::{bpm, Track};
+use crate::logging::*;
+
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+/// Leads files written since analyzer tags were added.
+/// Older files are led by [`MAGIC_V5`], [`MAGIC_V4`], [`MAGIC_V3`] or [`MAGIC_V2`],
+/// or are a bare bincode [`Stats`] holding only gain offsets
+const MAGIC: &[u8; 4] = b"OMS6";
+
+/// Leads files written since measured tempos were added, before analyzer tags
+const MAGIC_V5: &[u8; 4] = b"OMS5";
+
+/// Leads files written since quarantined tracks were added, before measured tempos
+const MAGIC_V4: &[u8; 4] = b"OMS4";
+
+/// Leads files written since loved tracks were added, before quarantined tracks
+const MAGIC_V3: &[u8; 4] = b"OMS3";
+
+/// Leads files written since bookmarks were added, before loved tracks
+const MAGIC_V2: &[u8; 4] = b"OMS2";
+
+/// A named point within a track
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Bookmark {
+    /// Unique per track
+    pub name: String,
+    /// Time from the start of the track
+    pub time: Duration,
+}
+
+/// Everything remembered about one track
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct TrackStats {
+    /// Manual gain in dB on top of replaygain
+    pub gain_offset: f32,
+    /// Sorted by time
+    pub bookmarks: Vec<Bookmark>,
+    /// Kept in the favorites view
+    pub loved: bool,
+    /// Failed to decode too many times, so it's skipped by next and shuffle
+    pub quarantined: bool,
+    /// Tempo measured from the decoded audio, for files without a bpm tag
+    pub bpm: Option<f32>,
+    /// Tags printed by the analyzer command. None if it never ran on the file
+    pub analyzed: Option<BTreeMap<String, String>>,
+}
+
+/// Stats files led by [`MAGIC_V5`]
+#[derive(Deserialize)]
+struct V5 {
+    tracks: HashMap<PathBuf, V5TrackStats>,
+}
+
+#[derive(Deserialize)]
+struct V5TrackStats {
+    gain_offset: f32,
+    bookmarks: Vec<Bookmark>,
+    loved: bool,
+    quarantined: bool,
+    bpm: Option<f32>,
+}
+
+impl From<V5> for Stats {
+    fn from(v5: V5) -> Self {
+        Self {
+            tracks: v5
+                .tracks
+                .into_iter()
+                .map(|(path, stats)| {
+                    (
+                        path,
+                        TrackStats {
+                            gain_offset: stats.gain_offset,
+                            bookmarks: stats.bookmarks,
+                            loved: stats.loved,
+                            quarantined: stats.quarantined,
+                            bpm: stats.bpm,
+                            ..Default::default()
+                        },
+                    )
+                })
+                .collect(),
+            file: None,
+        }
+    }
+}
+
+/// Stats files led by [`MAGIC_V4`]
+#[derive(Deserialize)]
+struct V4 {
+    tracks: HashMap<PathBuf, V4TrackStats>,
+}
+
+#[derive(Deserialize)]
+struct V4TrackStats {
+    gain_offset: f32,
+    bookmarks: Vec<Bookmark>,
+    loved: bool,
+    quarantined: bool,
+}
+
+impl From<V4> for Stats {
+    fn from(v4: V4) -> Self {
+        Self {
+            tracks: v4
+                .tracks
+                .into_iter()
+                .map(|(path, stats)| {
+                    (
+                        path,
+                        TrackStats {
+                            gain_offset: stats.gain_offset,
+                            bookmarks: stats.bookmarks,
+                            loved: stats.loved,
+                            quarantined: stats.quarantined,
+                            ..Default::default()
+                        },
+                    )
+                })
+                .collect(),
+            file: None,
+        }
+    }
+}
+
+/// Stats files led by [`MAGIC_V3`]
+#[derive(Deserialize)]
+struct V3 {
+    tracks: HashMap<PathBuf, V3TrackStats>,
+}
+
+#[derive(Deserialize)]
+struct V3TrackStats {
+    gain_offset: f32,
+    bookmarks: Vec<Bookmark>,
+    loved: bool,
+}
+
+impl From<V3> for Stats {
+    fn from(v3: V3) -> Self {
+        Self {
+            tracks: v3
+                .tracks
+                .into_iter()
+                .map(|(path, stats)| {
+                    (
+                        path,
+                        TrackStats {
+                            gain_offset: stats.gain_offset,
+                            bookmarks: stats.bookmarks,
+                            loved: stats.loved,
+                            ..Default::default()
+                        },
+                    )
+                })
+                .collect(),
+            file: None,
+        }
+    }
+}
+
+/// Stats files led by [`MAGIC_V2`]
+#[derive(Deserialize)]
+struct V2 {
+    tracks: HashMap<PathBuf, V2TrackStats>,
+}
+
+#[derive(Deserialize)]
+struct V2TrackStats {
+    gain_offset: f32,
+    bookmarks: Vec<Bookmark>,
+}
+
+impl From<V2> for Stats {
+    fn from(v2: V2) -> Self {
+        Self {
+            tracks: v2
+                .tracks
+                .into_iter()
+                .map(|(path, stats)| {
+                    (
+                        path,
+                        TrackStats {
+                            gain_offset: stats.gain_offset,
+                            bookmarks: stats.bookmarks,
+                            ..Default::default()
+                        },
+                    )
+                })
+                .collect(),
+            file: None,
+        }
+    }
+}
+
+/// Stats files from before [`MAGIC`]
+#[derive(Deserialize)]
+struct Legacy {
+    tracks: HashMap<PathBuf, LegacyTrackStats>,
+}
+
+#[derive(Deserialize)]
+struct LegacyTrackStats {
+    gain_offset: f32,
+}
+
+impl From<Legacy> for Stats {
+    fn from(legacy: Legacy) -> Self {
+        Self {
+            tracks: legacy
+                .tracks
+                .into_iter()
+                .map(|(path, stats)| {
+                    (
+                        path,
+                        TrackStats {
+                            gain_offset: stats.gain_offset,
+                            ..Default::default()
+                        },
+                    )
+                })
+                .collect(),
+            file: None,
+        }
+    }
+}
+
+/// Per-track data kept across runs, keyed by canonical path
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Stats {
+    tracks: HashMap<PathBuf, TrackStats>,
+    /// Where changes are written. None keeps everything in memory
+    #[serde(skip)]
+    file: Option<PathBuf>,
+}
+
+impl Stats {
+    /// Read FILE, starting fresh if it's missing.
+    /// A file that can't be read is moved aside to `FILE.bad` rather than overwritten,
+    /// and if even that fails nothing is written at all
+    pub fn open(file: PathBuf) -> Self {
+        let bytes = match std::fs::read(&file) {
+            Ok(bytes) => bytes,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                return Self {
+                    file: Some(file),
+                    ..Default::default()
+                }
+            }
+            Err(e) => {
+                error!("Could not read stats {}: {}, keeping them in memory only", file.display(), e);
+                return Self::default();
+            }
+        };
+        match Self::deserialize(&bytes) {
+            Ok(mut stats) => {
+                stats.file = Some(file);
+                stats
+            }
+            Err(e) => {
+                let bad = sibling(&file, ".bad");
+                match std::fs::rename(&file, &bad) {
+                    Ok(()) => {
+                        error!("Could not read stats {}: {}, moved to {}", file.display(), e, bad.display());
+                        Self {
+                            file: Some(file),
+                            ..Default::default()
+                        }
+                    }
+                    Err(move_e) => {
+                        error!(
+                            "Could not read stats {}: {}, keeping them in memory only as it couldn't be moved aside: {}",
+                            file.display(),
+                            e,
+                            move_e
+                        );
+                        Self::default()
+                    }
+                }
+            }
+        }
+    }
+
+    fn deserialize(bytes: &[u8]) -> bincode::Result<Self> {
+        if let Some(bytes) = bytes.strip_prefix(MAGIC) {
+            bincode::deserialize::<Self>(bytes)
+        } else if let Some(bytes) = bytes.strip_prefix(MAGIC_V5) {
+            bincode::deserialize::<V5>(bytes).map(Self::from)
+        } else if let Some(bytes) = bytes.strip_prefix(MAGIC_V4) {
+            bincode::deserialize::<V4>(bytes).map(Self::from)
+        } else if let Some(bytes) = bytes.strip_prefix(MAGIC_V3) {
+            bincode::deserialize::<V3>(bytes).map(Self::from)
+        } else if let Some(bytes) = bytes.strip_prefix(MAGIC_V2) {
+            bincode::deserialize::<V2>(bytes).map(Self::from)
+        } else {
+            bincode::deserialize::<Legacy>(bytes).map(Self::from)
+        }
+    }
+
+    fn serialize(&self) -> bincode::Result<Vec<u8>> {
+        let mut bytes = MAGIC.to_vec();
+        bincode::serialize_into(&mut bytes, self)?;
+        Ok(bytes)
+    }
+
+    /// Stats for the track at PATH, if any were ever stored
+    pub fn get(&self, path: &Path) -> Option<&TrackStats> {
+        self.tracks.get(path)
+    }
+
+    /// Change the stats for PATH, dropping the entry once it's back to defaults
+    pub fn update(&mut self, path: &Path, f: impl FnOnce(&mut TrackStats)) {
+        self.change(path, f);
+        self.save()
+    }
+
+    /// [`Stats::update`] each path in ITEMS with F and its value, saving once at the end
+    #[cfg(any(test, feature = "analyzer"))]
+    pub fn update_all<T>(&mut self, items: impl IntoIterator<Item = (PathBuf, T)>, f: impl Fn(&mut TrackStats, T)) {
+        for (path, value) in items {
+            self.change(&path, |s| f(s, value))
+        }
+        self.save()
+    }
+
+    fn change(&mut self, path: &Path, f: impl FnOnce(&mut TrackStats)) {
+        let entry = self.tracks.entry(path.to_path_buf()).or_default();
+        f(entry);
+        if *entry == TrackStats::default() {
+            self.tracks.remove(path);
+        }
+    }
+
+    /// Paths of every loved track, sorted
+    pub fn loved(&self) -> Vec<PathBuf> {
+        let mut loved: Vec<PathBuf> = self.tracks.iter().filter(|(_, s)| s.loved).map(|(p, _)| p.clone()).collect();
+        loved.sort();
+        loved
+    }
+
+    /// Paths of every quarantined track, sorted
+    pub fn quarantined(&self) -> Vec<PathBuf> {
+        let mut quarantined: Vec<PathBuf> = self.tracks.iter().filter(|(_, s)| s.quarantined).map(|(p, _)| p.clone()).collect();
+        quarantined.sort();
+        quarantined
+    }
+
+    /// Forget the track at PATH
+    pub fn remove(&mut self, path: &Path) {
+        if self.tracks.remove(path).is_some() {
+            self.save()
+        }
+    }
+
+    /// Follow a track that moved from FROM to TO
+    pub fn rename(&mut self, from: &Path, to: &Path) {
+        if let Some(stats) = self.tracks.remove(from) {
+            self.tracks.insert(to.to_path_buf(), stats);
+            self.save()
+        }
+    }
+
+    /// Put the stored values onto TRACK
+    pub fn apply(&self, track: &Track) {
+        track.gain_offset_set(self.get(track.path()).map(|s| s.gain_offset).unwrap_or_default())
+    }
+
+    /// Tag TRACK with the stored tempo and analyzer tags, unless its file has the same tags
+    pub fn tag(&self, track: &mut Track) {
+        let Some(stats) = self.get(track.path()) else { return };
+        let tempo = stats.bpm.map(|bpm| (bpm::TAG.to_string(), bpm::format(bpm)));
+        for (tag, value) in tempo.into_iter().chain(stats.analyzed.clone().into_iter().flatten()) {
+            if !track.tags().contains_key(&tag) {
+                track.tag_set(&tag, value)
+            }
+        }
+    }
+
+    fn save(&self) {
+        let Some(file) = self.file.as_ref() else { return };
+        // write beside the file and swap so a crash never leaves half of it
+        let temp = sibling(file, ".ompl-tmp");
+        let result = try_block!({
+            std::fs::write(&temp, self.serialize()?)?;
+            std::fs::rename(&temp, file)?;
+            Ok(())
+        });
+        if let Err(e) = result {
+            let _ = std::fs::remove_file(&temp);
+            error!("Could not save stats to {}: {}", file.display(), e)
+        }
+    }
+}
+
+/// FILE with SUFFIX added to its name
+fn sibling(file: &Path, suffix: &str) -> PathBuf {
+    let mut name = file.as_os_str().to_owned();
+    name.push(suffix);
+    PathBuf::from(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Bookmark, Stats, Track, MAGIC_V2, MAGIC_V3, MAGIC_V4, MAGIC_V5};
+    use std::collections::{BTreeMap, HashMap};
+    use std::path::{Path, PathBuf};
+    use std::time::Duration;
+
+    #[test]
+    fn defaults_dropped() {
+        let mut stats = Stats::default();
+        let path = Path::new("/music/song.flac");
+        stats.update(path, |s| s.gain_offset += 2.0);
+        assert_eq!(stats.get(path).map(|s| s.gain_offset), Some(2.0));
+        stats.update(path, |s| s.gain_offset -= 2.0);
+        assert!(stats.get(path).is_none());
+        let other = PathBuf::from("/music/other.flac");
+        stats.update_all([(path.to_path_buf(), true), (other.clone(), false)], |s, loved| s.loved = loved);
+        assert!(stats.get(path).is_some_and(|s| s.loved));
+        assert!(stats.get(&other).is_none());
+    }
+
+    #[test]
+    fn unreadable() {
+        let dir = std::env::temp_dir().join(format!("ompl-stats-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let file = dir.join("stats.bin");
+        std::fs::write(&file, b"OMS6 not bincode").unwrap();
+        let mut stats = Stats::open(file.clone());
+        assert!(stats.get(Path::new("/music/song.flac")).is_none());
+        stats.update(Path::new("/music/song.flac"), |s| s.loved = true);
+        // the old file is kept for recovery instead of overwritten
+        assert_eq!(std::fs::read(dir.join("stats.bin.bad")).unwrap(), b"OMS6 not bincode");
+        assert!(Stats::open(file).get(Path::new("/music/song.flac")).is_some_and(|s| s.loved));
+        assert!(!dir.join("stats.bin.ompl-tmp").exists());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn rename() {
+        let mut stats = Stats::default();
+        let (from, to) = (Path::new("/music/a.flac"), Path::new("/music/b.flac"));
+        stats.update(from, |s| s.gain_offset = -3.0);
+        stats.rename(from, to);
+        assert!(stats.get(from).is_none());
+        assert_eq!(stats.get(to).map(|s| s.gain_offset), Some(-3.0));
+    }
+
+    #[test]
+    fn legacy() {
+        let path = PathBuf::from("/music/song.flac");
+        let legacy: HashMap<PathBuf, f32> = [(path.clone(), 1.5)].into();
+        let stats = Stats::deserialize(&bincode::serialize(&legacy).unwrap()).unwrap();
+        assert_eq!(stats.get(&path).map(|s| s.gain_offset), Some(1.5));
+    }
+
+    #[test]
+    fn roundtrip() {
+        let mut stats = Stats::default();
+        let path = Path::new("/music/mix.flac");
+        let bookmark = Bookmark {
+            name: String::from("drop"),
+            time: Duration::from_secs(90),
+        };
+        stats.update(path, |s| s.bookmarks.push(bookmark.clone()));
+        let read = Stats::deserialize(&stats.serialize().unwrap()).unwrap();
+        assert_eq!(read.get(path).map(|s| s.bookmarks.clone()), Some(vec![bookmark]));
+    }
+
+    #[test]
+    fn loved() {
+        let mut stats = Stats::default();
+        let (a, b) = (Path::new("/music/b.flac"), Path::new("/music/a.flac"));
+        stats.update(a, |s| s.loved = true);
+        stats.update(b, |s| s.loved = true);
+        let read = Stats::deserialize(&stats.serialize().unwrap()).unwrap();
+        assert_eq!(read.loved(), vec![b.to_path_buf(), a.to_path_buf()]);
+        stats.update(a, |s| s.loved = false);
+        assert!(stats.get(a).is_none());
+    }
+
+    #[test]
+    fn v2() {
+        let path = PathBuf::from("/music/song.flac");
+        let bookmark = Bookmark {
+            name: String::from("intro"),
+            time: Duration::from_secs(5),
+        };
+        let v2: HashMap<PathBuf, (f32, Vec<Bookmark>)> = [(path.clone(), (-1.0, vec![bookmark.clone()]))].into();
+        let mut bytes = MAGIC_V2.to_vec();
+        bincode::serialize_into(&mut bytes, &v2).unwrap();
+        let stats = Stats::deserialize(&bytes).unwrap();
+        let read = stats.get(&path).unwrap();
+        assert_eq!((read.gain_offset, read.bookmarks.clone(), read.loved), (-1.0, vec![bookmark], false));
+    }
+
+    #[test]
+    fn v3() {
+        let path = PathBuf::from("/music/song.flac");
+        let v3: HashMap<PathBuf, (f32, Vec<Bookmark>, bool)> = [(path.clone(), (0.5, vec![], true))].into();
+        let mut bytes = MAGIC_V3.to_vec();
+        bincode::serialize_into(&mut bytes, &v3).unwrap();
+        let stats = Stats::deserialize(&bytes).unwrap();
+        let read = stats.get(&path).unwrap();
+        assert_eq!((read.gain_offset, read.loved, read.quarantined), (0.5, true, false));
+    }
+
+    #[test]
+    fn quarantined() {
+        let mut stats = Stats::default();
+        let path = Path::new("/music/broken.mp3");
+        stats.update(path, |s| s.quarantined = true);
+        let read = Stats::deserialize(&stats.serialize().unwrap()).unwrap();
+        assert_eq!(read.quarantined(), vec![path.to_path_buf()]);
+        assert!(read.loved().is_empty());
+    }
+
+    #[test]
+    fn v4() {
+        let path = PathBuf::from("/music/song.flac");
+        let v4: HashMap<PathBuf, (f32, Vec<Bookmark>, bool, bool)> = [(path.clone(), (0.0, vec![], false, true))].into();
+        let mut bytes = MAGIC_V4.to_vec();
+        bincode::serialize_into(&mut bytes, &v4).unwrap();
+        let stats = Stats::deserialize(&bytes).unwrap();
+        let read = stats.get(&path).unwrap();
+        assert_eq!((read.quarantined, read.bpm), (true, None));
+    }
+
+    #[test]
+    fn bpm() {
+        let mut stats = Stats::default();
+        let mut track = Track::tagged("/music/song.flac", &[]);
+        stats.update(track.path(), |s| s.bpm = Some(127.6));
+        let read = Stats::deserialize(&stats.serialize().unwrap()).unwrap();
+        read.tag(&mut track);
+        assert_eq!(track.tagstring("<bpm>"), "128");
+        // the file's own tag wins
+        track.tag_set("bpm", String::from("64"));
+        read.tag(&mut track);
+        assert_eq!(track.tagstring("<bpm>"), "64");
+    }
+
+    #[test]
+    fn v5() {
+        let path = PathBuf::from("/music/song.flac");
+        // gain offset, bookmarks, loved, quarantined, bpm
+        type Entry = (f32, Vec<Bookmark>, bool, bool, Option<f32>);
+        let v5: HashMap<PathBuf, Entry> = [(path.clone(), (0.0, vec![], true, false, Some(90.0)))].into();
+        let mut bytes = MAGIC_V5.to_vec();
+        bincode::serialize_into(&mut bytes, &v5).unwrap();
+        let stats = Stats::deserialize(&bytes).unwrap();
+        let read = stats.get(&path).unwrap();
+        assert_eq!((read.loved, read.bpm, read.analyzed.clone()), (true, Some(90.0), None));
+    }
+
+    #[test]
+    fn analyzed() {
+        let mut stats = Stats::default();
+        let mut track = Track::tagged("/music/song.flac", &[]);
+        track.tag_set("mood", String::from("sad"));
+        let analyzed: BTreeMap<String, String> = [("mood", "happy"), ("energy", "0.8")].map(|(k, v)| (k.to_string(), v.to_string())).into();
+        stats.update(track.path(), |s| s.analyzed = Some(analyzed));
+        let read = Stats::deserialize(&stats.serialize().unwrap()).unwrap();
+        read.tag(&mut track);
+        // the file's own tags win
+        assert_eq!(track.tagstring("<mood> <energy>"), "sad 0.8");
+    }
+}
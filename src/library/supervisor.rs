@@ -0,0 +1,144 @@
+#![warn(missing_docs)]
+
+//! Worker threads run under watch, so a panic is logged and reported instead of silently
+//! stopping something like track advancement until the daemon restarts.
+//! Read with `ompl print threads`.
+
+use std::any::Any;
+use std::fmt::Display;
+
+use parking_lot::Mutex;
+
+/// Times one worker is restarted before it's left stopped, so a panic on every run can't spin
+pub const RESTART_LIMIT: u32 = 5;
+
+/// What a supervised thread is doing
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum WorkerState {
+    /// Started and not yet returned
+    Running,
+    /// Returned normally, usually because the library or frontend it served is gone
+    Finished,
+    /// Panicked and wasn't restarted, with the panic message
+    Crashed(String),
+}
+
+/// One supervised thread
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Worker {
+    /// Thread name like "LIBRARY Sort Server"
+    pub name: String,
+    /// Times it panicked and was started again
+    pub restarts: u32,
+    /// What it's doing now
+    pub state: WorkerState,
+}
+
+impl Display for Worker {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.state {
+            WorkerState::Running => write!(f, "{}: running", self.name)?,
+            WorkerState::Finished => write!(f, "{}: finished", self.name)?,
+            WorkerState::Crashed(message) => write!(f, "{}: crashed, {}", self.name, message)?,
+        }
+        if self.restarts > 0 {
+            write!(f, " ({} restarts)", self.restarts)?
+        }
+        Ok(())
+    }
+}
+
+/// Every thread started through [`super::Library::supervise`]
+#[derive(Debug, Default)]
+pub(crate) struct Supervisor {
+    workers: Mutex<Vec<Worker>>,
+}
+
+impl Supervisor {
+    /// Track a new worker, returning its index.
+    /// A finished worker with the same name is replaced, so reattaching the TUI doesn't pile them up
+    pub(crate) fn register(&self, name: &str) -> usize {
+        let mut workers = self.workers.lock();
+        if let Some(id) = workers.iter().position(|w| w.name == name && w.state == WorkerState::Finished) {
+            workers[id].restarts = 0;
+            workers[id].state = WorkerState::Running;
+            return id;
+        }
+        workers.push(Worker {
+            name: name.to_string(),
+            restarts: 0,
+            state: WorkerState::Running,
+        });
+        workers.len() - 1
+    }
+
+    /// Record a panic in worker ID with MESSAGE. True if it may be started again
+    pub(crate) fn panicked(&self, id: usize, message: &str, restart: bool) -> bool {
+        let mut workers = self.workers.lock();
+        let worker = &mut workers[id];
+        if restart && worker.restarts < RESTART_LIMIT {
+            worker.restarts += 1;
+            true
+        } else {
+            worker.state = WorkerState::Crashed(message.to_string());
+            false
+        }
+    }
+
+    pub(crate) fn finished(&self, id: usize) {
+        self.workers.lock()[id].state = WorkerState::Finished
+    }
+
+    pub(crate) fn workers(&self) -> Vec<Worker> {
+        self.workers.lock().clone()
+    }
+}
+
+/// The message passed to `panic!`, if it was a string
+pub(crate) fn panic_message(payload: &(dyn Any + Send)) -> String {
+    payload
+        .downcast_ref::<&str>()
+        .map(|s| s.to_string())
+        .or_else(|| payload.downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| String::from("unknown panic"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn restart_limit() {
+        let supervisor = Supervisor::default();
+        let id = supervisor.register("test");
+        for _ in 0..RESTART_LIMIT {
+            assert!(supervisor.panicked(id, "boom", true));
+        }
+        assert!(!supervisor.panicked(id, "boom", true));
+        let worker = &supervisor.workers()[id];
+        assert_eq!(worker.restarts, RESTART_LIMIT);
+        assert_eq!(worker.state, WorkerState::Crashed(String::from("boom")));
+        assert_eq!(worker.to_string(), "test: crashed, boom (5 restarts)");
+    }
+
+    #[test]
+    fn no_restart() {
+        let supervisor = Supervisor::default();
+        let id = supervisor.register("once");
+        let other = supervisor.register("other");
+        assert!(!supervisor.panicked(id, "boom", false));
+        supervisor.finished(other);
+        assert_eq!(supervisor.workers()[other].to_string(), "other: finished");
+        assert_eq!(supervisor.workers()[id].restarts, 0);
+        assert_eq!(supervisor.register("other"), other);
+        assert_eq!(supervisor.register("once"), 2);
+    }
+
+    #[test]
+    fn message() {
+        let payload = std::panic::catch_unwind(|| panic!("formatted {}", 1)).unwrap_err();
+        assert_eq!(panic_message(payload.as_ref()), "formatted 1");
+        let payload = std::panic::catch_unwind(|| panic!("plain")).unwrap_err();
+        assert_eq!(panic_message(payload.as_ref()), "plain");
+    }
+}
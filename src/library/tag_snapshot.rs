@@ -0,0 +1,152 @@
+#![warn(missing_docs)]
+
+//! Saved copies of every track's tags, for checking what a tagging session changed.
+
+use super::Library;
+use crate::logging::*;
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+/// Tags that follow from the file rather than what's written in it, so they'd only add noise to a diff
+const FILE_TAGS: &[&str] = &["path", "filename", "ext", "mtime", "added", "filesize"];
+
+/// Every track's path and tags at one point in time
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct TagSnapshot {
+    tracks: BTreeMap<PathBuf, BTreeMap<String, String>>,
+}
+
+impl TagSnapshot {
+    /// The tags of every track in LIBRARY whose file is still there
+    pub fn from_library(library: &Library) -> Self {
+        Self {
+            tracks: library
+                .get_tracks()
+                .iter()
+                // deleted files stay loaded until played, and offline ones can't be checked
+                .filter(|t| t.is_remote() || library.is_offline(t.path()) || t.path().exists())
+                .map(|t| {
+                    let tags = t
+                        .tags()
+                        .iter()
+                        .filter(|(k, _)| !FILE_TAGS.contains(&k.as_str()))
+                        .map(|(k, v)| (k.clone(), v.clone()))
+                        .collect();
+                    (t.path().clone(), tags)
+                })
+                .collect(),
+        }
+    }
+
+    /// None if nothing was saved at PATH or it can't be read
+    pub fn load(path: &Path) -> Option<Self> {
+        let bytes = std::fs::read(path).ok()?;
+        match bincode::deserialize(&bytes) {
+            Ok(snapshot) => Some(snapshot),
+            Err(e) => {
+                info!("Discarding unreadable snapshot {}: {}", path.display(), e);
+                None
+            }
+        }
+    }
+
+    /// Write to PATH
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        let result = try_block!({
+            std::fs::write(path, bincode::serialize(self)?)?;
+            Ok(())
+        });
+        result.map_err(|e| format!("Could not save snapshot to {}: {}", path.display(), e))
+    }
+
+    /// Number of tracks recorded
+    pub fn len(&self) -> usize {
+        self.tracks.len()
+    }
+
+    /// Whether no tracks were recorded
+    pub fn is_empty(&self) -> bool {
+        self.tracks.is_empty()
+    }
+
+    /// What changed going from this snapshot to NEWER, one line per file in path order.
+    /// Added files start with `+`, removed with `-`, and retagged with `~` followed by each changed tag
+    pub fn diff(&self, newer: &Self) -> Vec<String> {
+        let mut entries: Vec<(&PathBuf, Vec<String>)> = Vec::new();
+        for (path, tags) in newer.tracks.iter() {
+            let Some(old) = self.tracks.get(path) else {
+                entries.push((path, vec![format!("+ {}", path.display())]));
+                continue;
+            };
+            let mut lines: Vec<String> = tags
+                .iter()
+                .filter(|(k, v)| old.get(*k) != Some(v))
+                .map(|(k, v)| match old.get(k) {
+                    Some(was) => format!("  {}: {} -> {}", k, was, v),
+                    None => format!("  {}: + {}", k, v),
+                })
+                .collect();
+            lines.extend(
+                old.iter()
+                    .filter(|(k, _)| !tags.contains_key(*k))
+                    .map(|(k, v)| format!("  {}: - {}", k, v)),
+            );
+            if !lines.is_empty() {
+                lines.insert(0, format!("~ {}", path.display()));
+                entries.push((path, lines))
+            }
+        }
+        entries.extend(
+            self.tracks
+                .keys()
+                .filter(|p| !newer.tracks.contains_key(*p))
+                .map(|p| (p, vec![format!("- {}", p.display())])),
+        );
+        entries.sort_by(|a, b| a.0.cmp(b.0));
+        entries.into_iter().flat_map(|e| e.1).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn snapshot(tracks: &[(&str, &[(&str, &str)])]) -> TagSnapshot {
+        TagSnapshot {
+            tracks: tracks
+                .iter()
+                .map(|(p, tags)| (PathBuf::from(p), tags.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect()))
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn changes() {
+        let before = snapshot(&[
+            ("/m/a.flac", &[("title", "A"), ("genre", "Rock")]),
+            ("/m/b.flac", &[("title", "B")]),
+            ("/m/c.flac", &[("title", "C")]),
+        ]);
+        let after = snapshot(&[
+            ("/m/a.flac", &[("title", "A"), ("date", "1999")]),
+            ("/m/b.flac", &[("title", "B2")]),
+            ("/m/d.flac", &[("title", "D")]),
+        ]);
+        assert_eq!(
+            before.diff(&after),
+            [
+                "~ /m/a.flac",
+                "  date: + 1999",
+                "  genre: - Rock",
+                "~ /m/b.flac",
+                "  title: B -> B2",
+                "- /m/c.flac",
+                "+ /m/d.flac"
+            ]
+        );
+        assert!(after.diff(&after).is_empty());
+    }
+}
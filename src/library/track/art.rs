@@ -0,0 +1,285 @@
+#![warn(missing_docs)]
+
+//! Where album art is looked for, and in what order.
+
+use super::{RawImage, Track};
+#[cfg(feature = "album-art")]
+use crate::logging::*;
+
+#[cfg(feature = "album-art")]
+use std::io::{BufRead, BufReader, Cursor, Read, Write};
+#[cfg(feature = "album-art")]
+use std::net::{TcpStream, ToSocketAddrs};
+#[cfg(feature = "album-art")]
+use std::path::{Path, PathBuf};
+#[cfg(feature = "album-art")]
+use std::time::Duration;
+
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+
+/// Tags holding a link or path to a cover
+#[cfg(feature = "album-art")]
+const URL_TAGS: &[&str] = &["cover_url", "coverurl", "coverart_url"];
+/// File names tried after the ones from tags, for art that isn't named after anything
+#[cfg(feature = "album-art")]
+const GENERIC_NAMES: &[&str] = &["cover", "folder", "front"];
+/// Largest remote cover that'll be downloaded
+#[cfg(feature = "album-art")]
+const URL_MAX: u64 = 32 * 1024 * 1024;
+#[cfg(feature = "album-art")]
+const URL_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// A place art is looked for
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Serialize, Deserialize, ValueEnum)]
+pub enum ArtSource {
+    /// Pictures inside the file, including METADATA_BLOCK_PICTURE in ogg
+    Embedded,
+    /// cover.jpg, folder.jpg, or an image named after the album or title next to the file
+    Folder,
+    /// The same names one folder up when the file is in a "Disc 1" or "CD2" folder
+    Disc,
+    /// A cover_url tag, either a plain http:// link or a path
+    Url,
+}
+
+impl ArtSource {
+    /// Order art is looked for unless set otherwise
+    pub const DEFAULT: [Self; 4] = [Self::Embedded, Self::Folder, Self::Disc, Self::Url];
+}
+
+/// Whether a folder called NAME holds one disc of a multi-disc album, like "Disc 1", "CD2", or "disk 03 - Bonus"
+#[cfg(feature = "album-art")]
+fn is_disc_dir(name: &str) -> bool {
+    let name = name.trim_start().to_lowercase();
+    ["disc", "disk", "cd"].iter().any(|prefix| {
+        name.strip_prefix(prefix)
+            .map(|rest| rest.trim_start_matches([' ', '_', '-', '.']))
+            .is_some_and(|rest| rest.starts_with(|c: char| c.is_ascii_digit()))
+    })
+}
+
+/// What a single GET came back with
+#[cfg(feature = "album-art")]
+enum Response {
+    Body(Vec<u8>),
+    Redirect(String),
+}
+
+/// GET PATH from HOST without following redirects
+#[cfg(feature = "album-art")]
+fn http_once(host: &str, path: &str) -> Result<Response, Box<dyn std::error::Error>> {
+    let address = match host.contains(':') {
+        true => host.to_string(),
+        false => format!("{}:80", host),
+    };
+    let address = address.to_socket_addrs()?.next().ok_or("Could not resolve host")?;
+    let mut stream = TcpStream::connect_timeout(&address, URL_TIMEOUT)?;
+    stream.set_read_timeout(Some(URL_TIMEOUT))?;
+    stream.write_all(format!("GET {} HTTP/1.1\r\nHost: {}\r\nConnection: close\r\n\r\n", path, host).as_bytes())?;
+
+    let mut reader = BufReader::new(stream);
+    let mut status = String::new();
+    reader.read_line(&mut status)?;
+    let status = status.split_whitespace().nth(1).unwrap_or_default().to_string();
+    let mut location = None;
+    loop {
+        let mut header = String::new();
+        if reader.read_line(&mut header)? == 0 || header.trim().is_empty() {
+            break;
+        }
+        if let Some((key, value)) = header.split_once(':') {
+            if key.trim().eq_ignore_ascii_case("location") {
+                location = Some(value.trim().to_string())
+            }
+        }
+    }
+    match (status.as_str(), location) {
+        ("301" | "302" | "303" | "307" | "308", Some(location)) => Ok(Response::Redirect(location)),
+        ("200", _) => {
+            let mut body = Vec::new();
+            reader.take(URL_MAX).read_to_end(&mut body)?;
+            Ok(Response::Body(body))
+        }
+        (status, _) => Err(format!("returned {}", status).into()),
+    }
+}
+
+/// GET an http:// URL, following a few redirects
+#[cfg(feature = "album-art")]
+fn http_get(url: &str) -> Result<Vec<u8>, String> {
+    let mut url = url.to_string();
+    for _ in 0..4 {
+        let rest = url
+            .strip_prefix("http://")
+            .ok_or_else(|| format!("Only http:// cover links are supported, not {}", url))?;
+        let (host, path) = match rest.split_once('/') {
+            Some((host, path)) => (host.to_string(), format!("/{}", path)),
+            None => (rest.to_string(), String::from("/")),
+        };
+        match http_once(&host, &path) {
+            Ok(Response::Body(body)) => return Ok(body),
+            Ok(Response::Redirect(location)) if location.starts_with('/') => url = format!("http://{}{}", host, location),
+            Ok(Response::Redirect(location)) => url = location,
+            Err(e) => return Err(format!("Could not get cover {}: {}", url, e)),
+        }
+    }
+    Err(format!("Too many redirects getting cover {}", url))
+}
+
+#[cfg(feature = "album-art")]
+fn decode(data: &[u8]) -> Option<image::DynamicImage> {
+    image::guess_format(data)
+        .ok()
+        .and_then(|format| image::load(Cursor::new(data), format).ok())
+}
+
+/// Pixel rows of IMG
+#[cfg(feature = "album-art")]
+fn raw(img: image::DynamicImage) -> RawImage {
+    let width = img.width();
+    img.into_rgba8()
+        .into_vec()
+        .chunks_exact(4)
+        .map(|chunk| chunk.try_into().unwrap())
+        .collect::<Vec<[u8; 4]>>()
+        .chunks_exact(width as usize)
+        .map(|v| v.into())
+        .collect()
+}
+
+/// The picture at PATH, None if it can't be read or ompl was built without art
+pub fn read_image(path: &std::path::Path) -> Option<RawImage> {
+    #[cfg(not(feature = "album-art"))]
+    {
+        let _ = path;
+        None
+    }
+    #[cfg(feature = "album-art")]
+    image::open(path).ok().map(raw)
+}
+
+impl Track {
+    /// First picture in the file, preferring a front cover
+    #[cfg(feature = "album-art")]
+    fn embedded_art(&self) -> Option<image::DynamicImage> {
+        use symphonia::core::meta::StandardVisualKey;
+        let mut probed = self.probe()?;
+        // ogg keeps its pictures with the comments, which the format reader only has once it's initialized
+        let mut visuals: Vec<_> = probed
+            .metadata
+            .get()
+            .and_then(|m| m.current().map(|r| r.visuals().to_vec()))
+            .unwrap_or_default();
+        visuals.extend(probed.format.metadata().current().map(|r| r.visuals().to_vec()).unwrap_or_default());
+        visuals.sort_by_key(|v| v.usage != Some(StandardVisualKey::FrontCover));
+        visuals.iter().find_map(|visual| decode(&visual.data))
+    }
+
+    /// An image in DIRECTORY named after the track's tags or something generic like "cover"
+    #[cfg(feature = "album-art")]
+    fn folder_art(&self, directory: &Path) -> Option<image::DynamicImage> {
+        let Ok(directory) = directory.read_dir() else {
+            return None;
+        };
+
+        let mut fnames_lc: Vec<String> = Self::ART_SEARCH_TAGS
+            .iter()
+            .filter_map(|s| self.tags.get(*s).map(|t| t.to_lowercase()))
+            .map(|t| [t.clone(), t + " cover"])
+            .collect::<Vec<[String; 2]>>()
+            .into_flattened();
+        fnames_lc.extend(GENERIC_NAMES.iter().map(|s| s.to_string()));
+
+        // Collect all images first or else you need to walk each file multiple times for every fname
+        // because the fnames are prioritized. Eats a few ms in fat directories but what can you do?
+        let image_paths = directory
+            .filter_map(|der| {
+                der.ok().map(|de| {
+                    de.path().extension().map(|e| {
+                        e.to_str()
+                            .map(|s| (Self::ART_SEARCH_EXTS.contains(&s.to_lowercase().as_str())).then_some(de.path()))
+                    })
+                })
+            })
+            .flatten()
+            .flatten()
+            .flatten()
+            .collect::<Vec<PathBuf>>();
+
+        for fname_lc in fnames_lc {
+            for path in image_paths.iter() {
+                let Some(stem_lc) = path.file_stem().and_then(|e| e.to_str()).map(|s| s.to_lowercase()) else {
+                    continue;
+                };
+                if fname_lc != stem_lc {
+                    continue;
+                };
+
+                if let Ok(img) = image::open(path) {
+                    return Some(img);
+                }
+            }
+        }
+        None
+    }
+
+    /// Art from the cover_url tag
+    #[cfg(feature = "album-art")]
+    fn url_art(&self) -> Option<image::DynamicImage> {
+        let url = URL_TAGS.iter().find_map(|t| self.tags.get(*t))?.trim();
+        if url.starts_with("http://") || url.starts_with("https://") {
+            match http_get(url) {
+                Ok(data) => decode(&data),
+                Err(e) => {
+                    debug!("{}", e);
+                    None
+                }
+            }
+        } else {
+            let path = Path::new(url.strip_prefix("file://").unwrap_or(url));
+            // relative paths are from the track's folder
+            let path = self.path.parent().map(|p| p.join(path)).unwrap_or(path.to_path_buf());
+            image::open(path).ok()
+        }
+    }
+
+    /// Art from the first of SOURCES that has any
+    pub fn read_art(&self, sources: &[ArtSource]) -> Option<RawImage> {
+        // {{{
+        #[cfg(not(feature = "album-art"))]
+        {
+            let _ = sources;
+            None
+        }
+        #[cfg(feature = "album-art")]
+        {
+            let directory = self.path.parent();
+            let img = sources.iter().find_map(|source| match source {
+                ArtSource::Embedded => self.embedded_art(),
+                ArtSource::Folder => directory.and_then(|d| self.folder_art(d)),
+                ArtSource::Disc => directory
+                    .filter(|d| d.file_name().and_then(|n| n.to_str()).is_some_and(is_disc_dir))
+                    .and_then(|d| d.parent())
+                    .and_then(|d| self.folder_art(d)),
+                ArtSource::Url => self.url_art(),
+            })?;
+            Some(raw(img))
+        }
+    } //}}}
+}
+
+#[cfg(all(test, feature = "album-art"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn disc_dirs() {
+        for name in ["Disc 1", "CD2", "disk 03 - Bonus", "cd_1", "Disc-2"] {
+            assert!(is_disc_dir(name), "{}", name)
+        }
+        for name in ["Discovery", "CDs", "Album", "Disco 2", ""] {
+            assert!(!is_disc_dir(name), "{}", name)
+        }
+    }
+}
@@ -1,24 +1,30 @@
 #![warn(missing_docs)]
 
+use super::{Cancel, Collation};
 use crate::logging::*;
 use std::collections::HashMap;
 use std::fs::File;
-use std::io::Cursor;
 use std::ops::Deref;
 use std::path::Path;
 use std::path::PathBuf;
-use std::time::Instant;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::RwLock;
+use std::time::{Duration, Instant, SystemTime};
 
-use symphonia::core::io::MediaSourceStream;
+use symphonia::core::formats::FormatOptions;
+use symphonia::core::io::{MediaSource, MediaSourceStream};
 use symphonia::core::meta::MetadataRevision;
-use symphonia::core::probe::Hint;
+use symphonia::core::probe::{Hint, ProbeResult};
 
-use lexical_sort::natural_lexical_cmp;
 use walkdir::WalkDir;
 
 pub type Tags = HashMap<String, String>;
+/// Format strings for pulling values out of tags
 pub mod tagstring;
 
+mod art;
+pub use art::{read_image, ArtSource};
+
 // ## ID3 TAGS ## {{{
 // TODO eventually cross-reference with non-free stuff?
 // Might be overkill...
@@ -319,7 +325,13 @@ const ID3_GENRES: &[&'static str] = &[
 
 // ## FNs ## {{{
 
+/// Find all files under PATH with an extension in TYPES. Metadata is not loaded
 pub fn find_tracks<T: AsRef<Path>>(path: T, types: &[String], include_hidden: bool) -> Vec<Track> {
+    find_tracks_cancellable(path, types, include_hidden, &Cancel::default())
+}
+
+/// [`find_tracks`] that stops walking once CANCEL is set, returning what it found so far
+pub fn find_tracks_cancellable<T: AsRef<Path>>(path: T, types: &[String], include_hidden: bool, cancel: &Cancel) -> Vec<Track> {
     debug!("Finding tracks...");
     let now = Instant::now();
 
@@ -328,6 +340,7 @@ pub fn find_tracks<T: AsRef<Path>>(path: T, types: &[String], include_hidden: bo
         .max_depth(10)
         .into_iter()
         .filter_entry(|e| e.file_name().to_str().map(|s| include_hidden || !s.starts_with(".")).unwrap_or(false))
+        .take_while(|_| !cancel.cancelled())
         .filter_map(|e| e.ok())
         .filter(|e| {
             if e.path().is_dir() {
@@ -355,61 +368,270 @@ pub fn find_tracks<T: AsRef<Path>>(path: T, types: &[String], include_hidden: bo
     tracks
 }
 
+/// Parse TAGSTRING for every track in track order
 pub fn get_taglist<T: AsRef<str>, U: Deref<Target = Track>>(tagstring: T, tracks: &Vec<U>) -> Vec<String> {
-    tracks
-        .iter()
-        .filter_map(|t| Some(tagstring::parse(tagstring.as_ref(), t.tags())))
-        .collect::<Vec<String>>()
+    tracks.iter().map(|t| t.tagstring(tagstring.as_ref())).collect::<Vec<String>>()
 }
 
-pub fn get_taglist_sort<T: AsRef<str>, U: Deref<Target = Track>>(tagstring: T, tracks: &Vec<U>) -> Vec<String> {
+/// Parse TAGSTRING for every track, deduplicated and ordered by COLLATION
+pub fn get_taglist_sort<T: AsRef<str>, U: Deref<Target = Track>>(tagstring: T, tracks: &Vec<U>, collation: &Collation) -> Vec<String> {
     let mut result = get_taglist(tagstring, tracks);
-    result.sort_by(|a, b| natural_lexical_cmp(&a, &b));
+    result.sort_by(|a, b| collation.cmp(a, b));
     result.dedup();
     result
 }
 
+/// Formats as "YYYY-MM-DD HH:MM:SS" UTC so times sort naturally as strings
+pub fn format_time(time: SystemTime) -> String {
+    let secs = time.duration_since(SystemTime::UNIX_EPOCH).map(|d| d.as_secs() as i64).unwrap_or(0);
+    let (days, rem) = (secs.div_euclid(86400), secs.rem_euclid(86400));
+
+    // Days to civil date
+    // http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02}:{:02}",
+        year,
+        month,
+        day,
+        rem / 3600,
+        rem / 60 % 60,
+        rem % 60
+    )
+}
+
 // ## FNs }}}
 
+/// Rows of RGBA pixels
 pub type RawImage = Box<[Box<[[u8; 4]]>]>;
 
+/// Memoized tagstring results for one track.
+/// Invisible to Clone/PartialEq so tracks still compare by their actual data
+#[derive(Debug, Default)]
+struct TagCache(RwLock<HashMap<String, String>>);
+
+impl TagCache {
+    /// Distinct tagstrings kept per track before starting over.
+    /// Plenty for sorters + filters + statusline without growing forever as they're edited
+    const CAPACITY: usize = 32;
+
+    fn get_or_parse(&self, tagstring: &str, tags: &Tags) -> String {
+        if let Some(cached) = self.0.read().ok().and_then(|c| c.get(tagstring).cloned()) {
+            return cached;
+        }
+        let parsed = tagstring::parse(tagstring, tags);
+        if let Ok(mut cache) = self.0.write() {
+            if cache.len() >= Self::CAPACITY {
+                cache.clear()
+            }
+            cache.insert(tagstring.to_string(), parsed.clone());
+        }
+        parsed
+    }
+
+    fn clear(&self) {
+        if let Ok(mut cache) = self.0.write() {
+            cache.clear()
+        }
+    }
+}
+
+/// Manual gain in dB on top of replaygain.
+/// Shared tracks are adjusted in place, so like TagCache it's invisible to PartialEq
+#[derive(Debug, Default)]
+struct GainOffset(AtomicU32);
+
+impl Clone for GainOffset {
+    fn clone(&self) -> Self {
+        Self(AtomicU32::new(self.0.load(Ordering::Relaxed)))
+    }
+}
+
+impl PartialEq for GainOffset {
+    fn eq(&self, _other: &Self) -> bool {
+        true
+    }
+}
+
+impl Clone for TagCache {
+    fn clone(&self) -> Self {
+        Self(RwLock::new(self.0.read().map(|c| c.clone()).unwrap_or_default()))
+    }
+}
+
+impl PartialEq for TagCache {
+    fn eq(&self, _other: &Self) -> bool {
+        true
+    }
+}
+
+/// Single audio file and its metadata
 #[derive(Debug, Clone, PartialEq)]
 pub struct Track {
     path: PathBuf,
     tags: Tags,
     gain: f32,
+    offset: GainOffset,
+    duration: Option<Duration>,
+    cache: TagCache,
 }
 
 impl Track {
+    /// Tags whose values are tried as art file names, in order
     pub const ART_SEARCH_TAGS: &'static [&'static str] = &["title", "grouping", "album", "artist"];
+    /// Extensions of art files
     pub const ART_SEARCH_EXTS: &'static [&'static str] = &["jpg", "png", "jpeg"];
+    /// Tags holding a MusicBrainz recording/track ID across the different container conventions
+    pub const MBID_TAGS: &'static [&'static str] = &[
+        "musicbrainz_trackid",
+        "musicbrainz track id",
+        "musicbrainz_releasetrackid",
+        "musicbrainz release track id",
+    ];
+    /// Display tags and the tags saying how to sort them, like "Beatles, The" for "The Beatles"
+    pub const SORT_TAGS: &'static [(&'static str, &'static str)] = &[
+        ("album", "albumsort"),
+        ("albumartist", "albumartistsort"),
+        ("artist", "artistsort"),
+        ("composer", "composersort"),
+        ("title", "titlesort"),
+    ];
 
+    /// New track at PATH without metadata. None if the path cannot be canonicalized
     pub fn new<T: AsRef<Path>>(path: T) -> Option<Self> {
         path.as_ref().canonicalize().ok().map(|path| Self {
             path,
             tags: Tags::new(),
             gain: 1.0,
+            offset: GainOffset::default(),
+            duration: None,
+            cache: TagCache::default(),
         })
     }
 
-    /// Reads the current metadata revision
-    fn read_metadata(&self) -> Option<MetadataRevision> {
-        // {{{
-        let Ok(file) = File::open(&self.path) else { return None };
-        let Ok(mut probed) = symphonia::default::get_probe().format(
-            Hint::new().with_extension(
-                self.path()
-                    .extension()
-                    .map(|s| s.to_str())
-                    .flatten()
-                    .expect("HINT EXTENSION FAIL - should be unreachable"),
-            ),
-            MediaSourceStream::new(Box::new(file), Default::default()),
-            &Default::default(),
-            &Default::default(),
-        ) else {
-            return None;
+    /// Track at PATH with only TAGS, never touching the filesystem
+    #[cfg(test)]
+    pub(crate) fn tagged(path: &str, tags: &[(&str, &str)]) -> Self {
+        Self {
+            path: PathBuf::from(path),
+            tags: tags.iter().map(|(tag, value)| (tag.to_string(), value.to_string())).collect(),
+            gain: 1.0,
+            offset: GainOffset::default(),
+            duration: None,
+            cache: TagCache::default(),
+        }
+    }
+
+    /// Track shared by another instance at URL, with metadata from its index
+    #[cfg(feature = "share")]
+    pub(crate) fn remote(url: String, tags: Tags, duration: Option<Duration>) -> Self {
+        let mut track = Self {
+            path: PathBuf::from(url),
+            tags: Tags::new(),
+            gain: 1.0,
+            offset: GainOffset::default(),
+            duration,
+            cache: TagCache::default(),
+        };
+        // path and filename come from the url, the server keeps its own to itself
+        track.load_file_tags();
+        track.tags.extend(tags);
+        track.load_gain();
+        track
+    }
+
+    /// The same track after its file was moved to PATH
+    pub(crate) fn moved(&self, path: PathBuf) -> Self {
+        let mut track = Self {
+            path,
+            cache: TagCache::default(),
+            ..self.clone()
         };
+        track.load_file_tags();
+        track
+    }
+
+    /// The same track with its metadata read again, as when the file was retagged.
+    /// The added time is kept since the file only changed, it wasn't added again
+    pub(crate) fn reloaded(&self) -> Self {
+        let mut track = Self {
+            tags: Tags::new(),
+            gain: 1.0,
+            duration: None,
+            cache: TagCache::default(),
+            ..self.clone()
+        };
+        track.load_meta();
+        if let Some(added) = self.tags.get("added") {
+            track.tags.insert("added".to_string(), added.clone());
+        }
+        track
+    }
+
+    /// Whether the file was modified since its metadata was read
+    pub(crate) fn changed(&self) -> bool {
+        if self.is_remote() {
+            return false;
+        }
+        let mtime = std::fs::metadata(&self.path).and_then(|m| m.modified()).ok().map(format_time);
+        mtime.is_some() && self.tags.get("mtime") != mtime.as_ref()
+    }
+
+    /// Whether the track streams from another instance instead of a local file
+    pub fn is_remote(&self) -> bool {
+        self.path.to_str().is_some_and(|p| p.starts_with("ompl://"))
+    }
+
+    /// The audio file's bytes, read from disk or streamed from a shared library
+    pub fn open(&self) -> std::io::Result<Box<dyn MediaSource>> {
+        #[cfg(feature = "share")]
+        if self.is_remote() {
+            return Ok(Box::new(crate::library::share::Stream::open(self.path.to_str().unwrap_or_default())?));
+        }
+        Ok(Box::new(File::open(&self.path)?))
+    }
+
+    /// Options every file is opened with. Gapless trims the encoder delay and padding from MP3 LAME/Info headers,
+    /// so back to back album tracks don't click or gap between them and lengths match what's heard
+    pub(crate) fn format_options() -> FormatOptions {
+        FormatOptions {
+            enable_gapless: true,
+            ..Default::default()
+        }
+    }
+
+    /// Probes the file's format
+    pub(crate) fn probe(&self) -> Option<ProbeResult> {
+        // {{{
+        let Ok(file) = self.open() else { return None };
+        symphonia::default::get_probe()
+            .format(
+                Hint::new().with_extension(
+                    self.path()
+                        .extension()
+                        .map(|s| s.to_str())
+                        .flatten()
+                        .expect("HINT EXTENSION FAIL - should be unreachable"),
+                ),
+                MediaSourceStream::new(file, Default::default()),
+                &Self::format_options(),
+                &Default::default(),
+            )
+            .ok()
+        // }}}
+    }
+
+    /// Current metadata revision of a probed file
+    fn probed_metadata(probed: &mut ProbeResult) -> Option<MetadataRevision> {
         probed
             .metadata
             .get()
@@ -417,60 +639,66 @@ impl Track {
             .flatten()
             // Vorbis comments aren't found until the FormatReader is initialized
             .or_else(|| probed.format.metadata().current().cloned())
-        // }}}
     }
 
-    #[cfg(feature = "album-art")]
-    fn find_art(&self) -> Option<image::DynamicImage> {
-        let Some(Ok(directory)) = self.path.parent().map(|p| p.read_dir()) else {
-            return None;
-        };
+    /// Tags sourced from the file itself rather than its metadata.
+    /// Real tags of the same name win, since they're read afterwards
+    fn load_file_tags(&mut self) {
+        let path = &self.path;
+        let names = [
+            ("path", path.to_str()),
+            ("filename", path.file_name().and_then(|n| n.to_str())),
+            ("ext", path.extension().and_then(|e| e.to_str())),
+        ];
+        for (tag, value) in names {
+            if let Some(value) = value {
+                self.tags.insert(tag.to_string(), value.to_string());
+            }
+        }
+        if let Some(ext) = self.tags.get_mut("ext") {
+            ext.make_ascii_lowercase()
+        }
 
-        let mut fnames_lc: Vec<String> = Self::ART_SEARCH_TAGS
-            .iter()
-            .filter_map(|s| self.tags.get(&s.to_string()).map(|t| t.to_lowercase()))
-            .map(|t| [t.clone(), t + " cover"])
-            .collect::<Vec<[String; 2]>>()
-            .into_flattened();
-        fnames_lc.push("cover".to_string());
-
-        // Collect all images first or else you need to walk each file multiple times for every fname
-        // because the fnames are prioritized. Eats a few ms in fat directories but what can you do?
-        let image_paths = directory
-            .filter_map(|der| {
-                der.ok().map(|de| {
-                    de.path().extension().map(|e| {
-                        e.to_str()
-                            .map(|s| (Self::ART_SEARCH_EXTS.contains(&s.to_lowercase().as_str())).then_some(de.path()))
-                    })
-                })
-            })
-            .flatten()
-            .flatten()
-            .flatten()
-            .collect::<Vec<PathBuf>>();
-
-        for fname_lc in fnames_lc {
-            for path in image_paths.iter() {
-                let Some(stem_lc) = path.file_stem().map(|e| e.to_str()).flatten().map(|s| s.to_lowercase()) else {
-                    continue;
-                };
-                if fname_lc != stem_lc {
-                    continue;
-                };
-
-                if let Ok(img) = image::open(path) {
-                    return Some(img);
-                }
+        let Ok(meta) = std::fs::metadata(&self.path) else { return };
+        if let Ok(mtime) = meta.modified() {
+            self.tags.insert("added".to_string(), format_time(mtime));
+            self.tags.insert("mtime".to_string(), format_time(mtime));
+        }
+        self.tags.insert("filesize".to_string(), meta.len().to_string());
+    }
+
+    /// Volume multiplier from the replaygain tag
+    fn load_gain(&mut self) {
+        if let Some(text) = self.tags.get("replaygain_track_gain") {
+            if let Ok(gain) = text[..text.rfind(|c: char| c.is_numeric()).unwrap_or(text.len() - 1) + 1]
+                .trim_start()
+                .parse::<f32>()
+            {
+                // according to the internet, A2 = A1 * 10(GdB / 20)
+                // where A1 is our volume set in library, G is the replaygain
+                // offset, and A2 is the final result Rodio should eat.
+                self.gain = 10f32.powf(gain / 20.0)
             }
         }
-        None
     }
 
     /// Reads metadata into the struct. This doesn't happen on ::new() for performance reasons.
     pub fn load_meta(&mut self) {
         // {{{
-        let Some(meta) = self.read_metadata() else {
+        self.cache.clear();
+        self.load_file_tags();
+
+        let Some(mut probed) = self.probe() else {
+            return;
+        };
+
+        self.duration = probed
+            .format
+            .default_track()
+            .and_then(|t| t.codec_params.n_frames.zip(t.codec_params.sample_rate))
+            .map(|(frames, rate)| Duration::from_secs_f64(frames as f64 / rate as f64));
+
+        let Some(meta) = Self::probed_metadata(&mut probed) else {
             return;
         };
 
@@ -507,17 +735,7 @@ impl Track {
             self.tags.insert(key, val);
         }
 
-        if let Some(text) = self.tags.get("replaygain_track_gain") {
-            if let Ok(gain) = text[..text.rfind(|c: char| c.is_numeric()).unwrap_or(text.len() - 1) + 1]
-                .trim_start()
-                .parse::<f32>()
-            {
-                // according to the internet, A2 = A1 * 10(GdB / 20)
-                // where A1 is our volume set in library, G is the replaygain
-                // offset, and A2 is the final result Rodio should eat.
-                self.gain = 10f32.powf(gain / 20.0)
-            }
-        }
+        self.load_gain();
 
         // use file stem if no title tag
         if !self.tags.contains_key("title") {
@@ -527,60 +745,84 @@ impl Track {
         }
     } // }}}
 
-    pub fn read_art(&self) -> Option<RawImage> {
-        // {{{
-        #[cfg(not(feature = "album-art"))]
-        {
-            None
-        }
-        #[cfg(feature = "album-art")]
-        {
-            let meta = self.read_metadata();
-            let Some(img) = meta
-                .as_ref()
-                .map(|m| m.visuals().get(0))
-                .flatten()
-                .map(|visual| {
-                    image::guess_format(&visual.data)
-                        .ok()
-                        .map(|format| image::load(Cursor::new(&visual.data), format).ok())
-                })
-                .flatten()
-                .flatten()
-                .or_else(|| self.find_art())
-            else {
-                return None;
-            };
-            let width = img.width();
-            Some(
-                img.into_rgba8()
-                    .into_vec()
-                    .chunks_exact(4)
-                    .map(|chunk| chunk.try_into().unwrap())
-                    .collect::<Vec<[u8; 4]>>()
-                    .chunks_exact(width as usize)
-                    .map(|v| v.into())
-                    .collect(),
-            )
-        }
-    } //}}}
-
     // ## GET / SET ## {{{
 
+    /// All tags, lowercased keys
     pub fn tags(&self) -> &Tags {
         &self.tags
     }
 
+    /// Set TAG to VALUE, as the library does to tell duplicates apart
+    pub(crate) fn tag_set(&mut self, tag: &str, value: String) {
+        self.cache.clear();
+        self.tags.insert(tag.to_string(), value);
+    }
+
+    /// Parse a tagstring against this track's tags.
+    /// Results are cached until the metadata is reloaded
     pub fn tagstring<T: AsRef<str>>(&self, tagstring: T) -> String {
-        tagstring::parse(tagstring, self.tags())
+        self.cache.get_or_parse(tagstring.as_ref(), &self.tags)
+    }
+
+    /// Parse a tagstring with display tags read from their sort tags where the track has them.
+    /// Only cached when there's nothing to swap
+    pub fn sort_tagstring<T: AsRef<str>>(&self, tagstring: T) -> String {
+        if !Self::SORT_TAGS.iter().any(|(_, sort)| self.tags.contains_key(*sort)) {
+            return self.tagstring(tagstring);
+        }
+        let mut tags = self.tags.clone();
+        for (display, sort) in Self::SORT_TAGS {
+            if let Some(value) = self.tags.get(*sort) {
+                tags.insert(display.to_string(), value.clone());
+            }
+        }
+        tagstring::parse(tagstring, &tags)
     }
 
+    /// Canonical path, or `ompl://` url for remote tracks
     pub fn path(&self) -> &PathBuf {
         &self.path
     }
 
+    /// Length from the file's header, if it has one
+    pub fn duration(&self) -> Option<Duration> {
+        self.duration
+    }
+
+    /// Volume multiplier from replaygain and the manual offset
     pub fn gain(&self) -> f32 {
-        self.gain
+        self.gain * 10f32.powf(self.gain_offset() / 20.0)
+    }
+
+    /// Manual gain in dB
+    pub fn gain_offset(&self) -> f32 {
+        f32::from_bits(self.offset.0.load(Ordering::Relaxed))
+    }
+
+    /// Players read [`Track::gain`] as they go, so this applies to a playing track too
+    pub fn gain_offset_set(&self, db: f32) {
+        self.offset.0.store(db.to_bits(), Ordering::Relaxed)
+    }
+
+    /// Identity of the recording independent of its path.
+    /// Prefers an embedded MusicBrainz ID, falling back to artist + title + whole seconds.
+    /// None if there's not enough metadata to be confident.
+    pub fn fingerprint(&self) -> Option<String> {
+        if let Some(mbid) = Self::MBID_TAGS.iter().find_map(|t| self.tags.get(*t)) {
+            return Some(format!("mbid:{}", mbid.trim().to_ascii_lowercase()));
+        }
+        let artist = self.tags.get("artist")?;
+        let title = self.tags.get("title")?;
+        // the title falls back to the file stem, which is exactly what changes on a rename
+        if self.path.file_stem().and_then(|s| s.to_str()) == Some(title) {
+            return None;
+        }
+        Some(format!(
+            "{}\0{}\0{}",
+            artist.to_lowercase(),
+            title.to_lowercase(),
+            self.duration.map(|d| d.as_secs_f64().round().to_string()).unwrap_or_default()
+        ))
     }
 
     // ## GET / SET ## }}}
@@ -601,6 +843,156 @@ impl std::fmt::Display for Track {
     }
 }
 
+#[cfg(test)]
+mod time_tests {
+    use super::format_time;
+    use std::time::{Duration, SystemTime};
+
+    #[test]
+    fn epoch() {
+        assert_eq!(format_time(SystemTime::UNIX_EPOCH), "1970-01-01 00:00:00");
+    }
+
+    #[test]
+    fn leap_day() {
+        assert_eq!(
+            format_time(SystemTime::UNIX_EPOCH + Duration::from_secs(1709210096)),
+            "2024-02-29 12:34:56"
+        );
+    }
+}
+
+#[cfg(test)]
+mod fingerprint_tests {
+    use super::Track;
+    use std::time::Duration;
+
+    fn track(tags: &[(&str, &str)], duration: Option<f64>) -> Track {
+        let mut track = Track::tagged("/music/01 - song.flac", tags);
+        track.duration = duration.map(Duration::from_secs_f64);
+        track
+    }
+
+    #[test]
+    fn mbid_preferred() {
+        let a = track(&[("musicbrainz_trackid", "ABC-123"), ("artist", "A"), ("title", "T")], Some(10.0));
+        let b = track(&[("musicbrainz track id", "abc-123")], None);
+        assert_eq!(a.fingerprint(), b.fingerprint());
+        assert_eq!(a.fingerprint().unwrap(), "mbid:abc-123");
+    }
+
+    #[test]
+    fn tags_and_duration() {
+        let a = track(&[("artist", "Artist"), ("title", "Title")], Some(180.2));
+        let b = track(&[("artist", "artist"), ("title", "TITLE")], Some(179.9));
+        let c = track(&[("artist", "Artist"), ("title", "Title")], Some(200.0));
+        assert!(a.fingerprint().is_some());
+        assert_eq!(a.fingerprint(), b.fingerprint());
+        assert_ne!(a.fingerprint(), c.fingerprint());
+    }
+
+    #[test]
+    fn insufficient() {
+        assert_eq!(track(&[("title", "Title")], Some(1.0)).fingerprint(), None);
+        assert_eq!(track(&[("artist", "Artist"), ("title", "01 - song")], Some(1.0)).fingerprint(), None);
+    }
+}
+
+#[cfg(test)]
+mod tagcache_tests {
+    use super::{TagCache, Track};
+
+    fn track() -> Track {
+        Track::tagged("/music/song.flac", &[("artist", "Artist"), ("title", "Title")])
+    }
+
+    #[test]
+    fn cached() {
+        let t = track();
+        assert_eq!(t.tagstring("<artist> - <title>"), "Artist - Title");
+        assert_eq!(t.tagstring("<artist> - <title>"), "Artist - Title");
+        assert_eq!(t.cache.0.read().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn bounded() {
+        let t = track();
+        for n in 0..TagCache::CAPACITY * 2 + 1 {
+            assert_eq!(t.tagstring(format!("{}<title>", n)), format!("{}Title", n));
+        }
+        assert!(t.cache.0.read().unwrap().len() <= TagCache::CAPACITY);
+    }
+
+    #[test]
+    fn invisible() {
+        let (a, b) = (track(), track());
+        a.tagstring("<title>");
+        assert_eq!(a, b);
+        assert_eq!(a.clone().cache.0.read().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn sort_tags() {
+        let mut t = track();
+        assert_eq!(t.sort_tagstring("<artist> - <title>"), "Artist - Title");
+        t.tags.insert("artistsort".to_string(), "Artist, The".to_string());
+        assert_eq!(t.sort_tagstring("<artist> - <title>"), "Artist, The - Title");
+        assert_eq!(t.sort_tagstring("artistsort"), "Artist, The");
+        assert_eq!(t.tagstring("<artist>"), "Artist");
+    }
+}
+
+#[cfg(test)]
+mod filetag_tests {
+    use super::Track;
+
+    #[test]
+    fn file_tags() {
+        let dir = std::env::temp_dir().join(format!("ompl-filetags-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("Song.FLAC");
+        std::fs::write(&path, [0u8; 100]).unwrap();
+
+        let mut track = Track::new(&path).unwrap();
+        track.load_file_tags();
+        assert_eq!(track.tagstring("filename"), "Song.FLAC");
+        assert_eq!(track.tagstring("ext"), "flac");
+        assert_eq!(track.tagstring("filesize"), "100");
+        assert_eq!(track.tagstring("path"), track.path().to_str().unwrap());
+        assert_eq!(track.tagstring("mtime"), track.tagstring("added"));
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn reload() {
+        let dir = std::env::temp_dir().join(format!("ompl-reload-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("song.wav");
+        std::fs::write(&path, [0u8; 100]).unwrap();
+
+        let mut track = Track::new(&path).unwrap();
+        track.load_file_tags();
+        track.tags.insert("added".to_string(), "2000-01-01 00:00:00".to_string());
+        assert!(!track.changed());
+
+        // as if a tagger rewrote the file while it was loaded
+        let file = std::fs::OpenOptions::new().write(true).open(&path).unwrap();
+        file.set_len(200).unwrap();
+        file.set_modified(std::time::SystemTime::now() + std::time::Duration::from_secs(60))
+            .unwrap();
+        drop(file);
+        assert!(track.changed());
+
+        let reloaded = track.reloaded();
+        assert!(!reloaded.changed());
+        assert_eq!(reloaded.tagstring("filesize"), "200");
+        assert_eq!(reloaded.tagstring("added"), "2000-01-01 00:00:00");
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
+
 #[cfg(test)]
 mod id3tests {
     use super::ID3_TAGS;
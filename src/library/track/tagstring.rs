@@ -73,6 +73,7 @@ fn parse_recurse(tagstring: &str, tags: &Tags) -> String {
     result
 }
 
+/// Parse TAGSTRING against TAGS. A string without any `<tag>` is treated as a tag name itself
 pub fn parse<T: AsRef<str>>(tagstring: T, tags: &Tags) -> String {
     let tagstring = tagstring.as_ref();
     let mut start = false;
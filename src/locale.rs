@@ -0,0 +1,123 @@
+//! Translations of UI strings, loaded once at startup.
+//!
+//! English is both the default and the key, like gettext, so anything left untranslated falls back to it as is.
+//! A translation is a `<lang>.lang` file of `English = Translation` lines, where `#` starts a comment and `\n` is a line break.
+//! Multi-line text like the TUI help is translated one line at a time, see [`tr_lines`].
+//! `{}` in formatted messages stands for each value in order, see [`trf`].
+
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+static STRINGS: OnceLock<HashMap<String, String>> = OnceLock::new();
+
+/// Every translation in TEXT. Errors name the first bad line
+pub fn parse(text: &str) -> Result<HashMap<String, String>, String> {
+    let mut strings = HashMap::new();
+    for (n, line) in text.lines().enumerate() {
+        if line.trim().is_empty() || line.trim_start().starts_with('#') {
+            continue;
+        }
+        match line.split_once(" = ") {
+            Some((english, translated)) if !english.trim().is_empty() => {
+                strings.insert(english.trim().replace("\\n", "\n"), translated.trim().replace("\\n", "\n"));
+            }
+            _ => return Err(format!("Line {}: expected English = Translation", n + 1)),
+        }
+    }
+    Ok(strings)
+}
+
+/// File names to try for LANG, most specific first. `de_DE.UTF-8` tries `de_DE` then `de`.
+/// Empty for English and the C locale, which need no file
+pub fn candidates(lang: &str) -> Vec<String> {
+    let lang = lang.split(['.', '@']).next().unwrap_or_default().replace('-', "_");
+    let base = lang.split('_').next().unwrap_or_default().to_string();
+    if base.is_empty() || ["en", "C", "POSIX"].contains(&base.as_str()) {
+        return Vec::new();
+    }
+    if base == lang {
+        vec![base]
+    } else {
+        vec![lang, base]
+    }
+}
+
+/// Language from the usual environment variables, first set of `LC_ALL`, `LC_MESSAGES`, `LANG`
+pub fn system_lang() -> Option<String> {
+    ["LC_ALL", "LC_MESSAGES", "LANG"]
+        .iter()
+        .find_map(|var| std::env::var(var).ok().filter(|v| !v.is_empty()))
+}
+
+/// Load the translation for LANG from DIR. Only the first load takes effect.
+/// Returns the file used, or None when LANG is English or has no file
+pub fn load(lang: &str, dir: &Path) -> Result<Option<PathBuf>, String> {
+    let Some(path) = candidates(lang)
+        .into_iter()
+        .map(|c| dir.join(c).with_extension("lang"))
+        .find(|p| p.is_file())
+    else {
+        return Ok(None);
+    };
+    let text = std::fs::read_to_string(&path).map_err(|e| format!("Could not read {}: {}", path.display(), e))?;
+    let strings = parse(&text).map_err(|e| format!("Could not load {}\n  {}", path.display(), e))?;
+    let _ = STRINGS.set(strings);
+    Ok(Some(path))
+}
+
+/// TEXT in the loaded language
+pub fn tr(text: &str) -> &str {
+    STRINGS.get().and_then(|s| s.get(text)).map(|s| s.as_str()).unwrap_or(text)
+}
+
+/// TEXT in the loaded language, line by line
+pub fn tr_lines(text: &str) -> String {
+    text.lines().map(tr).collect::<Vec<&str>>().join("\n")
+}
+
+/// TEMPLATE in the loaded language with each `{}` replaced by the next of ARGS
+pub fn trf(template: &str, args: &[&dyn Display]) -> String {
+    let mut args = args.iter();
+    let mut parts = tr(template).split("{}");
+    let mut result = parts.next().unwrap_or_default().to_string();
+    for part in parts {
+        if let Some(arg) = args.next() {
+            result.push_str(&arg.to_string())
+        }
+        result.push_str(part)
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lines() {
+        let strings = parse("# German\nHelp = Hilfe\n* a | play/pause = * a | Wiedergabe/Pause\n\nA\\nB = C\\nD").unwrap();
+        assert_eq!(strings.get("Help").map(String::as_str), Some("Hilfe"));
+        assert_eq!(strings.get("* a | play/pause").map(String::as_str), Some("* a | Wiedergabe/Pause"));
+        assert_eq!(strings.get("A\nB").map(String::as_str), Some("C\nD"));
+        assert!(parse("Help: Hilfe").unwrap_err().starts_with("Line 1"));
+    }
+
+    #[test]
+    fn langs() {
+        assert_eq!(candidates("de_DE.UTF-8"), ["de_DE", "de"]);
+        assert_eq!(candidates("pt-BR"), ["pt_BR", "pt"]);
+        assert_eq!(candidates("fr"), ["fr"]);
+        assert!(candidates("en_US.UTF-8").is_empty());
+        assert!(candidates("C.UTF-8").is_empty());
+    }
+
+    #[test]
+    fn untranslated() {
+        // nothing is loaded in tests, so everything falls back to English
+        assert_eq!(tr("Help"), "Help");
+        assert_eq!(tr_lines("a\nb"), "a\nb");
+        assert_eq!(trf("Could not append {}: {}", &[&"x", &5]), "Could not append x: 5");
+    }
+}
@@ -5,13 +5,17 @@
 
 #![warn(missing_docs)]
 
-use clap::{value_parser, ArgAction, Parser, Subcommand};
+use clap::{value_parser, ArgAction, Parser, Subcommand, ValueEnum};
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::error::Error;
-use std::io::{Read, Write};
+use std::io::{BufRead, Read, Write};
 use std::net::{Ipv4Addr, SocketAddrV4, TcpListener, TcpStream};
-use std::path::PathBuf;
-use std::sync::Arc;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{sync_channel, Receiver, SyncSender};
+use std::sync::{Arc, Mutex, RwLock, Weak};
 use std::thread;
 use std::time::Duration;
 
@@ -20,103 +24,140 @@ use regex::Regex;
 #[cfg(feature = "media-controls")]
 use souvlaki::{MediaControlEvent, MediaControls, MediaMetadata, MediaPlayback, PlatformConfig};
 
-mod library;
-use library::{Backend, Color, LibEvt, Library, Theme};
+use library::{
+    ArtColors, ArtDither, ArtSource, Backend, BpmAnalysis, Color, DuplicatePolicy, LibEvt, Library, Radio, RouteChange, SeekStep, SeekSteps, Theme,
+};
+use ompl_core::try_block;
+use ompl_core::{library, locale, logging};
 
 #[cfg(feature = "tui")]
 mod tui;
 
-/// Logging macros
-pub mod logging {
-    // {{{
+#[cfg(feature = "dbus")]
+mod dbus;
 
-    use std::sync::{
-        atomic::{AtomicBool, AtomicU8},
-        Mutex,
-    };
+#[cfg(feature = "cast")]
+mod cast;
 
-    /// Verbosity level of log to print/queue
-    pub static LOG_LEVEL: AtomicU8 = AtomicU8::new(0);
-    /// Print log if true else save for later
-    pub static PRINT_LOG: AtomicBool = AtomicBool::new(true);
-    /// The backlog of logs to print when resumed
-    pub static BACKLOG: Mutex<Vec<(u8, String)>> = Mutex::new(Vec::new());
-
-    /// If $v <= LOG_LEVEL print values
-    macro_rules! log {
-        ($v:expr, $($fmt_args:tt)*) => {
-            #[allow(unused_comparisons)]
-            if LOG_LEVEL.load(std::sync::atomic::Ordering::Relaxed) >= $v {
-                if PRINT_LOG.load(std::sync::atomic::Ordering::Relaxed) {
-                    println!($($fmt_args)*)
-                // Store if paused
-                } else if let Ok(mut backlog) = BACKLOG.lock() {
-                    backlog.push(($v, format!($($fmt_args)*)))
-                }
-            }
-        };
+mod completions;
+
+use logging::*;
+
+const ID: &str = "OMPL SERVER 0.11";
+const PORT: &str = "18346";
+/// How long a client has to finish sending a command or take in its response
+const CLIENT_TIMEOUT: Duration = Duration::from_secs(10);
+/// Finished tickets held for `ompl ticket` before the oldest are forgotten
+const TICKETS_KEPT: usize = 100;
+/// Largest command a client may send, checked before its token so strangers can't make the server allocate
+const MAX_COMMAND: usize = 1 << 20;
+
+/// Per-user cache directory for regenerable files such as exported cover art.
+/// Created on demand, falling back to the system temp dir.
+pub fn cache_dir() -> PathBuf {
+    let var = |k: &str| std::env::var_os(k).filter(|v| !v.is_empty()).map(PathBuf::from);
+    #[cfg(target_os = "windows")]
+    let base = var("LOCALAPPDATA");
+    #[cfg(target_os = "macos")]
+    let base = var("HOME").map(|h| h.join("Library").join("Caches"));
+    #[cfg(not(any(target_os = "windows", target_os = "macos")))]
+    let base = var("XDG_CACHE_HOME").or_else(|| var("HOME").map(|h| h.join(".cache")));
+
+    let dir = base.unwrap_or_else(std::env::temp_dir).join("ompl");
+    if let Err(e) = std::fs::create_dir_all(&dir) {
+        error!("Could not create cache dir {}: {}", dir.display(), e);
+        return std::env::temp_dir();
     }
+    dir
+}
 
-    /// Pause log and queue further entries
-    macro_rules! log_pause {
-        () => {
-            PRINT_LOG.store(false, std::sync::atomic::Ordering::Relaxed)
-        };
+/// Per-user data directory for what can't be regenerated, like play stats, snapshots, and saved sessions.
+/// Created on demand, falling back to the cache dir
+pub fn data_dir() -> PathBuf {
+    let var = |k: &str| std::env::var_os(k).filter(|v| !v.is_empty()).map(PathBuf::from);
+    #[cfg(target_os = "windows")]
+    let base = var("APPDATA");
+    #[cfg(target_os = "macos")]
+    let base = var("HOME").map(|h| h.join("Library").join("Application Support"));
+    #[cfg(not(any(target_os = "windows", target_os = "macos")))]
+    let base = var("XDG_DATA_HOME").or_else(|| var("HOME").map(|h| h.join(".local").join("share")));
+
+    let Some(dir) = base.map(|b| b.join("ompl")) else { return cache_dir() };
+    if let Err(e) = std::fs::create_dir_all(&dir) {
+        error!("Could not create data dir {}: {}", dir.display(), e);
+        return cache_dir();
     }
+    dir
+}
 
-    /// Resume log and print queued entries
-    macro_rules! log_resume {
-        () => {
-            PRINT_LOG.store(true, std::sync::atomic::Ordering::Relaxed);
-            if let Ok(mut backlog) = BACKLOG.lock() {
-                for (n, s) in backlog.drain(..) {
-                    if n == 0 {
-                        eprintln!("{}", s);
-                    } else {
-                        println!("{}", s);
-                    }
-                }
-                backlog.shrink_to_fit();
+/// NAME in the data dir. Older versions kept everything in the cache dir, so it's moved over on first use
+fn data_file(name: &str) -> PathBuf {
+    let path = data_dir().join(name);
+    let old = cache_dir().join(name);
+    if old != path && old.exists() && !path.exists() {
+        match std::fs::rename(&old, &path) {
+            Ok(()) => {
+                info!("Moved {} to {}", old.display(), path.display())
             }
-        };
+            Err(e) => {
+                error!("Could not move {} to {}: {}", old.display(), path.display(), e);
+                return old;
+            }
+        }
     }
+    path
+}
 
-    /// Level 0
-    macro_rules! error {
-        ($($fmt_args:tt)*) => {log!(0, $($fmt_args)*)}
-    }
-    /// Level 1
-    macro_rules! info {
-        ($($fmt_args:tt)*) => {log!(1, $($fmt_args)*)}
-    }
-    /// Level 2
-    macro_rules! bench {
-        ($($fmt_args:tt)*) => {log!(2, $($fmt_args)*)}
+/// Theme and statusline file, see [`library::config`]. $OMPL_CONFIG or ompl/ompl.conf in the per-user config directory.
+/// Not created, as there's nothing that has to go in it
+fn config_file() -> Option<PathBuf> {
+    match std::env::var_os("OMPL_CONFIG").filter(|v| !v.is_empty()) {
+        Some(path) => Some(PathBuf::from(path)),
+        None => config_dir().map(|d| d.join("ompl.conf")),
     }
-    /// Level 3
-    macro_rules! debug {
-        ($($fmt_args:tt)*) => {log!(3, $($fmt_args)*)}
-    }
-
-    pub(crate) use {bench, debug, error, info, log, log_pause, log_resume};
-} // }}}
+}
 
-use logging::*;
+/// Per-user ompl config folder, holding `ompl.conf` and `lang/` translations
+fn config_dir() -> Option<PathBuf> {
+    let var = |k: &str| std::env::var_os(k).filter(|v| !v.is_empty()).map(PathBuf::from);
+    #[cfg(target_os = "windows")]
+    let base = var("APPDATA");
+    #[cfg(target_os = "macos")]
+    let base = var("HOME").map(|h| h.join("Library").join("Application Support"));
+    #[cfg(not(any(target_os = "windows", target_os = "macos")))]
+    let base = var("XDG_CONFIG_HOME").or_else(|| var("HOME").map(|h| h.join(".config")));
+
+    base.map(|b| b.join("ompl"))
+}
 
-const ID: &str = "OMPL SERVER 0.10";
-const PORT: &str = "18346";
+/// Library folders from the last run on this port, for `ompl main` without paths. Empty if there's nothing saved
+fn load_roots(path: &Path) -> Vec<PathBuf> {
+    let Ok(bytes) = std::fs::read(path) else { return Vec::new() };
+    bincode::deserialize(&bytes).unwrap_or_else(|e| {
+        info!("Discarding unreadable roots file {}: {}", path.display(), e);
+        Vec::new()
+    })
+}
 
-/// petty 100
-#[macro_export]
-macro_rules! try_block {
-    ($enclosed:block) => {
-        || -> Result<(), Box<dyn Error>> { $enclosed }()
-    };
+/// Remember LIBRARY's folders in PATH. Nothing is written for an empty library so a --fresh run doesn't forget them
+fn save_roots(library: &Library, path: &Path) {
+    let roots = library.roots_get();
+    if roots.is_empty() {
+        return;
+    }
+    let result = try_block!({
+        std::fs::write(path, bincode::serialize(&roots)?)?;
+        Ok(())
+    });
+    if let Err(e) = result {
+        error!("Could not save library roots to {}: {}", path.display(), e)
+    }
 }
 
 // ### PARSERS ### {{{
 
 /// Parse "<tagstring>=a,b" => Filter {tagstring, [a, b]}
+/// ">=" and "<=" compare instead of matching exactly
 fn parse_filter(s: &str) -> Result<library::Filter, String> {
     let mut i = s.chars();
 
@@ -125,6 +166,7 @@ fn parse_filter(s: &str) -> Result<library::Filter, String> {
 
     let mut switch = false;
     let mut item_buff = String::new();
+    let mut op = library::FilterOp::Eq;
 
     let mut pos = 1;
 
@@ -141,7 +183,19 @@ fn parse_filter(s: &str) -> Result<library::Filter, String> {
                 }
             }
             Some('=') => match switch {
-                false => switch = true,
+                false => {
+                    switch = true;
+                    // only an operator if what's left is still a complete tagstring
+                    // so "<genre>=Rock" isn't read as "<genre" >= "Rock"
+                    let balanced = |t: &str| t.matches('<').count() == t.matches('>').count();
+                    if tag.ends_with('>') && balanced(&tag[..tag.len() - 1]) {
+                        tag.pop();
+                        op = library::FilterOp::Ge
+                    } else if tag.ends_with('<') && balanced(&tag[..tag.len() - 1]) {
+                        tag.pop();
+                        op = library::FilterOp::Le
+                    }
+                }
                 true => return Err(format!("Inappropriate equals @ position {} of \"{}\"", pos, s)),
             },
             Some(',') => match item_buff.is_empty() && switch {
@@ -167,7 +221,7 @@ fn parse_filter(s: &str) -> Result<library::Filter, String> {
         items.push(item_buff)
     }
 
-    Ok(library::Filter { tag, items })
+    Ok(library::Filter { tag, items, op })
 }
 
 // Weird macro lifetime bullshit. Could either spend a few hours researching or just do this
@@ -197,22 +251,179 @@ fn parse_time(s: &str) -> Result<Duration, Box<dyn Error + Send + Sync>> {
     Err(format!("Could not parse {} as time signature", string).into())
 }
 
+/// Parse a percentage from 0 to 100, with or without the '%'
+fn parse_percent(s: &str) -> Result<f32, Box<dyn Error + Send + Sync>> {
+    let percent: f32 = s.trim().trim_end_matches('%').parse()?;
+    if (0.0..=100.0).contains(&percent) {
+        Ok(percent)
+    } else {
+        Err("Percent must be from 0 to 100".into())
+    }
+}
+
+fn parse_step(s: &str) -> Result<f32, Box<dyn Error + Send + Sync>> {
+    let secs: f32 = s.parse()?;
+    if SeekSteps::valid(secs) {
+        Ok(secs)
+    } else {
+        Err("Seek steps must be positive seconds".into())
+    }
+}
+
 fn parse_art_size(s: &str) -> Result<u8, Box<dyn Error + Send + Sync>> {
     let uint8 = s.parse()?;
-    // 32x32 is already insanely big let's just make that a hard limit to reduce complexity
-    // 4b * 32 * 32 * 10,000 == 41MB thumbnails worst case so the naive caching will do just fine
-    // If ever a GUI is added this function can just be conditionaled
-    const VALID: &'static [u8] = &[0, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16];
-    if VALID.contains(&uint8) {
+    if Theme::ART_SIZES.contains(&uint8) {
         Ok(uint8)
     } else {
-        Err(format!("Art size must be one of {:?}", VALID).into())
+        Err(format!("Art size must be one of {:?}", Theme::ART_SIZES).into())
+    }
+}
+
+/// Parse decibels with an optional sign and dB suffix, as in +2dB
+fn parse_db(s: &str) -> Result<f32, Box<dyn Error + Send + Sync>> {
+    let s = s.trim();
+    let number = s.strip_suffix("dB").or_else(|| s.strip_suffix("db")).unwrap_or(s);
+    let db: f32 = number.trim().parse()?;
+    if db.is_finite() {
+        Ok(db)
+    } else {
+        Err(format!("{} is not a usable gain", s).into())
+    }
+}
+
+/// Parse "role=token" for --access
+fn parse_access(s: &str) -> Result<(Role, String), Box<dyn Error + Send + Sync>> {
+    let (role, token) = s.split_once('=').ok_or_else(|| format!("{} is not role=token", s))?;
+    if token.is_empty() {
+        return Err("Token can't be empty".into());
     }
+    Ok((Role::from_str(role, true)?, token.to_string()))
 }
 
 // ### PARSERS ### }}}
 
 // ### ARGS {{{
+/// Interactive interface for the main instance
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Serialize, Deserialize, ValueEnum)]
+pub enum Ui {
+    /// Full screen TUI
+    Full,
+    /// Single updating status line in the normal screen, for ssh sessions, small panes, and scripts
+    Line,
+    /// Plain lines for every change and a queue walked one item at a time, for terminal screen readers
+    Reader,
+}
+
+/// How `--resume` leaves the restored track
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Serialize, Deserialize, ValueEnum)]
+pub enum ResumeAs {
+    /// Paused at the old position
+    Paused,
+    /// Playing again if it was playing on exit
+    Playing,
+}
+
+/// What SIGUSR1 or SIGUSR2 does to the daemon
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Serialize, Deserialize, ValueEnum)]
+pub enum SignalAction {
+    /// Toggle between play and pause
+    PlayPause,
+    /// Next track
+    Next,
+    /// Previous track
+    Previous,
+    /// Stop playback
+    Stop,
+    /// Ignore the signal
+    None,
+}
+
+#[cfg(unix)]
+impl SignalAction {
+    fn run(self, library: &Library) {
+        match self {
+            Self::PlayPause => library.play_pause(),
+            Self::Next => library.next(),
+            Self::Previous => library.previous(),
+            Self::Stop => library.stop(),
+            Self::None => (),
+        }
+    }
+}
+
+/// Run USR1 and USR2 on LIBRARY whenever the process gets SIGUSR1 or SIGUSR2, for controlling playback with `kill`
+#[cfg(unix)]
+fn signals(library: &Arc<Library>, usr1: SignalAction, usr2: SignalAction) -> Result<(), Box<dyn Error>> {
+    use signal_hook::consts::{SIGUSR1, SIGUSR2};
+    let mut signals = signal_hook::iterator::Signals::new([SIGUSR1, SIGUSR2])?;
+    let library = Arc::downgrade(library);
+    thread::Builder::new().name(String::from("Signal Handler")).spawn(move || {
+        for signal in signals.forever() {
+            let Some(library) = library.upgrade() else { break };
+            debug!("Received signal {}", signal);
+            match signal {
+                SIGUSR1 => usr1.run(&library),
+                SIGUSR2 => usr2.run(&library),
+                _ => (),
+            }
+        }
+    })?;
+    Ok(())
+}
+
+/// What a client may do, from its token
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Serialize, Deserialize, ValueEnum)]
+pub enum Role {
+    /// Print and other getters
+    Read,
+    /// Read plus playback and volume
+    Control,
+    /// Everything
+    Admin,
+}
+
+/// Shells completion scripts can be generated for
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Serialize, Deserialize, ValueEnum)]
+pub enum CompletionShell {
+    /// Includes server completions
+    Bash,
+    /// Static only
+    Elvish,
+    /// Includes server completions
+    Fish,
+    /// Static only
+    #[value(name = "powershell")]
+    PowerShell,
+    /// Static only
+    Zsh,
+}
+
+#[derive(Subcommand, Debug, Clone, Serialize, Deserialize)]
+/// see Action
+pub enum SnapshotCmd {
+    /// Record every track's tags as NAME, replacing any saved before
+    Save {
+        /// Letters, numbers, '-' and '_'
+        name: String,
+    },
+    /// Read tags of modified files again and list what changed since NAME was saved
+    Diff {
+        /// Snapshot to compare against
+        name: String,
+    },
+    /// Saved snapshot names
+    List,
+}
+
+#[derive(Subcommand, Debug, Clone, Serialize, Deserialize)]
+/// see Action
+pub enum QuarantineCmd {
+    /// Paths of every quarantined track
+    List,
+    /// Let every quarantined track play again
+    Clear,
+}
+
 #[derive(Subcommand, Debug, Clone, Serialize, Deserialize)]
 /// see Action
 pub enum StatuslineCmd {
@@ -225,6 +436,18 @@ pub enum StatuslineCmd {
     },
 }
 
+#[derive(Subcommand, Debug, Clone, Serialize, Deserialize)]
+/// see Action
+pub enum GrouplineCmd {
+    /// Retreive groupline tagstring
+    Get,
+    /// Set groupline tagstring, or "" to stop grouping
+    Set {
+        /// Like "<album>"
+        tagstring: String,
+    },
+}
+
 /// see Action
 #[derive(Subcommand, Debug, Clone, Serialize, Deserialize)]
 pub enum VolumeCmd {
@@ -245,6 +468,86 @@ pub enum VolumeCmd {
         ///
         amount: f32,
     },
+    /// Drop volume to 0, remembering the current volume
+    Mute,
+    /// Restore the volume from before muting
+    Unmute,
+    /// Mute or unmute
+    ToggleMute,
+    /// Whether volume is muted
+    Muted,
+}
+
+/// see Action
+#[derive(Subcommand, Debug, Clone, Serialize, Deserialize)]
+pub enum GainCmd {
+    /// Manual gain of the current track in dB
+    Get,
+    /// Add onto the current track's manual gain, as in +2dB
+    Adjust {
+        ///
+        #[arg(value_parser = parse_db, allow_hyphen_values = true)]
+        db: f32,
+    },
+    /// Set the current track's manual gain, 0dB to clear it
+    Set {
+        ///
+        #[arg(value_parser = parse_db, allow_hyphen_values = true)]
+        db: f32,
+    },
+    /// Measure track and album ReplayGain of mp3 and flac files missing it and write the tags.
+    /// Runs here rather than on the server. Needs the `replaygain` feature
+    Scan {
+        /// Folder to scan, the current one if not given
+        path: Option<PathBuf>,
+        /// Rescan files that already have tags
+        #[arg(long)]
+        force: bool,
+    },
+}
+
+/// see Action
+#[derive(Subcommand, Debug, Clone, Serialize, Deserialize)]
+pub enum OutputCmd {
+    /// Devices besides the default, '*' marking enabled ones with their volumes
+    List,
+    /// Also play to device NAME
+    Enable {
+        ///
+        name: String,
+    },
+    /// Stop playing to device NAME
+    Disable {
+        ///
+        name: String,
+    },
+    /// Set enabled device NAME's volume from range 0.0 -> 1.0, on top of the main volume
+    Volume {
+        ///
+        name: String,
+        ///
+        amount: f32,
+    },
+    /// What happens when the output is unplugged or the system switches outputs mid-track.
+    /// Leaving POLICY out prints it
+    RouteChange {
+        /// pause stays paused, follow carries on from the same spot on the new output
+        policy: Option<RouteChange>,
+    },
+}
+
+/// see Action
+#[derive(Subcommand, Debug, Clone, Serialize, Deserialize)]
+pub enum CastCmd {
+    /// DLNA renderers on the local network
+    List,
+    /// Play the queue on renderer DEVICE, pausing local playback
+    Start {
+        ///
+        device: String,
+    },
+    /// Stop the renderer. Local playback stays paused
+    Stop,
 }
 
 /// see Action
@@ -282,6 +585,70 @@ pub enum FilterCmd {
         #[arg(value_parser=parse_filter)]
         filter: library::Filter,
     },
+    /// Add a filter on TAGSTRING selecting every value in the queue containing QUERY
+    Search {
+        /// Like "artist" or "<album>"
+        tagstring: String,
+        /// Matched anywhere in the value, ignoring case
+        query: String,
+    },
+}
+
+/// see Action
+#[derive(Subcommand, Debug, Clone, Serialize, Deserialize)]
+pub enum BrowseCmd {
+    /// Tracks by ARTIST
+    Artist {
+        ///
+        artist: String,
+        /// Narrow down to one album
+        #[command(subcommand)]
+        album: Option<BrowseAlbum>,
+    },
+}
+
+/// see BrowseCmd
+#[derive(Subcommand, Debug, Clone, Serialize, Deserialize)]
+pub enum BrowseAlbum {
+    /// Tracks on ALBUM by the artist
+    Album {
+        ///
+        album: String,
+        /// Narrow down to one track
+        #[command(subcommand)]
+        track: Option<BrowseTrack>,
+    },
+}
+
+/// see BrowseAlbum
+#[derive(Subcommand, Debug, Clone, Serialize, Deserialize)]
+pub enum BrowseTrack {
+    /// Only TITLE on the album
+    Track {
+        ///
+        title: String,
+    },
+}
+
+impl BrowseCmd {
+    /// One selected layer per level given, then an empty layer for the next level down
+    fn filters(self) -> Vec<library::Filter> {
+        let layer = |tag: &str, item: Option<String>| library::Filter {
+            tag: tag.to_string(),
+            items: item.into_iter().collect(),
+            op: Default::default(),
+        };
+        let BrowseCmd::Artist { artist, album } = self;
+        let mut filters = vec![layer("artist", Some(artist))];
+        match album {
+            None => filters.push(layer("album", None)),
+            Some(BrowseAlbum::Album { album, track }) => {
+                filters.push(layer("album", Some(album)));
+                filters.push(layer("title", track.map(|BrowseTrack::Track { title }| title)));
+            }
+        }
+        filters
+    }
 }
 
 /// see Action
@@ -292,10 +659,10 @@ pub enum SorterCmd {
         ///
         index: Option<usize>,
     },
-    /// Replace all sorters TAGSTRINGS
+    /// Replace all sorters TAGSTRINGS. Prefix one with '-' to sort it descending
     Set {
         ///
-        #[arg(num_args(1..))]
+        #[arg(num_args(1..), allow_hyphen_values = true)]
         tagstrings: Vec<String>,
     },
     /// Remove INDEX Sorter layer
@@ -308,6 +675,7 @@ pub enum SorterCmd {
         ///
         index: usize,
         ///
+        #[arg(allow_hyphen_values = true)]
         tagstring: String,
     },
     /// Replace INDEX sorter with TAGSTRING
@@ -315,23 +683,75 @@ pub enum SorterCmd {
         ///
         index: usize,
         ///
+        #[arg(allow_hyphen_values = true)]
         tagstring: String,
     },
+    /// Flip INDEX sorter between ascending and descending
+    Reverse {
+        /// Sorter layer to flip
+        index: usize,
+    },
+}
+
+/// see Action
+#[derive(Subcommand, Debug, Clone, Serialize, Deserialize)]
+pub enum ViewCmd {
+    /// List all views, marking the active one with '*'
+    Get,
+    /// Create a new view from the current filters and sorters then switch to it
+    New {
+        ///
+        name: String,
+    },
+    /// Switch to VIEW by name or index
+    Switch {
+        ///
+        view: String,
+    },
+    /// Remove VIEW by name or index
+    Remove {
+        ///
+        view: String,
+    },
+    /// Rename the active view
+    Rename {
+        ///
+        name: String,
+    },
 }
 
 /// see Action
 #[derive(Subcommand, Debug, Clone, Serialize, Deserialize)]
 pub enum ShuffleCmd {
-    /// true/false
+    /// true, false, or album
     Get,
     /// Set true, next track picked randomly
     True,
+    /// Set true, picking a random album and playing it through in queue order before the next
+    Album {
+        /// Tagstring grouping tracks into albums
+        #[arg(default_value = "<album>")]
+        tagstring: String,
+    },
     /// Set false, next track picked based on Sorter
     False,
     /// Toggle beteween true/false
     Toggle,
 }
 
+/// see Action
+#[derive(Subcommand, Debug, Clone, Serialize, Deserialize)]
+pub enum ConsumeCmd {
+    /// true/false
+    Get,
+    /// Drop tracks from the queue once played
+    On,
+    /// Keep tracks in the queue, bringing back any already consumed
+    Off,
+    /// Toggle between on/off
+    Toggle,
+}
+
 /// see Action
 #[derive(Subcommand, Debug, Clone, Serialize, Deserialize)]
 pub enum RepeatCmd {
@@ -359,17 +779,97 @@ pub enum SeekCmd {
     GetFloat,
     /// true/false, whether or not seeking is possible
     Seekable,
+    /// Seconds decoded so far. Seeking works within them before the whole track is seekable
+    Decoded,
     /// Seek to exact time in hh:mm:ss.dd format
     To {
         #[arg(value_parser=parse_time)]
         /// hh:mm:ss.dd
         time: Duration,
     },
+    /// Seek to PERCENT of the way through the track
+    ToPercent {
+        /// 0 -> 100
+        #[arg(value_parser = parse_percent)]
+        percent: f32,
+    },
     /// Advance time by seconds, positive or negative
     By {
         ///
         secs: f32,
     },
+    /// Jump back SECS seconds to hear them again
+    Replay {
+        ///
+        #[arg(default_value = "10")]
+        secs: f32,
+    },
+    /// Seek forward by one of the steps set with `seek steps`
+    Step {
+        /// Which step
+        step: SeekStep,
+        /// Seek backwards instead
+        #[arg(short, long)]
+        back: bool,
+    },
+    /// Set the seconds each step seeks by, or print them if none are given
+    Steps {
+        /// 5 by default
+        #[arg(long, value_parser = parse_step)]
+        small: Option<f32>,
+        /// 30 by default
+        #[arg(long, value_parser = parse_step)]
+        medium: Option<f32>,
+        /// 300 by default
+        #[arg(long, value_parser = parse_step)]
+        large: Option<f32>,
+    },
+}
+
+/// see Action
+#[derive(Subcommand, Debug, Clone, Serialize, Deserialize)]
+pub enum BookmarkCmd {
+    /// Bookmarks of the current track as mm:ss.dd and name, one per line
+    List,
+    /// Bookmark TIME in the current track as NAME, the current time if not given
+    Add {
+        ///
+        name: String,
+        #[arg(value_parser=parse_time)]
+        /// hh:mm:ss.dd
+        time: Option<Duration>,
+    },
+    /// Seek to bookmark NAME
+    Jump {
+        ///
+        name: String,
+    },
+    /// Delete bookmark NAME
+    Remove {
+        ///
+        name: String,
+    },
+}
+
+/// see Action
+#[derive(Subcommand, Debug, Clone, Serialize, Deserialize)]
+pub enum LoopCmd {
+    /// Get loop points in float format, start and end
+    Get,
+    /// Start the loop at TIME, the current time if not given
+    SetA {
+        #[arg(value_parser=parse_time)]
+        /// hh:mm:ss.dd
+        time: Option<Duration>,
+    },
+    /// End the loop at TIME, the current time if not given. Playback jumps back to A on reaching it
+    SetB {
+        #[arg(value_parser=parse_time)]
+        /// hh:mm:ss.dd
+        time: Option<Duration>,
+    },
+    /// Stop looping
+    Clear,
 }
 
 /// see Action
@@ -399,6 +899,35 @@ pub enum ThemeCmd {
         ///
         art_size: u8,
     },
+    /// Art dithering, for covers with fine detail or gradients on 16 or 256 colors
+    Dither {
+        /// Method
+        dither: ArtDither,
+    },
+    /// Art color depth
+    Colors {
+        /// Palette
+        colors: ArtColors,
+    },
+    /// Center art between bars instead of holding it to the corner
+    Letterbox {
+        /// true or false
+        #[arg(action = ArgAction::Set)]
+        letterbox: bool,
+    },
+    /// Color chip from each album's art beside the tracks in the queue
+    Chips {
+        /// true or false
+        #[arg(action = ArgAction::Set)]
+        chips: bool,
+    },
+    /// Take the accent from each track's album art, going back to the current one when turned off
+    /// or for tracks without art. Leaving STATE out prints whether it's on
+    AutoArt {
+        /// on or off
+        #[arg(value_parser = clap::builder::BoolishValueParser::new())]
+        state: Option<bool>,
+    },
 }
 
 /// see Action
@@ -425,6 +954,25 @@ pub enum PrintCmd {
     Statusline,
     /// Print current theme in either hex or terminal ID
     Theme,
+    /// Every distinct value of TAGSTRING across the library, one per line
+    Values {
+        ///
+        tagstring: String,
+    },
+    /// Counters for monitoring in the Prometheus text format
+    Metrics,
+    /// Whether the current track is in the favorites view
+    Loved,
+    /// Track count and total length of the queue, like "52 tracks · 3h 42m"
+    QueueInfo,
+    /// Where the server keeps its config, translations, data, and cache
+    Dirs,
+    /// Each supervised worker thread and whether it's running, finished, or crashed
+    Threads,
+    /// Peak and gain of the playing track, how far that is from clipping, and how much the limiter has caught
+    Headroom,
+    /// Sample rate and channels of the playing track, what the device plays it as, and the resampler between them
+    Format,
 }
 
 /// see Args
@@ -432,17 +980,29 @@ pub enum PrintCmd {
 pub enum Action {
     /// Create new server on PORT
     Main {
-        /// Paths to scan for music
+        /// Paths to scan for music. Without any, the folders loaded last time on this port are scanned again
         library: Vec<PathBuf>,
 
+        #[arg(long)]
+        /// Start with an empty library instead of the folders from last time
+        fresh: bool,
+
         #[arg(short = 'H', long)]
         /// Include hidden items ( '.' prefix )
         hidden: bool,
 
+        /// How tracks with the same tags as one already loaded are handled
+        #[arg(long, default_value = "path")]
+        duplicates: DuplicatePolicy,
+
         #[arg(short = 'S', long)]
         /// Disable shuffle on startup
         noshuffle: bool,
 
+        /// Pick next tracks by similarity to the playing one, once the queue ends or always
+        #[arg(long, default_value = "off")]
+        radio: Radio,
+
         #[arg(short = 'r', long)]
         /// Loop single track
         repeat_track: bool,
@@ -455,6 +1015,19 @@ pub enum Action {
         /// Daemon / no-gui mode. Does nothing if `tui` is disabled at compile-time
         daemon: bool,
 
+        #[arg(long, default_value = "full")]
+        /// Interface to run when not a daemon. Does nothing if `tui` is disabled at compile-time
+        ui: Ui,
+
+        #[arg(long)]
+        /// Start the full TUI again if it crashes, instead of exiting along with the server
+        reattach: bool,
+
+        #[arg(long, num_args = 0..=1, default_missing_value = "paused")]
+        /// Put back the track, position, and queue saved by the last `ompl exit`.
+        /// Filters and sorters given here win over the saved ones
+        resume: Option<ResumeAs>,
+
         #[arg(long, short)]
         /// Disable media interface.
         ///
@@ -467,7 +1040,7 @@ pub enum Action {
         /// Starting filters
         filters: Vec<library::Filter>,
 
-        #[arg(long = "sorters", short, num_args(1..))]
+        #[arg(long = "sorters", short, num_args(1..), allow_hyphen_values = true)]
         /// Starting sorters
         sorters: Vec<String>,
 
@@ -475,6 +1048,10 @@ pub enum Action {
         /// Starting volume
         volume: f32,
 
+        /// Highest volume anything can set
+        #[arg(long, default_value = "1.0")]
+        max_volume: f32,
+
         /// Tagstring to display on statusline
         #[arg(long, default_value = "title")]
         statusline: String,
@@ -495,6 +1072,27 @@ pub enum Action {
         #[arg(long, default_value = "5", value_parser=parse_art_size)]
         art_size: u8,
 
+        /// UI Art dithering
+        #[arg(long, default_value = "none")]
+        art_dither: ArtDither,
+
+        /// UI Art color depth
+        #[arg(long, default_value = "auto")]
+        art_colors: ArtColors,
+
+        /// Center UI Art between bars
+        #[arg(long)]
+        art_letterbox: bool,
+
+        /// Color chip from each album's art beside the tracks in the queue
+        #[arg(long)]
+        art_chips: bool,
+
+        /// Seconds between position updates sent to media controls while playing,
+        /// for Bluetooth/AVRCP displays that don't keep their own clock
+        #[arg(long, default_value = "1", value_parser = parse_time)]
+        media_interval: Duration,
+
         /// Select audio streaming backend
         #[arg(long, default_value = "default")]
         backend: Backend,
@@ -503,6 +1101,68 @@ pub enum Action {
         #[arg(long, default_value = None, value_parser=value_parser!(u32).range(48..=384000))]
         buffer: Option<u32>,
 
+        /// Where the snapcast backend streams 48kHz 16 bit stereo PCM, as host:port.
+        /// Prefix with udp:// for a raw UDP sink. Defaults to a local Snapcast server's TCP source
+        #[arg(long)]
+        sink: Option<String>,
+
+        /// How tracks are converted when the device can't play their sample rate.
+        /// linear, sinc, or sinc:TAPS for a sharper but slower filter. Sympal backend only
+        #[arg(long, default_value = "linear")]
+        resampler: library::Resampler,
+
+        /// What happens when the output is unplugged or the system switches outputs mid-track. Sympal backend only
+        #[arg(long, default_value = "pause")]
+        route_change: RouteChange,
+
+        /// Measure the tempo of tracks missing a bpm tag, keeping it in the stats file or also writing the tag.
+        /// Sympal backend only
+        #[arg(long, default_value = "off")]
+        bpm: BpmAnalysis,
+
+        /// Skip silence at the start and end of tracks. Sympal backend only
+        #[arg(long)]
+        trim_silence: bool,
+
+        /// Move on to the next track at the first silence at least this long, for tracks with a hidden track after a gap.
+        /// Sympal backend only
+        #[arg(long, value_parser = parse_time)]
+        split_silence: Option<Duration>,
+
+        /// Level at or below which audio counts as silent, in dBFS
+        #[arg(long, default_value = "-60dB", value_parser = parse_db, allow_hyphen_values = true)]
+        silence_threshold: f32,
+
+        /// Share the library over HTTP at host:port so other instances can stream from it.
+        /// Without a value only listens on 127.0.0.1:18347. Nothing is authenticated, so give an address like 0.0.0.0:18347
+        /// for other machines only on networks you trust
+        #[arg(long, num_args = 0..=1, default_missing_value = "127.0.0.1:18347")]
+        share: Option<String>,
+
+        /// Serve `ompl print metrics` over HTTP at host:port for Prometheus to scrape.
+        /// Without a value listens on 127.0.0.1:18348
+        #[arg(long, num_args = 0..=1, default_missing_value = library::metrics::DEFAULT_ADDRESS)]
+        metrics: Option<String>,
+
+        /// Only accept playback, volume, and read-only commands from clients, and hide destructive actions in the TUI.
+        /// For leaving ompl running on a shared machine. Pair with --max-volume
+        #[arg(long)]
+        kiosk: bool,
+
+        /// Give clients sending TOKEN a role: read for getters, control for playback and volume too, or admin for everything.
+        /// Once an admin token exists, clients without a token can only read, or control under --kiosk.
+        /// Can be passed multiple times
+        #[arg(long, value_name = "ROLE=TOKEN", value_parser = parse_access)]
+        access: Vec<(Role, String)>,
+
+        /// What SIGUSR1 does, like `kill -USR1 $(pidof ompl)`. Unix only
+        #[arg(long, default_value = "play-pause")]
+        sigusr1: SignalAction,
+
+        /// What SIGUSR2 does. Unix only
+        #[arg(long, default_value = "next")]
+        sigusr2: SignalAction,
+
         /// Verbosity level. Pass multiple times to get more verbose (spammy).
         #[arg(long, short = 'V', action(ArgAction::Count))]
         verbosity: u8,
@@ -521,18 +1181,124 @@ pub enum Action {
     Previous,
     /// Calls on the server to exit
     Exit,
+    /// Result of a command sent with --detach, or every ticket's state without an ID.
+    /// A finished ticket is forgotten once its result is read
+    Ticket {
+        /// ID printed by --detach
+        id: Option<u64>,
+    },
+    /// Keep one connection open and run commands read from stdin, one per line.
+    /// Each command's response is printed followed by a newline, empty if there's none
+    #[command(alias = "repl")]
+    Pipe,
+    /// Actions given together separated by ':', run in order without other clients cutting in.
+    /// Built by the client from the command line, not a subcommand of its own
+    #[command(skip)]
+    Batch(Vec<Action>),
     /// Work with volume in a range of 0.0 -> 1.0
     #[command(subcommand)]
     Volume(VolumeCmd),
+    /// Per-track gain offset on top of ReplayGain, remembered across runs
+    #[command(subcommand)]
+    Gain(GainCmd),
+    /// Measure the tempo of tracks missing a bpm tag once they're decoded, so <bpm> sorts and filters them.
+    /// Leaving ANALYSIS out prints it. Sympal backend only
+    Bpm {
+        /// off, store to keep tempos in the stats file, or write to also tag mp3 and flac files
+        analysis: Option<BpmAnalysis>,
+    },
+    /// Play to extra devices alongside the default one. Sympal backend only
+    #[command(subcommand)]
+    Output(OutputCmd),
+    /// Send playback to a DLNA renderer, with ompl serving the files. Needs the `cast` feature
+    #[command(subcommand)]
+    Cast(CastCmd),
     /// Control behavior after track ends
     #[command(subcommand)]
     Repeat(RepeatCmd),
+    /// Drop played tracks from the queue until turned off, without touching the filters
+    #[command(subcommand)]
+    Consume(ConsumeCmd),
     /// Control selection of next tracks
     #[command(subcommand)]
     Shuffle(ShuffleCmd),
+    /// Pick next tracks by genre, artist, tempo, and year closest to the playing one.
+    /// Leaving MODE out prints it
+    Radio {
+        /// off, end to carry on from the whole library once the queue runs out, or always in place of shuffle
+        mode: Option<Radio>,
+    },
     /// Scrub and seek current playback time. Sympal backend only
     #[command(subcommand)]
     Seek(SeekCmd),
+    /// Repeat part of the current track between points A and B. Sympal backend only
+    #[command(subcommand)]
+    Loop(LoopCmd),
+    /// Start every track TIME in, for podcast ads and long intros. 0 turns it off, and leaving TIME out prints it.
+    /// Applies from the next track on. Sympal backend only
+    SkipIntro {
+        /// hh:mm:ss.dd
+        #[arg(value_parser=parse_time)]
+        time: Option<Duration>,
+    },
+    /// Move on TIME before every track ends, for long fade outs. 0 turns it off, and leaving TIME out prints it.
+    /// Applies from the next track on. Sympal backend only
+    SkipOutro {
+        /// hh:mm:ss.dd
+        #[arg(value_parser=parse_time)]
+        time: Option<Duration>,
+    },
+    /// Named points within tracks, kept across runs
+    #[command(subcommand)]
+    Bookmark(BookmarkCmd),
+    /// Add the current track to the favorites view, kept across runs
+    Love,
+    /// Take the current track out of the favorites view
+    Unlove,
+    /// Keep loved tracks in the view called NAME, made once anything's loved. Leaving NAME out prints it
+    Favorites {
+        /// View name
+        name: Option<String>,
+    },
+    /// Where album art is looked for, first to last. Leaving SOURCES out prints them
+    ArtSources {
+        /// Sources in order. Any left out aren't looked in
+        sources: Vec<ArtSource>,
+    },
+    /// Run COMMAND before each track starts with any `{}` argument replaced by TAGSTRING,
+    /// ex `ompl announce espeak {}`. Leaving COMMAND out prints the current one
+    Announce {
+        /// What replaces `{}`
+        #[arg(long, default_value = "<title><artist| by <artist>>")]
+        tagstring: String,
+        /// Stop announcing
+        #[arg(long, conflicts_with = "command")]
+        off: bool,
+        /// Program and its arguments
+        #[arg(trailing_var_arg = true, allow_hyphen_values = true)]
+        command: Vec<String>,
+    },
+    /// Run COMMAND on each file it hasn't tagged yet, now and on every append, with any `{}` argument replaced by the path.
+    /// The JSON object it prints becomes tags, ex `{"mood": "happy"}`. Leaving COMMAND out prints the current one
+    Analyzer {
+        /// Stop analyzing
+        #[arg(long, conflicts_with = "command")]
+        off: bool,
+        /// Program and its arguments
+        #[arg(trailing_var_arg = true, allow_hyphen_values = true)]
+        command: Vec<String>,
+    },
+    /// Run COMMAND to look up artists without an artist.txt or picture for the TUI's info panel,
+    /// with any `{}` argument replaced by the artist. It prints a biography, optionally after an `image: PATH` line.
+    /// Answers are cached. Leaving COMMAND out prints the current one
+    ArtistProvider {
+        /// Only use local files and cached answers
+        #[arg(long, conflicts_with = "command")]
+        off: bool,
+        /// Program and its arguments
+        #[arg(trailing_var_arg = true, allow_hyphen_values = true)]
+        command: Vec<String>,
+    },
     /// Update theme colors.
     ///
     /// Examples:
@@ -551,6 +1317,9 @@ pub enum Action {
     /// Set/retrieve UI statusline
     #[command(subcommand)]
     Statusline(StatuslineCmd),
+    /// Set/retrieve the tagstring queue groups are separated by
+    #[command(subcommand)]
+    Groupline(GrouplineCmd),
     /// Retrieve various server information not in other commands
     #[command(subcommand)]
     Print(PrintCmd),
@@ -562,21 +1331,129 @@ pub enum Action {
     /// Control how tracks are filtered for final play queue using layers of Filters.
     #[command(subcommand)]
     Filter(FilterCmd),
+    /// Replace filters with an artist -> album -> track stack, ex `browse artist "Aphex Twin" album Syro`
+    #[command(subcommand)]
+    Browse(BrowseCmd),
     /// Control how tracks are sorted internally using layers of tagstrings
     #[command(subcommand)]
     Sorter(SorterCmd),
-    /// Append tracks to library from path
+    /// Change how sorters and filter panes order tagstrings. Without any options prints the current ones
+    Collate {
+        /// Language rules, like sv to put å ä ö after z or de to sort ä as ae. "none" turns them off
+        #[arg(long)]
+        locale: Option<String>,
+        /// Sort "The Beatles" under B. Drops English articles plus the locale's own
+        #[arg(long)]
+        articles: Option<bool>,
+        /// Compare "Björk" as "Bjork". Off puts accented letters after every plain one
+        #[arg(long)]
+        transliterate: Option<bool>,
+        /// Sort by tags like artistsort in place of artist when a track has them
+        #[arg(long)]
+        sort_tags: Option<bool>,
+    },
+    /// Swap between independent filter + sorter stacks
+    #[command(subcommand)]
+    View(ViewCmd),
+    /// Append tracks to library from path, or from another instance's shared library
     Append {
-        /// Path to scan for audio files
+        /// Path to scan for audio files, or ompl://host:port/ of an instance running with --share
         path: PathBuf,
     },
+    /// Stop any append or sort in progress, leaving the library as it was before it started
+    Cancel,
+    /// How tracks with the same tags as one already loaded are handled from the next append on,
+    /// for remuxes and copies that would otherwise show up twice. Leaving POLICY out prints it and every match found
+    Duplicates {
+        /// path skips only the same file, tags skips matches, keep loads both with the newer one's title marked
+        policy: Option<DuplicatePolicy>,
+    },
+    /// Save every track's tags and later list files added, removed, or retagged since, to check a tagging session
+    #[command(subcommand)]
+    Snapshot(SnapshotCmd),
     /// Remove all currently loaded tracks
     Purge,
-}
-
-#[derive(Parser, Debug, Clone, Serialize, Deserialize)]
-#[command(author, about, version)]
-struct Args {
+    /// Copy the queue into DEST for a portable player, naming files by tagstring.
+    /// The server lists the files and this client writes them, so both need to see the same paths
+    Export {
+        /// Folder to export into, such as the player's mount point
+        #[arg(long)]
+        dest: PathBuf,
+        /// Copy files as they are, or decode them to WAV for players that can't read them. There's no lossy transcoding
+        #[arg(long, default_value = "copy")]
+        format: library::export::Format,
+        /// Tagstring naming each file under DEST. '/' separates folders, and parts that come out empty are skipped
+        #[arg(long, default_value = library::export::DEFAULT_PATTERN)]
+        name: String,
+        /// Every track in the library instead of just the queue
+        #[arg(long)]
+        all: bool,
+        /// Replace files already in DEST instead of skipping them
+        #[arg(long)]
+        overwrite: bool,
+    },
+    /// Delete a track's file from disk and drop it from the library, moving on to the next track if it's playing.
+    /// Without PATH only names the current track's file, to be passed back as PATH with --yes
+    DeleteFile {
+        /// Library path of the track to delete, as named by the dry run
+        path: Option<PathBuf>,
+        /// Actually delete it
+        #[arg(long, requires = "path")]
+        yes: bool,
+        /// Send it to the system trash instead. Needs the `trash` feature
+        #[arg(long)]
+        trash: bool,
+    },
+    /// Rename and move the queue's files to where a tagstring names them under their library folder.
+    /// Only previews the moves unless given --apply
+    Organize {
+        /// Tagstring naming each file under its library folder. '/' separates folders, and parts that come out empty are skipped
+        #[arg(long, default_value = library::export::DEFAULT_PATTERN)]
+        pattern: String,
+        /// Every track in the library instead of just the queue
+        #[arg(long)]
+        all: bool,
+        /// Actually move the files
+        #[arg(long)]
+        apply: bool,
+    },
+    /// Read tags again for the playing track and any files modified since they were scanned, without stopping playback
+    Reload,
+    /// Tracks that failed to decode 3 times, which next and shuffle skip over
+    #[command(subcommand)]
+    Quarantine(QuarantineCmd),
+    /// Drop every track whose file is missing or unreadable from the library
+    Clean {
+        /// List the tracks found on the last check instead
+        #[arg(short, long)]
+        list: bool,
+    },
+    /// Load PATH into a throwaway library and report how long scanning, probing, sorting, and filtering took.
+    /// Runs on its own without a server
+    Bench {
+        /// Folder to load. Defaults to the current directory
+        path: Option<PathBuf>,
+        /// Filters applied throughout, as they would be on startup
+        #[arg(long, short, num_args(1..), value_parser=parse_filter)]
+        filters: Vec<library::Filter>,
+        /// Sorters to time
+        #[arg(long, short, num_args(1..), default_values = ["<artist>", "<album>", "<tracknumber>"])]
+        sorters: Vec<String>,
+        /// How many times to sort, for a steadier average
+        #[arg(long, default_value = "3", value_parser = value_parser!(u32).range(1..))]
+        runs: u32,
+    },
+    /// Print a completion script for SHELL.
+    /// Bash and fish also complete filter values and view names from the running server
+    Complete {
+        ///
+        shell: CompletionShell,
+    },
+}
+
+#[derive(Parser, Debug, Clone, Serialize, Deserialize)]
+#[command(author, about, version)]
+struct Args {
     /// Action to perform. Required
     #[command(subcommand)]
     action: Action,
@@ -588,232 +1465,990 @@ struct Args {
     /// Address to listen on for client signals
     #[arg(long, default_value = "127.0.0.1")]
     host: Ipv4Addr,
+
+    /// Don't print the response. True/false queries still set the exit code
+    #[arg(short, long)]
+    #[serde(skip)]
+    quiet: bool,
+
+    /// Run the command in the background on the server, printing a ticket ID to check on with `ompl ticket` instead of waiting
+    #[arg(short, long)]
+    detach: bool,
+
+    /// Access token for servers started with --access
+    #[arg(long, env = "OMPL_TOKEN", hide_env_values = true)]
+    token: Option<String>,
+
+    /// Language for the UI and messages, read from `lang/LANG.lang` in the config folder.
+    /// Defaults to the system language
+    #[arg(long, env = "OMPL_LANG")]
+    #[serde(skip)]
+    lang: Option<String>,
+}
+
+impl Action {
+    /// Only reads state
+    fn is_read_only(&self) -> bool {
+        match self {
+            Action::Print(_)
+            | Action::Ticket { .. }
+            | Action::Volume(VolumeCmd::Get | VolumeCmd::Muted)
+            | Action::Shuffle(ShuffleCmd::Get)
+            | Action::Repeat(RepeatCmd::Get)
+            | Action::Consume(ConsumeCmd::Get)
+            | Action::Seek(SeekCmd::Get | SeekCmd::GetSecs | SeekCmd::GetFloat | SeekCmd::Seekable | SeekCmd::Decoded)
+            | Action::Seek(SeekCmd::Steps {
+                small: None,
+                medium: None,
+                large: None,
+            })
+            | Action::Loop(LoopCmd::Get)
+            | Action::SkipIntro { time: None }
+            | Action::SkipOutro { time: None }
+            | Action::Bookmark(BookmarkCmd::List)
+            | Action::Favorites { name: None }
+            | Action::Theme(ThemeCmd::AutoArt { state: None })
+            | Action::Gain(GainCmd::Get)
+            | Action::Output(OutputCmd::List)
+            | Action::Output(OutputCmd::RouteChange { policy: None })
+            | Action::Filter(FilterCmd::Get { .. })
+            | Action::Sorter(SorterCmd::Get { .. })
+            | Action::Collate {
+                locale: None,
+                articles: None,
+                transliterate: None,
+                sort_tags: None,
+            }
+            | Action::View(ViewCmd::Get)
+            | Action::Statusline(StatuslineCmd::Get)
+            | Action::Groupline(GrouplineCmd::Get)
+            | Action::Snapshot(SnapshotCmd::List)
+            | Action::Quarantine(QuarantineCmd::List)
+            | Action::Clean { list: true } => true,
+            Action::Announce { off: false, command, .. } => command.is_empty(),
+            Action::Analyzer { off: false, command } => command.is_empty(),
+            Action::ArtistProvider { off: false, command } => command.is_empty(),
+            Action::ArtSources { sources } => sources.is_empty(),
+            Action::Duplicates { policy } => policy.is_none(),
+            Action::Bpm { analysis } => analysis.is_none(),
+            Action::Radio { mode } => mode.is_none(),
+            Action::Batch(actions) => actions.iter().all(|a| a.is_read_only()),
+            _ => false,
+        }
+    }
+
+    /// Playback and volume or read-only, the commands --kiosk leaves open
+    fn is_control(&self) -> bool {
+        match self {
+            Action::Play | Action::Pause | Action::Stop | Action::PlayPause | Action::Next | Action::Previous | Action::Volume(_) => true,
+            Action::Batch(actions) => actions.iter().all(|a| a.is_control()),
+            _ => self.is_read_only(),
+        }
+    }
+
+    /// Whether the response is a true/false answer, which clients also give as their exit code.
+    /// A batch answers with its last action
+    fn is_query(&self) -> bool {
+        match self {
+            Action::Print(PrintCmd::Playing | PrintCmd::Paused | PrintCmd::Stopped)
+            | Action::Seek(SeekCmd::Seekable)
+            | Action::Volume(VolumeCmd::Muted)
+            | Action::Shuffle(ShuffleCmd::Get)
+            | Action::Repeat(RepeatCmd::Get)
+            | Action::Consume(ConsumeCmd::Get)
+            | Action::Theme(ThemeCmd::AutoArt { state: None }) => true,
+            Action::Batch(actions) => actions.last().is_some_and(|a| a.is_query()),
+            _ => false,
+        }
+    }
 }
 
 // ### ARGS }}}
 
 // ### SERVER ### {{{
 
-fn server(listener: TcpListener, library: Arc<Library>) {
-    for stream in listener.incoming() {
-        debug!("Found client");
-        match stream {
-            Ok(mut s) => {
-                // # Get Data # {{{
-                // confirmation ID
-                if s.write_all(ID.as_bytes()).is_err() {
-                    continue;
+/// Saves the current track's art for platform media controls, which only take URLs.
+/// Returns the file URL, or None if there's no art.
+#[cfg(feature = "media-controls")]
+fn write_cover(library: &Library, path: &std::path::Path) -> Option<String> {
+    #[cfg(not(feature = "album-art"))]
+    {
+        let _ = (library, path);
+        None
+    }
+    #[cfg(feature = "album-art")]
+    {
+        let art = library.art()?;
+        let (w, h) = (art.first().map(|r| r.len()).unwrap_or(0) as u32, art.len() as u32);
+        let image = image::RgbaImage::from_raw(w, h, art.iter().flat_map(|r| r.iter().flatten().copied()).collect())?;
+        if let Err(e) = image.save(path) {
+            error!("Could not save cover art: {}", e);
+            return None;
+        }
+        library::http::file_url(path)
+    }
+}
+
+/// Runs a single client Action against the library, returning the response text.
+/// Exit is left to the caller as it ends the server.
+fn process(library: &Arc<Library>, action: Action) -> String {
+    let mut response = String::new();
+    // for anything status bars poll
+    let snapshot = library.snapshot();
+    match action {
+        Action::Main { .. } | Action::Exit | Action::Ticket { .. } | Action::Pipe | Action::Complete { .. } | Action::Bench { .. } => (),
+        // one set of events for the lot, so frontends never draw the in-between states
+        Action::Batch(actions) => {
+            response = library.batch(|| {
+                actions
+                    .into_iter()
+                    .map(|action| process(library, action))
+                    .filter(|r| !r.is_empty())
+                    .collect::<Vec<String>>()
+                    .join("\n")
+            })
+        }
+        Action::Next => library.next(),
+        Action::Previous => library.previous(),
+        Action::Pause => library.pause(),
+        Action::Play => library.play(),
+        Action::PlayPause => library.play_pause(),
+        Action::Stop => library.stop(),
+        Action::Seek(seek_cmd) => match seek_cmd {
+            SeekCmd::Get => {
+                if let Some((current, total)) = snapshot.times() {
+                    response = format!(
+                        "{:02}:{:02}:{:05.2} / {:02}:{:02}:{:05.2}",
+                        current.as_secs() / 360,
+                        current.as_secs() / 60 % 60,
+                        current.as_secs_f32() % 60.0,
+                        total.as_secs() / 360,
+                        total.as_secs() / 60 % 60,
+                        total.as_secs_f32() % 60.0,
+                    )
+                }
+            }
+            SeekCmd::GetSecs => {
+                if let Some((current, total)) = snapshot.times() {
+                    response = format!("{:.2} / {:.2}", current.as_secs_f32(), total.as_secs_f32())
+                }
+            }
+            SeekCmd::GetFloat => {
+                if let Some((current, total)) = snapshot.times() {
+                    response = format!("{:.8}", current.as_secs_f32() / total.as_secs_f32())
+                }
+            }
+            SeekCmd::Seekable => response = (snapshot.seekable == Some(true)).to_string(),
+            SeekCmd::Decoded => {
+                if let Some(decoded) = library.decoded() {
+                    response = format!("{:.2}", decoded.as_secs_f32())
+                }
+            }
+            SeekCmd::To { time } => library.seek(time),
+            SeekCmd::ToPercent { percent } => library.seek_fraction(percent / 100.0),
+            SeekCmd::By { secs } => library.seek_by(secs),
+            SeekCmd::Replay { secs } => library.seek_by(-secs.abs()),
+            SeekCmd::Step { step, back } => library.seek_step(step, !back),
+            SeekCmd::Steps { small, medium, large } => {
+                let mut steps = library.seek_steps_get();
+                if small.is_none() && medium.is_none() && large.is_none() {
+                    response = steps.to_string()
+                } else {
+                    steps.small = small.unwrap_or(steps.small);
+                    steps.medium = medium.unwrap_or(steps.medium);
+                    steps.large = large.unwrap_or(steps.large);
+                    library.seek_steps_set(steps)
+                }
+            }
+        },
+        Action::Love | Action::Unlove => {
+            let result = match library.track_get() {
+                Some(track) => library.love(&track, matches!(action, Action::Love)),
+                None => Err("No track is loaded".to_string()),
+            };
+            if let Err(e) = result {
+                response = e
+            }
+        }
+        Action::Favorites { name: None } => response = library.favorites_get(),
+        Action::Favorites { name: Some(name) } => library.favorites_set(name),
+        Action::ArtSources { sources } if sources.is_empty() => {
+            response = library
+                .art_sources_get()
+                .into_iter()
+                .filter_map(|s| s.to_possible_value().map(|v| v.get_name().to_string()))
+                .collect::<Vec<_>>()
+                .join(" ")
+        }
+        Action::ArtSources { sources } => library.art_sources_set(sources),
+        Action::Announce { off: true, .. } => library.announcer_set(None),
+        Action::Announce { command, .. } if command.is_empty() => {
+            response = library
+                .announcer_get()
+                .map(|a| format!("{}\ntagstring: {}", a.command.join(" "), a.tagstring))
+                .unwrap_or_default()
+        }
+        Action::Announce { tagstring, command, .. } => library.announcer_set(Some(library::Announcer { command, tagstring })),
+        #[cfg(feature = "analyzer")]
+        Action::Analyzer { off: true, .. } => library.analyzer_set(None),
+        #[cfg(feature = "analyzer")]
+        Action::Analyzer { command, .. } if command.is_empty() => response = library.analyzer_get().map(|a| a.command.join(" ")).unwrap_or_default(),
+        #[cfg(feature = "analyzer")]
+        Action::Analyzer { command, .. } => library.analyzer_set(Some(library::Analyzer { command })),
+        #[cfg(not(feature = "analyzer"))]
+        Action::Analyzer { .. } => response = String::from("Analyzing needs ompl built with the `analyzer` feature"),
+        Action::ArtistProvider { off: true, .. } => library.artist_provider_set(None),
+        Action::ArtistProvider { command, .. } if command.is_empty() => {
+            response = library.artist_provider_get().map(|p| p.command.join(" ")).unwrap_or_default()
+        }
+        Action::ArtistProvider { command, .. } => library.artist_provider_set(Some(library::ArtistProvider { command })),
+        Action::Bookmark(bookmark_cmd) => {
+            let result = match (library.track_get(), bookmark_cmd) {
+                (None, _) => Err("No track is loaded".to_string()),
+                (Some(track), BookmarkCmd::List) => {
+                    response = library
+                        .bookmarks(&track)
+                        .iter()
+                        .map(|b| format!("{:02}:{:05.2} {}", b.time.as_secs() / 60, b.time.as_secs_f32() % 60.0, b.name))
+                        .collect::<Vec<String>>()
+                        .join("\n");
+                    Ok(())
+                }
+                (Some(track), BookmarkCmd::Add { name, time }) => match time.or(library.times().map(|t| t.0)) {
+                    Some(time) => {
+                        library.bookmark_add(&track, name, time);
+                        Ok(())
+                    }
+                    None => Err("The current time isn't known yet".to_string()),
+                },
+                (Some(_), BookmarkCmd::Jump { name }) => library.bookmark_jump(&name),
+                (Some(track), BookmarkCmd::Remove { name }) => library.bookmark_remove(&track, &name),
+            };
+            if let Err(e) = result {
+                response = e
+            }
+        }
+        Action::Loop(loop_cmd) => {
+            let now = library.times().map(|t| t.0);
+            let (a, b) = library.loop_get();
+            let result = match loop_cmd {
+                LoopCmd::Get => {
+                    if let Some(b) = b {
+                        response = format!("{:.2} - {:.2}", a.unwrap_or_default().as_secs_f32(), b.as_secs_f32())
+                    } else if let Some(a) = a {
+                        response = format!("{:.2} -", a.as_secs_f32())
+                    }
+                    Ok(())
+                }
+                // an end before the new start no longer makes sense
+                LoopCmd::SetA { time } => match time.or(now) {
+                    Some(time) => library.loop_set(Some(time), b.filter(|b| *b > time)),
+                    None => Err("No track is loaded".to_string()),
+                },
+                LoopCmd::SetB { time } => match time.or(now) {
+                    Some(time) => library.loop_set(a, Some(time)),
+                    None => Err("No track is loaded".to_string()),
+                },
+                LoopCmd::Clear => library.loop_set(None, None),
+            };
+            if let Err(e) = result {
+                response = e
+            }
+        }
+        Action::SkipIntro { time: None } | Action::SkipOutro { time: None } => match library.silence_get() {
+            Some(silence) => {
+                let time = if matches!(action, Action::SkipIntro { .. }) {
+                    silence.intro
+                } else {
+                    silence.outro
                 };
+                response = format!("{:.2}", time.as_secs_f32())
+            }
+            None => response = String::from("Skipping needs the Sympal backend"),
+        },
+        Action::SkipIntro { time: Some(time) } => {
+            if let Err(e) = library.skip_set(Some(time), None) {
+                response = e
+            }
+        }
+        Action::SkipOutro { time: Some(time) } => {
+            if let Err(e) = library.skip_set(None, Some(time)) {
+                response = e
+            }
+        }
+        Action::Gain(gain_cmd) => match (library.track_get(), gain_cmd) {
+            (_, GainCmd::Scan { .. }) => (),
+            (None, _) => response = String::from("No track loaded"),
+            (Some(track), GainCmd::Get) => response = format!("{:+.1}dB", track.gain_offset()),
+            (Some(track), GainCmd::Adjust { db }) => library.gain_offset_set(&track, track.gain_offset() + db),
+            (Some(track), GainCmd::Set { db }) => library.gain_offset_set(&track, db),
+        },
+        Action::Bpm { analysis: Some(analysis) } => {
+            if let Err(e) = library.bpm_analysis_set(analysis) {
+                response = e
+            }
+        }
+        Action::Bpm { analysis: None } => {
+            response = library
+                .bpm_analysis_get()
+                .to_possible_value()
+                .map(|v| v.get_name().to_string())
+                .unwrap_or_default()
+        }
+        #[cfg(feature = "cast")]
+        Action::Cast(cast_cmd) => {
+            response = match cast_cmd {
+                CastCmd::List => cast::list(),
+                CastCmd::Start { device } => cast::start(library, &device),
+                CastCmd::Stop => cast::stop(),
+            }
+        }
+        #[cfg(not(feature = "cast"))]
+        Action::Cast(_) => response = String::from("Casting needs ompl built with the `cast` feature"),
+        Action::Output(output_cmd) => {
+            let result = match output_cmd {
+                OutputCmd::List => match library.outputs() {
+                    Some(outputs) => {
+                        response = outputs
+                            .iter()
+                            .map(|o| match o.enabled {
+                                true => format!("* {:.2} {}", o.volume, o.name),
+                                false => format!("       {}", o.name),
+                            })
+                            .collect::<Vec<String>>()
+                            .join("\n");
+                        Ok(())
+                    }
+                    None => Err("This backend only supports the default output".to_string()),
+                },
+                OutputCmd::Enable { name } => library.output_enable(&name, true),
+                OutputCmd::Disable { name } => library.output_enable(&name, false),
+                OutputCmd::Volume { name, amount } => library.output_volume_set(&name, amount),
+                OutputCmd::RouteChange { policy: Some(policy) } => {
+                    library.route_change_set(policy);
+                    Ok(())
+                }
+                OutputCmd::RouteChange { policy: None } => {
+                    response = library
+                        .route_change_get()
+                        .to_possible_value()
+                        .map(|v| v.get_name().to_string())
+                        .unwrap_or_default();
+                    Ok(())
+                }
+            };
+            if let Err(e) = result {
+                response = e
+            }
+        }
+        Action::Volume(vol_cmd) => match vol_cmd {
+            VolumeCmd::Get => {
+                response = format!("{:.2}", snapshot.volume);
+            }
+            VolumeCmd::Add { amount } => library.volume_add(amount),
+            VolumeCmd::Sub { amount } => library.volume_add(-amount),
+            VolumeCmd::Set { amount } => library.volume_set(amount),
+            VolumeCmd::Mute => library.mute(),
+            VolumeCmd::Unmute => library.unmute(),
+            VolumeCmd::ToggleMute => library.mute_toggle(),
+            VolumeCmd::Muted => response = library.muted().to_string(),
+        },
+        Action::Shuffle(shuffle_cmd) => match shuffle_cmd {
+            ShuffleCmd::Get => {
+                response = match (library.shuffle_get(), library.shuffle_album_get()) {
+                    (true, Some(_)) => String::from("album"),
+                    (shuffle, _) => shuffle.to_string(),
+                }
+            }
+            ShuffleCmd::True => {
+                library.shuffle_album_set(None);
+                library.shuffle_set(true)
+            }
+            ShuffleCmd::Album { tagstring } => {
+                library.shuffle_album_set(Some(tagstring));
+                library.shuffle_set(true)
+            }
+            ShuffleCmd::False => library.shuffle_set(false),
+            ShuffleCmd::Toggle => library.shuffle_toggle(),
+        },
+        Action::Radio { mode: Some(mode) } => library.radio_set(mode),
+        Action::Radio { mode: None } => {
+            response = library
+                .radio_get()
+                .to_possible_value()
+                .map(|v| v.get_name().to_string())
+                .unwrap_or_default()
+        }
+        Action::Repeat(repeat_cmd) => match repeat_cmd {
+            RepeatCmd::Get => {
+                response = match library.repeat_get() {
+                    Some(true) => true.to_string(),
+                    Some(false) => "track".to_string(),
+                    None => false.to_string(),
+                }
+            }
+            RepeatCmd::True => library.repeat_set(Some(true)),
+            RepeatCmd::Track => library.repeat_set(Some(false)),
+            RepeatCmd::False => library.repeat_set(None),
+            RepeatCmd::Toggle => library.repeat_toggle(),
+        },
+        Action::Consume(consume_cmd) => match consume_cmd {
+            ConsumeCmd::Get => response = library.consume_get().to_string(),
+            ConsumeCmd::On => library.consume_set(true),
+            ConsumeCmd::Off => library.consume_set(false),
+            ConsumeCmd::Toggle => library.consume_toggle(),
+        },
+        Action::Statusline(statusline_cmd) => match statusline_cmd {
+            StatuslineCmd::Set { tagstring } => library.statusline_set(tagstring),
+            StatuslineCmd::Get => response = library.statusline_get(),
+        },
+        Action::Groupline(groupline_cmd) => match groupline_cmd {
+            GrouplineCmd::Set { tagstring } => library.groupline_set(tagstring),
+            GrouplineCmd::Get => response = library.groupline_get(),
+        },
+        Action::Theme(ThemeCmd::AutoArt { state: None }) => response = library.accent_auto_get().to_string(),
+        Action::Theme(ThemeCmd::AutoArt { state: Some(state) }) => library.accent_auto_set(state),
+        Action::Theme(theme_cmd) => {
+            let mut theme = library.theme_get();
+            match theme_cmd {
+                ThemeCmd::FG { foreground } => theme.fg = foreground,
+                ThemeCmd::BG { background } => theme.bg = background,
+                ThemeCmd::ACC { accent } => theme.acc = accent,
+                ThemeCmd::Art { art_size } => theme.art_size = art_size,
+                ThemeCmd::Dither { dither } => theme.art_dither = dither,
+                ThemeCmd::Colors { colors } => theme.art_colors = colors,
+                ThemeCmd::Letterbox { letterbox } => theme.art_letterbox = letterbox,
+                ThemeCmd::Chips { chips } => theme.art_chips = chips,
+                ThemeCmd::AutoArt { .. } => unreachable!("auto-art is matched before the theme is touched"),
+            };
+            library.theme_set(theme)
+        }
+        Action::PlayFile { file } => {
+            if file.is_file() {
+                library.play_track(library::find_tracks(file, &library.types(), true).into_iter().last().map(|mut t| {
+                    t.load_meta();
+                    Arc::new(t)
+                }))
+            }
+        }
 
-                let mut response = String::new();
+        Action::Filter(cmd) => match cmd {
+            FilterCmd::Get { index } => {
+                response = if let Some(i) = index {
+                    library.get_filter(i).map(|f| f.to_string()).unwrap_or(String::new())
+                } else {
+                    library
+                        .get_filters()
+                        .into_iter()
+                        .map(|f| f.to_string())
+                        .collect::<Vec<String>>()
+                        .join("\n")
+                }
+            }
+            FilterCmd::Set { filters } => library.set_filters(filters),
+            FilterCmd::Remove { index } => library.remove_filter(index),
+            FilterCmd::Insert { index, filter } => library.insert_filter(filter, index),
+            FilterCmd::Replace { index, filter } => library.set_filter(index, filter),
+            FilterCmd::Search { tagstring, query } => {
+                if library.filter_search(tagstring, &query) == 0 {
+                    response = format!("Nothing in the queue contains \"{}\"", query)
+                }
+            }
+        },
+        Action::Browse(browse_cmd) => library.set_filters(browse_cmd.filters()),
 
-                // exchange size
-                let mut data = [0u8; std::mem::size_of::<usize>()];
-                if s.read_exact(&mut data).is_err() {
-                    continue;
-                };
-                let size: usize = usize::from_be_bytes(data);
+        Action::Sorter(cmd) => match cmd {
+            SorterCmd::Get { index } => {
+                response = if let Some(i) = index {
+                    library.get_sorter(i).unwrap_or(String::new())
+                } else {
+                    library.get_sorters().join("\n")
+                }
+            }
+            SorterCmd::Set { tagstrings } => library.set_sorters(tagstrings),
+            SorterCmd::Remove { index } => library.remove_sorter(index),
+            SorterCmd::Insert { index, tagstring } => library.insert_sorter(tagstring, index),
+            SorterCmd::Replace { index, tagstring } => library.set_sorter(index, tagstring),
+            SorterCmd::Reverse { index } => library.sorter_toggle_direction(index),
+        },
+        Action::Collate {
+            locale: None,
+            articles: None,
+            transliterate: None,
+            sort_tags: None,
+        } => response = library.collation_get().to_string(),
+        Action::Collate {
+            locale,
+            articles,
+            transliterate,
+            sort_tags,
+        } => {
+            let mut collation = library.collation_get();
+            if let Some(locale) = locale {
+                collation.locale = Some(locale).filter(|l| !l.is_empty() && l != "none")
+            }
+            collation.articles = articles.unwrap_or(collation.articles);
+            collation.transliterate = transliterate.unwrap_or(collation.transliterate);
+            collation.sort_tags = sort_tags.unwrap_or(collation.sort_tags);
+            library.collation_set(collation)
+        }
 
-                // exchange args
-                let mut data = vec![0u8; size];
-                if s.read_exact(&mut data).is_err() {
-                    continue;
-                };
-                // # Get Data # }}}
-
-                // # Process # {{{
-                debug!("Processing command...");
-                match bincode::deserialize::<Args>(&data) {
-                    Ok(args) => {
-                        match args.action.clone() {
-                            Action::Main { .. } => (),
-                            Action::Exit => {
-                                // finalize response 2
-                                if let Err(e) = s.write_all(response.as_bytes()) {
-                                    println!("{}", e)
-                                };
-                                break;
-                            }
-                            Action::Next => library.next(),
-                            Action::Previous => library.previous(),
-                            Action::Pause => library.pause(),
-                            Action::Play => library.play(),
-                            Action::PlayPause => library.play_pause(),
-                            Action::Stop => library.stop(),
-                            Action::Seek(seek_cmd) => match seek_cmd {
-                                SeekCmd::Get => {
-                                    if let Some((current, total)) = library.times() {
-                                        response = format!(
-                                            "{:02}:{:02}:{:05.2} / {:02}:{:02}:{:05.2}",
-                                            current.as_secs() / 360,
-                                            current.as_secs() / 60 % 60,
-                                            current.as_secs_f32() % 60.0,
-                                            total.as_secs() / 360,
-                                            total.as_secs() / 60 % 60,
-                                            total.as_secs_f32() % 60.0,
-                                        )
-                                    }
-                                }
-                                SeekCmd::GetSecs => {
-                                    if let Some((current, total)) = library.times() {
-                                        response = format!("{:.2} / {:.2}", current.as_secs_f32(), total.as_secs_f32())
-                                    }
-                                }
-                                SeekCmd::GetFloat => {
-                                    if let Some((current, total)) = library.times() {
-                                        response = format!("{:.8}", current.as_secs_f32() / total.as_secs_f32())
-                                    }
-                                }
-                                SeekCmd::Seekable => response = (library.seekable() == Some(true)).to_string(),
-                                SeekCmd::To { time } => library.seek(time),
-                                SeekCmd::By { secs } => library.seek_by(secs),
-                            },
-                            Action::Volume(vol_cmd) => match vol_cmd {
-                                VolumeCmd::Get => {
-                                    response = format!("{:.2}", library.volume_get());
-                                }
-                                VolumeCmd::Add { amount } => library.volume_add(amount),
-                                VolumeCmd::Sub { amount } => library.volume_add(-amount),
-                                VolumeCmd::Set { amount } => library.volume_set(amount),
-                            },
-                            Action::Shuffle(shuffle_cmd) => match shuffle_cmd {
-                                ShuffleCmd::Get => response = library.shuffle_get().to_string(),
-                                ShuffleCmd::True => library.shuffle_set(true),
-                                ShuffleCmd::False => library.shuffle_set(false),
-                                ShuffleCmd::Toggle => library.shuffle_toggle(),
-                            },
-                            Action::Repeat(repeat_cmd) => match repeat_cmd {
-                                RepeatCmd::Get => {
-                                    response = match library.repeat_get() {
-                                        Some(true) => true.to_string(),
-                                        Some(false) => "track".to_string(),
-                                        None => false.to_string(),
-                                    }
-                                }
-                                RepeatCmd::True => library.repeat_set(Some(true)),
-                                RepeatCmd::Track => library.repeat_set(Some(false)),
-                                RepeatCmd::False => library.repeat_set(None),
-                                RepeatCmd::Toggle => library.repeat_toggle(),
-                            },
-                            Action::Statusline(statusline_cmd) => match statusline_cmd {
-                                StatuslineCmd::Set { tagstring } => library.statusline_set(tagstring),
-                                StatuslineCmd::Get => response = library.statusline_get(),
-                            },
-                            Action::Theme(theme_cmd) => {
-                                let mut theme = library.theme_get();
-                                match theme_cmd {
-                                    ThemeCmd::FG { foreground } => theme.fg = foreground,
-                                    ThemeCmd::BG { background } => theme.bg = background,
-                                    ThemeCmd::ACC { accent } => theme.acc = accent,
-                                    ThemeCmd::Art { art_size } => theme.art_size = art_size,
-                                };
-                                library.theme_set(theme)
-                            }
-                            Action::PlayFile { file } => {
-                                if file.is_file() {
-                                    library.play_track(library::find_tracks(file, &library.types(), true).into_iter().last().map(|mut t| {
-                                        t.load_meta();
-                                        Arc::new(t)
-                                    }))
-                                }
-                            }
+        Action::View(cmd) => {
+            let find = |v: &str| library.find_view(v).or(v.parse::<usize>().ok());
+            match cmd {
+                ViewCmd::Get => {
+                    let index = library.view_index();
+                    response = library
+                        .get_views()
+                        .into_iter()
+                        .enumerate()
+                        .map(|(n, v)| format!("{}{}: {}", if n == index { "*" } else { " " }, n, v))
+                        .collect::<Vec<String>>()
+                        .join("\n")
+                }
+                ViewCmd::New { name } => library.new_view(name),
+                ViewCmd::Switch { view } => match find(&view) {
+                    Some(i) => library.switch_view(i),
+                    None => response = format!("No view \"{}\"", view),
+                },
+                ViewCmd::Remove { view } => match find(&view) {
+                    Some(i) => library.remove_view(i),
+                    None => response = format!("No view \"{}\"", view),
+                },
+                ViewCmd::Rename { name } => library.rename_view(library.view_index(), name),
+            }
+        }
 
-                            Action::Filter(cmd) => match cmd {
-                                FilterCmd::Get { index } => {
-                                    response = if let Some(i) = index {
-                                        library
-                                            .get_filter(i)
-                                            .map(|f| {
-                                                if f.items.is_empty() {
-                                                    f.tag
-                                                } else {
-                                                    format!("{}={}", f.tag, f.items.join(","))
-                                                }
-                                            })
-                                            .unwrap_or(String::new())
-                                    } else {
-                                        library
-                                            .get_filters()
-                                            .into_iter()
-                                            .map(|f| {
-                                                if f.items.is_empty() {
-                                                    f.tag
-                                                } else {
-                                                    format!("{}={}", f.tag, f.items.join(","))
-                                                }
-                                            })
-                                            .collect::<Vec<String>>()
-                                            .join("\n")
-                                    }
-                                }
-                                FilterCmd::Set { filters } => library.set_filters(filters),
-                                FilterCmd::Remove { index } => library.remove_filter(index),
-                                FilterCmd::Insert { index, filter } => library.insert_filter(filter, index),
-                                FilterCmd::Replace { index, filter } => library.set_filter(index, filter),
-                            },
-
-                            Action::Sorter(cmd) => match cmd {
-                                SorterCmd::Get { index } => {
-                                    response = if let Some(i) = index {
-                                        library.get_sorter(i).unwrap_or(String::new())
-                                    } else {
-                                        library.get_sorters().join("\n")
-                                    }
-                                }
-                                SorterCmd::Set { tagstrings } => library.set_sorters(tagstrings),
-                                SorterCmd::Remove { index } => library.remove_sorter(index),
-                                SorterCmd::Insert { index, tagstring } => library.insert_sorter(tagstring, index),
-                                SorterCmd::Replace { index, tagstring } => library.set_sorter(index, tagstring),
-                            },
-
-                            Action::Print(print_cmd) => match print_cmd {
-                                PrintCmd::Status => {
-                                    response = if library.playing() {
-                                        "playing".to_string()
-                                    } else if library.paused() {
-                                        "paused".to_string()
-                                    } else if library.stopped() {
-                                        "stopped".to_string()
-                                    } else {
-                                        "invalid".to_string()
-                                    }
-                                }
-                                PrintCmd::Track => response = library.track_get().map(|t| format!("{}", t)).unwrap_or("???".to_string()),
-                                PrintCmd::File => {
-                                    response = library
-                                        .track_get()
-                                        .map(|t| t.path().to_str().unwrap_or("???").to_string())
-                                        .unwrap_or("???".to_string())
-                                }
-                                PrintCmd::Tagstring { tagstring } => {
-                                    response = if let Some(track) = library.track_get() {
-                                        library::tagstring::parse(tagstring, track.tags())
-                                    } else {
-                                        String::new()
-                                    }
-                                }
-                                PrintCmd::Playing => response = library.playing().to_string(),
-                                PrintCmd::Paused => response = library.paused().to_string(),
-                                PrintCmd::Stopped => response = library.stopped().to_string(),
-                                PrintCmd::Statusline => response = library.statusline_get_format(),
-                                PrintCmd::Theme => response = library.theme_get().to_string(),
-                            },
-                            Action::Append { path } => library.append_library(path),
-                            Action::Purge => library.purge(),
-                        };
+        Action::Print(print_cmd) => match print_cmd {
+            PrintCmd::Status => {
+                response = if snapshot.playing {
+                    "playing".to_string()
+                } else if snapshot.paused {
+                    "paused".to_string()
+                } else if snapshot.stopped {
+                    "stopped".to_string()
+                } else {
+                    "invalid".to_string()
+                }
+            }
+            PrintCmd::Track => response = snapshot.track.as_ref().map(|t| format!("{}", t)).unwrap_or("???".to_string()),
+            PrintCmd::File => {
+                response = snapshot
+                    .track
+                    .as_ref()
+                    .map(|t| t.path().to_str().unwrap_or("???").to_string())
+                    .unwrap_or("???".to_string())
+            }
+            PrintCmd::Tagstring { tagstring } => {
+                response = if let Some(track) = snapshot.track.as_ref() {
+                    library::tagstring::parse(tagstring, track.tags())
+                } else {
+                    String::new()
+                }
+            }
+            PrintCmd::Playing => response = snapshot.playing.to_string(),
+            PrintCmd::Paused => response = snapshot.paused.to_string(),
+            PrintCmd::Stopped => response = snapshot.stopped.to_string(),
+            PrintCmd::Statusline => response = snapshot.statusline.clone(),
+            PrintCmd::Theme => response = library.theme_get().to_string(),
+            PrintCmd::Values { tagstring } => {
+                let mut values = library::get_taglist(tagstring, &library.get_tracks());
+                values.retain(|v| !v.is_empty());
+                values.sort_unstable();
+                values.dedup();
+                response = values.join("\n")
+            }
+            PrintCmd::Metrics => response = library.metrics().trim_end().to_string(),
+            PrintCmd::Loved => response = snapshot.track.as_ref().is_some_and(|t| library.loved(t)).to_string(),
+            PrintCmd::QueueInfo => response = library.queue_info().to_string(),
+            PrintCmd::Threads => response = library.workers().iter().map(|w| w.to_string()).collect::<Vec<_>>().join("\n"),
+            PrintCmd::Headroom => {
+                response = match library.headroom() {
+                    Some(headroom) => headroom.to_string(),
+                    None if snapshot.track.is_none() => String::from("Nothing playing"),
+                    None => String::from("This backend has no limiter"),
+                }
+            }
+            PrintCmd::Format => {
+                response = match library.format() {
+                    Some(format) => format.to_string(),
+                    None if snapshot.track.is_none() => String::from("Nothing playing"),
+                    None => String::from("This backend doesn't report its format"),
+                }
+            }
+            PrintCmd::Dirs => {
+                let show = |p: Option<PathBuf>| p.map(|p| p.display().to_string()).unwrap_or_else(|| String::from("none"));
+                response = format!(
+                    "config: {}\nlang: {}\ndata: {}\ncache: {}",
+                    show(config_file()),
+                    show(config_dir().map(|d| d.join("lang"))),
+                    data_dir().display(),
+                    cache_dir().display()
+                )
+            }
+        },
+        Action::Append { path } => library.append_library(path),
+        Action::Cancel => library.cancel(),
+        Action::Duplicates { policy: Some(policy) } => library.duplicate_policy_set(policy),
+        Action::Duplicates { policy: None } => {
+            response = std::iter::once(format!(
+                "policy: {}",
+                library
+                    .duplicate_policy_get()
+                    .to_possible_value()
+                    .map(|v| v.get_name().to_string())
+                    .unwrap_or_default()
+            ))
+            .chain(library.duplicates().into_iter().map(|d| d.to_string()))
+            .collect::<Vec<_>>()
+            .join("\n")
+        }
+        Action::Snapshot(cmd) => {
+            let dir = data_file("snapshots");
+            let file = |name: &str| match !name.is_empty() && name.chars().all(|c| c.is_alphanumeric() || c == '-' || c == '_') {
+                true => Ok(dir.join(format!("{}.bin", name))),
+                false => Err(format!("Invalid snapshot name \"{}\"", name)),
+            };
+            let result = match cmd {
+                SnapshotCmd::Save { name } => file(&name).and_then(|path| {
+                    std::fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+                    let snapshot = library::TagSnapshot::from_library(library);
+                    snapshot.save(&path)?;
+                    Ok(format!("Saved {} tracks as {}", snapshot.len(), name))
+                }),
+                SnapshotCmd::Diff { name } => file(&name).and_then(|path| {
+                    let saved = library::TagSnapshot::load(&path).ok_or_else(|| format!("No snapshot named {}", name))?;
+                    library.reload();
+                    let lines = saved.diff(&library::TagSnapshot::from_library(library));
+                    Ok(match lines.is_empty() {
+                        true => String::from("No changes"),
+                        false => lines.join("\n"),
+                    })
+                }),
+                SnapshotCmd::List => {
+                    let mut names: Vec<String> = dir
+                        .read_dir()
+                        .into_iter()
+                        .flatten()
+                        .flatten()
+                        .filter_map(|e| e.path().file_stem().map(|s| s.to_string_lossy().to_string()))
+                        .collect();
+                    names.sort();
+                    Ok(names.join("\n"))
+                }
+            };
+            response = result.unwrap_or_else(|e| e)
+        }
+        Action::Purge => library.purge(),
+        // the client does the copying, so it only needs where each file goes
+        // deleting needs the path back so a track change in between can't delete a file nobody was shown
+        Action::DeleteFile { path, yes, trash } => match (path, library.track_get()) {
+            (None, None) => response = String::from("No track loaded"),
+            (None, Some(track)) => {
+                let path = track.path().to_str().unwrap_or("???");
+                response = format!("Would delete {}\nRun `ompl delete-file --yes \"{}\"` to delete it", path, path)
+            }
+            (Some(path), _) => match library.get_tracks().into_iter().find(|t| t.path() == &path) {
+                None => response = format!("{} is not in the library", path.display()),
+                Some(_) if !yes => response = format!("Would delete {}\nAdd --yes to delete it", path.display()),
+                Some(track) => {
+                    if let Err(e) = library.delete_file(&track, trash) {
+                        response = e
                     }
-                    Err(e) => response = format!("Could not deserialize args\n{}\nOMPL version mismatch?", e),
-                };
-                // # Process # }}}
+                }
+            },
+        },
+        Action::Organize { pattern, all, apply } => {
+            let report = library.organize(&pattern, all, apply);
+            response = match (report.is_empty(), apply) {
+                (true, _) => String::from("Everything is already in place"),
+                (false, true) => report.join("\n"),
+                (false, false) => report.join("\n") + "\nDry run, nothing moved. Add --apply to move the files",
+            }
+        }
+        Action::Reload => response = format!("Reloaded {} tracks", library.reload()),
+        Action::Quarantine(QuarantineCmd::List) => {
+            response = library
+                .get_quarantined()
+                .iter()
+                .map(|p| p.to_string_lossy().to_string())
+                .collect::<Vec<String>>()
+                .join("\n")
+        }
+        Action::Quarantine(QuarantineCmd::Clear) => response = format!("Cleared {} quarantined tracks", library.quarantine_clear()),
+        Action::Clean { list: false } => response = format!("Removed {} missing or unreadable tracks", library.clean()),
+        Action::Clean { list: true } => {
+            response = library
+                .get_unreadable()
+                .iter()
+                .map(|p| p.to_string_lossy().to_string())
+                .collect::<Vec<String>>()
+                .join("\n")
+        }
+        Action::Export { name, all, .. } => {
+            let tracks = if all { library.get_tracks() } else { library.get_queue() };
+            response = library::export::plan(&tracks, &name)
+                .into_iter()
+                .map(|(source, target)| format!("{}\t{}", source.to_string_lossy(), target.to_string_lossy()))
+                // a tab or newline in a path would break the listing
+                .filter(|line| line.matches(['\t', '\n']).count() == 1)
+                .collect::<Vec<String>>()
+                .join("\n")
+        }
+    };
+    response
+}
 
-                // finalize response
-                if s.write_all(response.as_bytes()).is_err() {
-                    continue;
-                };
+/// Messages are length-prefixed so a connection can carry any number of exchanges
+fn write_message(stream: &mut TcpStream, data: &[u8]) -> std::io::Result<()> {
+    // one write, else Nagle holds the second half back waiting on an ACK
+    let mut message = data.len().to_be_bytes().to_vec();
+    message.extend_from_slice(data);
+    stream.write_all(&message)
+}
+
+/// Reads a message, refusing any over LIMIT bytes before allocating for it
+fn read_message(stream: &mut TcpStream, limit: usize) -> std::io::Result<Vec<u8>> {
+    let mut size = [0u8; std::mem::size_of::<usize>()];
+    stream.read_exact(&mut size)?;
+    let size = usize::from_be_bytes(size);
+    if size > limit {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            format!("Message of {} bytes is over the {} byte limit", size, limit),
+        ));
+    }
+    let mut data = vec![0u8; size];
+    stream.read_exact(&mut data)?;
+    Ok(data)
+}
+
+/// Tokens given to `ompl main`
+#[derive(Debug, Default)]
+struct Access(Vec<(Role, String)>);
+
+impl Access {
+    /// Role for a client sending TOKEN, or None if it matches nothing.
+    /// Without a token it's admin until an admin token is set, and control at most in kiosk mode
+    fn role(&self, token: Option<&str>, kiosk: bool) -> Option<Role> {
+        let Some(token) = token else {
+            return Some(if kiosk {
+                Role::Control
+            } else if self.0.iter().any(|(role, _)| *role == Role::Admin) {
+                Role::Read
+            } else {
+                Role::Admin
+            });
+        };
+        self.0.iter().find(|(_, t)| t == token).map(|(role, _)| *role)
+    }
+
+    /// Whether ARGS may run, or why not
+    fn check(&self, args: &Args, kiosk: bool) -> Result<(), String> {
+        let allowed = match self.role(args.token.as_deref(), kiosk) {
+            None => return Err(String::from("Invalid token")),
+            Some(Role::Read) => args.action.is_read_only(),
+            Some(Role::Control) => args.action.is_control(),
+            Some(Role::Admin) => true,
+        };
+        match allowed {
+            true => Ok(()),
+            false if args.token.is_some() => Err(String::from("Not allowed with this token")),
+            false if kiosk => Err(String::from("Not allowed in kiosk mode")),
+            false => Err(String::from("Not allowed without a token")),
+        }
+    }
+}
+
+/// Set by the connection asking to exit, which the server answers with how the shutdown went,
+/// then waits on to have sent that before the process ends
+type ExitRequest = Arc<Mutex<Option<(SyncSender<String>, Receiver<()>)>>>;
+
+/// Serves one connection until the client hangs up.
+/// Holds the library weakly so open connections don't keep an exiting server alive
+fn connection(mut s: TcpStream, library: Weak<Library>, access: Arc<Access>, exit: ExitRequest, turns: Arc<RwLock<()>>, tickets: Arc<Tickets>) {
+    debug!("Found client");
+    let _ = s.set_nodelay(true);
+    // a client that stops reading mid-response is dropped instead of holding this thread forever
+    let _ = s.set_write_timeout(Some(CLIENT_TIMEOUT));
+    // confirmation ID
+    if s.write_all(ID.as_bytes()).is_err() {
+        return;
+    };
+
+    loop {
+        // idle clients like `ompl pipe` may wait as long as they like, but a command has to arrive whole
+        let _ = s.set_read_timeout(None);
+        if !matches!(s.peek(&mut [0]), Ok(1..)) {
+            break;
+        }
+        let _ = s.set_read_timeout(Some(CLIENT_TIMEOUT));
+        let data = match read_message(&mut s, MAX_COMMAND) {
+            Ok(data) => data,
+            Err(e) => {
+                if e.kind() == std::io::ErrorKind::InvalidData {
+                    error!("Dropping client: {}", e);
+                }
+                break;
+            }
+        };
+
+        debug!("Processing command...");
+        let Some(library) = library.upgrade() else { break };
+        library.metrics_request();
+        let args = bincode::deserialize::<Args>(&data)
+            .map_err(|e| format!("Could not deserialize args\n{}\nOMPL version mismatch?", e))
+            .and_then(|args| access.check(&args, library.kiosk_get()).map(|_| args));
+        let response = match args {
+            Ok(Args { action: Action::Exit, .. }) => {
+                drop(library);
+                let ((status_s, status_r), (sent_s, sent_r)) = (sync_channel(1), sync_channel(1));
+                *exit.lock().unwrap_or_else(|e| e.into_inner()) = Some((status_s, sent_r));
+                // wake the listener so it sees the request
+                if let Ok(addr) = s.local_addr() {
+                    let _ = TcpStream::connect(addr);
+                }
+                let status = status_r
+                    .recv()
+                    .unwrap_or_else(|_| String::from("Server exited without finishing shutdown"));
+                let _ = write_message(&mut s, status.as_bytes());
+                let _ = sent_s.send(());
+                break;
+            }
+            Ok(Args {
+                action: Action::Ticket { id },
+                ..
+            }) => tickets.query(id),
+            // export's client needs the plan itself to copy files
+            Ok(Args { action, detach: true, .. }) if !matches!(action, Action::Export { .. }) => {
+                let id = tickets.open();
+                let (library_weak, turns, ticket) = (Arc::downgrade(&library), turns.clone(), tickets.clone());
+                let spawned = thread::Builder::new().name(String::from("Server Ticket")).spawn(move || {
+                    let response = match library_weak.upgrade() {
+                        Some(library) => take_turn(&library, action, &turns),
+                        None => String::from("Server exited before the command ran"),
+                    };
+                    ticket.close(id, response)
+                });
+                match spawned {
+                    Ok(_) => id.to_string(),
+                    Err(e) => {
+                        tickets.close(id, String::new());
+                        format!("Could not start ticket thread: {}", e)
+                    }
+                }
+            }
+            Ok(args) => take_turn(&library, args.action, &turns),
+            Err(e) => e,
+        };
+        drop(library);
+
+        if write_message(&mut s, response.as_bytes()).is_err() {
+            break;
+        };
+    }
+    debug!("End client connection");
+}
+
+/// Process ACTION in its turn. Batches take TURNS exclusively so no other connection's commands land mid-batch
+fn take_turn(library: &Arc<Library>, action: Action, turns: &RwLock<()>) -> String {
+    match action {
+        Action::Batch(_) => {
+            let _turn = turns.write().unwrap_or_else(|e| e.into_inner());
+            process(library, action)
+        }
+        _ => {
+            let _turn = turns.read().unwrap_or_else(|e| e.into_inner());
+            process(library, action)
+        }
+    }
+}
+
+/// Results of commands sent with --detach, by ticket ID
+#[derive(Default)]
+struct Tickets {
+    last: AtomicU64,
+    /// None while the command is still running
+    results: Mutex<BTreeMap<u64, Option<String>>>,
+}
+
+impl Tickets {
+    /// New pending ticket
+    fn open(&self) -> u64 {
+        let id = self.last.fetch_add(1, Ordering::Relaxed) + 1;
+        let mut results = self.results.lock().unwrap_or_else(|e| e.into_inner());
+        results.insert(id, None);
+        // results nobody came back for are forgotten oldest first
+        while results.values().filter(|r| r.is_some()).count() > TICKETS_KEPT {
+            let Some(oldest) = results.iter().find(|(_, r)| r.is_some()).map(|(id, _)| *id) else {
+                break;
+            };
+            results.remove(&oldest);
+        }
+        id
+    }
+
+    fn close(&self, id: u64, response: String) {
+        self.results.lock().unwrap_or_else(|e| e.into_inner()).insert(id, Some(response));
+    }
+
+    /// Response for `ompl ticket`. A finished ticket's result is handed over once
+    fn query(&self, id: Option<u64>) -> String {
+        let mut results = self.results.lock().unwrap_or_else(|e| e.into_inner());
+        match id {
+            Some(id) => match results.get(&id) {
+                None => format!("No ticket {}", id),
+                Some(None) => String::from("pending"),
+                Some(Some(_)) => results.remove(&id).flatten().unwrap_or_default(),
+            },
+            None => results
+                .iter()
+                .map(|(id, r)| format!("{}: {}", id, if r.is_some() { "done" } else { "pending" }))
+                .collect::<Vec<_>>()
+                .join("\n"),
+        }
+    }
+}
+
+/// Fades out, saves a [`library::Resume`] to RESUME_FILE and the library folders to ROOTS_FILE,
+/// then waits on the player's threads. Returns the status sent back to the client that asked
+fn shutdown(library: &Library, resume_file: &Path, roots_file: &Path) -> String {
+    // taken before fading out so it still knows whether it was playing
+    let resume = library::Resume::from_library(library);
+    let stopped = library.shutdown();
+    resume.save(resume_file);
+    save_roots(library, roots_file);
+    match stopped {
+        Ok(()) => String::from("Saved state and exited"),
+        Err(e) => format!("Saved state and exited, but playback didn't stop cleanly: {}", e),
+    }
+}
+
+/// Shuts down cleanly when a connection asks to exit
+fn server(listener: TcpListener, library: Arc<Library>, access: Access, resume_file: PathBuf, roots_file: PathBuf) {
+    let access = Arc::new(access);
+    let exit: ExitRequest = Default::default();
+    let turns = Arc::new(RwLock::new(()));
+    let tickets = Arc::new(Tickets::default());
+    for stream in listener.incoming() {
+        if let Some((status_s, sent_r)) = exit.lock().unwrap_or_else(|e| e.into_inner()).take() {
+            let _ = status_s.send(shutdown(&library, &resume_file, &roots_file));
+            let _ = sent_r.recv_timeout(CLIENT_TIMEOUT);
+            break;
+        }
+        match stream {
+            Ok(s) => {
+                let (library, access, exit) = (Arc::downgrade(&library), access.clone(), exit.clone());
+                let (turns, tickets) = (turns.clone(), tickets.clone());
+                if let Err(e) = thread::Builder::new()
+                    .name(String::from("Server Connection"))
+                    .spawn(move || connection(s, library, access, exit, turns, tickets))
+                {
+                    error!("Could not start connection thread: {}", e)
+                }
             }
             Err(e) => panic!("Listener panic: {}", e),
         }
-        debug!("End client connection");
     }
     debug!("Server exiting");
 }
@@ -825,12 +2460,17 @@ fn instance_main(listener: TcpListener, args: Args) -> Result<(), Box<dyn Error>
     match args.action {
         Action::Main {
             library: library_paths,
+            fresh,
             hidden,
+            duplicates,
             noshuffle,
+            radio,
             norepeat,
             repeat_track,
             daemon,
+            ui,
             no_media,
+            media_interval,
             filters,
             sorters,
             volume,
@@ -840,16 +2480,57 @@ fn instance_main(listener: TcpListener, args: Args) -> Result<(), Box<dyn Error>
             bg,
             acc,
             art_size,
+            art_dither,
+            art_colors,
+            art_letterbox,
+            art_chips,
             backend,
             buffer,
+            sink,
+            resampler,
+            route_change,
+            bpm,
+            trim_silence,
+            split_silence,
+            silence_threshold,
+            share,
+            max_volume,
+            kiosk,
+            access,
+            metrics,
+            reattach,
+            resume,
+            sigusr1,
+            sigusr2,
         } => {
             LOG_LEVEL.store(verbosity, std::sync::atomic::Ordering::Relaxed);
 
             debug!("Starting main...");
-            let library = Library::new(backend, buffer)?;
+            let library = Library::new(backend, buffer, sink)?;
+            if let Err(e) = library.resampler_set(resampler) {
+                if resampler != library::Resampler::default() {
+                    error!("{}", e)
+                }
+            }
             library.hidden_set(hidden);
+            library.duplicate_policy_set(duplicates);
+            library.artist_cache_set(cache_dir().join("artists"));
+            library.route_change_set(route_change);
+            if let Err(e) = library.bpm_analysis_set(bpm) {
+                error!("{}", e)
+            }
+            library.silence_set(library::Silence {
+                trim: trim_silence,
+                // anything shorter would split at every pause
+                split: split_silence.map(|d| d.max(Duration::from_secs(1))),
+                threshold: silence_threshold,
+                ..Default::default()
+            });
+            library.volume_max_set(max_volume);
             library.volume_set(volume);
+            library.kiosk_set(kiosk);
             library.shuffle_set(!noshuffle);
+            library.radio_set(radio);
             library.repeat_set(if norepeat {
                 None
             } else if repeat_track {
@@ -858,18 +2539,95 @@ fn instance_main(listener: TcpListener, args: Args) -> Result<(), Box<dyn Error>
                 Some(true)
             });
             library.statusline_set(statusline);
-            library.theme_set(Theme { fg, bg, acc, art_size });
+            library.theme_set(Theme {
+                fg,
+                bg,
+                acc,
+                art_size,
+                art_dither,
+                art_colors,
+                art_letterbox,
+                art_chips,
+            });
+            if let Some(config) = config_file() {
+                if let Err(e) = library::config::watch(&library, config) {
+                    error!("{}", e)
+                }
+            }
             library.set_filters(filters);
             library.set_sorters(sorters);
+            // after the sorters so a new favorites view starts with them
+            library.stats_open(data_file("stats.bin"));
+            let roots_file = data_file(&format!("roots-{}.bin", args.port));
+            let library_paths = if library_paths.is_empty() && !fresh {
+                load_roots(&roots_file)
+            } else {
+                library_paths
+            };
             for path in library_paths {
                 library.append_library(path)
             }
+            save_roots(&library, &roots_file);
+            let resume_file = data_file(&format!("resume-{}.bin", args.port));
+            if let Some(resume_as) = resume {
+                match library::Resume::load(&resume_file) {
+                    Some(previous) => previous.restore(&library, resume_as == ResumeAs::Playing),
+                    None => {
+                        info!("Nothing to resume")
+                    }
+                }
+            }
 
             let server_library = library.clone();
-            let jh = thread::spawn(move || server(listener, server_library));
+            let (server_resume, server_roots) = (resume_file.clone(), roots_file.clone());
+            let jh = thread::spawn(move || server(listener, server_library, Access(access), server_resume, server_roots));
             info!("OMPL server listening at {}:{}", args.host, args.port);
 
+            if let Some(address) = share {
+                #[cfg(feature = "share")]
+                match library::share::serve(&library, &address) {
+                    Ok(bound) => {
+                        info!("Sharing library at http://{}/", bound)
+                    }
+                    Err(e) => {
+                        error!("Could not share library at {}: {}", address, e)
+                    }
+                }
+                #[cfg(not(feature = "share"))]
+                error!("Sharing {} needs ompl built with the `share` feature", address);
+            }
+
+            #[cfg(unix)]
+            if let Err(e) = signals(&library, sigusr1, sigusr2) {
+                error!("Could not listen for signals: {}", e)
+            }
+            #[cfg(not(unix))]
+            let _ = (sigusr1, sigusr2);
+
+            if let Some(address) = metrics {
+                match library::metrics::serve(&library, &address) {
+                    Ok(bound) => {
+                        info!("Serving metrics at http://{}/metrics", bound)
+                    }
+                    Err(e) => {
+                        error!("Could not serve metrics at {}: {}", address, e)
+                    }
+                }
+            }
+
+            // Held until exit, dropping it closes the interface
+            #[cfg(feature = "dbus")]
+            let _dbus = match dbus::serve(&library, args.port) {
+                Ok(connection) => Some(connection),
+                Err(e) => {
+                    error!("Could not start D-Bus interface: {}", e);
+                    None
+                }
+            };
+
             // ## souvlaki ## {{{
+            #[cfg(not(feature = "media-controls"))]
+            let _ = media_interval;
             #[cfg(feature = "media-controls")]
             if !no_media {
                 debug!("Initializing media controls...");
@@ -918,8 +2676,8 @@ fn instance_main(listener: TcpListener, args: Args) -> Result<(), Box<dyn Error>
                                 MediaControlEvent::SetVolume(n) => library.volume_set(n as f32),
                                 MediaControlEvent::SetPosition(p) => library.seek(p.0),
                                 MediaControlEvent::Seek(d) => match d {
-                                    souvlaki::SeekDirection::Forward => library.seek_by(5.0),
-                                    souvlaki::SeekDirection::Backward => library.seek_by(-5.0),
+                                    souvlaki::SeekDirection::Forward => library.seek_step(SeekStep::Small, true),
+                                    souvlaki::SeekDirection::Backward => library.seek_step(SeekStep::Small, false),
                                 },
                                 MediaControlEvent::SeekBy(d, n) => match d {
                                     souvlaki::SeekDirection::Forward => library.seek_by(n.as_secs_f32()),
@@ -932,39 +2690,78 @@ fn instance_main(listener: TcpListener, args: Args) -> Result<(), Box<dyn Error>
                     .map_err(|e| format!("{:?}", e))?;
 
                 let meta_libr_wk = Arc::downgrade(&library);
+                let cover_dir = cache_dir();
+                let cover_prefix = format!("cover-{}", args.port);
+                // 0 would spin
+                let media_interval = media_interval.max(Duration::from_millis(100));
                 thread::spawn(move || {
+                    // track the cover belongs to, cover file and its url
+                    let mut cover: (Option<PathBuf>, Option<(PathBuf, String)>) = (None, None);
+                    // duration sent with the metadata, only known once the track is seekable
+                    let mut duration = None;
+                    // when the position was last sent
+                    let mut pushed = std::time::Instant::now();
                     let result = try_block!({
                         loop {
-                            match libevt_r.recv() {
-                                Ok(_) => {
-                                    if let Some(library) = meta_libr_wk.upgrade() {
-                                        let (pos, tot) = if let Some((cur, tot)) = library.times() {
-                                            (Some(souvlaki::MediaPosition(cur)), Some(tot))
-                                        } else {
-                                            (None, None)
-                                        };
-                                        controls
-                                            .set_metadata(MediaMetadata {
-                                                title: library.track_get().map(|t| t.tags().get("title").cloned()).flatten().as_deref(),
-                                                artist: library.track_get().map(|t| t.tags().get("artist").cloned()).flatten().as_deref(),
-                                                album: library.track_get().map(|t| t.tags().get("album").cloned()).flatten().as_deref(),
-                                                duration: tot,
-                                                cover_url: None,
-                                            })
-                                            .map_err(|e| format!("{:?}", e))?;
-                                        controls
-                                            .set_playback(if library.playing() {
-                                                MediaPlayback::Playing { progress: pos }
-                                            } else if library.paused() {
-                                                MediaPlayback::Paused { progress: pos }
+                            let event = match libevt_r.recv_timeout(media_interval.saturating_sub(pushed.elapsed())) {
+                                // periodic refresh while playing, as if the clock ticked
+                                Err(std::sync::mpsc::RecvTimeoutError::Timeout) => match meta_libr_wk.upgrade().is_some_and(|l| l.playing()) {
+                                    true => Ok(LibEvt::Clock),
+                                    false => continue,
+                                },
+                                event => event,
+                            };
+                            match event {
+                                Ok(event) => {
+                                    let Some(library) = meta_libr_wk.upgrade() else { break Ok(()) };
+                                    match event {
+                                        LibEvt::State | LibEvt::Track | LibEvt::Clock => {
+                                            let (pos, tot) = if let Some((cur, tot)) = library.times() {
+                                                (Some(souvlaki::MediaPosition(cur)), Some(tot))
                                             } else {
-                                                MediaPlayback::Stopped
-                                            })
-                                            .map_err(|e| format!("{:?}", e))?;
-                                        #[cfg(target_os = "linux")]
-                                        controls.set_volume(library.volume_get() as f64).map_err(|e| format!("{:?}", e))?;
-                                    } else {
-                                        break Ok(());
+                                                (None, None)
+                                            };
+                                            let track_path = library.track_get().map(|t| t.path().clone());
+                                            if cover.0 != track_path || duration != tot {
+                                                if cover.0 != track_path {
+                                                    if let Some((old, _)) = cover.1.as_ref() {
+                                                        let _ = std::fs::remove_file(old);
+                                                    }
+                                                    // Unique per track, else clients keep showing whatever they cached first
+                                                    let mut hasher = std::hash::DefaultHasher::new();
+                                                    std::hash::Hash::hash(&track_path, &mut hasher);
+                                                    let path =
+                                                        cover_dir.join(format!("{}-{:016x}.png", cover_prefix, std::hash::Hasher::finish(&hasher)));
+                                                    cover = (track_path, write_cover(&library, &path).map(|url| (path, url)));
+                                                }
+                                                duration = tot;
+                                                let track = library.track_get();
+                                                let tag = |name: &str| track.as_ref().and_then(|t| t.tags().get(name).cloned());
+                                                controls
+                                                    .set_metadata(MediaMetadata {
+                                                        title: tag("title").as_deref(),
+                                                        artist: tag("artist").as_deref(),
+                                                        album: tag("album").as_deref(),
+                                                        duration: tot,
+                                                        cover_url: cover.1.as_ref().map(|(_, url)| url.as_str()),
+                                                    })
+                                                    .map_err(|e| format!("{:?}", e))?;
+                                            }
+                                            pushed = std::time::Instant::now();
+                                            controls
+                                                .set_playback(if library.playing() {
+                                                    MediaPlayback::Playing { progress: pos }
+                                                } else if library.paused() {
+                                                    MediaPlayback::Paused { progress: pos }
+                                                } else {
+                                                    MediaPlayback::Stopped
+                                                })
+                                                .map_err(|e| format!("{:?}", e))?;
+                                        }
+                                        // Only MPRIS has a volume property. SMTC and MPNowPlayingInfoCenter leave it to the OS mixer
+                                        #[cfg(all(unix, not(target_os = "macos")))]
+                                        LibEvt::Volume => controls.set_volume(library.volume_get() as f64).map_err(|e| format!("{:?}", e))?,
+                                        _ => (),
                                     }
                                 }
                                 Err(_) => break Ok(()),
@@ -985,15 +2782,26 @@ fn instance_main(listener: TcpListener, args: Args) -> Result<(), Box<dyn Error>
                 loop {
                     match recv.recv() {
                         Ok(LibEvt::Error(e)) => eprintln!("{}", e),
+                        Ok(LibEvt::Offline(root)) => eprintln!("{} is offline", root.display()),
                         Ok(_) => (),
                         Err(_e) => break,
                     }
                 }
                 jh.join().map_err(|e| format!("{:?}", e))?;
             } else {
+                #[cfg(not(feature = "tui"))]
+                let _ = (ui, reattach);
                 #[cfg(feature = "tui")]
-                if tui::tui(library) {
+                if match ui {
+                    Ui::Full => tui::tui(library.clone(), data_file(&format!("tui-{}.bin", args.port)), reattach),
+                    Ui::Line => tui::line(library.clone()),
+                    Ui::Reader => tui::reader(library.clone()),
+                } {
+                    drop(library);
                     jh.join().map_err(|e| format!("{:?}", e))?;
+                } else {
+                    // quitting the UI ends the server without it seeing an exit
+                    info!("{}", shutdown(&library, &resume_file, &roots_file))
                 }
             }
         }
@@ -1007,7 +2815,8 @@ fn instance_main(listener: TcpListener, args: Args) -> Result<(), Box<dyn Error>
 
 // ### CLIENT ### {{{
 
-fn instance_sub(mut stream: TcpStream, args: Args) -> Result<(), Box<dyn Error>> {
+fn handshake(stream: &mut TcpStream) -> Result<(), Box<dyn Error>> {
+    stream.set_nodelay(true)?;
     // confirmation ID
     let mut confirmation_bytes = vec![0u8; ID.bytes().count()];
     stream.read_exact(&mut confirmation_bytes)?;
@@ -1015,54 +2824,400 @@ fn instance_sub(mut stream: TcpStream, args: Args) -> Result<(), Box<dyn Error>>
     if confirmation != ID {
         return Err(format!("OMPL sub ID '{}' did not match confirmation '{}'", ID, confirmation).into());
     };
+    Ok(())
+}
 
-    let data = bincode::serialize(&args)?;
+/// Send one command and wait for its response
+fn exchange(stream: &mut TcpStream, args: &Args) -> Result<String, Box<dyn Error>> {
+    write_message(stream, &bincode::serialize(args)?)?;
+    // responses like a whole library's tagstrings can be large, and come from the server we chose
+    Ok(String::from_utf8(read_message(stream, usize::MAX)?)?)
+}
 
-    // exchange size
-    stream.write_all(&data.len().to_be_bytes())?;
+/// Exits 1 if a true/false query answered false
+fn instance_sub(mut stream: TcpStream, args: Args) -> Result<ExitCode, Box<dyn Error>> {
+    handshake(&mut stream)?;
+    let response = exchange(&mut stream, &args)?;
+    if !response.is_empty() && !args.quiet {
+        println!("{}", response);
+    }
+    if args.action.is_query() && response.lines().last() == Some("false") {
+        Ok(ExitCode::FAILURE)
+    } else {
+        Ok(ExitCode::SUCCESS)
+    }
+}
 
-    // exchange args
-    stream.write_all(&data)?;
+/// Write out the files the server lists for an Export, printing each as it goes
+fn instance_export(mut stream: TcpStream, args: Args) -> Result<ExitCode, Box<dyn Error>> {
+    let Action::Export {
+        ref dest, format, overwrite, ..
+    } = args.action
+    else {
+        unreachable!()
+    };
+    handshake(&mut stream)?;
+    let response = exchange(&mut stream, &args)?;
+    let plan: Vec<(&str, &str)> = response.lines().filter_map(|line| line.split_once('\t')).collect();
+    if plan.is_empty() {
+        println!("Nothing to export");
+        return Ok(ExitCode::SUCCESS);
+    }
 
-    // finalize response
-    let mut response = String::new();
-    stream.read_to_string(&mut response)?;
-    if !response.is_empty() {
-        println!("{}", response);
+    let (mut written, mut skipped, mut failed) = (0, 0, 0);
+    for (n, (source, name)) in plan.iter().enumerate() {
+        let source = PathBuf::from(source);
+        let mut target = dest.join(name).into_os_string();
+        target.push(".");
+        target.push(format.extension(&source));
+        let target = PathBuf::from(target);
+        let progress = format!("[{}/{}]", n + 1, plan.len());
+        if target.exists() && !overwrite {
+            skipped += 1;
+            println!("{} exists  {}", progress, target.display());
+            continue;
+        }
+        match library::export::export(&source, &target, format) {
+            Ok(()) => {
+                written += 1;
+                println!("{} {}", progress, target.display())
+            }
+            Err(e) => {
+                failed += 1;
+                eprintln!("{} Could not export {}: {}", progress, source.display(), e)
+            }
+        }
+    }
+    println!("Exported {} files, skipped {}, failed {}", written, skipped, failed);
+    Ok(if failed > 0 { ExitCode::from(2) } else { ExitCode::SUCCESS })
+}
+
+/// Split a line into arguments like a shell would.
+/// Whitespace separates unless quoted with ' or ", and \ escapes the next character
+fn split_args(line: &str) -> Result<Vec<String>, String> {
+    let mut args = Vec::new();
+    let mut current: Option<String> = None;
+    let mut quote: Option<char> = None;
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        match (c, quote) {
+            ('\\', Some('\'')) => current.get_or_insert_with(String::new).push(c),
+            ('\\', _) => current.get_or_insert_with(String::new).push(chars.next().ok_or("Trailing escape")?),
+            (c, Some(q)) if c == q => quote = None,
+            (c, Some(_)) => current.get_or_insert_with(String::new).push(c),
+            ('\'' | '"', None) => {
+                quote = Some(c);
+                current.get_or_insert_with(String::new);
+            }
+            (c, None) if c.is_whitespace() => args.extend(current.take()),
+            (c, None) => current.get_or_insert_with(String::new).push(c),
+        }
+    }
+    if quote.is_some() {
+        return Err(String::from("Unterminated quote"));
+    }
+    args.extend(current);
+    Ok(args)
+}
+
+/// Swap the first command word of each ':' separated action for its alias from the config file, see [`library::config`].
+/// Words after the alias are added to the end of its expansion. Commands always win over aliases of the same name,
+/// and expansions aren't expanded again so aliases can't loop
+fn expand_aliases(words: Vec<String>) -> Result<Vec<String>, String> {
+    let Some(text) = config_file().and_then(|path| std::fs::read_to_string(path).ok()) else {
+        return Ok(words);
+    };
+    let aliases = library::config::aliases(&text);
+    if aliases.is_empty() {
+        return Ok(words);
+    }
+    let command = <Args as clap::CommandFactory>::command();
+
+    let mut expanded = Vec::with_capacity(words.len());
+    for (n, segment) in words.split(|w| w == ":").enumerate() {
+        if n > 0 {
+            expanded.push(String::from(":"))
+        }
+        // skip the options before the command, along with the values of those that take one
+        let mut i = 0;
+        while segment.get(i).is_some_and(|w| w.starts_with('-') && w != "--") {
+            if ["--port", "--host", "--token"].contains(&segment[i].as_str()) {
+                i += 1
+            }
+            i += 1
+        }
+        let alias = segment
+            .get(i)
+            .filter(|w| command.find_subcommand(w).is_none())
+            .and_then(|w| aliases.iter().rev().find(|(name, _)| name == w));
+        match alias {
+            Some((_, expansion)) => {
+                expanded.extend_from_slice(&segment[..i]);
+                expanded.extend(split_args(expansion)?);
+                expanded.extend_from_slice(&segment[i + 1..]);
+            }
+            None => expanded.extend_from_slice(segment),
+        }
+    }
+    Ok(expanded)
+}
+
+/// Parse client arguments, where several actions may be given separated by ':'.
+/// Options before the first action apply to the whole batch
+fn parse_args(words: Vec<String>) -> Result<Args, clap::Error> {
+    let parse = |words: &[String]| Args::try_parse_from(std::iter::once("ompl").chain(words.iter().map(|w| w.as_str())));
+    let mut segments = words.split(|w| w == ":");
+    let first = segments.next().unwrap_or_default();
+    let rest: Vec<&[String]> = segments.collect();
+    if rest.is_empty() {
+        return parse(first);
+    }
+    // `ompl -- filter set ... : play` keeps the shell from eating anything
+    let mut args = parse(first).or_else(|e| match first.iter().position(|w| w == "--") {
+        Some(n) => parse(&[&first[..n], &first[n + 1..]].concat()),
+        None => Err(e),
+    })?;
+    let mut actions = vec![args.action];
+    for segment in rest {
+        actions.push(parse(segment)?.action)
+    }
+    if actions.iter().any(|a| {
+        matches!(
+            a,
+            Action::Main { .. }
+                | Action::Exit
+                | Action::Ticket { .. }
+                | Action::Pipe
+                | Action::Complete { .. }
+                | Action::Gain(GainCmd::Scan { .. })
+                | Action::Export { .. }
+                | Action::Bench { .. }
+        )
+    }) {
+        return Err(clap::Error::raw(
+            clap::error::ErrorKind::InvalidSubcommand,
+            "main, exit, ticket, pipe, complete, gain scan, export, and bench can't be part of a batch\n",
+        ));
+    }
+    args.action = Action::Batch(actions);
+    Ok(args)
+}
+
+/// Client commands from stdin over a single connection
+fn instance_pipe(mut stream: TcpStream, args: Args) -> Result<(), Box<dyn Error>> {
+    handshake(&mut stream)?;
+    let mut stdo = std::io::stdout();
+    for line in std::io::stdin().lock().lines() {
+        let words = match split_args(&line?).and_then(expand_aliases) {
+            Ok(words) if words.is_empty() => continue,
+            Ok(words) => words,
+            Err(e) => {
+                eprintln!("{}", e);
+                writeln!(stdo)?;
+                continue;
+            }
+        };
+        match parse_args(words) {
+            Ok(Args {
+                action:
+                    Action::Main { .. }
+                    | Action::Pipe
+                    | Action::Complete { .. }
+                    | Action::Gain(GainCmd::Scan { .. })
+                    | Action::Export { .. }
+                    | Action::Bench { .. },
+                ..
+            }) => {
+                eprintln!("Not available in pipe mode");
+                writeln!(stdo)?;
+            }
+            Ok(Args { action, .. }) => {
+                let exit = matches!(action, Action::Exit);
+                writeln!(stdo, "{}", exchange(&mut stream, &Args { action, ..args.clone() })?)?;
+                if exit {
+                    break;
+                }
+            }
+            Err(e) => {
+                eprintln!("{}", e.render().to_string().trim_end());
+                writeln!(stdo)?;
+            }
+        }
+        // keybind daemons and scripts wait on each response
+        stdo.flush()?;
     }
     Ok(())
 }
 
+/// Tag files under PATH with ReplayGain, printing each as it's measured
+#[cfg(feature = "replaygain")]
+fn gain_scan(path: PathBuf, force: bool) -> ExitCode {
+    use library::replaygain::{scan, Progress};
+    let cancel = library::Cancel::default();
+    // the first Ctrl+C finishes the album being tagged, a second exits right away
+    #[cfg(unix)]
+    {
+        use signal_hook::consts::SIGINT;
+        let _ = signal_hook::flag::register_conditional_shutdown(SIGINT, 130, cancel.flag());
+        let _ = signal_hook::flag::register(SIGINT, cancel.flag());
+    }
+    let mut failed = false;
+    let tagged = scan(&path, force, &cancel, |progress| match progress {
+        Progress::Analyzed { n, total, track, result } => match result {
+            Ok(loudness) => match loudness.gain() {
+                Some(gain) => println!("[{}/{}] {:+.2} dB  {}", n, total, gain, track.path().display()),
+                None => println!("[{}/{}] silent  {}", n, total, track.path().display()),
+            },
+            Err(e) => {
+                failed = true;
+                eprintln!("[{}/{}] Could not measure {}: {}", n, total, track.path().display(), e)
+            }
+        },
+        Progress::Album { name, gain } => match gain {
+            Some(gain) => println!("Album {}: {:+.2} dB", name, gain),
+            None => println!("Album {}: silent", name),
+        },
+        Progress::Written { track, result } => {
+            if let Err(e) = result {
+                failed = true;
+                eprintln!("Could not tag {}: {}", track.path().display(), e)
+            }
+        }
+    });
+    match cancel.cancelled() {
+        true => println!("Cancelled, tagged {} files", tagged),
+        false => println!("Tagged {} files", tagged),
+    }
+    match failed {
+        true => ExitCode::from(2),
+        false => ExitCode::SUCCESS,
+    }
+}
+
+#[cfg(not(feature = "replaygain"))]
+fn gain_scan(_path: PathBuf, _force: bool) -> ExitCode {
+    eprintln!("OMPL was built without the replaygain feature");
+    ExitCode::from(2)
+}
+
+/// Time loading PATH and then sorting it RUNS times, printing each stage's totals
+fn bench(path: PathBuf, filters: Vec<library::Filter>, sorters: Vec<String>, runs: u32) -> ExitCode {
+    let library = match Library::new(Backend::Default, None, None) {
+        Ok(library) => library,
+        Err(e) => {
+            eprintln!("Could not start a library: {}", e);
+            return ExitCode::from(2);
+        }
+    };
+    // sorts run on the library's sort thread, so wait for each to land
+    let wait_sorts = |n: u32| {
+        while library.timings().iter().find(|t| t.stage == "sort").map_or(0, |t| t.runs) < n {
+            thread::sleep(Duration::from_millis(5))
+        }
+    };
+
+    let begin = std::time::Instant::now();
+    library.set_filters(filters);
+    library.set_sorters(sorters.clone());
+    library.append_library(&path);
+    let count = library.get_tracks().len();
+    if count == 0 {
+        eprintln!("No tracks found in {}", path.display());
+        return ExitCode::from(2);
+    }
+    wait_sorts(1);
+    for n in 2..=runs {
+        library.set_sorters(sorters.clone());
+        wait_sorts(n);
+    }
+
+    println!("{} tracks from {} in {:.2?}", count, path.display(), begin.elapsed());
+    println!("{:<8}{:>6}{:>10}{:>12}{:>12}", "stage", "runs", "tracks", "total", "per run");
+    for timing in library.timings() {
+        println!(
+            "{:<8}{:>6}{:>10}{:>12}{:>12}",
+            timing.stage,
+            timing.runs,
+            timing.items,
+            format!("{:.2?}", timing.total),
+            format!("{:.2?}", timing.total / timing.runs)
+        )
+    }
+    ExitCode::SUCCESS
+}
+
 // ### CLIENT ### }}}
 
 // ### MAIN ### {{{
-fn main() {
-    let args = Args::parse();
+fn main() -> ExitCode {
+    // 2 like clap's usage errors, so a failure never reads as a false answer
+    let failure = ExitCode::from(2);
+    let args = match expand_aliases(std::env::args().skip(1).collect()) {
+        Ok(words) => parse_args(words).unwrap_or_else(|e| e.exit()),
+        Err(e) => {
+            eprintln!("Could not expand alias: {}", e);
+            return failure;
+        }
+    };
+
+    if let (Some(lang), Some(dir)) = (args.lang.clone().or_else(locale::system_lang), config_dir()) {
+        if let Err(e) = locale::load(&lang, &dir.join("lang")) {
+            eprintln!("{}", e)
+        }
+    }
 
     match args.action {
+        Action::Complete { shell } => match completions::generate(shell) {
+            Ok(()) => ExitCode::SUCCESS,
+            Err(e) => {
+                eprintln!("\nCould not write completions:\n    {}\n", e);
+                failure
+            }
+        },
+        Action::Gain(GainCmd::Scan { path, force }) => gain_scan(path.unwrap_or_else(|| PathBuf::from(".")), force),
+        Action::Bench {
+            path,
+            filters,
+            sorters,
+            runs,
+        } => bench(path.unwrap_or_else(|| PathBuf::from(".")), filters, sorters, runs),
         Action::Main { .. } => match TcpListener::bind(SocketAddrV4::new(args.host, args.port)) {
-            Ok(listener) => {
-                if let Err(e) = instance_main(listener, args) {
-                    eprintln!("\nOMPL main instance failed:\n    {}\n", e)
+            Ok(listener) => match instance_main(listener, args) {
+                Ok(()) => ExitCode::SUCCESS,
+                Err(e) => {
+                    eprintln!("\nOMPL main instance failed:\n    {}\n", e);
+                    failure
                 }
-            }
-            Err(_) => eprintln!(
-                "\n\nCouldn't bind server socket to port {}.\n\
+            },
+            Err(_) => {
+                eprintln!(
+                    "\n\nCouldn't bind server socket to port {}.\n\
                     Try another port, or perhaps an instance is already running?\n\n",
-                args.port
-            ),
+                    args.port
+                );
+                failure
+            }
         },
         _ => match TcpStream::connect(SocketAddrV4::new(args.host, args.port)) {
             Ok(stream) => {
-                if let Err(e) = instance_sub(stream, args) {
-                    eprintln!("\nOMPL sub instance failed:\n    {}\n", e)
-                }
+                let result = match args.action {
+                    Action::Pipe => instance_pipe(stream, args).map(|_| ExitCode::SUCCESS),
+                    Action::Export { .. } => instance_export(stream, args),
+                    _ => instance_sub(stream, args),
+                };
+                result.unwrap_or_else(|e| {
+                    eprintln!("\nOMPL sub instance failed:\n    {}\n", e);
+                    failure
+                })
             }
-            Err(_) => eprintln!(
-                "\n\nCouldn't connect client socket to port {}.\n\
+            Err(_) => {
+                eprintln!(
+                    "\n\nCouldn't connect client socket to port {}.\n\
                         Are you sure there's an OMPL server running here?\n\n",
-                args.port
-            ),
+                    args.port
+                );
+                failure
+            }
         },
     }
 }
@@ -0,0 +1,62 @@
+#![warn(missing_docs)]
+
+//! The TUI's key bindings as shown in the help overlay.
+//! Keep in step with [`super::UI::process_event`] when adding or changing a binding.
+
+/// Keys in the main view as (keys, what they do)
+pub const KEYS: &[(&str, &str)] = &[
+    ("0-9", "navigate top menu"),
+    ("Ctrl+c/q", "exit program"),
+    ("Ctrl+z", "exit only TUI"),
+    ("a", "play/pause"),
+    ("x", "stop"),
+    ("n/p", "next/previous"),
+    ("-/=", "volume decrease/increase"),
+    ("m", "toggle mute"),
+    (".,><", "seek small/medium step, 5/30 seconds by default"),
+    ("Alt+.,", "seek large step, 5 minutes by default"),
+    ("R", "replay last 10 seconds"),
+    ("Alt+0-9", "seek to 0-90%"),
+    ("b", "A-B loop: set A, set B, clear"),
+    ("s", "love/unlove playing track"),
+    ("X", "delete highlighted queue track's file, else playing track's"),
+    ("e", "toggle shuffle"),
+    ("r", "toggle repeat"),
+    ("h/j/k/l", "left/down/up/right"),
+    ("H/L", "move panes"),
+    ("J/K", "half page down/up, extend selection in filters"),
+    ("g/G", "scroll to top/bottom"),
+    ("z", "focus playing"),
+    ("Z", "toggle follow playback"),
+    ("f", "select item"),
+    ("F", "select only item"),
+    ("v/V", "invert/clear selection"),
+    ("Shift+click", "select range in filters"),
+    ("*", "select all matching last search"),
+    ("&", "filter by search, as a new filter"),
+    ("M <key>", "mark highlighted queue track"),
+    ("` <key>", "jump to mark, `` back, `. last played, `/ last search"),
+    ("Tab", "change focus"),
+    ("P", "preview highlighted track for 10 seconds"),
+    ("o", "flip sorter between ascending/descending"),
+    ("i/I", "insert after/before"),
+    ("D", "delete"),
+    ("/", "search"),
+    ("'", "edit"),
+    ("c", "toggle art view"),
+    ("A", "toggle artist info panel"),
+    ("[/]", "previous/next view"),
+    ("?", "help"),
+];
+
+/// Keys in text inputs
+pub const INPUT_KEYS: &[(&str, &str)] = &[("Enter/Esc", "submit/cancel"), ("Ctrl-y/p", "copy/paste"), ("Ctrl-x", "delete word")];
+
+/// Keys in the help overlay itself
+pub const HELP_KEYS: &[(&str, &str)] = &[
+    ("j/k", "scroll down/up"),
+    ("J/K", "page down/up"),
+    ("g/G", "scroll to top/bottom"),
+    ("/", "search, Enter to keep and Esc to clear"),
+    ("q/Esc", "close"),
+];
@@ -0,0 +1,203 @@
+#![warn(missing_docs)]
+
+use std::io::{stdout, IsTerminal, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use super::{get_event, TermGuard};
+use crate::library::{LibEvt, Library, SeekStep};
+use crate::locale::tr;
+use crate::logging::*;
+
+use ratatui::crossterm::{
+    cursor,
+    event::{Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers},
+    queue,
+    style::Print,
+    terminal,
+};
+
+/// Both the key and event threads draw
+static DRAW: Mutex<()> = Mutex::new(());
+
+pub const HELP: &str =
+    "a/space play/pause | x stop | n/p next/previous | -/+ volume | m mute | .,>< seek small/medium step | Alt+., large step | 0-9 seek to 0-90% | R replay | b a-b loop | s love | e shuffle | r repeat | q quit | Ctrl+z exit only UI";
+
+/// Everything worth knowing on one line
+fn status(library: &Library, times: bool) -> String {
+    let mut status = format!(
+        "{} {}",
+        if library.playing() {
+            ">"
+        } else if library.paused() {
+            "|"
+        } else {
+            "#"
+        },
+        library
+            .track_get()
+            .map(|t| t.tagstring(library.statusline_get()))
+            .unwrap_or("???".to_string())
+    );
+    if times {
+        if let Some((cur, tot)) = library.times() {
+            status.push_str(&format!(
+                " | {:02.0}:{:02.0} / {:02.0}:{:02.0}",
+                (cur.as_secs_f32() / 60.0).floor(),
+                (cur.as_secs_f32() % 60.0).floor(),
+                (tot.as_secs_f32() / 60.0).floor(),
+                (tot.as_secs_f32() % 60.0).floor(),
+            ))
+        }
+    }
+    status.push_str(&format!(
+        " | {}{}{}{}",
+        match library.muted() {
+            true => String::from("muted"),
+            false => format!("vol {:.2}", library.volume_get()),
+        },
+        match (library.shuffle_get(), library.shuffle_album_get()) {
+            (true, Some(_)) => " | album shuffle",
+            (true, None) => " | shuffle",
+            (false, _) => "",
+        },
+        match library.repeat_get() {
+            Some(true) => " | repeat",
+            Some(false) => " | repeat track",
+            None => "",
+        },
+        if library.consume_get() { " | consume" } else { "" },
+    ));
+    status
+}
+
+/// Redraw the status line in place, clipped to the terminal width
+fn draw(library: &Library) {
+    let _guard = DRAW.lock();
+    let width = terminal::size().ok().map(|s| s.0 as usize).filter(|w| *w > 1).unwrap_or(80);
+    let mut stdo = stdout();
+    let _ = queue!(
+        stdo,
+        cursor::MoveToColumn(0),
+        terminal::Clear(terminal::ClearType::CurrentLine),
+        Print(status(library, true).chars().take(width.saturating_sub(1)).collect::<String>())
+    );
+    let _ = stdo.flush();
+}
+
+/// Without a terminal just print each distinct status as its own line so it can be piped.
+/// Times are left out as they'd change every tick.
+fn plain(library: Arc<Library>) -> bool {
+    let Ok(mut libevt_r) = library.get_receiver() else { return false };
+    let mut last = String::new();
+    loop {
+        let current = status(&library, false);
+        if current != last {
+            println!("{}", current);
+            last = current;
+        }
+        match libevt_r.recv() {
+            Ok(LibEvt::Error(message)) => eprintln!("{}", message),
+            Ok(LibEvt::Offline(root)) => eprintln!("{} is offline", root.display()),
+            Ok(_) => (),
+            Err(_) => break false,
+        }
+    }
+}
+
+/// Minimal UI, a single self-updating status line in the normal screen.
+/// Returns true if the server should be kept running.
+pub fn line(library: Arc<Library>) -> bool {
+    if !stdout().is_terminal() {
+        return plain(library);
+    }
+
+    let mut libevt_r = library.get_receiver().unwrap();
+    debug!("Entering line UI...");
+    println!("{}", tr(HELP));
+    log_pause!();
+
+    let guard = TermGuard::new(false);
+    draw(&library);
+
+    let done = Arc::new(AtomicBool::new(false));
+    let done_evt = done.clone();
+    let libweak_evt = Arc::downgrade(&library);
+    thread::Builder::new()
+        .name(String::from("Line LibEvt Receiver"))
+        .spawn(move || loop {
+            let event = libevt_r.recv();
+            if done_evt.load(Ordering::Relaxed) {
+                break;
+            }
+            let Some(library) = libweak_evt.upgrade() else { break };
+            match event {
+                // print errors above so they're not drawn over
+                Ok(LibEvt::Error(message)) => {
+                    let guard = DRAW.lock();
+                    let _ = queue!(
+                        stdout(),
+                        cursor::MoveToColumn(0),
+                        terminal::Clear(terminal::ClearType::CurrentLine),
+                        Print(message.replace('\n', " ")),
+                        Print("\r\n")
+                    );
+                    drop(guard);
+                    draw(&library)
+                }
+                Ok(_) => draw(&library),
+                Err(_) => break,
+            }
+        })
+        .unwrap();
+
+    let join = loop {
+        let Some(Event::Key(KeyEvent {
+            code,
+            modifiers,
+            kind: KeyEventKind::Press,
+            ..
+        })) = get_event(None)
+        else {
+            continue;
+        };
+        match (code, modifiers.contains(KeyModifiers::CONTROL)) {
+            (KeyCode::Char('c'), true) | (KeyCode::Char('q'), _) => break false,
+            (KeyCode::Char('z'), true) => break true,
+            (KeyCode::Char('a') | KeyCode::Char(' '), false) => library.play_pause(),
+            (KeyCode::Char('x'), false) => library.stop(),
+            (KeyCode::Char('n'), false) => library.next(),
+            (KeyCode::Char('p'), false) => library.previous(),
+            (KeyCode::Char('=') | KeyCode::Char('+'), false) => library.volume_add(0.05),
+            (KeyCode::Char('-'), false) => library.volume_add(-0.05),
+            (KeyCode::Char('m'), false) => library.mute_toggle(),
+            (KeyCode::Char('e'), false) => library.shuffle_toggle(),
+            (KeyCode::Char('r'), false) => library.repeat_toggle(),
+            (KeyCode::Char('.'), false) if modifiers.contains(KeyModifiers::ALT) => library.seek_step(SeekStep::Large, true),
+            (KeyCode::Char(','), false) if modifiers.contains(KeyModifiers::ALT) => library.seek_step(SeekStep::Large, false),
+            (KeyCode::Char('.') | KeyCode::Right, false) => library.seek_step(SeekStep::Small, true),
+            (KeyCode::Char(',') | KeyCode::Left, false) => library.seek_step(SeekStep::Small, false),
+            (KeyCode::Char('>'), false) => library.seek_step(SeekStep::Medium, true),
+            (KeyCode::Char('<'), false) => library.seek_step(SeekStep::Medium, false),
+            (KeyCode::Char('R'), false) => library.seek_by(-10.0),
+            (KeyCode::Char(c @ '0'..='9'), false) => library.seek_fraction((c as u8 - b'0') as f32 / 10.0),
+            (KeyCode::Char('b'), false) => {
+                let _ = library.loop_cycle();
+            }
+            (KeyCode::Char('s'), false) => {
+                if let Some(track) = library.track_get() {
+                    let _ = library.love(&track, !library.loved(&track));
+                }
+            }
+            _ => (),
+        }
+        // most of the above broadcast anyway but volume and toggles may not
+        draw(&library);
+    };
+    done.store(true, Ordering::Relaxed);
+
+    drop(guard);
+    log_resume!();
+    join
+}
@@ -2,13 +2,16 @@
 
 use std::cmp::min;
 use std::io::{stdout, Write};
-use std::path::PathBuf;
+use std::panic;
+use std::path::{Path, PathBuf};
 use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{sync_channel, Receiver, SyncSender};
 use std::sync::{Arc, Mutex, Weak};
 use std::thread;
 use std::time::{Duration, Instant};
 
-use crate::library::{Color, Filter, LibEvt, Library, Theme};
+use crate::library::{Color, Filter, LibEvt, Library, SeekStep, Theme};
+use crate::locale::{tr, trf};
 use crate::{logging::*, parse_art_size, parse_time};
 
 #[cfg(feature = "clipboard")]
@@ -17,7 +20,7 @@ use arboard::Clipboard;
 use ratatui::crossterm::{
     cursor, event,
     event::{Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers, MouseEvent, MouseEventKind},
-    queue, terminal,
+    queue, style, terminal,
 };
 
 use ratatui::backend::{Backend, CrosstermBackend};
@@ -27,8 +30,14 @@ use ratatui::Terminal;
 
 mod stylesheet;
 use stylesheet::StyleSheet;
+
+/// Upper bound on full redraws. Anything requested in between is coalesced into the next frame
+const MAX_FPS: u64 = 30;
 mod widgets;
-use widgets::{Art, Clickable, ContainedWidget, FilterPanes, MTree, MenuBar, Scrollable, Searchable, Seeker, SortPanes, StatusBar};
+use widgets::{
+    Art, ArtistPanel, Clickable, ContainedWidget, FilterPanes, MTree, MenuBar, QueuePane, Scrollable, Searchable, Seeker, SortPanes, StatusBar,
+    TabBar,
+};
 
 // ### FNs ### {{{
 
@@ -66,6 +75,16 @@ macro_rules! km_s {
         })
     };
 }
+macro_rules! km_a {
+    ($ch:expr) => {
+        Event::Key(KeyEvent {
+            code: KeyCode::Char($ch),
+            modifiers: KeyModifiers::ALT,
+            kind: KeyEventKind::Press,
+            ..
+        })
+    };
+}
 /// get crossterm event with optional poll duration.
 fn get_event(duration: Option<Duration>) -> Option<Event> {
     match duration {
@@ -80,37 +99,92 @@ fn get_event(duration: Option<Duration>) -> Option<Event> {
     }
 }
 
+type PanicHook = Box<dyn Fn(&panic::PanicHookInfo<'_>) + Sync + Send + 'static>;
+
+/// Takes over the terminal, and puts it back when dropped however the UI ends, panics included.
+/// Panic messages would be lost on the alternate screen, so they're held and printed after restoring
+struct TermGuard {
+    /// Full screen UI on the alternate screen, otherwise only raw mode
+    alternate: bool,
+    panics: Arc<Mutex<Vec<String>>>,
+    hook: Option<PanicHook>,
+}
+
+impl TermGuard {
+    fn new(alternate: bool) -> Self {
+        let panics = Arc::new(Mutex::new(Vec::new()));
+        let panics_hook = panics.clone();
+        let hook = panic::take_hook();
+        panic::set_hook(Box::new(move |info| {
+            let message = format!("thread '{}' {}", thread::current().name().unwrap_or("<unnamed>"), info);
+            panics_hook.lock().unwrap_or_else(|e| e.into_inner()).push(message)
+        }));
+
+        terminal::enable_raw_mode().unwrap();
+        let mut stdo = stdout();
+        if alternate {
+            let _ = queue!(
+                stdo,
+                terminal::EnterAlternateScreen,
+                terminal::Clear(terminal::ClearType::All),
+                event::EnableMouseCapture,
+                cursor::Hide
+            );
+        } else {
+            let _ = queue!(stdo, cursor::Hide);
+        }
+        let _ = stdo.flush();
+
+        Self {
+            alternate,
+            panics,
+            hook: Some(hook),
+        }
+    }
+}
+
+impl Drop for TermGuard {
+    fn drop(&mut self) {
+        let mut stdo = stdout();
+        if self.alternate {
+            let _ = queue!(
+                stdo,
+                terminal::Clear(terminal::ClearType::All),
+                cursor::MoveTo(0, 0),
+                terminal::LeaveAlternateScreen,
+                event::DisableMouseCapture,
+                cursor::Show
+            );
+        } else {
+            let _ = queue!(stdo, style::Print("\r\n"), cursor::Show);
+        }
+        let _ = stdo.flush();
+        let _ = terminal::disable_raw_mode();
+
+        for message in self.panics.lock().unwrap_or_else(|e| e.into_inner()).drain(..) {
+            eprintln!("{}", message)
+        }
+        // the hook can't be swapped mid panic, but then the process is going down anyway
+        if !thread::panicking() {
+            if let Some(hook) = self.hook.take() {
+                panic::set_hook(hook)
+            }
+        }
+    }
+}
+
 // ### FNs ### }}}
 
-pub const HELP: &str = &"\
-* 0-9 | navigate top menu
-* Ctrl+c/q | exit program
-* Ctrl+z | exit only TUI
-* a | play/pause
-* x | stop
-* n/p | next/previous
-* -/+ | volume decrease/increase
-* .,>< | seek 5/30 seconds
-* e | toggle shuffle
-* r | toggle repeat
-* h/j/k/l | left/down/up/right
-* H/L | move panes
-* g/G | scroll to top/bottom
-* z | focus playing
-* f | select item
-* F | select only item
-* v/V | invert/clear selection
-* Tab | change focus
-* i/I | insert after/before
-* D | delete
-* / | search
-* ' | edit
-* c | toggle art view
-
-* input
-  * Ctrl-y/p | copy/paste
-  * Ctrl-x | delete word
-";
+mod line;
+pub use line::line;
+
+mod reader;
+pub use reader::reader;
+
+mod session;
+use session::Session;
+
+mod keymap;
 
 // ### UI ### {{{
 
@@ -125,13 +199,21 @@ pub enum Action {
     // Library
     SeekTo,
     Statusline,
+    Groupline,
     ACC,
     FG,
     BG,
     ArtSize,
     ArtView,
+    ArtistView,
     Append,
     Purge,
+    Exit,
+
+    // Views
+    ViewNew,
+    ViewRename,
+    ViewClose,
 
     // Active pane
     Delete,
@@ -148,51 +230,73 @@ struct UI<T: Backend> {
     status_bar: StatusBar,
     seeker: Seeker,
     art: Art,
+    artist_panel: ArtistPanel,
+    tabbar: TabBar,
     filterpanes: FilterPanes,
     sortpanes: SortPanes,
+    queue: QueuePane,
     stylesheet: StyleSheet,
     terminal: Option<Terminal<T>>,
     art_inspect: bool,
+    /// Show the artist info panel beside the panes
+    artist_view: bool,
+    last_search: String,
+    /// Waiting on the key after `M` to set a mark (true) or '`' to jump to one (false)
+    marking: Option<bool>,
+    /// Quit the whole program once the current event is done
+    exit: bool,
     debug: bool,
     draw_count: u128,
+    frame_tx: SyncSender<()>,
     #[cfg(feature = "clipboard")]
     clipboard: Option<Clipboard>,
 }
 
 impl<T: Backend> UI<T> {
-    fn from_library(library: Arc<Library>, terminal: Terminal<T>, stylesheet: StyleSheet) -> Self {
+    fn from_library(library: Arc<Library>, terminal: Terminal<T>, stylesheet: StyleSheet, frame_tx: SyncSender<()>) -> Self {
+        // kiosk mode leaves out what changes the library itself
+        let kiosk = library.kiosk_get();
+        let mut library_menu = vec![
+            (tr("Seek To").to_string(), MTree::Action(Action::SeekTo)),
+            (tr("Statusline").to_string(), MTree::Action(Action::Statusline)),
+            (tr("Groupline").to_string(), MTree::Action(Action::Groupline)),
+        ];
+        let mut view_menu = vec![
+            (tr("New").to_string(), MTree::Action(Action::ViewNew)),
+            (tr("Rename").to_string(), MTree::Action(Action::ViewRename)),
+        ];
+        if !kiosk {
+            library_menu.push((tr("Append").to_string(), MTree::Action(Action::Append)));
+            library_menu.push((tr("Purge").to_string(), MTree::Action(Action::Purge)));
+            library_menu.push((tr("Exit").to_string(), MTree::Action(Action::Exit)));
+            view_menu.push((tr("Close").to_string(), MTree::Action(Action::ViewClose)));
+        }
         let tree = MTree::Tree(vec![
-            (String::from("Help"), MTree::Action(Action::Help)),
-            (
-                String::from("Pane"),
-                MTree::Tree(vec![
-                    (String::from("Insert <-"), MTree::Action(Action::InsertBefore)),
-                    (String::from("Insert ->"), MTree::Action(Action::InsertAfter)),
-                    (String::from("Move <-"), MTree::Action(Action::MoveLeft)),
-                    (String::from("Move ->"), MTree::Action(Action::MoveRight)),
-                    (String::from("Edit"), MTree::Action(Action::Edit)),
-                    (String::from("Delete"), MTree::Action(Action::Delete)),
-                ]),
-            ),
+            (tr("Help").to_string(), MTree::Action(Action::Help)),
             (
-                String::from("Library"),
+                tr("Pane").to_string(),
                 MTree::Tree(vec![
-                    (String::from("Seek To"), MTree::Action(Action::SeekTo)),
-                    (String::from("Statusline"), MTree::Action(Action::Statusline)),
-                    (String::from("Append"), MTree::Action(Action::Append)),
-                    (String::from("Purge"), MTree::Action(Action::Purge)),
+                    (tr("Insert <-").to_string(), MTree::Action(Action::InsertBefore)),
+                    (tr("Insert ->").to_string(), MTree::Action(Action::InsertAfter)),
+                    (tr("Move <-").to_string(), MTree::Action(Action::MoveLeft)),
+                    (tr("Move ->").to_string(), MTree::Action(Action::MoveRight)),
+                    (tr("Edit").to_string(), MTree::Action(Action::Edit)),
+                    (tr("Delete").to_string(), MTree::Action(Action::Delete)),
                 ]),
             ),
+            (tr("Library").to_string(), MTree::Tree(library_menu)),
+            (tr("View").to_string(), MTree::Tree(view_menu)),
             (
-                String::from("Theme"),
+                tr("Theme").to_string(),
                 MTree::Tree(vec![
-                    (String::from("Foreground"), MTree::Action(Action::FG)),
-                    (String::from("Background"), MTree::Action(Action::BG)),
-                    (String::from("Accent"), MTree::Action(Action::ACC)),
-                    (String::from("Art Size"), MTree::Action(Action::ArtSize)),
+                    (tr("Foreground").to_string(), MTree::Action(Action::FG)),
+                    (tr("Background").to_string(), MTree::Action(Action::BG)),
+                    (tr("Accent").to_string(), MTree::Action(Action::ACC)),
+                    (tr("Art Size").to_string(), MTree::Action(Action::ArtSize)),
+                    (tr("Artist Info").to_string(), MTree::Action(Action::ArtistView)),
                 ]),
             ),
-            (String::from("Debug"), MTree::Action(Action::Debug)),
+            (tr("Debug").to_string(), MTree::Action(Action::Debug)),
         ]);
 
         let debug = if LOG_LEVEL.load(Ordering::Relaxed) >= 3 { true } else { false };
@@ -203,18 +307,35 @@ impl<T: Backend> UI<T> {
             status_bar: StatusBar::new(&library),
             seeker: Seeker::new(&library),
             art: Art::new(&library),
+            artist_panel: ArtistPanel::new(&library),
+            tabbar: TabBar::new(&library),
             filterpanes: FilterPanes::new(library.clone()),
             sortpanes: SortPanes::new(library.clone()),
+            queue: QueuePane::new(library.clone()),
             stylesheet,
             terminal: Some(terminal),
             art_inspect: false,
+            artist_view: false,
+            last_search: String::new(),
+            marking: None,
+            exit: false,
             debug,
             draw_count: 0,
+            frame_tx,
             #[cfg(feature = "clipboard")]
             clipboard: Clipboard::new().ok(),
         }
     }
 
+    fn session(&self) -> Session {
+        let mut session = self.lib_weak.upgrade().map(|l| Session::from_library(&l)).unwrap_or_default();
+        session.filter_panes = self.filterpanes.pane_state();
+        session.sort_panes = self.sortpanes.pane_state();
+        session.queue = self.queue.pane_state();
+        session.follow = self.queue.follow;
+        session
+    }
+
     // ## Action FNs ## {{{
 
     // # insert # {{{
@@ -231,7 +352,14 @@ impl<T: Backend> UI<T> {
             let tag = self.input("Filter", "", false).trim().to_string();
             if !tag.is_empty() {
                 let pos = self.filterpanes.index() + if before { 0 } else { 1 };
-                library.insert_filter(Filter { tag, items: Vec::new() }, pos);
+                library.insert_filter(
+                    Filter {
+                        tag,
+                        items: Vec::new(),
+                        op: Default::default(),
+                    },
+                    pos,
+                );
                 *self.filterpanes.index_mut() = min(pos, library.filter_count().saturating_sub(1));
             }
             *self.sortpanes.active_mut() = false;
@@ -246,6 +374,10 @@ impl<T: Backend> UI<T> {
         if self.sortpanes.active() {
             library.remove_sorter(self.sortpanes.index());
         } else {
+            let selected = library.get_filter(self.filterpanes.index()).is_some_and(|f| !f.items.is_empty());
+            if selected && !self.confirm("Delete filter with selected items?") {
+                return;
+            }
             library.remove_filter(self.filterpanes.index());
             if library.filter_count() == 0 {
                 *self.sortpanes.active_mut() = true;
@@ -255,6 +387,29 @@ impl<T: Backend> UI<T> {
     }
     // # delete # }}}
 
+    // # delete_file # {{{
+    fn delete_file(&mut self) {
+        let Some(library) = self.lib_weak.upgrade() else { return };
+        if library.kiosk_get() {
+            return;
+        }
+        // the highlighted track while the queue has focus, else the playing one
+        let highlighted = if self.sortpanes.active() { self.queue.selected() } else { None };
+        let Some(track) = highlighted.or_else(|| library.track_get()) else {
+            return;
+        };
+        let name = track.path().file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+        let yes = tr("yes");
+        let answer = self.input(&trf("Delete {} from disk? Type {}", &[&name, &yes]), "", true);
+        if answer.trim() == yes {
+            // the trash can be recovered from, so it's used whenever available
+            if let Err(e) = library.delete_file(&track, cfg!(feature = "trash")) {
+                self.message("Could not delete", &e)
+            }
+        }
+    }
+    // # delete_file # }}}
+
     // # move_pane # {{{
     fn move_pane(&mut self, left: bool) {
         let Some(library) = self.lib_weak.upgrade() else { return };
@@ -312,6 +467,7 @@ impl<T: Backend> UI<T> {
                             Filter {
                                 tag: input.to_string(),
                                 items: vec![],
+                                op: Default::default(),
                             },
                         )
                     }
@@ -321,13 +477,27 @@ impl<T: Backend> UI<T> {
     }
     // # edit # }}}
 
+    // # switch_view # {{{
+    fn switch_view(&mut self, index: usize) {
+        let Some(library) = self.lib_weak.upgrade() else { return };
+        library.switch_view(index);
+        if library.filter_count() == 0 {
+            *self.sortpanes.active_mut() = true;
+            *self.filterpanes.active_mut() = false;
+        }
+    }
+    // # switch_view # }}}
+
     // # search {{{
     fn search(&mut self) {
         match self.input("Search", "", false).trim().to_ascii_lowercase().as_str() {
             "" => (),
             input => {
+                self.last_search = input.to_string();
                 if self.sortpanes.active() {
-                    self.sortpanes.find(input);
+                    if self.queue.find(input) {
+                        self.queue.mark('/')
+                    }
                 } else {
                     self.filterpanes.find(input);
                 };
@@ -335,12 +505,42 @@ impl<T: Backend> UI<T> {
             }
         }
     }
+
+    /// Narrow down by a search instead of jumping to it, as a new filter layer
+    fn filter_search(&mut self) {
+        let Some(library) = self.lib_weak.upgrade() else { return };
+        let query = self.input("Filter by search", &self.last_search.clone(), false).trim().to_string();
+        if query.is_empty() {
+            return;
+        }
+        // same tag as the highlighted filter pane, as that's likely what's being narrowed
+        let tag = library
+            .get_filters()
+            .get(self.filterpanes.index())
+            .map_or(String::from("title"), |f| f.tag.clone());
+        let tag = self.input("In tagstring", &tag, false).trim().to_string();
+        if tag.is_empty() {
+            return;
+        }
+        self.last_search = query.to_ascii_lowercase();
+        match library.filter_search(tag, &query) {
+            0 => self.message("No matches", &format!("Nothing in the queue contains \"{}\"", query)),
+            _ => *self.filterpanes.index_mut() = library.filter_count().saturating_sub(1),
+        }
+    }
     // # search # }}}
 
     // ## Action FNs ## }}}
 
     // ## draw ## {{{
+    /// Schedule a redraw on the next frame
     fn draw(&mut self) {
+        // a full channel means a frame is already pending
+        let _ = self.frame_tx.try_send(());
+    }
+
+    /// Draw right now, for the frame scheduler
+    fn draw_now(&mut self) {
         self.draw_inject(|_| {});
     }
 
@@ -366,6 +566,11 @@ impl<T: Backend> UI<T> {
                 .split(size) else {
                     return;
                 };
+                let [body, artist_area] =
+                    *Layout::horizontal([Constraint::Min(1), Constraint::Length(if self.artist_view { body.width / 3 } else { 0 })]).split(body)
+                else {
+                    return;
+                };
                 let [action_area, art_area] = *Layout::horizontal([
                     Constraint::Min(1),
                     if self.art_inspect {
@@ -386,7 +591,8 @@ impl<T: Backend> UI<T> {
                 .split(action_area) else {
                     return;
                 };
-                let [art_area2, filterpanes_area, sortpanes_area] = *Layout::vertical([
+                let [tabbar_area, art_area2, filterpanes_area, lower_area] = *Layout::vertical([
+                    Constraint::Length(if library.get_views().len() > 1 { 1 } else { 0 }),
                     if self.art_inspect {
                         Constraint::Length(body.height.min(body.width / 2))
                     } else {
@@ -402,6 +608,9 @@ impl<T: Backend> UI<T> {
                 .split(body) else {
                     return;
                 };
+                let [sortpanes_area, queue_area] = *Layout::vertical([Constraint::Length(2), Constraint::Min(0)]).split(lower_area) else {
+                    return;
+                };
 
                 self.status_bar.render(f.buffer_mut(), status_bar_area, self.stylesheet);
                 self.menubar.render(f.buffer_mut(), menubar_area, self.stylesheet);
@@ -417,13 +626,21 @@ impl<T: Backend> UI<T> {
                 self.art
                     .render(f.buffer_mut(), if self.art_inspect { art_area2 } else { art_area }, self.stylesheet);
 
+                if self.artist_view {
+                    self.artist_panel.render(f.buffer_mut(), artist_area, self.stylesheet)
+                }
+
                 let time_art = Instant::now();
 
+                self.tabbar.render(f.buffer_mut(), tabbar_area, self.stylesheet);
+
                 self.filterpanes.render(f.buffer_mut(), filterpanes_area, self.stylesheet);
 
                 let time_panes = Instant::now();
 
+                *self.queue.active_mut() = self.sortpanes.active();
                 self.sortpanes.render(f.buffer_mut(), sortpanes_area, self.stylesheet);
+                self.queue.render(f.buffer_mut(), queue_area, self.stylesheet);
 
                 let time_queue = Instant::now();
 
@@ -454,6 +671,7 @@ impl<T: Backend> UI<T> {
     // ## Popops ## {{{
 
     pub fn message(&mut self, title: &str, message: &str) {
+        let title = tr(title);
         let message = message.trim();
         loop {
             let style = self.stylesheet.active;
@@ -463,7 +681,8 @@ impl<T: Backend> UI<T> {
                 let mut width: u16 = 0;
                 for line in message.split('\n') {
                     height += 1;
-                    width = width.max(line.len() as u16);
+                    // translations may not be ascii
+                    width = width.max(line.chars().count() as u16);
                 }
 
                 let area = Rect {
@@ -499,12 +718,202 @@ impl<T: Backend> UI<T> {
         self.draw();
     }
 
+    /// Ask a y/n QUESTION, true for yes. Always yes when confirmations are turned off
+    fn confirm(&mut self, question: &str) -> bool {
+        let Some(library) = self.lib_weak.upgrade() else { return false };
+        if !library.confirm_get() {
+            return true;
+        }
+        let text = format!("{} (y/n)", tr(question));
+        let answer = loop {
+            let style = self.stylesheet.active;
+            self.draw_inject(|f| {
+                let size = f.area();
+                let width = text.chars().count() as u16 + 2;
+                let area = Rect {
+                    x: size.width.saturating_sub(width) / 2,
+                    y: size.height.saturating_sub(3) / 2,
+                    width: width.min(size.width),
+                    height: 3.min(size.height),
+                };
+                f.render_widget(Clear, area);
+                f.render_widget(
+                    Paragraph::new(text.as_str()).style(style).block(Block::default().borders(Borders::ALL)),
+                    area,
+                )
+            });
+            match get_event(None) {
+                Some(km!('y') | km_s!('Y')) => break true,
+                Some(Event::Key(KeyEvent {
+                    kind: KeyEventKind::Press, ..
+                }))
+                | Some(Event::Mouse(MouseEvent {
+                    kind: MouseEventKind::Down(_),
+                    ..
+                })) => break false,
+                _ => (),
+            }
+        };
+        self.draw();
+        answer
+    }
+
+    /// Help text built from the keymap and the menus as they are right now
+    fn help_lines(&self) -> Vec<String> {
+        let mut lines = Vec::new();
+        let mut section = |title: &str, keys: &mut dyn Iterator<Item = (String, String)>| {
+            if !lines.is_empty() {
+                lines.push(String::new())
+            }
+            lines.push(tr(title).to_string());
+            lines.extend(keys.map(|(k, d)| format!("  {} | {}", k, d)));
+        };
+        let tr_keys = |keys: &'static [(&str, &str)]| keys.iter().map(|(k, d)| (k.to_string(), tr(d).to_string()));
+        section("Keys", &mut tr_keys(keymap::KEYS));
+        section(
+            "Menu",
+            &mut self.menubar.actions().into_iter().map(|(k, path)| {
+                let keys = k.chars().map(String::from).collect::<Vec<String>>().join(" ");
+                (keys, path)
+            }),
+        );
+        section("Input", &mut tr_keys(keymap::INPUT_KEYS));
+        section("Help", &mut tr_keys(keymap::HELP_KEYS));
+        lines
+    }
+
+    /// Scrollable help overlay, narrowed down to matching lines by searching
+    fn help(&mut self) {
+        let lines = self.help_lines();
+        let width = lines.iter().map(|l| l.chars().count()).max().unwrap_or_default() as u16;
+        let mut query = String::new();
+        let mut typing = false;
+        let mut offset = 0;
+        let mut page = 1;
+        loop {
+            let lower = query.to_lowercase();
+            let shown: Vec<&str> = lines
+                .iter()
+                .filter(|l| lower.is_empty() || l.to_lowercase().contains(&lower))
+                .map(|l| l.as_str())
+                .collect();
+            offset = min(offset, shown.len().saturating_sub(page));
+            let title = if typing || !query.is_empty() {
+                format!("{} /{}", tr("Help"), query)
+            } else {
+                tr("Help").to_string()
+            };
+            let style = self.stylesheet.active;
+            self.draw_inject(|f| {
+                let size = f.area();
+                let area = Rect {
+                    x: size.width.saturating_sub(width + 2) / 2,
+                    y: 1.min(size.height),
+                    width: (width + 2).min(size.width),
+                    height: (lines.len() as u16 + 2).min(size.height.saturating_sub(2)),
+                };
+                page = area.height.saturating_sub(2).max(1) as usize;
+                f.render_widget(Clear, area);
+                f.render_widget(
+                    Paragraph::new(shown[offset.min(shown.len())..].join("\n"))
+                        .style(style)
+                        .block(Block::default().borders(Borders::ALL).title(title)),
+                    area,
+                )
+            });
+            let Some(event) = get_event(None) else { continue };
+            if typing {
+                if let Event::Key(KeyEvent {
+                    code,
+                    kind: KeyEventKind::Press,
+                    ..
+                }) = event
+                {
+                    match code {
+                        KeyCode::Enter => typing = false,
+                        KeyCode::Esc => {
+                            typing = false;
+                            query.clear()
+                        }
+                        KeyCode::Backspace => drop(query.pop()),
+                        KeyCode::Char(c) => query.push(c),
+                        _ => (),
+                    }
+                }
+                offset = 0;
+                continue;
+            }
+            match event {
+                km!('j')
+                | Event::Key(KeyEvent {
+                    code: KeyCode::Down,
+                    kind: KeyEventKind::Press,
+                    ..
+                })
+                | Event::Mouse(MouseEvent {
+                    kind: MouseEventKind::ScrollDown,
+                    ..
+                }) => offset += 1,
+                km!('k')
+                | Event::Key(KeyEvent {
+                    code: KeyCode::Up,
+                    kind: KeyEventKind::Press,
+                    ..
+                })
+                | Event::Mouse(MouseEvent {
+                    kind: MouseEventKind::ScrollUp,
+                    ..
+                }) => offset = offset.saturating_sub(1),
+                km_s!('J')
+                | Event::Key(KeyEvent {
+                    code: KeyCode::PageDown,
+                    kind: KeyEventKind::Press,
+                    ..
+                }) => offset += page,
+                km_s!('K')
+                | Event::Key(KeyEvent {
+                    code: KeyCode::PageUp,
+                    kind: KeyEventKind::Press,
+                    ..
+                }) => offset = offset.saturating_sub(page),
+                km!('g') => offset = 0,
+                km_s!('G') => offset = usize::MAX,
+                km!('/') => {
+                    typing = true;
+                    query.clear();
+                    offset = 0
+                }
+                Event::Key(KeyEvent {
+                    code: KeyCode::Esc,
+                    kind: KeyEventKind::Press,
+                    ..
+                }) if !query.is_empty() => query.clear(),
+                km!('q')
+                | km!('?')
+                | km_c!('c')
+                | Event::Key(KeyEvent {
+                    code: KeyCode::Esc,
+                    kind: KeyEventKind::Press,
+                    ..
+                })
+                | Event::Mouse(MouseEvent {
+                    kind: MouseEventKind::Down(_),
+                    ..
+                }) => break,
+                _ => (),
+            }
+        }
+        self.draw();
+    }
+
     fn input(&mut self, query: &str, prefill: &str, header: bool) -> String {
+        let query = tr(query);
         let mut result = String::from(prefill);
 
         let Some(library) = self.lib_weak.upgrade() else { return result };
         let art_size = library.theme_get().art_size;
-        let header_height: u16 = if library.seekable().is_some() { 4 } else { 2 }.max(if self.art_inspect { 0 } else { art_size.into() });
+        let header_height: u16 = if library.seekable().is_some() { 4 } else { 2 }.max(if self.art_inspect { 0 } else { art_size.into() })
+            + if library.get_views().len() > 1 { 1 } else { 0 };
         const BOX_HEIGHT: u16 = 3;
         // +5 -> borders(2), pad(1), ": "(2)
         const BOX_PAD: u16 = 5;
@@ -619,14 +1028,18 @@ impl<T: Backend> UI<T> {
                 self.art_inspect = !self.art_inspect;
                 self.draw()
             }
+            Action::ArtistView => {
+                self.artist_view = !self.artist_view;
+                self.draw()
+            }
             Action::Draw => self.draw(),
-            Action::Help => self.message("Help", HELP),
+            Action::Help => self.help(),
             Action::None => (),
 
             // Library
             Action::SeekTo => {
                 if let Some(library) = self.lib_weak.upgrade() {
-                    if library.seekable() == Some(true) {
+                    if library.seekable() == Some(true) || library.decoded().is_some() {
                         let text = self.input("hh:mm:ss.dd", "", true);
                         if text.len() > 0 {
                             match parse_time(&text) {
@@ -642,6 +1055,11 @@ impl<T: Backend> UI<T> {
                     library.statusline_set(self.input("Statusline", &library.statusline_get(), true))
                 }
             }
+            Action::Groupline => {
+                if let Some(library) = self.lib_weak.upgrade() {
+                    library.groupline_set(self.input("Groupline", &library.groupline_get(), true))
+                }
+            }
             Action::ACC | Action::FG | Action::BG => {
                 if let Some(library) = self.lib_weak.upgrade() {
                     let text = self.input("Hex, terminal/ansi #, space function, or none", "", true);
@@ -681,7 +1099,35 @@ impl<T: Backend> UI<T> {
             }
             Action::Purge => {
                 if let Some(library) = self.lib_weak.upgrade() {
-                    library.purge();
+                    if self.confirm("Purge every track from the library?") {
+                        library.purge();
+                    }
+                }
+            }
+            Action::Exit => self.exit = self.confirm("Exit and stop playback?"),
+
+            // Views
+            Action::ViewNew => {
+                if let Some(library) = self.lib_weak.upgrade() {
+                    let name = self.input("View name", "", true).trim().to_string();
+                    if !name.is_empty() {
+                        library.new_view(name)
+                    }
+                }
+            }
+            Action::ViewRename => {
+                if let Some(library) = self.lib_weak.upgrade() {
+                    let index = library.view_index();
+                    let current = library.get_views().get(index).cloned().unwrap_or_default();
+                    let name = self.input("View name", &current, true).trim().to_string();
+                    if !name.is_empty() {
+                        library.rename_view(index, name)
+                    }
+                }
+            }
+            Action::ViewClose => {
+                if let Some(library) = self.lib_weak.upgrade() {
+                    library.remove_view(library.view_index())
                 }
             }
 
@@ -699,6 +1145,27 @@ impl<T: Backend> UI<T> {
     // ## process_event ## {{{
     fn process_event(&mut self, event: Event) {
         let Some(library) = self.lib_weak.upgrade() else { return };
+        if let (
+            Some(set),
+            Event::Key(KeyEvent {
+                code,
+                kind: KeyEventKind::Press,
+                ..
+            }),
+        ) = (self.marking, &event)
+        {
+            self.marking = None;
+            if let KeyCode::Char(key) = code {
+                if set {
+                    self.queue.mark(*key)
+                } else if self.queue.jump(*key) && library.filter_count() != 0 {
+                    *self.filterpanes.active_mut() = false;
+                    *self.sortpanes.active_mut() = true;
+                }
+                self.draw();
+            }
+            return;
+        }
         match event {
             // # Key Events # {{{
             Event::Key(KeyEvent {
@@ -766,28 +1233,36 @@ impl<T: Backend> UI<T> {
                 ..
             }) => {
                 if self.sortpanes.active() {
-                    self.sortpanes.scroll_by_n_lock(1)
+                    self.queue.scroll_by_n_lock(1)
                 } else {
                     self.filterpanes.scroll_by_n_lock(1)
                 }
                 self.draw();
             }
-            km_s!('J')
-            | Event::Key(KeyEvent {
+            Event::Key(KeyEvent {
                 code: KeyCode::Down,
                 modifiers: KeyModifiers::SHIFT,
                 kind: KeyEventKind::Press,
                 ..
             }) => {
                 if self.sortpanes.active() {
-                    self.sortpanes.scroll_down();
-                    self.sortpanes.scroll_by_n_lock(0);
+                    self.queue.scroll_down();
+                    self.queue.scroll_by_n_lock(0);
                 } else {
                     self.filterpanes.scroll_down();
                     self.filterpanes.scroll_by_n_lock(0);
                 }
                 self.draw();
             }
+            km_s!('J') => {
+                if self.sortpanes.active() {
+                    self.queue.scroll_down();
+                    self.queue.scroll_by_n_lock(0);
+                    self.draw();
+                } else {
+                    self.filterpanes.extend_selection(1)
+                }
+            }
             km!('k')
             | Event::Key(KeyEvent {
                 code: KeyCode::Up,
@@ -796,32 +1271,40 @@ impl<T: Backend> UI<T> {
                 ..
             }) => {
                 if self.sortpanes.active() {
-                    self.sortpanes.scroll_by_n_lock(-1)
+                    self.queue.scroll_by_n_lock(-1)
                 } else {
                     self.filterpanes.scroll_by_n_lock(-1)
                 }
                 self.draw();
             }
-            km_s!('K')
-            | Event::Key(KeyEvent {
+            Event::Key(KeyEvent {
                 code: KeyCode::Up,
                 modifiers: KeyModifiers::SHIFT,
                 kind: KeyEventKind::Press,
                 ..
             }) => {
                 if self.sortpanes.active() {
-                    self.sortpanes.scroll_up();
-                    self.sortpanes.scroll_by_n_lock(0)
+                    self.queue.scroll_up();
+                    self.queue.scroll_by_n_lock(0)
                 } else {
                     self.filterpanes.scroll_up();
                     self.filterpanes.scroll_by_n_lock(0);
                 }
                 self.draw();
             }
+            km_s!('K') => {
+                if self.sortpanes.active() {
+                    self.queue.scroll_up();
+                    self.queue.scroll_by_n_lock(0);
+                    self.draw();
+                } else {
+                    self.filterpanes.extend_selection(-1)
+                }
+            }
 
             km!('g') => {
                 if self.sortpanes.active() {
-                    self.sortpanes.scroll_by_n_lock(i32::MIN)
+                    self.queue.scroll_by_n_lock(i32::MIN)
                 } else {
                     self.filterpanes.scroll_by_n(i32::MIN)
                 };
@@ -830,7 +1313,7 @@ impl<T: Backend> UI<T> {
             km_s!('G') => {
                 if self.sortpanes.active() {
                     // i32::max will overflow since it gets added to pos. easy avoidance lol.
-                    self.sortpanes.scroll_by_n_lock(i16::MAX.into())
+                    self.queue.scroll_by_n_lock(i16::MAX.into())
                 } else {
                     self.filterpanes.scroll_by_n(i16::MAX.into())
                 };
@@ -838,17 +1321,15 @@ impl<T: Backend> UI<T> {
             }
 
             km!('z') => {
-                if let Some(track) = library.track_get() {
-                    if self.sortpanes.active() {
-                        if let Some(index) = library.get_queue().iter().position(|t| *t == track) {
-                            self.sortpanes.scroll_by_n(i32::MIN);
-                            self.sortpanes.scroll_by_n_lock(index as i32);
-                            self.draw();
-                        }
-                    } else {
-                    }
+                if self.sortpanes.active() {
+                    self.queue.focus_playing();
+                    self.draw();
                 }
             }
+            km_s!('Z') => {
+                self.queue.toggle_follow();
+                self.draw();
+            }
 
             km!('f')
             | Event::Key(KeyEvent {
@@ -858,7 +1339,7 @@ impl<T: Backend> UI<T> {
                 ..
             }) => {
                 if self.sortpanes.active() {
-                    library.play_track(library.get_queue().get(self.sortpanes.position()).cloned())
+                    library.play_track(self.queue.selected())
                 } else {
                     self.filterpanes.toggle_current()
                 }
@@ -888,11 +1369,44 @@ impl<T: Backend> UI<T> {
                 }
             }
 
+            km_s!('P') => {
+                if self.sortpanes.active() {
+                    if let Some(track) = self.queue.selected() {
+                        library.preview(track)
+                    }
+                }
+            }
+
+            km!('o') => {
+                if self.sortpanes.active() {
+                    library.sorter_toggle_direction(self.sortpanes.index())
+                }
+            }
+
             km_s!('D') => self.delete(),
             km!('i') => self.insert(false),
             km_s!('I') => self.insert(true),
             km!('/') => self.search(),
+            km!('*') => {
+                if !self.sortpanes.active() {
+                    if self.last_search.is_empty() {
+                        self.search()
+                    }
+                    let query = self.last_search.clone();
+                    // a cancelled search would match everything
+                    if !query.is_empty() {
+                        self.filterpanes.select_matching(&query)
+                    }
+                }
+            }
+            km!('&') => self.filter_search(),
+            km_s!('M') => self.marking = Some(true),
+            km!('`') => self.marking = Some(false),
             km!('\'') => self.edit(),
+            km!('?') => self.help(),
+
+            km!('[') => self.switch_view(library.view_index().saturating_sub(1)),
+            km!(']') => self.switch_view(library.view_index() + 1),
 
             km!('a') => library.play_pause(),
             km!('x') => library.stop(),
@@ -900,19 +1414,44 @@ impl<T: Backend> UI<T> {
             km!('p') => library.previous(),
             km!('=') => library.volume_add(0.05),
             km!('-') => library.volume_add(-0.05),
+            km!('m') => library.mute_toggle(),
             km!('e') => library.shuffle_toggle(),
             km!('r') => library.repeat_toggle(),
 
-            km!('.') => library.seek_by(5.0),
-            km!(',') => library.seek_by(-5.0),
-            km!('>') => library.seek_by(30.0),
-            km!('<') => library.seek_by(-30.0),
+            km!('.') => library.seek_step(SeekStep::Small, true),
+            km!(',') => library.seek_step(SeekStep::Small, false),
+            km!('>') => library.seek_step(SeekStep::Medium, true),
+            km!('<') => library.seek_step(SeekStep::Medium, false),
+            km_a!('.') => library.seek_step(SeekStep::Large, true),
+            km_a!(',') => library.seek_step(SeekStep::Large, false),
+            km_s!('R') => library.seek_by(-10.0),
+            Event::Key(KeyEvent {
+                code: KeyCode::Char(c @ '0'..='9'),
+                modifiers: KeyModifiers::ALT,
+                kind: KeyEventKind::Press,
+                ..
+            }) => library.seek_fraction((c as u8 - b'0') as f32 / 10.0),
+            km!('b') => {
+                if let Err(e) = library.loop_cycle() {
+                    self.message("Could not loop", &e)
+                }
+            }
+            km!('s') => {
+                if let Some(track) = library.track_get() {
+                    if let Err(e) = library.love(&track, !library.loved(&track)) {
+                        self.message("Could not love", &e)
+                    }
+                }
+            }
+
+            km_s!('X') => self.delete_file(),
 
             // c for cover I guess..?
             km!('c') => {
                 self.art_inspect = !self.art_inspect;
                 self.draw()
             }
+            km_s!('A') => self.action(Action::ArtistView),
 
             // yay vim macros
             km!('0')
@@ -978,8 +1517,10 @@ impl<T: Backend> UI<T> {
                     self.menubar.process_event(event),
                     self.seeker.process_event(event),
                     self.art.process_event(event),
+                    self.tabbar.process_event(event),
                     self.filterpanes.process_event(event),
                     self.sortpanes.process_event(event),
+                    self.queue.process_event(event),
                 ];
 
                 let draws = self.draw_count;
@@ -991,6 +1532,11 @@ impl<T: Backend> UI<T> {
                     }
                 }
 
+                // the queue belongs to the lower half along with the sorters
+                if self.queue.active() {
+                    (*self.filterpanes.active_mut(), *self.sortpanes.active_mut()) = (false, true)
+                }
+
                 // Ensure one pane is always active
                 if (!self.sortpanes.active() && !self.filterpanes.active()) || (self.sortpanes.active() && self.filterpanes.active()) {
                     match q {
@@ -1029,116 +1575,177 @@ impl<T: Backend> UI<T> {
 // ### UI ### }}}
 
 // ### tui ### {{{
-pub fn tui(library: Arc<Library>) -> bool {
+/// Draws at most MAX_FPS times a second no matter how many draws are requested.
+/// Exits once the UI and its sender are dropped
+fn frame_scheduler<T: Backend>(ui: Weak<Mutex<UI<T>>>, frame_rx: Receiver<()>) {
+    let interval = Duration::from_millis(1000 / MAX_FPS);
+    let mut last = Instant::now();
+    while frame_rx.recv().is_ok() {
+        if let Some(wait) = interval.checked_sub(last.elapsed()) {
+            thread::sleep(wait)
+        }
+        // anything requested while waiting lands in this frame
+        while frame_rx.try_recv().is_ok() {}
+        let Some(ui) = ui.upgrade() else { break };
+        last = Instant::now();
+        if let Ok(mut uiw) = ui.lock() {
+            uiw.draw_now()
+        };
+    }
+}
+
+/// Runs the full screen TUI until quit, returning true if only the TUI was exited.
+/// Panes, filters, and the loaded track are saved to SESSION on the way out and restored from it on launch.
+/// With REATTACH a crashed TUI is started again over the still running server
+pub fn tui(library: Arc<Library>, session: PathBuf, reattach: bool) -> bool {
+    loop {
+        let started = Instant::now();
+        match run(library.clone(), &session) {
+            Some(join) => return join,
+            // something that breaks right away will only break again
+            None if reattach && started.elapsed() > Duration::from_secs(5) => {
+                eprintln!("TUI crashed, reattaching...");
+                thread::sleep(Duration::from_secs(2))
+            }
+            None => return false,
+        }
+    }
+}
+
+/// Start F once under the library's supervisor, so a panic is reported as it takes the TUI down
+fn supervise<F: FnOnce() + Send + 'static>(library: &Arc<Library>, name: &str, f: F) {
+    let mut f = Some(f);
+    library
+        .supervise(name, false, move || {
+            if let Some(f) = f.take() {
+                f()
+            }
+        })
+        .unwrap();
+}
+
+/// One TUI session. None if it crashed instead of being quit
+fn run(library: Arc<Library>, session: &Path) -> Option<bool> {
     let mut libevt_r = library.get_receiver().unwrap();
     debug!("Entering interactive terminal...");
     log_pause!();
 
-    terminal::enable_raw_mode().unwrap();
-    let mut stdo = stdout();
-
-    queue!(
-        stdo,
-        terminal::EnterAlternateScreen,
-        terminal::Clear(terminal::ClearType::All),
-        event::EnableMouseCapture,
-        cursor::Hide
-    )
-    .unwrap();
+    let guard = TermGuard::new(true);
 
     let join = Arc::new(AtomicBool::new(false));
+    let quit = Arc::new(AtomicBool::new(false));
     let libweak_evt = Arc::downgrade(&library);
 
+    let previous = Session::load(session);
+    if let Some(previous) = previous.as_ref() {
+        previous.restore_library(&library)
+    }
+
     let theme = library.theme_get();
+    let (frame_tx, frame_rx) = sync_channel(1);
     let ui = Arc::new(Mutex::new(UI::from_library(
-        library,
+        library.clone(),
         Terminal::new(CrosstermBackend::new(stdout())).unwrap(),
         StyleSheet::from(theme),
+        frame_tx,
     )));
-    ui.lock().unwrap().draw();
+    if let Some(previous) = previous {
+        let mut uiw = ui.lock().unwrap();
+        uiw.filterpanes.restore(previous.filter_panes);
+        uiw.sortpanes.restore(previous.sort_panes);
+        uiw.queue.restore(previous.queue);
+        uiw.queue.follow = previous.follow;
+    }
+    ui.lock().unwrap().draw_now();
+
+    let ui_session = ui.clone();
 
     let uiw_libevt = Arc::downgrade(&ui);
+    let uiw_frame = Arc::downgrade(&ui);
 
     let egg = Arc::new(true);
     let egg_tui = egg.clone();
     let egg_evt = egg.clone();
 
     let join_tui = join.clone();
-    thread::Builder::new()
-        .name(String::from("TUI Event Poller"))
-        .spawn(move || {
-            let _egg_tui = egg_tui;
-            loop {
-                if let Some(ev) = get_event(None) {
-                    match ev {
-                        km_c!('c') | km_c!('q') => break,
-                        km_c!('z') => {
-                            join_tui.store(true, Ordering::Relaxed);
-                            break;
-                        }
-                        _ => (),
+    let quit_tui = quit.clone();
+    supervise(&library, "TUI Event Poller", move || {
+        loop {
+            // polled so a crash elsewhere doesn't leave this eating a reattached TUI's input
+            if Arc::strong_count(&egg_tui) < 3 {
+                break;
+            }
+            if let Some(ev) = get_event(Some(Duration::from_millis(100))) {
+                match ev {
+                    km_c!('c') | km_c!('q') => {
+                        quit_tui.store(true, Ordering::Relaxed);
+                        break;
+                    }
+                    km_c!('z') => {
+                        join_tui.store(true, Ordering::Relaxed);
+                        quit_tui.store(true, Ordering::Relaxed);
+                        break;
                     }
-                    // process_event will draw for us
-                    ui.lock().unwrap().process_event(ev);
+                    _ => (),
+                }
+                // process_event will draw for us
+                let mut uiw = ui.lock().unwrap();
+                uiw.process_event(ev);
+                if uiw.exit {
+                    quit_tui.store(true, Ordering::Relaxed);
+                    break;
                 }
             }
-        })
-        .unwrap();
-
-    thread::Builder::new()
-        .name(String::from("TUI LibEvt Receiver"))
-        .spawn(move || {
-            let _egg_evt = egg_evt;
-            loop {
-                match libevt_r.recv() {
-                    Ok(action) => match uiw_libevt.upgrade() {
-                        Some(ui) => match action {
-                            LibEvt::Playback | LibEvt::Update => ui.lock().unwrap().draw(),
-                            LibEvt::Theme => {
-                                if let Ok(mut uiw) = ui.lock() {
-                                    if let Some(libw) = libweak_evt.upgrade() {
-                                        uiw.stylesheet = StyleSheet::from(libw.theme_get());
-                                        uiw.draw()
-                                    }
-                                }
-                            }
-                            LibEvt::Error(message) => {
-                                let mut uiw = ui.lock().unwrap();
-                                uiw.message("Library Error", &message)
+        }
+    });
+
+    supervise(&library, "TUI LibEvt Receiver", move || {
+        let _egg_evt = egg_evt;
+        while let Ok(action) = libevt_r.recv() {
+            match uiw_libevt.upgrade() {
+                Some(ui) => match action {
+                    LibEvt::Theme => {
+                        if let Ok(mut uiw) = ui.lock() {
+                            if let Some(libw) = libweak_evt.upgrade() {
+                                uiw.stylesheet = StyleSheet::from(libw.theme_get());
+                                uiw.draw()
                             }
-                        },
-                        None => break,
-                    },
-                    Err(_) => break,
-                }
+                        }
+                    }
+                    LibEvt::Error(message) => {
+                        let mut uiw = ui.lock().unwrap();
+                        uiw.message("Library Error", &message)
+                    }
+                    LibEvt::Offline(root) => {
+                        let mut uiw = ui.lock().unwrap();
+                        uiw.message(
+                            "Library Offline",
+                            &trf("{} is not responding\nRetrying in the background", &[&root.to_str().unwrap_or("???")]),
+                        )
+                    }
+                    // panes pick out what they care about from their own receivers
+                    _ => ui.lock().unwrap().draw(),
+                },
+                None => break,
             }
-        })
-        .unwrap();
+        }
+    });
+
+    supervise(&library, "TUI Frame Scheduler", move || frame_scheduler(uiw_frame, frame_rx));
 
     // waits for any thread to drop the egg and die.
     while Arc::strong_count(&egg) == 3 {
         std::thread::sleep(std::time::Duration::from_millis(50))
     }
 
-    // lets you read panic messages
-    // yes this is the dumbest solution
-    if LOG_LEVEL.load(Ordering::Relaxed) > 3 {
-        std::thread::sleep(std::time::Duration::from_secs(10));
+    // a poisoned lock means the crash was mid update, so that session isn't worth keeping
+    if let Ok(uiw) = ui_session.lock() {
+        uiw.session().save(session)
     }
+    drop(ui_session);
 
-    queue!(
-        stdo,
-        terminal::Clear(terminal::ClearType::All),
-        cursor::MoveTo(0, 0),
-        terminal::LeaveAlternateScreen,
-        event::DisableMouseCapture,
-        cursor::Show
-    )
-    .unwrap();
-    stdo.flush().unwrap();
-    terminal::disable_raw_mode().unwrap();
-
+    drop(guard);
     log_resume!();
-    join.load(Ordering::Relaxed)
+    quit.load(Ordering::Relaxed).then(|| join.load(Ordering::Relaxed))
 }
 // ### tui ### }}}
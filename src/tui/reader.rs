@@ -0,0 +1,220 @@
+#![warn(missing_docs)]
+
+use std::io::{stdout, IsTerminal, Write};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use super::{get_event, TermGuard};
+use crate::library::{LibEvt, Library, SeekStep};
+use crate::locale::{tr, trf};
+use crate::logging::*;
+
+use ratatui::crossterm::{
+    event::{Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers},
+    queue,
+    style::Print,
+    terminal,
+};
+
+/// Both the key and event threads print
+static PRINT: Mutex<()> = Mutex::new(());
+
+pub const HELP: &str =
+    "a/space play/pause | x stop | n/p next/previous | -/+ volume | m mute | .,>< seek small/medium step | Alt+., large step | 0-9 seek to 0-90% | t time | s love | e shuffle | r repeat \
+| j/k or down/up move through queue | g/G first/last | enter play | i status | q quit | Ctrl+z exit only UI";
+
+/// Everything that's announced when it changes, in order
+fn facts(library: &Library) -> Vec<String> {
+    let track = library.track_get();
+    let mut facts = vec![match track.as_ref() {
+        Some(track) if library.playing() => trf("Playing {}", &[&track.tagstring(library.statusline_get())]),
+        Some(track) if library.paused() => trf("Paused {}", &[&track.tagstring(library.statusline_get())]),
+        _ => tr("Stopped").to_string(),
+    }];
+    if let Some(track) = track {
+        facts.push(tr(if library.loved(&track) { "Loved" } else { "Not loved" }).to_string())
+    }
+    facts.push(match library.muted() {
+        true => tr("Muted").to_string(),
+        false => trf("Volume {}%", &[&format!("{:.0}", library.volume_get() * 100.0)]),
+    });
+    facts.push(
+        tr(match (library.shuffle_get(), library.shuffle_album_get()) {
+            (true, Some(_)) => "Album shuffle",
+            (true, None) => "Shuffle",
+            (false, _) => "No shuffle",
+        })
+        .to_string(),
+    );
+    facts.push(
+        tr(match library.repeat_get() {
+            Some(true) => "Repeat",
+            Some(false) => "Repeat track",
+            None => "No repeat",
+        })
+        .to_string(),
+    );
+    facts.push(tr(if library.consume_get() { "Consume" } else { "No consume" }).to_string());
+    facts
+}
+
+/// Print MESSAGE as its own line, since raw mode doesn't return the cursor
+fn say(message: &str) {
+    let _guard = PRINT.lock();
+    let mut stdo = stdout();
+    for line in message.lines() {
+        let _ = queue!(stdo, Print(line), Print("\r\n"));
+    }
+    let _ = stdo.flush();
+}
+
+/// Say whichever facts differ from LAST, or all of them with EVERYTHING
+fn announce(library: &Library, last: &Mutex<Vec<String>>, everything: bool) {
+    let current = facts(library);
+    let mut last = last.lock().unwrap_or_else(|e| e.into_inner());
+    // pairing by position is fine as only the loved line comes and goes, right after the track
+    let changed: Vec<&String> = if everything || current.len() != last.len() {
+        current.iter().filter(|f| everything || !last.contains(f)).collect()
+    } else {
+        current.iter().zip(last.iter()).filter(|(c, l)| c != l).map(|(c, _)| c).collect()
+    };
+    if !changed.is_empty() {
+        say(&changed.into_iter().cloned().collect::<Vec<_>>().join(", "))
+    }
+    *last = current;
+}
+
+/// Say the queue item at FOCUS and put it in the terminal title, where screen readers can fetch it on demand
+fn focus(library: &Library, focus: usize) {
+    let queue = library.get_queue();
+    let text = match queue.get(focus) {
+        Some(track) => trf("{} of {}: {}", &[&(focus + 1), &queue.len(), &track.tagstring(library.statusline_get())]),
+        None => tr("Queue empty").to_string(),
+    };
+    let _guard = PRINT.lock();
+    let mut stdo = stdout();
+    let _ = queue!(stdo, terminal::SetTitle(&text), Print(&text), Print("\r\n"));
+    let _ = stdo.flush();
+}
+
+/// Current time and length
+fn time(library: &Library) -> String {
+    match library.times() {
+        Some((cur, tot)) => trf(
+            "{} of {}",
+            &[
+                &format!("{}:{:02}", cur.as_secs() / 60, cur.as_secs() % 60),
+                &format!("{}:{:02}", tot.as_secs() / 60, tot.as_secs() % 60),
+            ],
+        ),
+        None => tr("No time").to_string(),
+    }
+}
+
+/// Screen reader friendly UI. Nothing is drawn or redrawn, instead each change is said as a plain line of text
+/// and the queue is walked one item at a time, with the focused item also set as the terminal title.
+/// Returns true if the server should be kept running.
+pub fn reader(library: Arc<Library>) -> bool {
+    // there's nothing to walk without keys, and the line UI already prints plain lines when piped
+    if !stdout().is_terminal() {
+        return super::line(library);
+    }
+
+    let mut libevt_r = library.get_receiver().unwrap();
+    debug!("Entering reader UI...");
+    println!("{}", tr(HELP));
+    log_pause!();
+
+    let guard = TermGuard::new(false);
+    let last = Arc::new(Mutex::new(Vec::new()));
+    announce(&library, &last, true);
+    let focused = Arc::new(AtomicUsize::new(0));
+
+    let done = Arc::new(AtomicBool::new(false));
+    let done_evt = done.clone();
+    let last_evt = last.clone();
+    let focused_evt = focused.clone();
+    let libweak_evt = Arc::downgrade(&library);
+    thread::Builder::new()
+        .name(String::from("Reader LibEvt Receiver"))
+        .spawn(move || loop {
+            let event = libevt_r.recv();
+            if done_evt.load(Ordering::Relaxed) {
+                break;
+            }
+            let Some(library) = libweak_evt.upgrade() else { break };
+            match event {
+                Ok(LibEvt::Error(message)) => say(&message),
+                Ok(LibEvt::Offline(root)) => say(&trf("{} is offline", &[&root.display()])),
+                Ok(LibEvt::Queue | LibEvt::Tracks | LibEvt::Filters(_)) => {
+                    let len = library.get_queue().len();
+                    if focused_evt.load(Ordering::Relaxed) >= len {
+                        focused_evt.store(len.saturating_sub(1), Ordering::Relaxed)
+                    }
+                    announce(&library, &last_evt, false)
+                }
+                Ok(_) => announce(&library, &last_evt, false),
+                Err(_) => break,
+            }
+        })
+        .unwrap();
+
+    let join = loop {
+        let Some(Event::Key(KeyEvent {
+            code,
+            modifiers,
+            kind: KeyEventKind::Press,
+            ..
+        })) = get_event(None)
+        else {
+            continue;
+        };
+        let len = library.get_queue().len();
+        let current = focused.load(Ordering::Relaxed).min(len.saturating_sub(1));
+        let move_to = |n: usize| {
+            focused.store(n, Ordering::Relaxed);
+            focus(&library, n)
+        };
+        match (code, modifiers.contains(KeyModifiers::CONTROL)) {
+            (KeyCode::Char('c'), true) | (KeyCode::Char('q'), _) => break false,
+            (KeyCode::Char('z'), true) => break true,
+            (KeyCode::Char('a') | KeyCode::Char(' '), false) => library.play_pause(),
+            (KeyCode::Char('x'), false) => library.stop(),
+            (KeyCode::Char('n'), false) => library.next(),
+            (KeyCode::Char('p'), false) => library.previous(),
+            (KeyCode::Char('=') | KeyCode::Char('+'), false) => library.volume_add(0.05),
+            (KeyCode::Char('-'), false) => library.volume_add(-0.05),
+            (KeyCode::Char('m'), false) => library.mute_toggle(),
+            (KeyCode::Char('e'), false) => library.shuffle_toggle(),
+            (KeyCode::Char('r'), false) => library.repeat_toggle(),
+            (KeyCode::Char('.'), false) if modifiers.contains(KeyModifiers::ALT) => library.seek_step(SeekStep::Large, true),
+            (KeyCode::Char(','), false) if modifiers.contains(KeyModifiers::ALT) => library.seek_step(SeekStep::Large, false),
+            (KeyCode::Char('.') | KeyCode::Right, false) => library.seek_step(SeekStep::Small, true),
+            (KeyCode::Char(',') | KeyCode::Left, false) => library.seek_step(SeekStep::Small, false),
+            (KeyCode::Char('>'), false) => library.seek_step(SeekStep::Medium, true),
+            (KeyCode::Char('<'), false) => library.seek_step(SeekStep::Medium, false),
+            (KeyCode::Char(c @ '0'..='9'), false) => library.seek_fraction((c as u8 - b'0') as f32 / 10.0),
+            (KeyCode::Char('t'), false) => say(&time(&library)),
+            (KeyCode::Char('i'), false) => announce(&library, &last, true),
+            (KeyCode::Char('s'), false) => {
+                if let Some(track) = library.track_get() {
+                    let _ = library.love(&track, !library.loved(&track));
+                }
+            }
+            (KeyCode::Char('j') | KeyCode::Down, false) => move_to((current + 1).min(len.saturating_sub(1))),
+            (KeyCode::Char('k') | KeyCode::Up, false) => move_to(current.saturating_sub(1)),
+            (KeyCode::Char('g') | KeyCode::Home, false) => move_to(0),
+            (KeyCode::Char('G') | KeyCode::End, false) => move_to(len.saturating_sub(1)),
+            (KeyCode::Enter, false) => library.play_track(library.get_queue().get(current).cloned()),
+            _ => (),
+        }
+        // volume and toggles may not broadcast
+        announce(&library, &last, false);
+    };
+    done.store(true, Ordering::Relaxed);
+
+    drop(guard);
+    log_resume!();
+    join
+}
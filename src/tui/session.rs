@@ -0,0 +1,67 @@
+use super::widgets::PaneState;
+use crate::library::{Filter, Library};
+use crate::logging::*;
+use crate::try_block;
+
+use std::path::{Path, PathBuf};
+
+/// Everything the TUI puts back on the next launch
+#[derive(Default, serde::Serialize, serde::Deserialize)]
+pub struct Session {
+    pub filter_panes: PaneState,
+    pub sort_panes: PaneState,
+    pub queue: PaneState,
+    pub follow: bool,
+    filters: Vec<Filter>,
+    track: Option<PathBuf>,
+}
+
+impl Session {
+    /// Library half of the session. Panes are filled in by the UI
+    pub fn from_library(library: &Library) -> Self {
+        Self {
+            filters: library.get_filters(),
+            track: library.track_get().map(|t| t.path().clone()),
+            ..Default::default()
+        }
+    }
+
+    /// None if there's no previous session or it can't be read
+    pub fn load(path: &Path) -> Option<Self> {
+        let bytes = std::fs::read(path).ok()?;
+        match bincode::deserialize(&bytes) {
+            Ok(session) => Some(session),
+            Err(e) => {
+                info!("Discarding unreadable TUI session {}: {}", path.display(), e);
+                None
+            }
+        }
+    }
+
+    pub fn save(&self, path: &Path) {
+        let result = try_block!({
+            std::fs::write(path, bincode::serialize(self)?)?;
+            Ok(())
+        });
+        if let Err(e) = result {
+            error!("Could not save TUI session to {}: {}", path.display(), e)
+        }
+    }
+
+    /// Put back the selected filter items and loaded track.
+    /// Filters are only restored over the same layout or none at all, so filters given on launch win
+    pub fn restore_library(&self, library: &Library) {
+        let current = library.get_filters();
+        let same_layout = current.iter().map(|f| &f.tag).eq(self.filters.iter().map(|f| &f.tag));
+        if !self.filters.is_empty() && (current.is_empty() || same_layout) && current != self.filters {
+            library.set_filters(self.filters.clone())
+        }
+        if let Some(track) = self
+            .track
+            .as_ref()
+            .and_then(|path| library.get_tracks().into_iter().find(|t| t.path() == path))
+        {
+            library.load_track(Some(track))
+        }
+    }
+}
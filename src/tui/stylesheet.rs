@@ -3,7 +3,7 @@
 use crate::library::Theme;
 use ratatui::style::{Color, Modifier, Style};
 
-#[derive(Clone, Copy, Debug, PartialEq)]
+#[derive(Clone, Copy, Debug, PartialEq, Hash)]
 pub struct StyleSheet {
     pub base: Style,
     pub base_sel: Style,
@@ -34,21 +34,20 @@ const COLORMAP: [Color; 16] = [
     Color::White,
 ];
 
-impl From<crate::library::Color> for Color {
-    fn from(value: crate::library::Color) -> Self {
-        match value {
-            crate::library::Color::None => Color::Reset,
-            crate::library::Color::Term(n) => COLORMAP[n as usize],
-            crate::library::Color::RGB(rgb) => Color::Rgb(rgb[0], rgb[1], rgb[2]),
-        }
+/// Library colors live in ompl_core so the conversion can't be a From impl
+fn color(value: crate::library::Color) -> Color {
+    match value {
+        crate::library::Color::None => Color::Reset,
+        crate::library::Color::Term(n) => COLORMAP[n as usize],
+        crate::library::Color::RGB(rgb) => Color::Rgb(rgb[0], rgb[1], rgb[2]),
     }
 }
 
 impl From<Theme> for StyleSheet {
     fn from(value: Theme) -> Self {
-        let fg = value.fg.into();
-        let bg = value.bg.into();
-        let acc = value.acc.into();
+        let fg = color(value.fg);
+        let bg = color(value.bg);
+        let acc = color(value.acc);
         let fg_alt = if bg == Color::Reset { Color::Black } else { bg };
         let bg_alt = if fg == Color::Reset { Color::White } else { fg };
 
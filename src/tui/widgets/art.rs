@@ -2,7 +2,10 @@
 
 use super::{Action, Clickable, ContainedWidget, StyleSheet};
 
-use crate::{library::Library, logging::*};
+use crate::{
+    library::{ArtColors, ArtDither, Library},
+    logging::*,
+};
 
 use std::sync::{Arc, Weak};
 
@@ -72,6 +75,182 @@ fn pixel2col(rgba: [u8; 4], quantize: bool) -> Color {
     }
 }
 
+/// xterm's defaults for the 16 terminal colors, near enough to most themes to measure dithering error against
+const ANSI16: [[u8; 3]; 16] = [
+    [0, 0, 0],
+    [205, 0, 0],
+    [0, 205, 0],
+    [205, 205, 0],
+    [0, 0, 238],
+    [205, 0, 205],
+    [0, 205, 205],
+    [229, 229, 229],
+    [127, 127, 127],
+    [255, 0, 0],
+    [0, 255, 0],
+    [255, 255, 0],
+    [92, 92, 255],
+    [255, 0, 255],
+    [0, 255, 255],
+    [255, 255, 255],
+];
+
+/// Channel levels of the 6x6x6 cube in the 256 color palette
+const CUBE: [u8; 6] = [0, 95, 135, 175, 215, 255];
+
+/// 4x4 Bayer matrix
+const BAYER: [[u8; 4]; 4] = [[0, 8, 2, 10], [12, 4, 14, 6], [3, 11, 1, 9], [15, 7, 13, 5]];
+
+fn distance(a: [f32; 3], b: [u8; 3]) -> f32 {
+    a.iter().zip(b).map(|(a, b)| (a - b as f32).powi(2)).sum()
+}
+
+/// Closest of the 16 colors by xterm's values
+fn nearest16(rgb: [f32; 3]) -> (Color, [u8; 3]) {
+    let (n, col) = ANSI16
+        .iter()
+        .enumerate()
+        .min_by(|a, b| distance(rgb, *a.1).total_cmp(&distance(rgb, *b.1)))
+        .unwrap();
+    (Color::Indexed(n as u8), *col)
+}
+
+/// Closest of the cube and grayscale ramp in the 256 color palette, skipping the first 16 as they vary by theme
+fn nearest256(rgb: [f32; 3]) -> (Color, [u8; 3]) {
+    let level = |c: f32| {
+        (0..CUBE.len())
+            .min_by(|a, b| (CUBE[*a] as f32 - c).abs().total_cmp(&(CUBE[*b] as f32 - c).abs()))
+            .unwrap()
+    };
+    let [r, g, b] = rgb.map(level);
+    let cube = [CUBE[r], CUBE[g], CUBE[b]];
+    let gray_n = ((rgb.iter().sum::<f32>() / 3.0 - 8.0) / 10.0).round().clamp(0.0, 23.0) as u8;
+    let gray = [8 + gray_n * 10; 3];
+    if distance(rgb, gray) < distance(rgb, cube) {
+        (Color::Indexed(232 + gray_n), gray)
+    } else {
+        (Color::Indexed(16 + 36 * r as u8 + 6 * g as u8 + b as u8), cube)
+    }
+}
+
+/// RGB as the closest color COLORS can show, for art chips
+pub fn chip_color(rgb: [u8; 3], colors: ArtColors) -> Color {
+    let colors = match colors {
+        ArtColors::Auto if available_color_count() <= 16 => ArtColors::Ansi16,
+        ArtColors::Auto => ArtColors::Truecolor,
+        colors => colors,
+    };
+    let [r, g, b] = rgb;
+    quantize(&[Box::new([[r, g, b, u8::MAX]])], colors, ArtDither::None)[0][0]
+}
+
+/// Colors for each pixel in THUMBNAIL. Auto colors should already be resolved by the caller
+fn quantize(thumbnail: &[Box<[[u8; 4]]>], colors: ArtColors, dither: ArtDither) -> Vec<Vec<Color>> {
+    let nearest: fn([f32; 3]) -> (Color, [u8; 3]) = match colors {
+        ArtColors::Ansi256 => nearest256,
+        ArtColors::Ansi16 => nearest16,
+        ArtColors::Auto | ArtColors::Truecolor => return thumbnail.iter().map(|row| row.iter().map(|px| pixel2col(*px, false)).collect()).collect(),
+    };
+    // without dithering the hand picked buckets read better than the nearest of 16
+    if colors == ArtColors::Ansi16 && dither == ArtDither::None {
+        return thumbnail.iter().map(|row| row.iter().map(|px| pixel2col(*px, true)).collect()).collect();
+    }
+    // about one step between neighboring colors
+    let spread = if colors == ArtColors::Ansi16 { 128.0 } else { 40.0 };
+
+    let mut work: Vec<Vec<[f32; 3]>> = thumbnail
+        .iter()
+        .map(|row| row.iter().map(|px| alpha(*px).map(|c| c as f32)).collect())
+        .collect();
+    let mut result = vec![vec![Color::Black; thumbnail.first().map(|r| r.len()).unwrap_or(0)]; thumbnail.len()];
+    for y in 0..work.len() {
+        for x in 0..work[y].len() {
+            let mut rgb = work[y][x];
+            if dither == ArtDither::Ordered {
+                let offset = (BAYER[y % 4][x % 4] as f32 + 0.5) / 16.0 - 0.5;
+                rgb = rgb.map(|c| c + offset * spread);
+            }
+            let (color, chosen) = nearest(rgb.map(|c| c.clamp(0.0, 255.0)));
+            result[y][x] = color;
+
+            if dither == ArtDither::FloydSteinberg {
+                let error: Vec<f32> = rgb.iter().zip(chosen).map(|(c, q)| c - q as f32).collect();
+                let mut spill = |y: usize, x: Option<usize>, weight: f32| {
+                    if let Some(px) = x.and_then(|x| work.get_mut(y).and_then(|row| row.get_mut(x))) {
+                        px.iter_mut().zip(&error).for_each(|(c, e)| *c += e * weight)
+                    }
+                };
+                spill(y, Some(x + 1), 7.0 / 16.0);
+                spill(y + 1, x.checked_sub(1), 3.0 / 16.0);
+                spill(y + 1, Some(x), 5.0 / 16.0);
+                spill(y + 1, Some(x + 1), 1.0 / 16.0);
+            }
+        }
+    }
+    result
+}
+
+/// THUMBNAIL as half block lines in COLORS, two pixel rows per line, with transparent pixels drawn as FILL.
+/// Also returns the width in cells
+pub fn render_lines(thumbnail: &[Box<[[u8; 4]]>], colors: ArtColors, dither: ArtDither, fill: Color) -> (Vec<Line<'static>>, u16) {
+    let colors = match colors {
+        ArtColors::Auto if available_color_count() <= 16 => ArtColors::Ansi16,
+        ArtColors::Auto => ArtColors::Truecolor,
+        colors => colors,
+    };
+    // clip at 5% or less
+    const CLIP: u8 = u8::MAX / 20;
+    let pixels = quantize(thumbnail, colors, dither);
+    let lines: Vec<Line<'static>> = thumbnail
+        .chunks(2)
+        .zip(pixels.chunks(2))
+        .map(|(rows, colors)| {
+            let (empty, empty_col) = (Default::default(), Vec::new());
+            let mut bgiter = rows.get(1).unwrap_or(&empty).iter().zip(colors.get(1).unwrap_or(&empty_col));
+
+            rows[0]
+                .iter()
+                .zip(&colors[0])
+                .map(|(fg, fg_col)| {
+                    let mut style = Style::default();
+
+                    // no alpha blending because the 16 terminal colors aren't readable
+                    let content = if let Some((bg, bg_col)) = bgiter.next() {
+                        // All alpha only draw space to support
+                        // terminal emulator transparency
+                        if bg[3] <= CLIP && fg[3] <= CLIP {
+                            style = style.bg(fill);
+                            " "
+                        // Uniform solid only draw block to avoid
+                        // terminal emulator transparency
+                        } else if bg_col == fg_col {
+                            style = style.fg(*fg_col);
+                            "█"
+                        } else {
+                            style = style.fg(*fg_col).bg(*bg_col);
+                            "▀"
+                        }
+                    } else if fg[3] <= CLIP {
+                        style = style.bg(fill);
+                        " "
+                    } else {
+                        style = style.fg(*fg_col);
+                        "▀"
+                    };
+
+                    Span {
+                        content: content.into(),
+                        style,
+                    }
+                })
+                .collect::<Vec<Span<'static>>>()
+                .into()
+        })
+        .collect();
+    let width = pixels.first().map(|r| r.len()).unwrap_or(0) as u16;
+    (lines, width)
+}
+
 //}}}
 
 pub struct Art {
@@ -101,56 +280,18 @@ impl ContainedWidget for Art {
         let Some(library) = self.lib_weak.upgrade() else { return };
         let (w, h) = (self.area.width as usize, self.area.height as usize * 2);
         if let Some(thumbnail) = library.thumbnail(w, h) {
-            let quantize = available_color_count() <= 16;
-            let fill = stylesheet.base.bg.unwrap_or(Color::Black);
-            // clip at 5% or less
-            const CLIP: u8 = u8::MAX / 20;
-            let lines: Vec<Line> = thumbnail
-                .chunks(2)
-                .map(|rows| {
-                    let empty = Default::default();
-                    let mut bgiter = rows.get(1).unwrap_or(&empty).into_iter();
-
-                    rows[0]
-                        .iter()
-                        .map(|fg| {
-                            let mut style = Style::default();
-
-                            // no alpha blending because the 16 terminal colors aren't readable
-                            let content = if let Some(bg) = bgiter.next() {
-                                // All alpha only draw space to support
-                                // terminal emulator transparency
-                                if bg[3] <= CLIP && fg[3] <= CLIP {
-                                    style = style.bg(fill);
-                                    " "
-                                // Uniform solid only draw block to avoid
-                                // terminal emulator transparency
-                                } else if bg == fg {
-                                    style = style.fg(pixel2col(*fg, quantize));
-                                    "█"
-                                } else {
-                                    style = style.fg(pixel2col(*fg, quantize)).bg(pixel2col(*bg, quantize));
-                                    "▀"
-                                }
-                            } else if fg[3] <= CLIP {
-                                style = style.bg(fill);
-                                " "
-                            } else {
-                                style = style.fg(pixel2col(*fg, quantize));
-                                "▀"
-                            };
-
-                            Span {
-                                content: content.into(),
-                                style,
-                            }
-                        })
-                        .collect::<Vec<Span>>()
-                        .into()
-                })
-                .collect();
+            let theme = library.theme_get();
+            let (lines, width) = render_lines(&thumbnail, theme.art_colors, theme.art_dither, stylesheet.base.bg.unwrap_or(Color::Black));
 
-            Paragraph::new(lines).render(self.area, buf)
+            let mut area = self.area;
+            if theme.art_letterbox {
+                let height = lines.len() as u16;
+                area.x += area.width.saturating_sub(width) / 2;
+                area.y += area.height.saturating_sub(height) / 2;
+                area.width = width.min(area.width);
+                area.height = height.min(area.height);
+            }
+            Paragraph::new(lines).render(area, buf)
         } else {
             Block::new().style(stylesheet.base).borders(Borders::ALL).render(self.area, buf)
         }
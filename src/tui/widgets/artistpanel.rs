@@ -0,0 +1,64 @@
+#![warn(missing_docs)]
+
+use super::art::render_lines;
+use super::{ContainedWidget, StyleSheet};
+use crate::library::Library;
+
+use std::sync::{Arc, Weak};
+
+use ratatui::buffer::Buffer;
+use ratatui::layout::{Constraint, Layout, Rect};
+use ratatui::style::Color;
+use ratatui::widgets::{Block, Borders, Paragraph, Widget, Wrap};
+
+/// Picture and biography of the playing track's artist
+pub struct ArtistPanel {
+    lib_weak: Weak<Library>,
+    area: Rect,
+}
+
+impl ArtistPanel {
+    pub fn new(library: &Arc<Library>) -> Self {
+        Self {
+            lib_weak: Arc::downgrade(library),
+            area: Rect::default(),
+        }
+    }
+}
+
+impl ContainedWidget for ArtistPanel {
+    fn render(&mut self, buf: &mut Buffer, area: Rect, stylesheet: StyleSheet) {
+        self.area = area;
+        let Some(library) = self.lib_weak.upgrade() else { return };
+        let info = library.artist_info();
+        let block = Block::new()
+            .style(stylesheet.base)
+            .borders(Borders::ALL)
+            .title(info.as_ref().map(|i| i.name.as_str()).unwrap_or("Artist").to_string());
+        let inner = block.inner(area);
+        block.render(area, buf);
+        let Some(info) = info else { return };
+
+        // picture up to half the panel, bio under it
+        let image_height = match info.image {
+            Some(_) => (inner.width / 2).min(inner.height / 2),
+            None => 0,
+        };
+        let [image_area, bio_area] = *Layout::vertical([Constraint::Length(image_height), Constraint::Min(0)]).split(inner) else {
+            return;
+        };
+        if image_area.height > 0 {
+            if let Some(thumbnail) = library.artist_thumbnail(image_area.width as usize, image_area.height as usize * 2) {
+                let theme = library.theme_get();
+                let (lines, width) = render_lines(&thumbnail, theme.art_colors, theme.art_dither, stylesheet.base.bg.unwrap_or(Color::Black));
+                let mut image_area = image_area;
+                image_area.x += image_area.width.saturating_sub(width) / 2;
+                image_area.width = width.min(image_area.width);
+                Paragraph::new(lines).render(image_area, buf)
+            }
+        }
+        if let Some(bio) = info.bio.as_deref() {
+            Paragraph::new(bio).style(stylesheet.base).wrap(Wrap { trim: true }).render(bio_area, buf)
+        }
+    }
+}
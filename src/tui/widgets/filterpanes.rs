@@ -1,7 +1,7 @@
 #![warn(missing_docs)]
 
-use super::{Action, Clickable, ContainedWidget, PaneArray, PaneArrayEvt, Scrollable, Searchable, StyleSheet};
-use crate::library::{get_taglist_sort, LibEvt, Library};
+use super::{Action, Clickable, ContainedWidget, PaneArray, PaneArrayEvt, PaneState, Scrollable, Searchable, StyleSheet};
+use crate::library::{EventReceiver, LibEvt, Library};
 
 use std::sync::{Arc, Weak};
 
@@ -14,8 +14,10 @@ use ratatui::layout::Rect;
 pub struct FilterPanes {
     lib_weak: Weak<Library>,
     pane_array: PaneArray,
-    recv: bus::BusReader<LibEvt>,
+    recv: EventReceiver,
     pane_cache: (Vec<(String, Vec<String>)>, Vec<Vec<String>>),
+    /// bumped whenever pane_cache is rebuilt
+    generation: u64,
 }
 
 impl FilterPanes {
@@ -26,6 +28,7 @@ impl FilterPanes {
             pane_array: PaneArray::new(false, count),
             recv: library.get_receiver().unwrap(),
             pane_cache: Default::default(),
+            generation: 0,
         }
     }
 
@@ -43,11 +46,19 @@ impl FilterPanes {
         &mut self.pane_array.index
     }
 
+    pub fn pane_state(&self) -> PaneState {
+        self.pane_array.state()
+    }
+    pub fn restore(&mut self, state: PaneState) {
+        self.pane_array.restore(state)
+    }
+
     pub fn toggle_current(&mut self) {
         let Some(library) = self.lib_weak.upgrade() else { return };
         let (tags, data) = library.get_filter_tree_display();
         let Some(mut fi) = library.get_filter_items(self.index()) else { return };
-        let Some(item) = get_taglist_sort(&tags[self.index()].tag, &data[self.index()])
+        let Some(item) = library
+            .filter_values(&tags[self.index()].tag, &data[self.index()])
             .get(self.pane_array.positions[self.index()] as usize)
             .cloned()
         else {
@@ -65,7 +76,8 @@ impl FilterPanes {
     pub fn select_current(&mut self) {
         let Some(library) = self.lib_weak.upgrade() else { return };
         let (tags, data) = library.get_filter_tree_display();
-        let item = get_taglist_sort(&tags[self.index()].tag, &data[self.index()])
+        let item = library
+            .filter_values(&tags[self.index()].tag, &data[self.index()])
             .get(self.pane_array.positions[self.index()])
             .cloned();
         if let Some(item) = item {
@@ -73,6 +85,48 @@ impl FilterPanes {
         }
     }
 
+    /// Adds every item between `from` and `to` inclusive to the current filter
+    pub fn select_range(&mut self, from: usize, to: usize) {
+        let Some(library) = self.lib_weak.upgrade() else { return };
+        let (tags, data) = library.get_filter_tree_display();
+        let Some(mut fi) = library.get_filter_items(self.index()) else { return };
+        let (from, to) = (from.min(to), from.max(to));
+
+        for item in library
+            .filter_values(&tags[self.index()].tag, &data[self.index()])
+            .into_iter()
+            .skip(from)
+            .take(to - from + 1)
+        {
+            if !fi.contains(&item) {
+                fi.push(item)
+            }
+        }
+
+        library.set_filter_items(self.index(), fi);
+    }
+
+    /// Adds every item containing `query` to the current filter
+    pub fn select_matching(&mut self, query: &str) {
+        let Some(library) = self.lib_weak.upgrade() else { return };
+        let Some(mut fi) = library.get_filter_items(self.index()) else { return };
+
+        for item in self.get_items() {
+            if item.trim().to_ascii_lowercase().contains(query) && !fi.contains(&item) {
+                fi.push(item)
+            }
+        }
+
+        library.set_filter_items(self.index(), fi);
+    }
+
+    /// Selects the current item then moves the cursor by `n`, selecting the new item as well.
+    pub fn extend_selection(&mut self, n: i32) {
+        let from = self.pane_array.positions[self.index()];
+        self.scroll_by_n_lock(n);
+        self.select_range(from, self.pane_array.positions[self.index()]);
+    }
+
     pub fn deselect_all(&mut self) {
         let Some(library) = self.lib_weak.upgrade() else { return };
         if library.get_filter_items(self.index()).map(|f| f.is_empty()) == Some(false) {
@@ -86,7 +140,8 @@ impl FilterPanes {
         if let Some(fi) = library.get_filter_items(self.index()) {
             library.set_filter_items(
                 self.index(),
-                get_taglist_sort(&tags[self.index()].tag, &data[self.index()])
+                library
+                    .filter_values(&tags[self.index()].tag, &data[self.index()])
                     .into_iter()
                     .filter(|i| !fi.contains(i))
                     .collect(),
@@ -110,7 +165,7 @@ impl Scrollable for FilterPanes {
                 &mut self.pane_array.positions[i],
                 &mut self.pane_array.views[i],
                 area.height.saturating_sub(2).into(),
-                get_taglist_sort(&tags[i].tag, &data[i]).len(),
+                library.filter_values(&tags[i].tag, &data[i]).len(),
             )
         })
     }
@@ -124,7 +179,7 @@ impl Searchable for FilterPanes {
         };
         let (tags, data) = library.get_filter_tree_display();
         let i = self.index().min(library.filter_count());
-        get_taglist_sort(&tags[i].tag, &data[i])
+        library.filter_values(&tags[i].tag, &data[i])
     }
 }
 
@@ -136,33 +191,58 @@ impl ContainedWidget for FilterPanes {
         self.pane_array.area = area;
         let Some(library) = self.lib_weak.upgrade() else { return };
 
-        let mut update = false;
+        // None for a full rebuild
+        let mut update = Some(Vec::<usize>::new());
         while let Ok(i) = self.recv.try_recv() {
             // Should only need to update pane item cache
             // If the filters are updated
-            if i == LibEvt::Update {
-                update = true
+            match i {
+                LibEvt::Tracks => update = None,
+                LibEvt::Filters(changed) => {
+                    if let Some(panes) = update.as_mut() {
+                        panes.extend(changed)
+                    }
+                }
+                _ => (),
             }
         }
 
-        // Cache parsed tagstrings for all frames
-        if update || self.pane_cache.0.is_empty() {
-            let (filters, tracks) = library.get_filter_tree_display();
+        if self.pane_cache.0.is_empty() || self.pane_cache.0.len() != library.filter_count() {
+            update = None
+        }
 
-            let mut new_items = Vec::<(String, Vec<String>)>::new();
-            let mut new_highlights = Vec::<Vec<String>>::new();
+        // Cache parsed tagstrings for all frames
+        match update {
+            None => {
+                let (filters, tracks) = library.get_filter_tree_display();
+                let mut new_items = Vec::<(String, Vec<String>)>::new();
+                let mut new_highlights = Vec::<Vec<String>>::new();
+
+                for (filter, track_list) in filters.into_iter().zip(tracks.into_iter()) {
+                    new_highlights.push(filter.items); // lightly confusing
+                    let tl_tags = library.filter_values(&filter.tag, &track_list);
+                    new_items.push((filter.tag, tl_tags));
+                }
 
-            for (filter, track_list) in filters.into_iter().zip(tracks.into_iter()) {
-                new_highlights.push(filter.items); // lightly confusing
-                let tl_tags = get_taglist_sort(&filter.tag, &track_list);
-                new_items.push((filter.tag, tl_tags));
+                self.pane_cache = (new_items, new_highlights);
+                self.generation += 1;
             }
-
-            self.pane_cache = (new_items, new_highlights);
+            // Each pane shows the previous layer's tracks, so a changed layer touches itself and the next pane
+            Some(changed) if !changed.is_empty() => {
+                let (filters, tracks) = library.get_filter_tree_display();
+                for (n, (filter, track_list)) in filters.into_iter().zip(tracks).enumerate() {
+                    if changed.contains(&n) || n.checked_sub(1).is_some_and(|p| changed.contains(&p)) {
+                        self.pane_cache.0[n] = (filter.tag.clone(), library.filter_values(&filter.tag, &track_list));
+                        self.pane_cache.1[n] = filter.items;
+                    }
+                }
+                self.generation += 1;
+            }
+            Some(_) => (),
         }
 
         let (items, highlights) = &self.pane_cache;
-        self.pane_array.render(buffer, stylesheet, items, highlights)
+        self.pane_array.render(buffer, stylesheet, items, highlights, self.generation)
     }
 }
 // ### impl ContainedWidget ### }}}
@@ -186,14 +266,22 @@ impl Clickable for FilterPanes {
         let mut taglists = Vec::<Vec<String>>::new();
 
         for (ft, tl) in filters.into_iter().zip(tracks.into_iter()) {
-            let taglist = get_taglist_sort(&ft.tag, &tl);
+            let taglist = library.filter_values(&ft.tag, &tl);
             lengths.push((ft.tag.len(), taglist.len()));
             highlights.push(ft.items);
             taglists.push(taglist);
         }
 
+        let oldpos = self.pane_array.positions.get(self.index()).copied();
+        let oldindex = self.index();
+
         match self.pane_array.prep_event(event, &lengths) {
             PaneArrayEvt::Click => self.select_current(),
+            PaneArrayEvt::ShiftClick => match oldpos {
+                // only extend if the click lands in the same pane as the cursor
+                Some(from) if oldindex == self.index() => self.select_range(from, self.pane_array.positions[self.index()]),
+                _ => self.select_current(),
+            },
             PaneArrayEvt::RClick => self.toggle_current(),
             PaneArrayEvt::ClickTit => self.invert_selection(),
             PaneArrayEvt::RClickTit => self.deselect_all(),
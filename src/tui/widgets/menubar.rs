@@ -69,6 +69,25 @@ impl<T: Clone> MenuBar<T> {
         ptr
     }
 
+    /// Every action in the tree as the number keys leading to it and its labels joined by " > "
+    pub fn actions(&self) -> Vec<(String, String)> {
+        fn walk<T>(tree: &MTree<T>, keys: &str, path: &str, result: &mut Vec<(String, String)>) {
+            match tree {
+                MTree::Action(_) => result.push((keys.to_string(), path.to_string())),
+                MTree::Tree(v) => {
+                    for (n, (name, sub)) in v.iter().enumerate() {
+                        let keys = format!("{}{}", keys, n + 1);
+                        let path = if path.is_empty() { name.clone() } else { format!("{} > {}", path, name) };
+                        walk(sub, &keys, &path, result)
+                    }
+                }
+            }
+        }
+        let mut result = Vec::new();
+        walk(&self.tree, "", "", &mut result);
+        result
+    }
+
     /// Nav to the deepest tree. Good for display.
     fn nav_to_tree(&self) -> Option<&Vec<(String, MTree<T>)>> {
         let mut ptr = None;
@@ -1,6 +1,9 @@
 #![warn(missing_docs)]
 
 use std::cmp::Ordering;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, HashSet};
+use std::hash::{Hash, Hasher};
 
 pub use super::stylesheet::StyleSheet;
 pub use super::Action;
@@ -13,15 +16,22 @@ mod filterpanes;
 pub use filterpanes::FilterPanes;
 mod sortpanes;
 pub use sortpanes::SortPanes;
+mod queuepane;
+pub use queuepane::QueuePane;
 mod seeker;
 pub use seeker::Seeker;
 mod art;
 pub use art::Art;
+mod artistpanel;
+pub use artistpanel::ArtistPanel;
+mod tabbar;
+pub use tabbar::TabBar;
 
 use ratatui::buffer::Buffer;
-use ratatui::crossterm::event::{MouseButton, MouseEvent, MouseEventKind};
+use ratatui::crossterm::event::{KeyModifiers, MouseButton, MouseEvent, MouseEventKind};
 use ratatui::layout::{Alignment, Constraint, Direction, Layout, Rect};
-use ratatui::text::Span;
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::{Line, Span};
 use ratatui::widgets::{Block, Borders, List, ListItem, Paragraph, Widget};
 
 /// Creates constraints for a distance that meet 3 criteria:
@@ -96,7 +106,8 @@ pub trait Scrollable {
 pub trait Searchable: Scrollable {
     fn get_items<'a>(&self) -> Vec<String>;
 
-    fn find(&mut self, query: &str) {
+    /// Move to the first item starting with QUERY, else the first containing it. False if none do
+    fn find(&mut self, query: &str) -> bool {
         let items = self.get_items();
 
         for x in 0..=1 {
@@ -108,10 +119,11 @@ pub trait Searchable: Scrollable {
                 } {
                     self.scroll_by_n(i32::MIN);
                     self.scroll_by_n_lock(n as i32);
-                    return;
+                    return true;
                 }
             }
         }
+        false
     }
 }
 
@@ -138,14 +150,40 @@ pub struct PaneArray {
     pub positions: Vec<usize>,
     pub views: Vec<usize>,
     pub drag_vals: Vec<usize>,
+    /// Show and handle the pane editing buttons in the bottom border
+    pub buttons: bool,
+    /// Rows of the first pane drawn dimmed and struck through, like tracks with missing files.
+    /// Bump the render generation when changed
+    pub dimmed: HashSet<usize>,
+    /// Art chip for each row of the first pane, drawn before the item. Empty for none.
+    /// Bump the render generation when changed
+    pub chips: Vec<Option<Color>>,
+    /// Rows of the first pane that start a group, with its label drawn to the right.
+    /// The row before each is underlined as a separator. Bump the render generation when changed
+    pub groups: HashMap<usize, String>,
+    /// State hash of the last render and what it drew
+    frame_cache: Option<(u64, Buffer)>,
 }
 
+/// Where a PaneArray was left, kept across restarts
+#[derive(Clone, Debug, Default, serde::Serialize, serde::Deserialize)]
+pub struct PaneState {
+    headers: Vec<String>,
+    active: bool,
+    index: usize,
+    positions: Vec<usize>,
+    views: Vec<usize>,
+}
+
+/// Drawn in the art chip color before each row, blank without art
+const CHIP: &str = "▌ ";
 const PA_LONG: &'static str = "<<++::--++>>";
 const PA_SHORT: &'static str = "<+:-+>";
 
 /// Updates positions before sending
 pub enum PaneArrayEvt {
     Click,
+    ShiftClick,
     ClickTit,
     RClick,
     RClickTit,
@@ -166,9 +204,47 @@ impl PaneArray {
             positions: vec![0; if joined { 1 } else { count }],
             views: vec![0; if joined { 1 } else { count }],
             drag_vals: Vec::new(),
+            buttons: true,
+            dimmed: HashSet::new(),
+            chips: Vec::new(),
+            groups: HashMap::new(),
+            frame_cache: None,
+        }
+    }
+
+    pub fn state(&self) -> PaneState {
+        PaneState {
+            headers: self.current_headers.clone(),
+            active: self.active,
+            index: self.index,
+            positions: self.positions.clone(),
+            views: self.views.clone(),
         }
     }
 
+    /// Positions are matched up by header on the next render, same as when panes move,
+    /// and clamped there if the items changed
+    pub fn restore(&mut self, state: PaneState) {
+        let count = if self.joined { 1 } else { state.headers.len() };
+        if state.positions.len() != count || state.views.len() != count {
+            return;
+        }
+        self.current_headers = state.headers;
+        self.active = state.active;
+        self.index = state.index;
+        self.positions = state.positions;
+        self.views = state.views;
+        self.frame_cache = None;
+    }
+
+    /// Everything a render reads besides the items themselves,
+    /// which the owner summarizes as `generation`
+    fn state_hash(&self, stylesheet: StyleSheet, generation: u64) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        (self.area, self.active, self.index, &self.positions, &self.views, stylesheet, generation).hash(&mut hasher);
+        hasher.finish()
+    }
+
     // # prep_event # {{{
     pub fn prep_event(&mut self, event: MouseEvent, items: &[(usize, usize)]) -> PaneArrayEvt {
         let none = PaneArrayEvt::Action(Action::None);
@@ -223,7 +299,8 @@ impl PaneArray {
                                     MouseButton::Middle => (),
                                 }
                             // click footer
-                            } else if zY == zone.height.saturating_sub(1)
+                            } else if self.buttons
+                                && zY == zone.height.saturating_sub(1)
                                 && zX > zone.width.saturating_sub(footer + 2)
                                 && zX < zone.width.saturating_sub(1)
                             {
@@ -253,7 +330,10 @@ impl PaneArray {
                                 match button {
                                     MouseButton::Left => {
                                         self.positions[num_join] = zY as usize + self.views[num_join] - 1;
-                                        return PaneArrayEvt::Click;
+                                        return match event.modifiers.contains(KeyModifiers::SHIFT) {
+                                            true => PaneArrayEvt::ShiftClick,
+                                            false => PaneArrayEvt::Click,
+                                        };
                                     }
                                     MouseButton::Right => {
                                         let pos = zY as usize + self.views[num_join] - 1;
@@ -303,7 +383,32 @@ impl PaneArray {
     // # prep_event # }}}
 
     // # draw_from # {{{
-    pub fn render(&mut self, buf: &mut Buffer, stylesheet: StyleSheet, items: &Vec<(String, Vec<String>)>, highlights: &Vec<Vec<String>>) {
+    /// `generation` must change whenever `items` or `highlights` do.
+    /// If nothing else changed either the last frame is reused
+    pub fn render(
+        &mut self,
+        buf: &mut Buffer,
+        stylesheet: StyleSheet,
+        items: &Vec<(String, Vec<String>)>,
+        highlights: &Vec<Vec<String>>,
+        generation: u64,
+    ) {
+        let hash = self.state_hash(stylesheet, generation);
+        if let Some((cached, frame)) = &self.frame_cache {
+            if *cached == hash {
+                buf.merge(frame);
+                return;
+            }
+        }
+
+        let mut frame = Buffer::empty(self.area);
+        self.render_panes(&mut frame, stylesheet, items, highlights);
+        buf.merge(&frame);
+        // hashed again as rendering clamps positions and views
+        self.frame_cache = Some((self.state_hash(stylesheet, generation), frame));
+    }
+
+    fn render_panes(&mut self, buf: &mut Buffer, stylesheet: StyleSheet, items: &Vec<(String, Vec<String>)>, highlights: &Vec<Vec<String>>) {
         // clamp index
         self.index = self.index.min(items.len().saturating_sub(1));
 
@@ -362,7 +467,7 @@ impl PaneArray {
                     .skip(self.views[num_join])
                     .take(self.area.height.saturating_sub(2).into())
                     .map(|(n, s)| {
-                        ListItem::new(s.clone()).style(if self.active && num == self.index {
+                        let style = if self.active && num == self.index {
                             match highlights.get(num).unwrap_or(&vec![]).contains(&s) {
                                 true => match n == self.positions[num_join] {
                                     true => stylesheet.active_hi_sel,
@@ -384,7 +489,29 @@ impl PaneArray {
                                     false => stylesheet.base,
                                 },
                             }
-                        })
+                        };
+                        let mut line = match self.chips.get(n).filter(|_| num == 0) {
+                            Some(Some(chip)) => Line::from(vec![Span::styled(CHIP, Style::new().fg(*chip)), Span::raw(s.as_str())]),
+                            Some(None) => Line::from(vec![Span::raw(" ".repeat(CHIP.chars().count())), Span::raw(s.as_str())]),
+                            None => Line::from(s.as_str()),
+                        };
+                        // right aligned, left off if it won't fit beside the row
+                        if let Some(label) = self.groups.get(&n).filter(|_| num == 0) {
+                            let room = usize::from(area.width.saturating_sub(2)).saturating_sub(line.width() + 1);
+                            let width = label.chars().count();
+                            if width <= room {
+                                line.push_span(Span::raw(" ".repeat(room - width + 1)));
+                                line.push_span(Span::styled(label.as_str(), Style::new().add_modifier(Modifier::ITALIC)));
+                            }
+                        }
+                        let mut style = style;
+                        if num == 0 && self.dimmed.contains(&n) {
+                            style = style.add_modifier(Modifier::DIM | Modifier::CROSSED_OUT)
+                        }
+                        if num == 0 && self.groups.contains_key(&(n + 1)) {
+                            style = style.add_modifier(Modifier::UNDERLINED)
+                        }
+                        ListItem::new(line).style(style)
                     })
                     .collect::<Vec<ListItem>>(),
             )
@@ -413,6 +540,10 @@ impl PaneArray {
             )
             .render(*area, buf);
 
+            if !self.buttons {
+                continue;
+            }
+
             Paragraph::new(if area.width < PA_LONG.len() as u16 { PA_SHORT } else { PA_LONG })
                 .alignment(Alignment::Right)
                 .render(
@@ -0,0 +1,345 @@
+#![warn(missing_docs)]
+
+use super::art::chip_color;
+use super::{Action, Clickable, ContainedWidget, PaneArray, PaneArrayEvt, PaneState, Scrollable, Searchable, StyleSheet};
+use crate::library::{get_taglist, EventReceiver, LibEvt, Library, Track};
+
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+use std::sync::{Arc, Weak};
+use std::time::{Duration, Instant};
+
+use ratatui::buffer::Buffer;
+use ratatui::crossterm::event::{MouseEvent, MouseEventKind};
+use ratatui::layout::Rect;
+
+const TITLE: &str = "Queue";
+const TITLE_FOLLOW: &str = "Queue [follow]";
+/// How long moving the cursor by hand holds off follow mode
+const FOLLOW_PAUSE: Duration = Duration::from_secs(5);
+
+// ### struct QueuePane {{{
+
+/// The final play queue in order, each track formatted with the statusline tagstring
+/// and split by the groupline tagstring if one is set.
+/// Separate from the sorter panes, and keeps the cursor on the same track when the queue is reordered
+pub struct QueuePane {
+    lib_weak: Weak<Library>,
+    pane_array: PaneArray,
+    recv: EventReceiver,
+    /// Tracks as currently displayed
+    tracks: Vec<Arc<Track>>,
+    items_cache: Vec<(String, Vec<String>)>,
+    /// Row of the playing track
+    highlights: Vec<Vec<String>>,
+    /// bumped whenever items_cache or highlights change
+    generation: u64,
+    /// Center the playing track whenever it changes
+    pub follow: bool,
+    /// Cursor and view as of the last frame, to notice the user scrolling
+    last: (usize, usize),
+    /// When the user last moved the cursor or view
+    moved: Option<Instant>,
+    /// Tracks marked by key, kept through reordering
+    marks: HashMap<char, Arc<Track>>,
+}
+
+impl QueuePane {
+    pub fn new(library: Arc<Library>) -> Self {
+        let mut pane_array = PaneArray::new(true, 1);
+        pane_array.buttons = false;
+        Self {
+            lib_weak: Arc::downgrade(&library),
+            pane_array,
+            recv: library.get_receiver().unwrap(),
+            tracks: Vec::new(),
+            items_cache: Vec::new(),
+            highlights: Vec::new(),
+            generation: 0,
+            follow: true,
+            last: (0, 0),
+            moved: None,
+            marks: HashMap::new(),
+        }
+    }
+    pub fn active(&self) -> bool {
+        self.pane_array.active
+    }
+    pub fn active_mut(&mut self) -> &mut bool {
+        &mut self.pane_array.active
+    }
+
+    pub fn pane_state(&self) -> PaneState {
+        self.pane_array.state()
+    }
+    pub fn restore(&mut self, state: PaneState) {
+        self.pane_array.restore(state);
+        self.last = (self.pane_array.positions[0], self.pane_array.views[0]);
+    }
+
+    /// Track under the cursor
+    pub fn selected(&self) -> Option<Arc<Track>> {
+        self.tracks.get(self.pane_array.positions[0]).cloned()
+    }
+
+    /// Move the cursor to the playing track and center it
+    pub fn focus_playing(&mut self) {
+        let Some(library) = self.lib_weak.upgrade() else { return };
+        let Some(track) = library.track_get() else { return };
+        if let Some(index) = self.tracks.iter().position(|t| Arc::ptr_eq(t, &track)) {
+            self.scroll_by_n(i32::MIN);
+            self.scroll_by_n_lock(index as i32);
+            // back on the playing track, so follow picks up again
+            self.last = (self.pane_array.positions[0], self.pane_array.views[0]);
+            self.moved = None;
+        }
+    }
+
+    /// Mark the track under the cursor as KEY
+    pub fn mark(&mut self, key: char) {
+        if let Some(track) = self.selected() {
+            self.marks.insert(key, track);
+        }
+    }
+
+    /// Move the cursor to the track marked KEY, or the last played for '.'.
+    /// Where the cursor was is marked '`' so the same jump goes back.
+    /// False if there's no such mark or its track isn't in the queue
+    pub fn jump(&mut self, key: char) -> bool {
+        let track = match key {
+            '.' => self.lib_weak.upgrade().and_then(|l| l.history_last()),
+            key => self.marks.get(&key).cloned(),
+        };
+        let Some(index) = track.and_then(|track| self.tracks.iter().position(|t| Arc::ptr_eq(t, &track))) else {
+            return false;
+        };
+        self.mark('`');
+        self.scroll_by_n(i32::MIN);
+        self.scroll_by_n_lock(index as i32);
+        true
+    }
+
+    /// Flip follow mode, jumping to the playing track if it's now on
+    pub fn toggle_follow(&mut self) {
+        self.follow = !self.follow;
+        if self.follow {
+            self.moved = None;
+            self.focus_playing()
+        }
+    }
+
+    fn rebuild(&mut self, library: &Library) {
+        let selected = self.selected();
+        let row = self.pane_array.positions[0].saturating_sub(self.pane_array.views[0]);
+        // first build only jumps to the playing track if there's no restored cursor
+        let follow = self.items_cache.is_empty() && self.follow && self.pane_array.positions[0] == 0;
+
+        self.tracks = library.get_queue();
+        self.items_cache = vec![(self.title().to_string(), get_taglist(library.statusline_get(), &self.tracks))];
+
+        // same track at the same height, so reordering doesn't move what you're looking at
+        if let Some(index) = selected.and_then(|s| self.tracks.iter().position(|t| Arc::ptr_eq(t, &s))) {
+            self.pane_array.positions[0] = index;
+            self.pane_array.views[0] = index.saturating_sub(row);
+        }
+        if self.tracks.is_empty() {
+            self.pane_array.positions[0] = 0;
+            self.pane_array.views[0] = 0;
+        } else {
+            self.scroll_by_n(0);
+        }
+
+        self.highlight(library, follow);
+        self.dim(library);
+        self.chip(library);
+        self.group(library);
+    }
+
+    /// Mark where the groupline tagstring changes, so each album or such gets a separator
+    fn group(&mut self, library: &Library) {
+        let groupline = library.groupline_get();
+        self.pane_array.groups.clear();
+        if !groupline.is_empty() {
+            let mut last = None;
+            for (n, track) in self.tracks.iter().enumerate() {
+                let label = track.tagstring(&groupline);
+                if last.as_ref() != Some(&label) {
+                    self.pane_array.groups.insert(n, label.clone());
+                    last = Some(label);
+                }
+            }
+        }
+        self.generation += 1;
+    }
+
+    /// Art chips for every track that has one read so far, or none if they're off
+    fn chip(&mut self, library: &Library) {
+        let theme = library.theme_get();
+        self.pane_array.chips = match theme.art_chips {
+            true => self
+                .tracks
+                .iter()
+                .map(|t| library.art_chip(t).map(|c| chip_color(c, theme.art_colors)))
+                .collect(),
+            false => Vec::new(),
+        };
+        self.generation += 1;
+    }
+
+    /// Dim tracks whose files are missing or unreadable
+    fn dim(&mut self, library: &Library) {
+        let unreadable: HashSet<PathBuf> = library.get_unreadable().into_iter().collect();
+        self.pane_array.dimmed = self
+            .tracks
+            .iter()
+            .enumerate()
+            .filter(|(_, t)| unreadable.contains(t.path()))
+            .map(|(n, _)| n)
+            .collect();
+        self.generation += 1;
+    }
+
+    fn title(&self) -> &'static str {
+        match self.follow {
+            true => TITLE_FOLLOW,
+            false => TITLE,
+        }
+    }
+
+    /// Highlight the playing track, centering it if `follow`
+    fn highlight(&mut self, library: &Library, follow: bool) {
+        let rows = self.items_cache.first().map(|i| i.1.as_slice()).unwrap_or_default();
+        let playing = library.track_get().and_then(|c| self.tracks.iter().position(|t| Arc::ptr_eq(t, &c)));
+        self.highlights = vec![playing.and_then(|i| rows.get(i)).cloned().into_iter().collect()];
+        self.generation += 1;
+
+        if let (Some(index), true) = (playing, follow) {
+            self.scroll_by_n(i32::MIN);
+            self.scroll_by_n_lock(index as i32);
+        }
+    }
+}
+
+// }}}
+
+// ### impl ContainedWidget {{{
+impl ContainedWidget for QueuePane {
+    fn render(&mut self, buf: &mut Buffer, area: Rect, stylesheet: StyleSheet) {
+        self.pane_array.area = area;
+        let Some(library) = self.lib_weak.upgrade() else { return };
+
+        if self.last != (self.pane_array.positions[0], self.pane_array.views[0]) {
+            self.moved = Some(Instant::now())
+        }
+
+        let (mut update, mut playing, mut unreadable, mut chips) = (false, false, false, false);
+        while let Ok(i) = self.recv.try_recv() {
+            match i {
+                // Theme covers the statusline tagstring
+                LibEvt::Tracks | LibEvt::Queue | LibEvt::Theme => update = true,
+                LibEvt::Track => playing = true,
+                LibEvt::Unreadable => unreadable = true,
+                LibEvt::ArtChips => chips = true,
+                _ => (),
+            }
+        }
+
+        if update || self.items_cache.is_empty() {
+            self.rebuild(&library)
+        } else {
+            if playing {
+                let follow = self.follow && self.moved.is_none_or(|t| t.elapsed() > FOLLOW_PAUSE);
+                self.highlight(&library, follow)
+            }
+            if unreadable {
+                self.dim(&library)
+            }
+            if chips {
+                self.chip(&library)
+            }
+        }
+
+        // only what's on screen, the chip server reads the rest as it's scrolled to
+        if !self.pane_array.chips.is_empty() {
+            let start = self.pane_array.views[0].min(self.tracks.len());
+            let end = (start + usize::from(area.height)).min(self.tracks.len());
+            library.art_chips_request(&self.tracks[start..end])
+        }
+
+        let title = self.title();
+        if let Some(item) = self.items_cache.first_mut().filter(|i| i.0 != title) {
+            item.0 = title.to_string();
+            self.generation += 1;
+        }
+
+        self.pane_array
+            .render(buf, stylesheet, &self.items_cache, &self.highlights, self.generation);
+        self.last = (self.pane_array.positions[0], self.pane_array.views[0]);
+    }
+}
+// ### impl ContainedWidget }}}
+
+// ### impl Scrollable, Searchable {{{
+
+impl Scrollable for QueuePane {
+    fn get_fields(&mut self) -> Option<(&mut usize, &mut usize, usize, usize)> {
+        Some((
+            &mut self.pane_array.positions[0],
+            &mut self.pane_array.views[0],
+            self.pane_array.area.height.saturating_sub(2).into(),
+            self.tracks.len(),
+        ))
+    }
+}
+
+impl Searchable for QueuePane {
+    fn get_items<'a>(&self) -> Vec<String> {
+        self.items_cache.first().map(|i| i.1.clone()).unwrap_or_default()
+    }
+}
+
+// ### impl Scrollable, Searchable }}}
+
+// ### impl Clickable {{{
+impl Clickable for QueuePane {
+    fn process_event(&mut self, event: MouseEvent) -> Action {
+        let none = Action::None;
+        let draw = Action::Draw;
+        match event.kind {
+            MouseEventKind::Moved | MouseEventKind::Drag(..) | MouseEventKind::Up(..) => return none,
+            _ => (),
+        }
+
+        let Some(library) = self.lib_weak.upgrade() else { return none };
+
+        let oldpos = self.pane_array.positions[0];
+
+        match self.pane_array.prep_event(event, &[(self.title().len(), self.tracks.len())]) {
+            PaneArrayEvt::Click | PaneArrayEvt::ShiftClick => library.play_track(self.selected()),
+            PaneArrayEvt::RClick => {
+                if self.pane_array.positions[0] == oldpos {
+                    self.scroll_by_n_lock(0)
+                };
+                return draw;
+            }
+            PaneArrayEvt::ClickTit => {
+                self.toggle_follow();
+                return draw;
+            }
+            PaneArrayEvt::RClickTit => (),
+            PaneArrayEvt::RDrag => (),
+            PaneArrayEvt::ScrollUp => {
+                self.scroll_up();
+                return draw;
+            }
+            PaneArrayEvt::ScrollDown => {
+                self.scroll_down();
+                return draw;
+            }
+            PaneArrayEvt::Action(a) => return a,
+        }
+
+        none
+    }
+}
+// ### impl Clickable }}}
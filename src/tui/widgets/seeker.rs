@@ -38,6 +38,7 @@ impl ContainedWidget for Seeker {
         if seekable {
             let sparklen: usize = area.width.into();
             let Some((start, end)) = library.times() else { return };
+            let bookmarks = library.bookmarks(&track);
             let ratio = start.as_secs_f32() / end.as_secs_f32();
             let split = (sparklen as f32 * ratio + 0.5).round() as u16;
 
@@ -77,6 +78,37 @@ impl ContainedWidget for Seeker {
                 .data(&sparkwave[past.width as usize..])
                 .style(stylesheet.base)
                 .render(future, buf);
+
+            for bookmark in bookmarks {
+                let x = (bookmark.time.as_secs_f32() / end.as_secs_f32() * sparklen as f32) as u16;
+                if let Some(cell) = buf.cell_mut((area.x + x.min(area.width.saturating_sub(1)), area.y)) {
+                    cell.set_symbol("▼").set_style(stylesheet.active_hi);
+                }
+            }
+        } else if let (Some(decoded), Some((start, end))) = (library.decoded(), library.times()) {
+            // no waveform until decoding finishes, so raise the decoded part to show how far seeking reaches
+            let width = area.width as f32;
+            let split = ((start.as_secs_f32() / end.as_secs_f32() * width).round() as u16).min(area.width);
+            let edge = (decoded.as_secs_f32() / end.as_secs_f32() * width).round() as usize;
+            let data = (0..area.width as usize).map(|x| if x < edge { 2 } else { 1 }).collect::<Vec<u64>>();
+
+            let [past, future] = *Layout::default()
+                .direction(Direction::Horizontal)
+                .constraints([Constraint::Length(split), Constraint::Length(area.width.saturating_sub(split))])
+                .split(area)
+            else {
+                unreachable!("Sparkline past/future split was not 2")
+            };
+            Sparkline::default()
+                .max(4)
+                .data(&data[..past.width as usize])
+                .style(stylesheet.active)
+                .render(past, buf);
+            Sparkline::default()
+                .max(4)
+                .data(&data[past.width as usize..])
+                .style(stylesheet.base)
+                .render(future, buf);
         } else {
             Sparkline::default().max(4).data(&vec![1; area.width.into()]).render(area, buf);
         }
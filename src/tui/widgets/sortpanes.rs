@@ -1,7 +1,7 @@
 #![warn(missing_docs)]
 
-use super::{Action, Clickable, ContainedWidget, PaneArray, PaneArrayEvt, Scrollable, Searchable, StyleSheet};
-use crate::library::{LibEvt, Library};
+use super::{Action, Clickable, ContainedWidget, PaneArray, PaneArrayEvt, PaneState, StyleSheet};
+use crate::library::{parse_sorter, Library};
 
 use std::sync::{Arc, Weak};
 
@@ -11,11 +11,13 @@ use ratatui::layout::Rect;
 
 // ### struct SortPanes {{{
 
+/// Headers for editing the sorter stack. The queue itself is shown by [`super::QueuePane`]
 pub struct SortPanes {
     lib_weak: Weak<Library>,
     pane_array: PaneArray,
-    recv: bus::BusReader<LibEvt>,
     items_cache: Vec<(String, Vec<String>)>,
+    /// bumped whenever items_cache is rebuilt
+    generation: u64,
 }
 
 impl SortPanes {
@@ -25,8 +27,8 @@ impl SortPanes {
         Self {
             lib_weak: Arc::downgrade(&library),
             pane_array,
-            recv: library.get_receiver().unwrap(),
             items_cache: Default::default(),
+            generation: 0,
         }
     }
     pub fn active(&self) -> bool {
@@ -43,92 +45,50 @@ impl SortPanes {
         &mut self.pane_array.index
     }
 
-    pub fn position(&self) -> usize {
-        self.pane_array.positions[0]
+    pub fn pane_state(&self) -> PaneState {
+        self.pane_array.state()
     }
-
-    fn get_rows(&self) -> Vec<Vec<String>> {
-        let mut rows = Vec::<Vec<String>>::new();
-        let Some(library) = self.lib_weak.upgrade() else { return rows };
-
-        let mut tags = library.get_sorters();
-
-        // if nothing then fetch title since title will always exist
-        if tags.is_empty() {
-            tags.push("title".to_string())
-        }
-
-        let items = tags.iter().map(|t| library.get_taglist(t)).collect::<Vec<Vec<String>>>();
-
-        for x in 0..items[0].len() {
-            rows.push(items.iter().map(|i| i[x].clone()).collect::<Vec<String>>());
-        }
-
-        rows
+    pub fn restore(&mut self, state: PaneState) {
+        self.pane_array.restore(state)
     }
 }
 
 // }}}
 
+/// Pane titles, each sorter with an arrow for its direction
+fn headers(library: &Library) -> Vec<String> {
+    let mut headers = library
+        .get_sorters()
+        .iter()
+        .map(|s| match parse_sorter(s) {
+            (tagstring, true) => format!("{} ▼", tagstring),
+            (tagstring, false) => format!("{} ▲", tagstring),
+        })
+        .collect::<Vec<String>>();
+    if headers.is_empty() {
+        headers.push("[unsorted]".to_string())
+    }
+    headers
+}
+
 // ### impl ContainedWidget {{{
 impl ContainedWidget for SortPanes {
     fn render(&mut self, buf: &mut Buffer, area: Rect, stylesheet: StyleSheet) {
         self.pane_array.area = area;
         let Some(library) = self.lib_weak.upgrade() else { return };
 
-        let mut update = false;
-        while let Ok(i) = self.recv.try_recv() {
-            // Should only need to update pane item cache
-            // If the filters are updated
-            if i == LibEvt::Update {
-                update = true
-            }
-        }
-
-        // Cache parsed tagstrings for all frames
-        if update || self.items_cache.is_empty() {
-            let mut new_items = Vec::<(String, Vec<String>)>::new();
-
-            for ts in library.get_sorters() {
-                let list = library.get_taglist(&ts);
-                new_items.push((ts, list));
-            }
-
-            if new_items.is_empty() {
-                new_items.push(("[unsorted]".to_string(), library.get_taglist("title")))
-            }
+        let headers = headers(&library);
 
-            self.items_cache = new_items
+        if !self.items_cache.iter().map(|i| &i.0).eq(headers.iter()) {
+            self.items_cache = headers.into_iter().map(|h| (h, Vec::new())).collect();
+            self.generation += 1;
         }
 
-        self.pane_array.render(buf, stylesheet, &self.items_cache, &Vec::new());
+        self.pane_array.render(buf, stylesheet, &self.items_cache, &Vec::new(), self.generation);
     }
 }
 // ### impl ContainedWidget }}}
 
-// ### impl Scrollable, Searchable {{{
-
-impl Scrollable for SortPanes {
-    fn get_fields(&mut self) -> Option<(&mut usize, &mut usize, usize, usize)> {
-        self.lib_weak.upgrade().map(|library| {
-            (
-                &mut self.pane_array.positions[0],
-                &mut self.pane_array.views[0],
-                self.pane_array.area.height.saturating_sub(2).into(),
-                library.get_queue().len(),
-            )
-        })
-    }
-}
-
-impl Searchable for SortPanes {
-    fn get_items<'a>(&self) -> Vec<String> {
-        self.get_rows().into_iter().map(|v| v[self.index()].clone()).collect::<Vec<String>>()
-    }
-}
-
-// ### impl Scrollable, Searchable }}}
-
 // ### impl Clickable {{{
 impl Clickable for SortPanes {
     fn process_event(&mut self, event: MouseEvent) -> Action {
@@ -141,40 +101,15 @@ impl Clickable for SortPanes {
 
         let Some(library) = self.lib_weak.upgrade() else { return none };
 
-        let mut items = Vec::<(usize, usize)>::new();
-
-        for s in library.get_sorters().into_iter() {
-            items.push((s.len(), library.get_queue().len()))
-        }
-
-        let queue = library.get_queue();
-
-        if items.is_empty() {
-            items.push(("[unsorted]".len(), queue.len()))
-        }
-
-        let oldpos = self.position();
+        let items = headers(&library)
+            .into_iter()
+            .map(|s| (s.chars().count(), 0))
+            .collect::<Vec<(usize, usize)>>();
 
         match self.pane_array.prep_event(event, &items) {
-            PaneArrayEvt::Click => library.play_track(queue.get(self.position()).cloned()),
-            PaneArrayEvt::RClick => {
-                if self.position() == oldpos {
-                    self.scroll_by_n_lock(0)
-                };
-                return draw;
-            }
-            PaneArrayEvt::ClickTit => (),
-            PaneArrayEvt::RClickTit => (),
-            PaneArrayEvt::RDrag => (),
-            PaneArrayEvt::ScrollUp => {
-                self.scroll_up();
-                return draw;
-            }
-            PaneArrayEvt::ScrollDown => {
-                self.scroll_down();
-                return draw;
-            }
+            PaneArrayEvt::ClickTit => return draw,
             PaneArrayEvt::Action(a) => return a,
+            _ => (),
         }
 
         none
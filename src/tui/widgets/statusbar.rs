@@ -32,8 +32,11 @@ impl ContainedWidget for StatusBar {
 
         Paragraph::new(Line::from(vec![
             Span::from(format!(
-                " -- {:.2} ++ | ({}) ",
-                library.volume_get(),
+                " -- {} ++ | ({}) ",
+                match library.muted() {
+                    true => String::from("MUTE"),
+                    false => format!("{:.2}", library.volume_get()),
+                },
                 match library.repeat_get() {
                     None =>
                         if library.shuffle_get() {
@@ -67,6 +70,12 @@ impl ContainedWidget for StatusBar {
                     None => String::new(),
                 }
             )),
+            Span::from(format!(" | {}", library.queue_info())),
+            // after everything clickable so it doesn't shift their columns
+            match library.headroom().is_some_and(|h| h.limited > 0) {
+                true => Span::styled(" | LIMIT", stylesheet.base_hi),
+                false => Span::from(""),
+            },
         ]))
         .style(stylesheet.base)
         .render(area, buf);
@@ -95,6 +104,11 @@ impl Clickable for StatusBar {
                         29..=30 => library.next(),
                         _ => (),
                     },
+                    MouseButton::Middle => {
+                        if (1..=10).contains(&event.column) {
+                            library.mute_toggle()
+                        }
+                    }
                     MouseButton::Right => {
                         let len_sl = library.statusline_get_format().len() as u16;
                         if event.column >= 34 && event.column < 34 + len_sl {
@@ -103,7 +117,6 @@ impl Clickable for StatusBar {
                             return Action::SeekTo;
                         }
                     }
-                    _ => (),
                 }
             }
         } else if event.kind == MouseEventKind::ScrollUp || event.kind == MouseEventKind::ScrollDown {
@@ -120,6 +133,12 @@ impl Clickable for StatusBar {
                         MouseEventKind::ScrollUp => library.previous(),
                         _ => (),
                     }
+                } else if event.column >= 34 && event.column < 34 + library.statusline_get_format().len() as u16 {
+                    match event.kind {
+                        MouseEventKind::ScrollDown => library.seek_by(-5.0),
+                        MouseEventKind::ScrollUp => library.seek_by(5.0),
+                        _ => (),
+                    }
                 }
             }
         }
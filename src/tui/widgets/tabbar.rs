@@ -0,0 +1,83 @@
+#![warn(missing_docs)]
+
+use super::{Action, Clickable, ContainedWidget, StyleSheet};
+use crate::library::Library;
+
+use std::sync::{Arc, Weak};
+
+use ratatui::buffer::Buffer;
+use ratatui::crossterm::event::{MouseButton, MouseEvent, MouseEventKind};
+use ratatui::layout::Rect;
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Paragraph, Widget};
+
+/// Row of view names. Only worth drawing with more than one view.
+/// Names go unnumbered as the digit keys already navigate the menu and seek, views are stepped through with `[` and `]`
+pub struct TabBar {
+    lib_weak: Weak<Library>,
+    area: Rect,
+    /// start, end columns of each tab
+    zones: Vec<(u16, u16)>,
+}
+
+impl TabBar {
+    pub fn new(library: &Arc<Library>) -> Self {
+        Self {
+            lib_weak: Arc::downgrade(library),
+            area: Rect::default(),
+            zones: Vec::new(),
+        }
+    }
+}
+
+impl ContainedWidget for TabBar {
+    fn render(&mut self, buf: &mut Buffer, area: Rect, stylesheet: StyleSheet) {
+        self.area = area;
+        self.zones.clear();
+        let Some(library) = self.lib_weak.upgrade() else { return };
+        let index = library.view_index();
+
+        let mut spans = Vec::new();
+        let mut x = area.x;
+        for (n, name) in library.get_views().into_iter().enumerate() {
+            let text = format!(" {} ", name);
+            let width = text.chars().count() as u16;
+            self.zones.push((x, x + width));
+            x += width + 1;
+            spans.push(Span::styled(text, if n == index { stylesheet.active_hi } else { stylesheet.base }));
+            spans.push(Span::from(" "));
+        }
+
+        Paragraph::new(Line::from(spans)).style(stylesheet.base).render(area, buf);
+    }
+}
+
+impl Clickable for TabBar {
+    fn process_event(&mut self, event: MouseEvent) -> Action {
+        let Some(library) = self.lib_weak.upgrade() else {
+            return Action::None;
+        };
+
+        if !self.area.intersects(Rect::new(event.column, event.row, 1, 1)) {
+            return Action::None;
+        }
+
+        match event.kind {
+            MouseEventKind::Down(MouseButton::Left) => {
+                if let Some(n) = self.zones.iter().position(|(a, b)| event.column >= *a && event.column < *b) {
+                    library.switch_view(n)
+                }
+            }
+            MouseEventKind::Down(MouseButton::Right) => {
+                if let Some(n) = self.zones.iter().position(|(a, b)| event.column >= *a && event.column < *b) {
+                    library.switch_view(n);
+                    return Action::ViewRename;
+                }
+            }
+            MouseEventKind::ScrollUp => library.switch_view(library.view_index().saturating_sub(1)),
+            MouseEventKind::ScrollDown => library.switch_view(library.view_index() + 1),
+            _ => (),
+        }
+        Action::None
+    }
+}